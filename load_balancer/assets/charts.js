@@ -0,0 +1,53 @@
+// Biểu đồ latency nhỏ trong bảng dashboard (không cần thư viện ngoài)
+const HISTORY_LEN = 20;
+
+// Khoảng cách giữa 2 lần check lớn hơn ngưỡng này = mất dữ liệu (giống gap_threshold phía Rust)
+function gapThreshold(samples) {
+  const deltas = [];
+  for (let i = 1; i < samples.length; i++) deltas.push(samples[i].at - samples[i - 1].at);
+  if (deltas.length === 0) return null;
+  deltas.sort((a, b) => a - b);
+  return Math.max(1, deltas[Math.floor(deltas.length / 2)]) * 2;
+}
+
+function timeLabel(at) {
+  return new Date(at * 1000).toLocaleTimeString();
+}
+
+// Hàm tạo graph từ các mẫu { at, healthy, latencyMs }
+function createGraph(samples) {
+  const latencies = samples.map((s) => s.latencyMs).filter((v) => typeof v === "number");
+  const max = latencies.length > 0 ? Math.max(1, ...latencies) : 1;
+  const gap = gapThreshold(samples);
+
+  const empty = '<div style="width: .5rem; height: 1px; background-color: #e9ecef; border-radius: 1px;"></div>';
+  const gapCell = (from, to) =>
+    `<div style="width: .5rem; height: 20px; background-color: #fff3cd; border-radius: 1px;" title="Không có dữ liệu ${timeLabel(from)} - ${timeLabel(to)}"></div>`;
+
+  const cells = [];
+  samples.forEach((s, i) => {
+    if (i > 0 && gap !== null && s.at - samples[i - 1].at > gap) {
+      cells.push(gapCell(samples[i - 1].at, s.at));
+    }
+    if (!s.healthy) {
+      cells.push(`<div style="width: .5rem; height: 2px; background-color: #dc3545; border-radius: 1px;" title="DOWN lúc ${timeLabel(s.at)}"></div>`);
+      return;
+    }
+    const height = Math.max(1, (s.latencyMs / max) * 20);
+    cells.push(`<div style="width: .5rem; height: ${height}px; background-color: #007bff; border-radius: 1px;" title="${s.latencyMs}ms lúc ${timeLabel(s.at)}"></div>`);
+  });
+  // Lần check cuối đã quá lâu (health check bị treo / load balancer vừa chạy lại)
+  const now = Date.now() / 1000;
+  const last = samples[samples.length - 1];
+  if (last && gap !== null && now - last.at > gap) {
+    cells.push(gapCell(last.at, now));
+  }
+
+  const shown = cells.slice(-HISTORY_LEN);
+  let graphHtml =
+    '<div style="display: flex; align-items: flex-end; justify-content: center; gap: 1px; height: 20px; min-width: 60px;">';
+  graphHtml += empty.repeat(HISTORY_LEN - shown.length) + shown.join("");
+
+  graphHtml += "</div>";
+  return graphHtml;
+}
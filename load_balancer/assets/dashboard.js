@@ -0,0 +1,132 @@
+const tbody = document.getElementById("dashboard-tbody");
+// Các cột hiển thị, theo [dashboard] columns trong config.toml (template truyền vào)
+const COLUMNS = window.LB_DASHBOARD.columns;
+
+// Hàm cập nhật nội dung bảng
+function updateTable(servers) {
+  let tableRows = "";
+  servers.forEach((s) => {
+    const uptimePercent =
+      s.availability24h != null ? s.availability24h.toFixed(2) : "-";
+
+    const healthStatus = s.disabled
+      ? '<span style="color: gray;">⏸️ DISABLED</span>'
+      : s.healthy
+      ? '<span style="color: green;">🟢 ALIVE</span>'
+      : '<span style="color: red;">🔴 DOWN</span>';
+
+    const action = `<button data-pool="${s.pool}" data-url="${s.url}" data-enable="${s.disabled}" onclick="toggleBackend(this)">${s.disabled ? "Enable" : "Disable"}</button>`;
+
+    const graph = createGraph(s.history);
+
+    const cells = {
+      url: s.url,
+      region: s.region || "-",
+      pool: s.pool,
+      health: healthStatus,
+      uptime: `${uptimePercent} %`,
+      response_time: s.responseTime || "-",
+      graph,
+      last_check: s.lastCheck || "-",
+      actions: action,
+    };
+
+    tableRows += `<tr>${COLUMNS.map((c) => `<td>${cells[c]}</td>`).join("")}</tr>`;
+  });
+  tbody.innerHTML = tableRows;
+}
+
+// Bật / tắt backend thủ công (bảng sẽ tự cập nhật qua SSE).
+// Cần admin token ([admin] trong config.toml) và mở dashboard qua listener routes = "admin"
+async function toggleBackend(button) {
+  const { pool, url, enable } = button.dataset;
+  button.disabled = true;
+  const send = () =>
+    fetch("/load-balancer/api/backends", {
+      method: "PUT",
+      headers: {
+        "Content-Type": "application/json",
+        Authorization: `Bearer ${sessionStorage.getItem("adminToken") || ""}`,
+      },
+      body: JSON.stringify({ pool, url, enabled: enable === "true" }),
+    });
+  let res = await send();
+  if (res.status === 401) {
+    const token = prompt("Admin token:");
+    if (token) {
+      sessionStorage.setItem("adminToken", token);
+      res = await send();
+    }
+  }
+  if (!res.ok) {
+    alert(await res.text());
+    button.disabled = false;
+  }
+}
+
+function handleUpdate(data) {
+  try {
+    const servers = JSON.parse(data);
+    updateTable(servers);
+  } catch (e) {
+    console.error("Error parsing update data", e);
+  }
+}
+
+// Ưu tiên WebSocket (đi qua được proxy công ty hay buffer SSE), không mở được thì dùng SSE
+function connect() {
+  if (!("WebSocket" in window)) {
+    connectSse();
+    return;
+  }
+  const proto = location.protocol === "https:" ? "wss:" : "ws:";
+  const ws = new WebSocket(`${proto}//${location.host}/load-balancer/ws`);
+  let opened = false;
+
+  ws.onopen = () => {
+    opened = true;
+    console.log("WebSocket connection established!");
+  };
+  ws.onmessage = (event) => handleUpdate(event.data);
+  ws.onclose = () => {
+    // Đã từng kết nối được -> mất mạng tạm thời, thử lại; chưa từng -> WebSocket bị chặn
+    if (opened) {
+      setTimeout(connect, 2000);
+    } else {
+      console.warn("WebSocket không khả dụng, chuyển sang SSE");
+      connectSse();
+    }
+  };
+}
+
+function connectSse() {
+  // Kết nối đến route SSE của server Rust
+  const evtSource = new EventSource("/load-balancer/events");
+
+  evtSource.onopen = () => {
+    console.log("SSE Connection established!");
+  };
+
+  evtSource.onmessage = (event) => handleUpdate(event.data);
+
+  evtSource.onerror = (err) => {
+    console.error("EventSource error:", err);
+    // EventSource tự động reconnect, không cần code thêm logic
+  };
+}
+
+// Thống kê theo quốc gia (chỉ có khi cấu hình [geoip], nếu không API trả 404 và bảng bị ẩn)
+async function updateGeo() {
+  const res = await fetch("/load-balancer/api/geo");
+  if (!res.ok) return;
+  const { countries } = await res.json();
+  document.getElementById("geo").hidden = false;
+  document.getElementById("geo-tbody").innerHTML = countries
+    .map((c) => `<tr><td>${c.country}</td><td>${c.requests}</td><td>${c.denied}</td></tr>`)
+    .join("");
+  setTimeout(updateGeo, 5000);
+}
+
+// Bắt đầu kết nối khi trang được tải
+connect();
+updateGeo();
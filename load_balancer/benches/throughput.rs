@@ -0,0 +1,182 @@
+// Đo throughput một stream qua load balancer: `cargo bench --bench throughput`
+// Dựng backend giả trả về body lớn, chạy binary load balancer trỏ tới backend đó,
+// rồi so tốc độ tải trực tiếp từ backend với tải qua load balancer.
+// Tuỳ chỉnh: BENCH_MB (kích thước body, mặc định 1024), BENCH_ROUNDS (số lần tải, mặc định 5).
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+const CHUNK: usize = 256 * 1024;
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+// Backend: "/big" trả về `size` byte, path khác (health check) trả về "ok"
+async fn backend(listener: TcpListener, size: usize) {
+    let payload = vec![b'x'; CHUNK];
+    loop {
+        let Ok((mut stream, _)) = listener.accept().await else { continue };
+        let payload = payload.clone();
+        tokio::spawn(async move {
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let big = request.starts_with(b"GET /big ");
+            let length = if big { size } else { 2 };
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", length);
+            if stream.write_all(head.as_bytes()).await.is_err() {
+                return;
+            }
+            if !big {
+                let _ = stream.write_all(b"ok").await;
+                return;
+            }
+            let mut left = size;
+            while left > 0 {
+                let n = left.min(CHUNK);
+                if stream.write_all(&payload[..n]).await.is_err() {
+                    return;
+                }
+                left -= n;
+            }
+        });
+    }
+}
+
+// Tải "/big", trả về số byte body và thời gian
+async fn download(addr: SocketAddr) -> (usize, Duration) {
+    let started = Instant::now();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /big HTTP/1.1\r\nHost: bench\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut buf = vec![0u8; CHUNK];
+    let mut head = Vec::new();
+    // None: chưa đọc hết header
+    let mut body = None;
+    loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        match body.as_mut() {
+            Some(body) => *body += n,
+            None => {
+                head.extend_from_slice(&buf[..n]);
+                if let Some(pos) = head.windows(4).position(|w| w == b"\r\n\r\n") {
+                    body = Some(head.len() - pos - 4);
+                }
+            }
+        }
+    }
+    let body = body.unwrap_or(0);
+    (body, started.elapsed())
+}
+
+async fn measure(name: &str, addr: SocketAddr, size: usize, rounds: usize) {
+    let mut best = f64::MAX;
+    let mut total = 0.0;
+    for _ in 0..rounds {
+        let (bytes, elapsed) = download(addr).await;
+        assert_eq!(bytes, size, "{}: body thiếu byte", name);
+        best = best.min(elapsed.as_secs_f64());
+        total += elapsed.as_secs_f64();
+    }
+    let gbps = |secs: f64| size as f64 * 8.0 / secs / 1e9;
+    println!(
+        "{:<8} {:>6} MB x {}: trung bình {:>6.2} Gbps, tốt nhất {:>6.2} Gbps",
+        name,
+        size / (1024 * 1024),
+        rounds,
+        gbps(total / rounds as f64),
+        gbps(best)
+    );
+}
+
+struct LoadBalancer {
+    child: Child,
+    dir: PathBuf,
+}
+
+impl Drop for LoadBalancer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn start_load_balancer(backend: SocketAddr, port: u16) -> LoadBalancer {
+    let dir = std::env::temp_dir().join(format!("lb-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("servers.json"),
+        format!(r#"[{{ "url": "http://{}" }}]"#, backend),
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("config.toml"),
+        format!("[listen]\naddresses = [\"127.0.0.1:{}\"]\n", port),
+    )
+    .unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_rust-load-balancer"))
+        .current_dir(&dir)
+        .env("RUST_LOG", "warn")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("không chạy được binary load balancer");
+    LoadBalancer { child, dir }
+}
+
+// Chờ load balancer nhận kết nối và health check đánh dấu backend UP
+async fn wait_ready(addr: SocketAddr) {
+    for _ in 0..100 {
+        if let Ok(mut stream) = TcpStream::connect(addr).await {
+            let _ = stream.write_all(b"GET /ping HTTP/1.1\r\nHost: bench\r\nConnection: close\r\n\r\n").await;
+            let mut response = Vec::new();
+            let _ = stream.read_to_end(&mut response).await;
+            if response.starts_with(b"HTTP/1.1 200") {
+                return;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("load balancer không sẵn sàng");
+}
+
+#[tokio::main]
+async fn main() {
+    let size = env_or("BENCH_MB", 1024) * 1024 * 1024;
+    let rounds = env_or("BENCH_ROUNDS", 5);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_addr = listener.local_addr().unwrap();
+    tokio::spawn(backend(listener, size));
+
+    let port = free_port();
+    let _lb = start_load_balancer(backend_addr, port);
+    let lb_addr = SocketAddr::from(([127, 0, 0, 1], port));
+    wait_ready(lb_addr).await;
+
+    measure("direct", backend_addr, size, rounds).await;
+    measure("proxied", lb_addr, size, rounds).await;
+}
@@ -0,0 +1,56 @@
+// Xác thực API quản trị (bật/tắt backend, bỏ cấm IP, log level, debug): "Authorization: Bearer <admin.token>".
+// Các route này chỉ có trên listener routes = "admin" và không bật CORS, không có [admin] thì luôn bị từ chối.
+use crate::{basic_auth::constant_time_eq, config::AdminConfig};
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+// Response từ chối, None nếu token hợp lệ
+pub fn check(config: Option<&AdminConfig>, headers: &HeaderMap) -> Option<Response> {
+    let Some(config) = config else {
+        return Some((StatusCode::FORBIDDEN, "API quản trị bị tắt (chưa cấu hình [admin])").into_response());
+    };
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer ").or_else(|| v.strip_prefix("bearer ")))
+        .map(str::trim);
+    match token {
+        Some(token) if token_matches(config, token) => None,
+        _ => {
+            let mut resp = (StatusCode::UNAUTHORIZED, "Cần admin token").into_response();
+            resp.headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            Some(resp)
+        }
+    }
+}
+
+// So digest để thời gian so sánh không phụ thuộc độ dài token
+pub fn token_matches(config: &AdminConfig, token: &str) -> bool {
+    constant_time_eq(&Sha256::digest(token), &Sha256::digest(&config.token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::config::AdminConfig;
+    use axum::http::{header, HeaderMap, StatusCode};
+
+    #[test]
+    fn requires_configured_token() {
+        let config = AdminConfig { token: "0123456789abcdef".to_string() };
+        let mut headers = HeaderMap::new();
+        assert_eq!(check(Some(&config), &headers).unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        headers.insert(header::AUTHORIZATION, "Bearer sai-token".parse().unwrap());
+        assert_eq!(check(Some(&config), &headers).unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        headers.insert(header::AUTHORIZATION, "Bearer 0123456789abcdef".parse().unwrap());
+        assert!(check(Some(&config), &headers).is_none());
+        // Chưa cấu hình [admin]: luôn từ chối
+        assert_eq!(check(None, &headers).unwrap().status(), StatusCode::FORBIDDEN);
+    }
+}
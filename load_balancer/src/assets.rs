@@ -0,0 +1,39 @@
+// File tĩnh của dashboard (thư mục assets/) được nhúng vào binary lúc build,
+// phục vụ tại /load-balancer/assets/*. Khi build debug rust-embed đọc thẳng từ đĩa.
+use axum::{
+    extract::Path,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use rust_embed::RustEmbed;
+
+#[derive(RustEmbed)]
+#[folder = "assets/"]
+struct Assets;
+
+pub async fn handler(Path(path): Path<String>, headers: HeaderMap) -> Response {
+    let Some(file) = Assets::get(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let etag = format!(
+        "\"{}\"",
+        file.metadata.sha256_hash().iter().map(|b| format!("{:02x}", b)).collect::<String>()
+    );
+    let etag = HeaderValue::from_str(&etag).unwrap();
+    if headers.get(header::IF_NONE_MATCH) == Some(&etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    let content_type = HeaderValue::from_str(file.metadata.mimetype())
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+    (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, HeaderValue::from_static("public, max-age=3600")),
+        ],
+        file.data,
+    )
+        .into_response()
+}
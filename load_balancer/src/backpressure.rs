@@ -0,0 +1,247 @@
+// Đệm body giữa client và backend theo watermark ([streaming] trong config.toml), áp dụng cho cả hai chiều.
+// Phía gửi được đọc trước tối đa high_watermark_kb trong lúc phía nhận còn chậm (giải phóng backend sớm
+// với response ngắn, chịu được phía nhận khựng trong chốc lát). Đệm đủ high thì ngừng đọc hẳn: phía gửi bị
+// TCP backpressure như bình thường, tới khi phía nhận lấy bớt xuống low_watermark_kb mới đọc tiếp.
+// Phía nhận ngắt (client đóng kết nối / backend lỗi) thì ngừng đọc và bỏ phía gửi ngay.
+use crate::config::StreamingConfig;
+use axum::{body::Bytes, BoxError};
+use futures::{task::AtomicWaker, Stream, StreamExt};
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+use tokio::sync::Notify;
+
+type Chunk = Result<Bytes, BoxError>;
+type Source = Pin<Box<dyn Stream<Item = Chunk> + Send>>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    // Client -> backend
+    Request,
+    // Backend -> client
+    Response,
+}
+
+pub struct Watermarks {
+    high: usize,
+    low: usize,
+    // Số lần phải ngừng đọc vì phía nhận chậm
+    request_pauses: AtomicU64,
+    response_pauses: AtomicU64,
+}
+
+impl Watermarks {
+    pub fn new(config: &StreamingConfig) -> Self {
+        Self {
+            high: config.high_watermark_kb * 1024,
+            low: config.low_watermark_kb * 1024,
+            request_pauses: AtomicU64::new(0),
+            response_pauses: AtomicU64::new(0),
+        }
+    }
+
+    fn pauses_counter(&self, direction: Direction) -> &AtomicU64 {
+        match direction {
+            Direction::Request => &self.request_pauses,
+            Direction::Response => &self.response_pauses,
+        }
+    }
+
+    pub fn pauses(&self, direction: Direction) -> u64 {
+        self.pauses_counter(direction).load(Ordering::Relaxed)
+    }
+
+    // Chỉ bắt đầu đọc phía gửi khi phía nhận poll lần đầu
+    pub fn wrap<S, E>(self: &Arc<Self>, body: S, direction: Direction) -> Buffered
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        Buffered {
+            watermarks: self.clone(),
+            direction,
+            source: Some(Box::pin(body.map(|chunk| chunk.map_err(Into::into)))),
+            shared: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    chunks: VecDeque<Chunk>,
+    // Tổng số byte đang đệm
+    bytes: usize,
+    // Phía gửi đã hết (hoặc lỗi)
+    finished: bool,
+    // Phía nhận đã bỏ stream
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    // Phía nhận chờ chunk mới
+    reader: AtomicWaker,
+    // Phía gửi chờ phía nhận lấy bớt xuống low watermark
+    space: Notify,
+    // Phía nhận bỏ stream trong lúc đang chờ phía gửi
+    closed: Notify,
+}
+
+pub struct Buffered {
+    watermarks: Arc<Watermarks>,
+    direction: Direction,
+    source: Option<Source>,
+    shared: Option<Arc<Shared>>,
+}
+
+async fn pump(mut source: Source, shared: Arc<Shared>, watermarks: Arc<Watermarks>, direction: Direction) {
+    let mut paused = false;
+    loop {
+        // Đủ high watermark: ngừng đọc tới khi phía nhận lấy bớt xuống low watermark
+        loop {
+            let space = shared.space.notified();
+            {
+                let queue = shared.queue.lock().unwrap();
+                if queue.closed {
+                    return;
+                }
+                let full = if paused { queue.bytes > watermarks.low } else { queue.bytes >= watermarks.high };
+                if !full {
+                    paused = false;
+                    break;
+                }
+            }
+            if !paused {
+                paused = true;
+                watermarks.pauses_counter(direction).fetch_add(1, Ordering::Relaxed);
+            }
+            space.await;
+        }
+
+        let chunk = tokio::select! {
+            chunk = source.next() => chunk,
+            _ = shared.closed.notified() => return,
+        };
+        let finished = {
+            let mut queue = shared.queue.lock().unwrap();
+            if queue.closed {
+                return;
+            }
+            match chunk {
+                Some(Ok(bytes)) => {
+                    queue.bytes += bytes.len();
+                    queue.chunks.push_back(Ok(bytes));
+                }
+                Some(Err(e)) => {
+                    queue.chunks.push_back(Err(e));
+                    queue.finished = true;
+                }
+                None => queue.finished = true,
+            }
+            queue.finished
+        };
+        shared.reader.wake();
+        if finished {
+            return;
+        }
+    }
+}
+
+impl Stream for Buffered {
+    type Item = Chunk;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(source) = this.source.take() {
+            let shared = Arc::new(Shared::default());
+            tokio::spawn(pump(source, shared.clone(), this.watermarks.clone(), this.direction));
+            this.shared = Some(shared);
+        }
+        let Some(shared) = &this.shared else {
+            return Poll::Ready(None);
+        };
+
+        shared.reader.register(cx.waker());
+        let mut queue = shared.queue.lock().unwrap();
+        match queue.chunks.pop_front() {
+            Some(chunk) => {
+                if let Ok(bytes) = &chunk {
+                    queue.bytes -= bytes.len();
+                }
+                if queue.bytes <= this.watermarks.low {
+                    drop(queue);
+                    shared.space.notify_one();
+                }
+                Poll::Ready(Some(chunk))
+            }
+            None if queue.finished => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Buffered {
+    fn drop(&mut self) {
+        if let Some(shared) = &self.shared {
+            shared.queue.lock().unwrap().closed = true;
+            shared.closed.notify_one();
+            shared.space.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Direction, Watermarks};
+    use crate::config::StreamingConfig;
+    use axum::body::Bytes;
+    use futures::StreamExt;
+    use std::{
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    // Backend gửi liên tục chunk 1 KB, client đọc chậm: high = 4 KB, low = 1 KB
+    #[tokio::test]
+    async fn slow_reader_pauses_at_high_and_resumes_at_low() {
+        let watermarks = Arc::new(Watermarks::new(&StreamingConfig { high_watermark_kb: 4, low_watermark_kb: 1 }));
+        let read = Arc::new(AtomicUsize::new(0));
+        let counter = read.clone();
+        let upstream = futures::stream::repeat_with(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, Infallible>(Bytes::from(vec![0u8; 1024]))
+        });
+        let mut body = watermarks.wrap(upstream, Direction::Response);
+        let settle = || tokio::time::sleep(Duration::from_millis(50));
+
+        // Poll lần đầu để bắt đầu đọc backend, client chưa lấy gì: đệm tới high (4 chunk) thì ngừng đọc
+        assert!(futures::poll!(body.next()).is_pending());
+        settle().await;
+        assert_eq!(read.load(Ordering::SeqCst), 4);
+        assert_eq!(watermarks.pauses(Direction::Response), 1);
+
+        // Client lấy 2 chunk, còn 2 KB > low: vẫn ngừng
+        body.next().await.unwrap().unwrap();
+        body.next().await.unwrap().unwrap();
+        settle().await;
+        assert_eq!(read.load(Ordering::SeqCst), 4);
+
+        // Xuống 1 KB (low): đọc tiếp tới high rồi lại ngừng
+        body.next().await.unwrap().unwrap();
+        settle().await;
+        assert_eq!(read.load(Ordering::SeqCst), 7);
+        assert_eq!(watermarks.pauses(Direction::Response), 2);
+        assert_eq!(watermarks.pauses(Direction::Request), 0);
+    }
+}
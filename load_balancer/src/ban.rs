@@ -0,0 +1,136 @@
+// Danh sách IP bị cấm tạm thời (trong bộ nhớ).
+// IP vi phạm WAF / giới hạn tần suất quá `threshold` lần trong `window_secs` thì bị cấm,
+// thời gian cấm tăng gấp đôi mỗi lần tái phạm. Client IPv6 bị tính và bị cấm theo cả dải /ipv6_prefix.
+use crate::{client_limits::client_key, config::BanConfig};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+struct Entry {
+    // Bắt đầu cửa sổ đếm vi phạm và số vi phạm trong cửa sổ
+    window_start: Instant,
+    strikes: u32,
+    banned_until: Option<Instant>,
+    // Số lần đã bị cấm (để tính thời gian cấm lần sau)
+    bans: u32,
+    last_seen: Instant,
+}
+
+#[derive(Serialize)]
+pub struct BanInfo {
+    // Địa chỉ mạng của dải bị cấm với IPv6
+    pub ip: IpAddr,
+    pub prefix_len: u8,
+    pub remaining_secs: u64,
+    pub bans: u32,
+}
+
+pub struct BanList {
+    config: BanConfig,
+    entries: Mutex<HashMap<IpAddr, Entry>>,
+}
+
+impl BanList {
+    pub fn new(config: BanConfig) -> Self {
+        Self { config, entries: Mutex::new(HashMap::new()) }
+    }
+
+    // Thời gian cấm còn lại của IP (None nếu không bị cấm)
+    pub fn banned_for(&self, ip: IpAddr) -> Option<Duration> {
+        let ip = self.key(ip);
+        let entries = self.entries.lock().unwrap();
+        let until = entries.get(&ip)?.banned_until?;
+        until.checked_duration_since(Instant::now())
+    }
+
+    // Ghi nhận một lần vi phạm
+    pub fn strike(&self, ip: IpAddr, reason: &str) {
+        let ip = self.key(ip);
+        let now = Instant::now();
+        let window = Duration::from_secs(self.config.window_secs);
+        let mut entries = self.entries.lock().unwrap();
+        // Dọn IP đã im lặng đủ lâu để map không phình mãi
+        if entries.len() > 1000 {
+            entries.retain(|_, e| !self.forgotten(e, now));
+        }
+        if entries.get(&ip).is_some_and(|e| self.forgotten(e, now)) {
+            entries.remove(&ip);
+        }
+
+        let entry = entries.entry(ip).or_insert(Entry {
+            window_start: now,
+            strikes: 0,
+            banned_until: None,
+            bans: 0,
+            last_seen: now,
+        });
+        entry.last_seen = now;
+        if entry.banned_until.is_some_and(|until| until > now) {
+            return;
+        }
+        if now.duration_since(entry.window_start) >= window {
+            entry.window_start = now;
+            entry.strikes = 0;
+        }
+        entry.strikes += 1;
+
+        if entry.strikes >= self.config.threshold {
+            let secs = self
+                .config
+                .base_ban_secs
+                .saturating_mul(1u64 << entry.bans.min(32))
+                .min(self.config.max_ban_secs);
+            entry.bans += 1;
+            entry.strikes = 0;
+            entry.banned_until = Some(now + Duration::from_secs(secs));
+            warn!("🚫 Cấm {}/{} trong {}s (lần {}, lý do: {})", ip, self.prefix_len(ip), secs, entry.bans, reason);
+        }
+    }
+
+    pub fn list(&self) -> Vec<BanInfo> {
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap();
+        let mut list: Vec<BanInfo> = entries
+            .iter()
+            .filter_map(|(ip, e)| {
+                let remaining = e.banned_until?.checked_duration_since(now)?;
+                Some(BanInfo {
+                    ip: *ip,
+                    prefix_len: self.prefix_len(*ip),
+                    remaining_secs: remaining.as_secs(),
+                    bans: e.bans,
+                })
+            })
+            .collect();
+        list.sort_by_key(|b| std::cmp::Reverse(b.remaining_secs));
+        list
+    }
+
+    // Gỡ cấm thủ công (IPv6: cả dải chứa IP), xoá luôn lịch sử vi phạm
+    pub fn unban(&self, ip: IpAddr) -> bool {
+        self.entries.lock().unwrap().remove(&self.key(ip)).is_some()
+    }
+
+    fn key(&self, ip: IpAddr) -> IpAddr {
+        client_key(ip, self.config.ipv6_prefix)
+    }
+
+    fn prefix_len(&self, key: IpAddr) -> u8 {
+        if key.is_ipv4() {
+            32
+        } else {
+            self.config.ipv6_prefix
+        }
+    }
+
+    fn forgotten(&self, entry: &Entry, now: Instant) -> bool {
+        let quiet_since = entry.banned_until.map_or(entry.last_seen, |until| until.max(entry.last_seen));
+        now.checked_duration_since(quiet_since)
+            .is_some_and(|quiet| quiet >= Duration::from_secs(self.config.forget_after_secs))
+    }
+}
@@ -0,0 +1,329 @@
+// Bảo vệ một số path bằng HTTP Basic auth (user/hash kiểu htpasswd) ngay tại load balancer,
+// dùng để chặn truy cập vào backend staging mà không phải sửa backend.
+use crate::config::BasicAuthConfig;
+use axum::{
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::Semaphore;
+use tracing::{debug, info};
+
+// bcrypt tốn ~50-100ms CPU mỗi lần -> nhớ các Authorization header đã kiểm tra đúng
+const VERIFIED_TTL: Duration = Duration::from_secs(300);
+// Số Authorization header nhớ tối đa mỗi mục, đầy thì bỏ mục lâu không dùng nhất
+const MAX_VERIFIED: usize = 10_000;
+
+// Authorization header đã kiểm tra đúng
+struct Verified {
+    user: String,
+    at: Instant,
+    last_used: Instant,
+}
+
+const APR1_MAGIC: &str = "$apr1$";
+const ITOA64: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+struct Rule {
+    paths: Vec<String>,
+    realm: String,
+    users: HashMap<String, String>,
+    user_header: HeaderName,
+    // HMAC-SHA256(Authorization header) -> user; không giữ password / hash yếu của nó trong bộ nhớ
+    verified: Mutex<HashMap<[u8; 32], Verified>>,
+}
+
+pub struct Guard {
+    rules: Vec<Arc<Rule>>,
+    // Key HMAC của cache, ngẫu nhiên mỗi lần chạy
+    cache_key: [u8; 32],
+    // Giới hạn số lần kiểm tra bcrypt chạy cùng lúc: đoán password dồn dập không chiếm hết blocking pool
+    verify_permits: Arc<Semaphore>,
+}
+
+impl Guard {
+    pub fn new(configs: &[BasicAuthConfig]) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for config in configs {
+            let mut users: HashMap<String, String> = HashMap::new();
+            if let Some(path) = &config.htpasswd_file {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Không đọc được {}: {}", path.display(), e))?;
+                users.extend(parse_htpasswd(&content));
+            }
+            users.extend(config.users.iter().map(|(u, h)| (u.clone(), h.clone())));
+
+            for (user, hash) in &users {
+                if !is_supported_hash(hash) {
+                    return Err(format!("basic_auth: hash của user '{}' không được hỗ trợ (dùng bcrypt, $apr1$ hoặc {{SHA}})", user));
+                }
+            }
+
+            info!("🔒 Basic auth: {:?} ({} user)", config.paths, users.len());
+            rules.push(Arc::new(Rule {
+                paths: config.paths.clone(),
+                realm: config.realm.clone(),
+                users,
+                // Đã kiểm tra trong config::validate
+                user_header: HeaderName::from_bytes(config.user_header.as_bytes()).unwrap(),
+                verified: Mutex::new(HashMap::new()),
+            }));
+        }
+        let parallelism = std::thread::available_parallelism().map_or(4, |n| n.get());
+        Ok(Self {
+            rules,
+            cache_key: rand::random(),
+            verify_permits: Arc::new(Semaphore::new(parallelism)),
+        })
+    }
+
+    // Header username do guard đặt, phải xóa khỏi request của client để không bị giả mạo
+    pub fn identity_header_names(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|r| r.user_header.as_str())
+    }
+
+    fn cache_key(&self, authorization: &str) -> [u8; 32] {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.cache_key).expect("HMAC nhận key mọi độ dài");
+        mac.update(authorization.as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    // Ok(None): path không cần đăng nhập.
+    // Ok(Some(header)): đăng nhập đúng, header username gửi lên backend (Authorization phải bị xóa).
+    // Err(401): thiếu hoặc sai user/password.
+    pub async fn check(&self, path: &str, headers: &HeaderMap) -> Result<Option<(HeaderName, HeaderValue)>, Response> {
+        let Some(rule) = self
+            .rules
+            .iter()
+            .find(|r| r.paths.iter().any(|p| path.starts_with(p.as_str())))
+        else {
+            return Ok(None);
+        };
+
+        let Some(raw) = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) else {
+            return Err(unauthorized(&rule.realm));
+        };
+
+        let key = self.cache_key(raw);
+        let cached = {
+            let mut verified = rule.verified.lock().unwrap();
+            match verified.get_mut(&key) {
+                Some(entry) if entry.at.elapsed() < VERIFIED_TTL => {
+                    entry.last_used = Instant::now();
+                    Some(entry.user.clone())
+                }
+                Some(_) => {
+                    verified.remove(&key);
+                    None
+                }
+                None => None,
+            }
+        };
+
+        let user = match cached {
+            Some(user) => user,
+            None => {
+                let Some((user, password)) = parse_basic(raw) else {
+                    return Err(unauthorized(&rule.realm));
+                };
+                let Ok(permit) = self.verify_permits.clone().acquire_owned().await else {
+                    return Err(unauthorized(&rule.realm));
+                };
+                let rule_clone = rule.clone();
+                let user_clone = user.clone();
+                let ok = tokio::task::spawn_blocking(move || {
+                    let _permit = permit;
+                    rule_clone
+                        .users
+                        .get(&user_clone)
+                        .is_some_and(|hash| verify_password(&password, hash))
+                })
+                .await
+                .unwrap_or(false);
+
+                if !ok {
+                    debug!("Basic auth sai cho user '{}'", user);
+                    return Err(unauthorized(&rule.realm));
+                }
+                rule.remember(key, &user);
+                user
+            }
+        };
+
+        Ok(HeaderValue::from_str(&user).ok().map(|v| (rule.user_header.clone(), v)))
+    }
+}
+
+impl Rule {
+    fn remember(&self, key: [u8; 32], user: &str) {
+        let now = Instant::now();
+        let mut verified = self.verified.lock().unwrap();
+        if verified.len() >= MAX_VERIFIED && !verified.contains_key(&key) {
+            verified.retain(|_, entry| entry.at.elapsed() < VERIFIED_TTL);
+            if verified.len() >= MAX_VERIFIED {
+                if let Some(oldest) = verified.iter().min_by_key(|(_, entry)| entry.last_used).map(|(k, _)| *k) {
+                    verified.remove(&oldest);
+                }
+            }
+        }
+        verified.insert(key, Verified { user: user.to_string(), at: now, last_used: now });
+    }
+}
+
+fn unauthorized(realm: &str) -> Response {
+    let mut resp = (StatusCode::UNAUTHORIZED, "Cần đăng nhập").into_response();
+    if let Ok(value) = HeaderValue::from_str(&format!("Basic realm=\"{}\", charset=\"UTF-8\"", realm.replace('"', ""))) {
+        resp.headers_mut().insert(header::WWW_AUTHENTICATE, value);
+    }
+    resp
+}
+
+fn parse_basic(raw: &str) -> Option<(String, String)> {
+    let (scheme, encoded) = raw.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+// Mỗi dòng "user:hash", bỏ qua dòng trống và comment
+fn parse_htpasswd(content: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_once(':'))
+        .map(|(u, h)| (u.to_string(), h.to_string()))
+}
+
+fn is_supported_hash(hash: &str) -> bool {
+    hash.starts_with("$2") || hash.starts_with(APR1_MAGIC) || hash.starts_with("{SHA}")
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    if hash.starts_with("$2") {
+        bcrypt::verify(password, hash).unwrap_or(false)
+    } else if let Some(rest) = hash.strip_prefix(APR1_MAGIC) {
+        let salt = rest.split('$').next().unwrap_or("");
+        constant_time_eq(apr1(password, salt).as_bytes(), hash.as_bytes())
+    } else if let Some(expected) = hash.strip_prefix("{SHA}") {
+        let digest = STANDARD.encode(Sha1::digest(password.as_bytes()));
+        constant_time_eq(digest.as_bytes(), expected.as_bytes())
+    } else {
+        false
+    }
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// MD5 của Apache (htpasswd -m, mặc định của htpasswd)
+fn apr1(password: &str, salt: &str) -> String {
+    let pw = password.as_bytes();
+    let salt = &salt.as_bytes()[..salt.len().min(8)];
+
+    let mut alt = md5::Context::new();
+    alt.consume(pw);
+    alt.consume(salt);
+    alt.consume(pw);
+    let alt = alt.compute().0;
+
+    let mut ctx = md5::Context::new();
+    ctx.consume(pw);
+    ctx.consume(APR1_MAGIC.as_bytes());
+    ctx.consume(salt);
+    let mut remaining = pw.len();
+    while remaining > 0 {
+        let n = remaining.min(16);
+        ctx.consume(&alt[..n]);
+        remaining -= n;
+    }
+    let mut i = pw.len();
+    while i > 0 {
+        if i & 1 == 1 {
+            ctx.consume([0u8]);
+        } else {
+            ctx.consume(&pw[..1]);
+        }
+        i >>= 1;
+    }
+    let mut digest = ctx.compute().0;
+
+    for round in 0..1000 {
+        let mut ctx = md5::Context::new();
+        if round & 1 == 1 {
+            ctx.consume(pw);
+        } else {
+            ctx.consume(digest);
+        }
+        if round % 3 != 0 {
+            ctx.consume(salt);
+        }
+        if round % 7 != 0 {
+            ctx.consume(pw);
+        }
+        if round & 1 == 1 {
+            ctx.consume(digest);
+        } else {
+            ctx.consume(pw);
+        }
+        digest = ctx.compute().0;
+    }
+
+    let mut out = format!("{}{}$", APR1_MAGIC, String::from_utf8_lossy(salt));
+    let mut to64 = |mut v: u32, n: usize| {
+        for _ in 0..n {
+            out.push(ITOA64[(v & 0x3f) as usize] as char);
+            v >>= 6;
+        }
+    };
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        to64(((digest[a] as u32) << 16) | ((digest[b] as u32) << 8) | digest[c] as u32, 4);
+    }
+    to64(digest[11] as u32, 2);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rule, MAX_VERIFIED};
+    use axum::http::HeaderName;
+    use std::{collections::HashMap, sync::Mutex};
+
+    #[test]
+    fn verified_cache_is_bounded() {
+        let rule = Rule {
+            paths: vec!["/".to_string()],
+            realm: String::new(),
+            users: HashMap::new(),
+            user_header: HeaderName::from_static("x-user"),
+            verified: Mutex::new(HashMap::new()),
+        };
+        let key = |i: usize| {
+            let mut key = [0u8; 32];
+            key[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            key
+        };
+        // key(0) lâu không dùng nhất
+        rule.remember(key(0), "alice");
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        for i in 1..=MAX_VERIFIED {
+            rule.remember(key(i), "alice");
+        }
+        let verified = rule.verified.lock().unwrap();
+        assert_eq!(verified.len(), MAX_VERIFIED);
+        assert!(!verified.contains_key(&key(0)));
+        assert!(verified.contains_key(&key(MAX_VERIFIED)));
+    }
+}
@@ -0,0 +1,119 @@
+// Chặn bot theo User-Agent (exact / prefix / regex) và xử lý request không có User-Agent.
+use crate::config::{BotsConfig, EmptyUaAction};
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use rand::{distributions::Alphanumeric, Rng};
+use regex::Regex;
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tracing::debug;
+
+const CHALLENGE_COOKIE: &str = "lb_ua_challenge";
+
+// Số request bị chặn theo từng lý do, xuất ra /load-balancer/metrics
+#[derive(Default)]
+pub struct Counters {
+    pub exact: AtomicU64,
+    pub prefix: AtomicU64,
+    pub regex: AtomicU64,
+    pub empty_ua: AtomicU64,
+    pub challenged: AtomicU64,
+}
+
+pub struct Filter {
+    exact: HashSet<String>,
+    prefixes: Vec<String>,
+    regexes: Vec<Regex>,
+    empty_ua: EmptyUaAction,
+    // Bí mật để tạo cookie challenge, đổi mỗi lần khởi động
+    secret: String,
+    pub counters: Counters,
+}
+
+impl Filter {
+    // Regex đã được kiểm tra trong config::validate
+    pub fn new(config: &BotsConfig) -> Self {
+        Self {
+            exact: config.deny_exact.iter().cloned().collect(),
+            prefixes: config.deny_prefix.iter().map(|p| p.to_lowercase()).collect(),
+            regexes: config.deny_regex.iter().map(|p| Regex::new(p).unwrap()).collect(),
+            empty_ua: config.empty_ua,
+            secret: rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect(),
+            counters: Counters::default(),
+        }
+    }
+
+    pub fn is_active(config: &BotsConfig) -> bool {
+        !config.deny_exact.is_empty()
+            || !config.deny_prefix.is_empty()
+            || !config.deny_regex.is_empty()
+            || config.empty_ua != EmptyUaAction::Allow
+    }
+
+    // None: cho qua. Some(response): 403 hoặc trang challenge
+    pub fn check(&self, ip: IpAddr, headers: &HeaderMap) -> Option<Response> {
+        let ua = headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .unwrap_or("");
+
+        if ua.is_empty() {
+            return match self.empty_ua {
+                EmptyUaAction::Allow => None,
+                EmptyUaAction::Block => {
+                    self.counters.empty_ua.fetch_add(1, Ordering::Relaxed);
+                    Some(forbidden())
+                }
+                EmptyUaAction::Challenge => {
+                    let token = self.challenge_token(ip);
+                    if crate::get_cookie(headers, CHALLENGE_COOKIE) == Some(token.as_str()) {
+                        None
+                    } else {
+                        self.counters.challenged.fetch_add(1, Ordering::Relaxed);
+                        Some(challenge_page(&token))
+                    }
+                }
+            };
+        }
+
+        let counter = if self.exact.contains(ua) {
+            &self.counters.exact
+        } else if self.prefixes.iter().any(|p| ua.to_lowercase().starts_with(p.as_str())) {
+            &self.counters.prefix
+        } else if self.regexes.iter().any(|re| re.is_match(ua)) {
+            &self.counters.regex
+        } else {
+            return None;
+        };
+
+        debug!("🤖 Chặn User-Agent '{}' từ {}", ua, ip);
+        counter.fetch_add(1, Ordering::Relaxed);
+        Some(forbidden())
+    }
+
+    // Cookie gắn với IP để không dùng lại được ở máy khác
+    fn challenge_token(&self, ip: IpAddr) -> String {
+        format!("{:x}", md5::compute(format!("{}:{}", self.secret, ip)))
+    }
+}
+
+fn forbidden() -> Response {
+    (StatusCode::FORBIDDEN, "User-Agent bị chặn").into_response()
+}
+
+fn challenge_page(token: &str) -> Response {
+    let body = format!(
+        "<!DOCTYPE html><html><body><noscript>Cần bật JavaScript.</noscript>\
+         <script>document.cookie=\"{}={}; path=/; SameSite=Lax\";location.reload();</script></body></html>",
+        CHALLENGE_COOKIE, token
+    );
+    let mut resp = (StatusCode::FORBIDDEN, Html(body)).into_response();
+    resp.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    resp
+}
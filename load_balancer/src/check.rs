@@ -0,0 +1,169 @@
+// `load_balancer check`: kiểm tra config.toml và servers.json rồi thoát, không chạy load balancer.
+// Ngoài lỗi cú pháp / giá trị (như khi khởi động) còn kiểm tra các lỗi chỉ lộ ra lúc chạy:
+// route / variant / SLO trỏ tới pool không tồn tại, backend trùng lặp, route không bao giờ khớp,
+// CIDR của trusted_proxies, file cert TLS, database GeoIP. Exit code 1 nếu có lỗi (dùng trong CI/CD).
+use crate::{config, geoip, pools, tls, trusted_proxies};
+use std::{collections::HashSet, path::Path};
+
+#[derive(Default)]
+struct Report {
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Report {
+    fn error(&mut self, message: String) {
+        self.errors.push(message);
+    }
+
+    fn warning(&mut self, message: String) {
+        self.warnings.push(message);
+    }
+}
+
+// Route `later` không bao giờ được chọn nếu `earlier` (đứng trước) khớp mọi request mà `later` khớp
+fn shadows(earlier: &config::Route, later: &config::Route) -> bool {
+    let host = match (&earlier.host, &later.host) {
+        (None, _) => true,
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        (Some(_), None) => false,
+    };
+    let path = match (&earlier.path_prefix, &later.path_prefix) {
+        (None, _) => true,
+        (Some(a), Some(b)) => b.starts_with(a.as_str()),
+        (Some(_), None) => false,
+    };
+    let countries = match (&earlier.countries, &later.countries) {
+        (None, _) => true,
+        (Some(a), Some(b)) => b.iter().all(|c| a.iter().any(|x| x.eq_ignore_ascii_case(c))),
+        (Some(_), None) => false,
+    };
+    host && path && countries
+}
+
+fn describe(route: &config::Route) -> String {
+    let mut parts = vec![format!("pool={}", route.pool)];
+    if let Some(host) = &route.host {
+        parts.push(format!("host={}", host));
+    }
+    if let Some(prefix) = &route.path_prefix {
+        parts.push(format!("path_prefix={}", prefix));
+    }
+    if let Some(countries) = &route.countries {
+        parts.push(format!("countries={}", countries.join(",")));
+    }
+    parts.join(" ")
+}
+
+fn check_config(config: &config::Config, report: &mut Report) {
+    if let Err(e) = trusted_proxies::TrustedProxies::new(&config.trusted_proxies) {
+        report.error(format!("trusted_proxies: {}", e));
+    }
+    if let Some(tls_config) = &config.tls {
+        let vault_pki = config.vault.as_ref().is_some_and(|v| v.pki.is_some());
+        if !vault_pki {
+            if let Err(e) = tls::load_files(tls_config) {
+                report.error(format!("tls: {}", e));
+            }
+        }
+        for (i, entry) in tls_config.certificates.iter().enumerate() {
+            if let Err(e) = tls::load_pair(&entry.cert_file, &entry.key_file) {
+                report.error(format!("tls.certificates[{}]: {}", i, e));
+            }
+        }
+    }
+    if let Some(geoip_config) = &config.geoip {
+        if let Err(e) = geoip::Locator::new(geoip_config) {
+            report.error(format!("geoip: {}", e));
+        }
+    }
+
+    let routes = &config.routing.routes;
+    for (j, later) in routes.iter().enumerate() {
+        if let Some(i) = routes[..j].iter().position(|earlier| shadows(earlier, later)) {
+            report.warning(format!(
+                "routing.routes[{}] ({}) không bao giờ khớp: routes[{}] ({}) đứng trước đã khớp mọi request của nó",
+                j,
+                describe(later),
+                i,
+                describe(&routes[i])
+            ));
+        }
+    }
+}
+
+fn check_servers(config: &config::Config, servers: &Path, report: &mut Report) {
+    let data = match std::fs::read_to_string(servers) {
+        Ok(data) => data,
+        Err(e) => {
+            report.error(format!("không đọc được {}: {}", servers.display(), e));
+            return;
+        }
+    };
+    let pools = match pools::parse(&data, &config.timeouts) {
+        Ok(pools) => pools,
+        Err(e) => {
+            report.error(format!("{} không hợp lệ: {}", servers.display(), e));
+            return;
+        }
+    };
+
+    for pool in &pools {
+        if pool.servers.is_empty() {
+            report.warning(format!("pool {} không có backend nào", pool.name));
+        }
+        let mut seen = HashSet::new();
+        for server in &pool.servers {
+            if !seen.insert(server.url.trim_end_matches('/')) {
+                report.error(format!("pool {}: backend {} khai báo trùng", pool.name, server.url));
+            }
+        }
+    }
+
+    let known = |name: &str| pools.iter().any(|p| p.name == name);
+    for (i, route) in config.routing.routes.iter().enumerate() {
+        if !known(&route.pool) {
+            report.error(format!("routing.routes[{}]: pool {} không có trong {}", i, route.pool, servers.display()));
+        }
+    }
+    if let Some(name) = config.routing.default_pool.as_deref().filter(|name| !known(name)) {
+        report.error(format!("routing.default_pool: pool {} không có trong {}", name, servers.display()));
+    }
+    if let Some(experiment) = &config.experiment {
+        for variant in experiment.variants.iter().filter(|v| !known(&v.pool)) {
+            report.error(format!("experiment {} / variant {}: pool {} không có trong {}", experiment.name, variant.name, variant.pool, servers.display()));
+        }
+    }
+    for slo in config.slo.iter().filter(|s| !known(&s.pool)) {
+        report.error(format!("slo {}: pool {} không có trong {}", slo.name, slo.pool, servers.display()));
+    }
+}
+
+// Trả về exit code
+pub fn run(config_path: &Path, servers: &Path) -> i32 {
+    let mut report = Report::default();
+    if !config_path.exists() {
+        report.warning(format!("không có {}, dùng cấu hình mặc định", config_path.display()));
+    }
+    match config::load(config_path) {
+        Ok(config) => {
+            check_config(&config, &mut report);
+            check_servers(&config, servers, &mut report);
+        }
+        Err(e) => report.error(e),
+    }
+
+    for warning in &report.warnings {
+        println!("⚠️  {}", warning);
+    }
+    for error in &report.errors {
+        println!("❌ {}", error);
+    }
+    if report.errors.is_empty() {
+        println!("✅ {} và {} hợp lệ ({} cảnh báo)", config_path.display(), servers.display(), report.warnings.len());
+        0
+    } else {
+        println!("{} lỗi, {} cảnh báo", report.errors.len(), report.warnings.len());
+        1
+    }
+}
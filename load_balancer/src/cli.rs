@@ -0,0 +1,132 @@
+// Tham số dòng lệnh
+use clap::{Parser, Subcommand, ValueEnum};
+use std::{io::IsTerminal, net::SocketAddr, path::PathBuf};
+
+#[derive(Debug, Parser)]
+#[command(name = "load_balancer", version, about = "Load balancer (Rust/Axum)")]
+pub struct Cli {
+    /// Đường dẫn file cấu hình (không có file thì dùng mặc định)
+    #[arg(long, global = true, default_value = "config.toml")]
+    pub config: PathBuf,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Chạy dưới dạng Windows service: `--service` (do SCM gọi),
+    /// `--service install` để đăng ký, `--service uninstall` để gỡ
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "run")]
+    pub service: Option<ServiceAction>,
+
+    /// Chạy nền (Unix): fork, ghi pid file và chuyển log vào file
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Đường dẫn pid file khi chạy --daemon
+    #[arg(long, default_value = "load_balancer.pid")]
+    pub pid_file: PathBuf,
+
+    /// Thư mục chứa file log khi chạy --daemon
+    #[arg(long, default_value = "logs")]
+    pub log_dir: PathBuf,
+
+    /// Không vẽ bảng trạng thái (xoá màn hình mỗi 5 giây), thay bằng dòng log tóm tắt định kỳ.
+    /// Tự bật khi stdout không phải terminal (systemd, docker, chuyển hướng ra file)
+    #[arg(long)]
+    pub no_tui: bool,
+
+    /// Không in trạng thái backend ra console, chỉ còn log
+    #[arg(long, conflicts_with = "no_tui")]
+    pub quiet: bool,
+
+    /// Định dạng log: text (dễ đọc) hoặc json (mỗi dòng một object, cho Loki / ELK)
+    #[arg(long, value_enum, default_value = "text")]
+    pub log_format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+// Cách hiển thị trạng thái backend ra console khi đang chạy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Console {
+    // Bảng trạng thái vẽ lại toàn màn hình
+    Tui,
+    // Dòng log tóm tắt định kỳ
+    Log,
+    Off,
+}
+
+impl Cli {
+    pub fn console(&self) -> Console {
+        // Chạy nền thì không có terminal, log đã ghi vào file
+        if self.quiet || self.daemon {
+            Console::Off
+        } else if self.no_tui || !std::io::stdout().is_terminal() {
+            Console::Log
+        } else {
+            Console::Tui
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Kiểm tra config.toml và servers.json rồi thoát (exit code 1 nếu có lỗi), dùng trong CI/CD
+    Check {
+        /// Đường dẫn servers.json
+        #[arg(long, default_value = "servers.json")]
+        servers: PathBuf,
+    },
+    /// Tạo config.toml + servers.json khởi đầu (thiếu --backend thì hỏi từng bước)
+    Init {
+        /// Địa chỉ nhận request (mặc định 0.0.0.0:8080)
+        #[arg(long)]
+        listen: Option<SocketAddr>,
+        /// Đặt dashboard / API / metrics trên địa chỉ riêng, vd. 127.0.0.1:9000
+        #[arg(long)]
+        admin: Option<SocketAddr>,
+        /// URL backend (lặp lại cho nhiều backend)
+        #[arg(long = "backend")]
+        backends: Vec<String>,
+        /// Path health check (mặc định /healthz)
+        #[arg(long)]
+        health_path: Option<String>,
+        /// Thư mục ghi config.toml và servers.json
+        #[arg(long, default_value = ".")]
+        out_dir: PathBuf,
+        /// Ghi đè config.toml / servers.json đã có
+        #[arg(long)]
+        force: bool,
+    },
+    /// Chuyển cấu hình nginx / HAProxy sang config.toml + servers.json
+    Import {
+        /// File cấu hình nginx (nginx.conf) hoặc HAProxy (haproxy.cfg)
+        #[arg(long)]
+        from: PathBuf,
+        /// Định dạng file nguồn (mặc định: đoán theo nội dung)
+        #[arg(long, value_enum)]
+        format: Option<crate::import::Format>,
+        /// Thư mục ghi config.toml và servers.json
+        #[arg(long, default_value = ".")]
+        out_dir: PathBuf,
+        /// Ghi đè config.toml / servers.json đã có
+        #[arg(long)]
+        force: bool,
+    },
+    /// Nâng cấp process đang chạy lên binary mới mà không rớt kết nối (cần mục [handoff], Unix)
+    Upgrade {
+        /// Binary mới (mặc định: chính binary đang chạy lệnh này)
+        #[arg(long)]
+        binary: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ServiceAction {
+    Run,
+    Install,
+    Uninstall,
+}
@@ -0,0 +1,116 @@
+// Giới hạn số kết nối và số request đang xử lý đồng thời của mỗi IP client.
+// Vượt giới hạn thì trả 429 (lớp phòng thủ đầu tiên trước các đợt flood đơn giản).
+// Client IPv6 được đếm theo dải /ipv6_prefix: đổi địa chỉ trong cùng /64 không lách được giới hạn.
+use crate::config::ClientLimitsConfig;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv6Addr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Connection,
+    Request,
+}
+
+#[derive(Default)]
+pub struct Limiter {
+    max_connections: usize,
+    max_requests: usize,
+    ipv6_prefix: u8,
+    connections: Mutex<HashMap<IpAddr, usize>>,
+    requests: Mutex<HashMap<IpAddr, usize>>,
+    // Số lần từ chối, xuất ra /load-balancer/metrics
+    pub rejected_connections: AtomicU64,
+    pub rejected_requests: AtomicU64,
+}
+
+// Giữ chỗ cho 1 kết nối / request, tự trả lại khi drop
+pub struct Permit {
+    limiter: Arc<Limiter>,
+    ip: IpAddr,
+    kind: Kind,
+}
+
+impl Limiter {
+    pub fn new(config: &ClientLimitsConfig) -> Self {
+        Self {
+            max_connections: config.max_connections_per_ip,
+            max_requests: config.max_requests_per_ip,
+            ipv6_prefix: config.ipv6_prefix,
+            ..Default::default()
+        }
+    }
+
+    pub fn try_connection(self: &Arc<Self>, ip: IpAddr) -> Option<Permit> {
+        self.acquire(ip, Kind::Connection)
+    }
+
+    pub fn try_request(self: &Arc<Self>, ip: IpAddr) -> Option<Permit> {
+        self.acquire(ip, Kind::Request)
+    }
+
+    fn table(&self, kind: Kind) -> (&Mutex<HashMap<IpAddr, usize>>, usize, &AtomicU64) {
+        match kind {
+            Kind::Connection => (&self.connections, self.max_connections, &self.rejected_connections),
+            Kind::Request => (&self.requests, self.max_requests, &self.rejected_requests),
+        }
+    }
+
+    fn acquire(self: &Arc<Self>, ip: IpAddr, kind: Kind) -> Option<Permit> {
+        let ip = client_key(ip, self.ipv6_prefix);
+        let (table, max, rejected) = self.table(kind);
+        let mut counts = table.lock().unwrap();
+        let count = counts.entry(ip).or_insert(0);
+        if max > 0 && *count >= max {
+            rejected.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        *count += 1;
+        Some(Permit { limiter: self.clone(), ip, kind })
+    }
+}
+
+// Khoá đếm / cấm của client: IPv4 (kể cả IPv4-mapped) giữ nguyên, IPv6 lấy địa chỉ mạng của dải /prefix
+pub fn client_key(ip: IpAddr, ipv6_prefix: u8) -> IpAddr {
+    match ip.to_canonical() {
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(ipv6_prefix.min(128))).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask))
+        }
+        v4 => v4,
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let (table, _, _) = self.limiter.table(self.kind);
+        let mut counts = table.lock().unwrap();
+        if let Some(count) = counts.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::client_key;
+    use std::net::IpAddr;
+
+    #[test]
+    fn ipv6_clients_share_their_prefix() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        assert_eq!(client_key(ip("2001:db8:1:2:aaaa::1"), 64), ip("2001:db8:1:2::"));
+        assert_eq!(client_key(ip("2001:db8:1:2:bbbb::9"), 64), ip("2001:db8:1:2::"));
+        assert_eq!(client_key(ip("2001:db8:1:2:aaaa::1"), 128), ip("2001:db8:1:2:aaaa::1"));
+        assert_eq!(client_key(ip("203.0.113.7"), 64), ip("203.0.113.7"));
+        assert_eq!(client_key(ip("::ffff:203.0.113.7"), 64), ip("203.0.113.7"));
+    }
+}
@@ -0,0 +1,317 @@
+// Đẩy metrics lên AWS CloudWatch (PutMetricData, query API ký SigV4) để alarm trực tiếp
+// trên EC2 / ECS mà không cần dựng thêm hệ thống metrics.
+//
+// Credentials lấy theo thứ tự: config.toml -> biến môi trường AWS_* -> ECS task role -> EC2 instance profile.
+use crate::{
+    config::CloudWatchConfig,
+    metrics::{BackendSample, TrafficWindow},
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, time::Duration};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+// Giới hạn số metric trong một request PutMetricData
+const BATCH_SIZE: usize = 1000;
+
+// Ký tự không cần encode theo SigV4 (RFC 3986 unreserved)
+const AWS_ENCODE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+const IMDS: &str = "http://169.254.169.254";
+const ECS_CREDENTIALS: &str = "http://169.254.170.2";
+
+#[derive(Clone)]
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    // None = không hết hạn (key tĩnh)
+    expires: Option<DateTime<Utc>>,
+}
+
+// Định dạng JSON của ECS / EC2 metadata
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RoleCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: String,
+    expiration: DateTime<Utc>,
+}
+
+impl From<RoleCredentials> for Credentials {
+    fn from(c: RoleCredentials) -> Self {
+        Self {
+            access_key_id: c.access_key_id,
+            secret_access_key: c.secret_access_key,
+            session_token: Some(c.token),
+            expires: Some(c.expiration),
+        }
+    }
+}
+
+struct Datum {
+    name: &'static str,
+    unit: &'static str,
+    value: f64,
+    pool: String,
+    backend: String,
+}
+
+pub struct Exporter {
+    config: CloudWatchConfig,
+    endpoint: reqwest::Url,
+    client: reqwest::Client,
+    traffic: TrafficWindow,
+    credentials: Mutex<Option<Credentials>>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC nhận key mọi độ dài");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, AWS_ENCODE).to_string()
+}
+
+impl Exporter {
+    pub fn new(config: CloudWatchConfig) -> Self {
+        let endpoint = config
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://monitoring.{}.amazonaws.com/", config.region));
+        info!("📈 Đẩy metrics lên CloudWatch ({}) namespace {}", endpoint, config.namespace);
+        Self {
+            // Đã kiểm tra trong config::validate (endpoint tự ghép từ region luôn hợp lệ)
+            endpoint: reqwest::Url::parse(&endpoint).unwrap(),
+            client: reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap(),
+            traffic: TrafficWindow::default(),
+            credentials: Mutex::new(None),
+            config,
+        }
+    }
+
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_secs(self.config.flush_interval_secs.max(1))
+    }
+
+    pub fn record(&self, pool: &str, backend: Option<&str>, status: u16, elapsed: Duration) {
+        self.traffic.record(pool, backend, status, elapsed);
+    }
+
+    async fn credentials(&self) -> Result<Credentials, String> {
+        let mut cached = self.credentials.lock().await;
+        // Làm mới trước khi hết hạn 5 phút
+        let fresh = cached
+            .as_ref()
+            .is_some_and(|c| c.expires.is_none_or(|t| t - chrono::Duration::minutes(5) > Utc::now()));
+        if !fresh {
+            *cached = Some(self.load_credentials().await?);
+        }
+        Ok(cached.clone().unwrap())
+    }
+
+    async fn load_credentials(&self) -> Result<Credentials, String> {
+        if let (Some(id), Some(secret)) = (&self.config.access_key_id, &self.config.secret_access_key) {
+            return Ok(Credentials {
+                access_key_id: id.clone(),
+                secret_access_key: secret.clone(),
+                session_token: None,
+                expires: None,
+            });
+        }
+        if let (Ok(id), Ok(secret)) = (std::env::var("AWS_ACCESS_KEY_ID"), std::env::var("AWS_SECRET_ACCESS_KEY")) {
+            return Ok(Credentials {
+                access_key_id: id,
+                secret_access_key: secret,
+                session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+                expires: None,
+            });
+        }
+
+        // ECS task role
+        if let Ok(path) = std::env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI") {
+            let creds: RoleCredentials = self
+                .client
+                .get(format!("{}{}", ECS_CREDENTIALS, path))
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| format!("ECS credentials: {}", e))?
+                .json()
+                .await
+                .map_err(|e| format!("ECS credentials: {}", e))?;
+            return Ok(creds.into());
+        }
+
+        // EC2 instance profile (IMDSv2)
+        let imds = async {
+            let token = self
+                .client
+                .put(format!("{}/latest/api/token", IMDS))
+                .header("X-aws-ec2-metadata-token-ttl-seconds", "21600")
+                .timeout(Duration::from_secs(2))
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            let get = |path: String| {
+                self.client
+                    .get(format!("{}/latest/meta-data/iam/security-credentials/{}", IMDS, path))
+                    .header("X-aws-ec2-metadata-token", &token)
+                    .timeout(Duration::from_secs(2))
+                    .send()
+            };
+            let role = get(String::new()).await?.error_for_status()?.text().await?;
+            let role = role.lines().next().unwrap_or_default().to_string();
+            get(role).await?.error_for_status()?.json::<RoleCredentials>().await
+        };
+        imds.await
+            .map(Credentials::from)
+            .map_err(|e| format!("không tìm thấy AWS credentials (config, env, ECS, EC2 metadata): {}", e))
+    }
+
+    fn data(&self, backends: &[BackendSample]) -> Vec<Datum> {
+        // Gộp các nhóm status theo (pool, backend)
+        let mut traffic: BTreeMap<(String, String), (u64, u64, f64)> = BTreeMap::new();
+        for t in self.traffic.take() {
+            let entry = traffic.entry((t.pool, t.backend)).or_default();
+            entry.0 += t.count;
+            if t.status_class == 5 {
+                entry.1 += t.count;
+            }
+            entry.2 += t.latency_ms_avg * t.count as f64;
+        }
+
+        let mut data = Vec::new();
+        for ((pool, backend), (count, errors, latency_total)) in traffic {
+            let datum = |name, unit, value| Datum { name, unit, value, pool: pool.clone(), backend: backend.clone() };
+            data.push(datum("RequestCount", "Count", count as f64));
+            data.push(datum("5xxCount", "Count", errors as f64));
+            data.push(datum("RequestLatency", "Milliseconds", latency_total / count as f64));
+        }
+        for b in backends {
+            let datum = |name, unit, value| Datum { name, unit, value, pool: b.pool.clone(), backend: b.url.clone() };
+            data.push(datum("Healthy", "None", b.up as u8 as f64));
+            data.push(datum("ActiveRequests", "Count", b.active as f64));
+            if let Some(ms) = b.response_time {
+                data.push(datum("HealthCheckLatency", "Milliseconds", ms as f64));
+            }
+        }
+        data
+    }
+
+    fn body(&self, data: &[Datum], timestamp: &str) -> String {
+        let mut params = vec![
+            ("Action".to_string(), "PutMetricData".to_string()),
+            ("Version".to_string(), "2010-08-01".to_string()),
+            ("Namespace".to_string(), self.config.namespace.clone()),
+        ];
+        for (i, d) in data.iter().enumerate() {
+            let m = format!("MetricData.member.{}", i + 1);
+            params.push((format!("{}.MetricName", m), d.name.to_string()));
+            params.push((format!("{}.Unit", m), d.unit.to_string()));
+            params.push((format!("{}.Value", m), d.value.to_string()));
+            params.push((format!("{}.Timestamp", m), timestamp.to_string()));
+            params.push((format!("{}.Dimensions.member.1.Name", m), "Pool".to_string()));
+            params.push((format!("{}.Dimensions.member.1.Value", m), d.pool.clone()));
+            params.push((format!("{}.Dimensions.member.2.Name", m), "Backend".to_string()));
+            params.push((format!("{}.Dimensions.member.2.Value", m), d.backend.clone()));
+        }
+        params.iter().map(|(k, v)| format!("{}={}", encode(k), encode(v))).collect::<Vec<_>>().join("&")
+    }
+
+    // Ký request theo AWS Signature Version 4
+    fn sign(&self, creds: &Credentials, body: &str, now: DateTime<Utc>) -> Vec<(&'static str, String)> {
+        let host = match self.endpoint.port() {
+            Some(port) => format!("{}:{}", self.endpoint.host_str().unwrap_or_default(), port),
+            None => self.endpoint.host_str().unwrap_or_default().to_string(),
+        };
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let content_type = "application/x-www-form-urlencoded; charset=utf-8";
+
+        let mut headers = vec![("content-type", content_type.to_string()), ("host", host), ("x-amz-date", amz_date.clone())];
+        if let Some(token) = &creds.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical_headers: String = headers.iter().map(|(k, v)| format!("{}:{}\n", k, v)).collect();
+        let signed_headers = headers.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(";");
+
+        let canonical_request = format!(
+            "POST\n{}\n\n{}\n{}\n{}",
+            self.endpoint.path(),
+            canonical_headers,
+            signed_headers,
+            hex(&Sha256::digest(body.as_bytes()))
+        );
+        let scope = format!("{}/{}/monitoring/aws4_request", date, self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = hmac(format!("AWS4{}", creds.secret_access_key).as_bytes(), &date);
+        let key = hmac(&key, &self.config.region);
+        let key = hmac(&key, "monitoring");
+        let key = hmac(&key, "aws4_request");
+        let signature = hex(&hmac(&key, &string_to_sign));
+
+        headers.retain(|(k, _)| *k != "host");
+        headers.push((
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                creds.access_key_id, scope, signed_headers, signature
+            ),
+        ));
+        headers
+    }
+
+    pub async fn flush(&self, backends: &[BackendSample]) {
+        let data = self.data(backends);
+        if data.is_empty() {
+            return;
+        }
+        let creds = match self.credentials().await {
+            Ok(creds) => creds,
+            Err(e) => {
+                warn!("⚠️ CloudWatch: {}", e);
+                return;
+            }
+        };
+
+        let now = Utc::now();
+        let timestamp = now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        for batch in data.chunks(BATCH_SIZE) {
+            let body = self.body(batch, &timestamp);
+            let mut request = self.client.post(self.endpoint.clone());
+            for (name, value) in self.sign(&creds, &body, now) {
+                request = request.header(name, value);
+            }
+            let result = request.body(body).send().await;
+            match result {
+                Ok(res) if res.status().is_success() => {}
+                Ok(res) => {
+                    let status = res.status();
+                    let text = res.text().await.unwrap_or_default();
+                    warn!("⚠️ CloudWatch PutMetricData lỗi {}: {}", status, text.chars().take(300).collect::<String>());
+                }
+                Err(e) => warn!("⚠️ Không gửi được metrics lên CloudWatch: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,1836 @@
+// Cấu hình chung của load balancer (config.toml).
+// Danh sách backend vẫn nằm trong servers.json; file này chứa các tuỳ chọn vận hành.
+// Không có config.toml thì dùng giá trị mặc định cho mọi mục.
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Listener mặc định (dùng [tls] nếu có, phục vụ mọi route) khi không khai báo [[listeners]]
+    pub listen: ListenConfig,
+    // Nhiều listener, mỗi listener một bộ route riêng (vd. :80 proxy, :443 TLS, :9000 admin)
+    pub listeners: Vec<ListenerConfig>,
+    // Dải IP của CDN / reverse proxy phía trước (vd. ["10.0.0.0/8", "2400:cb00::/32"]):
+    // kết nối từ các dải này thì IP client lấy từ X-Forwarded-For
+    pub trusted_proxies: Vec<String>,
+    pub sticky: StickyConfig,
+    pub affinity: AffinityConfig,
+    // Có mục [tls] thì listener phục vụ HTTPS
+    pub tls: Option<TlsConfig>,
+    pub security_headers: SecurityHeadersConfig,
+    // Có mục [oidc] thì mọi request được proxy phải đăng nhập qua OIDC provider
+    pub oidc: Option<OidcConfig>,
+    // Có mục [jwt] thì các path được bảo vệ phải có "Authorization: Bearer <JWT>" hợp lệ
+    pub jwt: Option<JwtConfig>,
+    // Bảo vệ một số path bằng username/password ([[basic_auth]], có thể nhiều mục)
+    pub basic_auth: Vec<BasicAuthConfig>,
+    // Có mục [admin] thì API quản trị (bật/tắt backend, bỏ cấm IP, log level, debug) dùng được trên listener
+    // routes = "admin" với "Authorization: Bearer <token>"; không có thì các API này luôn bị từ chối
+    pub admin: Option<AdminConfig>,
+    // Luật WAF, kiểm tra trước khi proxy ([[waf.rules]])
+    pub waf: WafConfig,
+    // Chặn bot theo User-Agent
+    pub bots: BotsConfig,
+    // Từ chối request sai định dạng (Content-Length / Transfer-Encoding, ký tự lạ trong header, URI tới host khác)
+    pub malformed_requests: MalformedRequestsConfig,
+    // Chống slowloris / slow-read
+    pub slow_clients: SlowClientsConfig,
+    // Keep-alive và tuỳ chọn TCP cho kết nối từ client (áp dụng cho mọi listener)
+    pub connections: ConnectionsConfig,
+    // HTTP/2 với client: chọn qua ALPN trên listener TLS, giới hạn stream của mỗi kết nối
+    pub http2: Http2Config,
+    // Giới hạn kích thước / số header và độ dài URI của request (431 / 414)
+    pub request_limits: RequestLimitsConfig,
+    // Chuẩn hoá path ("..", "//", %2e) trước khi routing
+    pub path_normalization: PathNormalizationConfig,
+    // Có mục [canonicalization] thì chuẩn hoá thêm host / port / "/" cuối path / query param trước khi routing
+    pub canonicalization: Option<CanonicalizationConfig>,
+    // Giới hạn số kết nối / request đồng thời của mỗi IP
+    pub client_limits: ClientLimitsConfig,
+    // Tự động cấm tạm thời IP liên tục vi phạm WAF / giới hạn tần suất
+    pub ban: BanConfig,
+    // Ưu tiên backend cùng region, chỉ tràn sang region khác khi cần
+    pub region: RegionConfig,
+    // Đưa request vào pool backend theo host / path
+    pub routing: RoutingConfig,
+    // Thử nghiệm A/B: chia client vào các variant, mỗi variant một pool backend
+    pub experiment: Option<ExperimentConfig>,
+    // Gửi bản sao một phần traffic tới service khác ([[shadow]]), bỏ qua response
+    pub shadow: Vec<ShadowConfig>,
+    // Route chỉ đọc: gửi thêm tới backend thứ hai khi backend đầu trả lời chậm ([[hedging]])
+    pub hedging: Vec<HedgingConfig>,
+    // Mục tiêu chất lượng dịch vụ theo pool / backend ([[slo]])
+    pub slo: Vec<SloConfig>,
+    pub uptime: UptimeConfig,
+    pub dashboard: DashboardConfig,
+    // Có mục [statsd] thì gửi metrics qua UDP tới StatsD / DogStatsD agent
+    pub statsd: Option<StatsdConfig>,
+    // Có mục [influxdb] thì định kỳ đẩy metrics (line protocol) lên InfluxDB
+    pub influxdb: Option<InfluxConfig>,
+    // Có mục [graphite] thì định kỳ gửi metrics (plaintext protocol) tới Graphite / carbon
+    pub graphite: Option<GraphiteConfig>,
+    // Có mục [cloudwatch] thì định kỳ đẩy metrics lên AWS CloudWatch (PutMetricData)
+    pub cloudwatch: Option<CloudWatchConfig>,
+    // Gọi webhook khi backend chuyển UP <-> DOWN ([[webhooks]])
+    pub webhooks: Vec<WebhookConfig>,
+    // Chờ request đang xử lý xong khi tắt load balancer / tắt backend
+    pub drain: DrainConfig,
+    // Có mục [handoff] thì process mới (cùng config) nhận socket đang listen và sticky / health state
+    // từ process cũ qua control socket, process cũ drain rồi thoát: restart không rớt kết nối (Unix)
+    pub handoff: Option<HandoffConfig>,
+    // Số thread và cách lập lịch của tokio runtime
+    pub runtime: RuntimeConfig,
+    // Timeout khi gọi backend, backend trong servers.json có thể ghi đè ("timeouts")
+    pub timeouts: UpstreamTimeouts,
+    // Có mục [deadline] thì báo backend thời gian còn lại trước khi load balancer bỏ chờ
+    pub deadline: Option<DeadlineConfig>,
+    // Có mục [dns] thì tự phân giải hostname của backend và cache theo TTL
+    pub dns: Option<DnsConfig>,
+    // Có mục [geoip] thì tra quốc gia / vùng của client từ database GeoLite2 (geo routing, chặn theo quốc gia)
+    pub geoip: Option<GeoIpConfig>,
+    // Có mục [response_buffering] thì response nhỏ được gom lại và gửi kèm Content-Length
+    pub response_buffering: Option<ResponseBufferingConfig>,
+    // Có mục [streaming] thì body được đệm giữa client và backend theo high / low watermark (cả hai chiều)
+    pub streaming: Option<StreamingConfig>,
+    // Có mục [vault] thì lấy cert TLS (PKI) và credential backend (KV) từ HashiCorp Vault, tự gia hạn
+    pub vault: Option<VaultConfig>,
+    // Có mục [syslog] thì gửi thêm log tới syslog (RFC 5424) qua UDP / TCP / unix socket, vd. rsyslog
+    pub syslog: Option<SyslogConfig>,
+    // Giới hạn số dòng log lặp lại mỗi giây (vd. "Đã chọn server" khi traffic tăng vọt), đếm số dòng bị bỏ
+    pub log_sampling: LogSamplingConfig,
+    // Ghi một dòng log cho mỗi request đã trả (pool, backend, status, latency).
+    // Luôn bật khi ghi thẳng vào journald (lọc được theo field, vd. STATUS=502)
+    pub access_log: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenConfig {
+    // Địa chỉ IPv4 / IPv6 để nhận kết nối, vd. ["0.0.0.0:8080", "[::]:8080"] cho dual-stack.
+    // Có địa chỉ IPv4 cùng port thì listener IPv6 chỉ nhận IPv6 (IPV6_V6ONLY), nếu không "[::]" nhận cả hai.
+    pub addresses: Vec<SocketAddr>,
+    // Kết nối tới từ L4 load balancer gửi PROXY protocol (v1 / v2) header
+    pub proxy_protocol: bool,
+    // Nhận HTTP/2 không TLS (h2c: prior knowledge hoặc "Upgrade: h2c"), chỉ nên bật trong mạng nội bộ tin cậy
+    pub h2c: bool,
+}
+
+// Nhóm route một listener phục vụ
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListenerRoutes {
+    // Proxy + dashboard / API / metrics
+    #[default]
+    All,
+    // Chỉ proxy tới backend (không lộ /load-balancer/*)
+    Proxy,
+    // Chỉ dashboard / API / metrics
+    Admin,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerConfig {
+    pub addresses: Vec<SocketAddr>,
+    // Dùng chứng chỉ trong [tls]
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default)]
+    pub routes: ListenerRoutes,
+    // Đọc địa chỉ client thật từ PROXY protocol header (listener đứng sau HAProxy / NLB)
+    #[serde(default)]
+    pub proxy_protocol: bool,
+    // HTTP/2 không TLS cho client nội bộ (vd. gRPC), listener không bật chỉ nhận HTTP/1.1
+    #[serde(default)]
+    pub h2c: bool,
+}
+
+impl Config {
+    // [[listeners]] nếu có, ngược lại một listener từ [listen]
+    pub fn listeners(&self) -> Vec<ListenerConfig> {
+        if !self.listeners.is_empty() {
+            return self.listeners.clone();
+        }
+        vec![ListenerConfig {
+            addresses: self.listen.addresses.clone(),
+            tls: self.tls.is_some(),
+            routes: ListenerRoutes::All,
+            proxy_protocol: self.listen.proxy_protocol,
+            h2c: self.listen.h2c,
+        }]
+    }
+}
+
+impl Default for ListenConfig {
+    fn default() -> Self {
+        Self {
+            addresses: vec![SocketAddr::from(([0, 0, 0, 0], 8080))],
+            proxy_protocol: false,
+            h2c: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StickyConfig {
+    // File lưu sticky map để khôi phục sau khi restart (bỏ trống = không lưu)
+    pub persist_file: Option<PathBuf>,
+    // Chu kỳ ghi sticky map xuống file (giây)
+    pub persist_interval_secs: u64,
+}
+
+impl Default for StickyConfig {
+    fn default() -> Self {
+        Self {
+            persist_file: None,
+            persist_interval_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DrainConfig {
+    // Thời gian tối đa chờ request / stream (SSE...) đang chạy, quá thì cắt ngang (giây)
+    pub timeout_secs: u64,
+}
+
+impl Default for DrainConfig {
+    fn default() -> Self {
+        Self { timeout_secs: 30 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    // Số worker thread xử lý kết nối (mặc định: số CPU khả dụng, đã tính giới hạn cgroup / CPU affinity)
+    pub worker_threads: Option<usize>,
+    // Số thread tối đa cho việc blocking (đọc / ghi file, exec health check...)
+    pub max_blocking_threads: usize,
+    // Thread blocking rảnh quá thời gian này thì thoát (giây)
+    pub thread_keep_alive_secs: u64,
+    // Số task một worker chạy giữa hai lần poll IO / timer:
+    // nhỏ thì nhận kết nối / dữ liệu mới nhanh hơn, lớn thì throughput cao hơn khi tải nặng
+    pub event_interval: u32,
+    // Số task giữa hai lần worker lấy việc từ hàng đợi chung (mặc định do tokio tự chỉnh)
+    pub global_queue_interval: Option<u32>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        // Giống mặc định của tokio
+        Self {
+            worker_threads: None,
+            max_blocking_threads: 512,
+            thread_keep_alive_secs: 10,
+            event_interval: 61,
+            global_queue_interval: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HandoffConfig {
+    // Unix socket để process mới xin bàn giao, vd. "/run/load_balancer/handoff.sock"
+    pub socket: PathBuf,
+    // Process cũ chờ process mới báo đã sẵn sàng tối đa bao lâu, quá thì huỷ bàn giao và phục vụ tiếp (giây)
+    #[serde(default = "default_handoff_ready_timeout_secs")]
+    pub ready_timeout_secs: u64,
+    // Binary được phép chạy qua `load_balancer upgrade` ngoài binary đang chạy, vd. ["/opt/lb/releases/current/load_balancer"]
+    #[serde(default)]
+    pub upgrade_binaries: Vec<PathBuf>,
+}
+
+fn default_handoff_ready_timeout_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DnsConfig {
+    // TTL của bản ghi được kẹp trong khoảng này (giây)
+    pub min_ttl_secs: u64,
+    pub max_ttl_secs: u64,
+    // Nhớ lỗi phân giải (NXDOMAIN, timeout...) trong bao lâu
+    pub negative_ttl_secs: u64,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            min_ttl_secs: 5,
+            max_ttl_secs: 300,
+            negative_ttl_secs: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeoIpConfig {
+    // File .mmdb (GeoLite2-Country / GeoLite2-City), tự nạp lại khi file thay đổi
+    pub database: PathBuf,
+    // Chu kỳ kiểm tra file database có thay đổi (giây)
+    #[serde(default = "default_geoip_reload_secs")]
+    pub reload_secs: u64,
+    // Mã quốc gia ISO (vd. "CN"), request từ các quốc gia này bị chặn 403
+    #[serde(default)]
+    pub deny_countries: Vec<String>,
+    // Nếu khai báo thì chỉ nhận request từ các quốc gia này (kể cả IP không tra được quốc gia cũng bị chặn)
+    #[serde(default)]
+    pub allow_countries: Vec<String>,
+}
+
+fn default_geoip_reload_secs() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VaultConfig {
+    // vd. "https://vault.internal:8200"
+    pub address: String,
+    // Token của load balancer (nên dùng "env:VAULT_TOKEN" hoặc "file:...", xem secrets.rs)
+    pub token: String,
+    // Vault Enterprise namespace
+    #[serde(default)]
+    pub namespace: Option<String>,
+    // Chu kỳ đọc lại credential backend ("vault:..." trong auth của servers.json) để nhận giá trị đã xoay vòng
+    #[serde(default = "default_vault_refresh_secs")]
+    pub refresh_secs: u64,
+    // Có mục [vault.pki] thì cert của listener TLS được cấp từ PKI engine thay cho cert_file / key_file
+    #[serde(default)]
+    pub pki: Option<VaultPkiConfig>,
+}
+
+fn default_vault_refresh_secs() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VaultPkiConfig {
+    // Mount của PKI engine
+    #[serde(default = "default_vault_pki_mount")]
+    pub mount: String,
+    pub role: String,
+    pub common_name: String,
+    #[serde(default)]
+    pub alt_names: Vec<String>,
+    // Thời hạn cert, cú pháp của Vault (vd. "72h"); bỏ trống = mặc định của role
+    #[serde(default)]
+    pub ttl: Option<String>,
+}
+
+fn default_vault_pki_mount() -> String {
+    "pki".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponseBufferingConfig {
+    // Response kết thúc trong ngưỡng này (KB) thì gửi một lần kèm Content-Length, lớn hơn thì stream
+    pub threshold_kb: u64,
+    // Chờ body tối đa bấy nhiêu ms, backend trả chậm hơn (long polling, NDJSON...) thì chuyển sang stream
+    pub max_wait_ms: u64,
+}
+
+impl Default for ResponseBufferingConfig {
+    fn default() -> Self {
+        Self {
+            threshold_kb: 64,
+            max_wait_ms: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeadlineConfig {
+    // Header gửi cho request thường (request gRPC luôn dùng grpc-timeout)
+    pub header: String,
+    pub format: DeadlineFormat,
+}
+
+impl Default for DeadlineConfig {
+    fn default() -> Self {
+        Self {
+            header: "x-request-deadline".to_string(),
+            format: DeadlineFormat::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadlineFormat {
+    // Số ms còn lại (không phụ thuộc đồng hồ của backend)
+    #[default]
+    RemainingMs,
+    // Thời điểm hết hạn, Unix timestamp (ms)
+    UnixMs,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StreamingConfig {
+    // Phía nhận chậm: đọc trước phía gửi tối đa bấy nhiêu KB rồi ngừng đọc
+    pub high_watermark_kb: usize,
+    // Phía nhận lấy bớt xuống dưới mức này (KB) thì đọc tiếp
+    pub low_watermark_kb: usize,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            high_watermark_kb: 256,
+            low_watermark_kb: 64,
+        }
+    }
+}
+
+// Đơn vị ms, 0 = không giới hạn
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpstreamTimeouts {
+    // Mở kết nối TCP (+ TLS) tới backend
+    pub connect_ms: u64,
+    // Từ lúc gửi request tới khi nhận đủ response header
+    pub response_header_ms: u64,
+    // Khoảng lặng tối đa giữa hai chunk của response body (mặc định tắt để không cắt SSE / long polling)
+    pub body_idle_ms: u64,
+}
+
+impl Default for UpstreamTimeouts {
+    fn default() -> Self {
+        Self {
+            connect_ms: 5000,
+            response_header_ms: 60000,
+            body_idle_ms: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsdConfig {
+    // host:port của agent
+    pub address: String,
+    // Tiền tố tên metric
+    pub prefix: String,
+    // Gửi tag kiểu DogStatsD (|#pool:api) thay vì ghép pool / backend vào tên metric
+    pub dogstatsd: bool,
+    // Chu kỳ gửi gauge trạng thái backend (giây)
+    pub gauge_interval_secs: u64,
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:8125".to_string(),
+            prefix: "lb".to_string(),
+            dogstatsd: false,
+            gauge_interval_secs: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    // Template payload (cú pháp {{ }} của minijinja), hoặc đọc từ file.
+    // Bỏ trống cả hai: gửi JSON mặc định của sự kiện
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub template_file: Option<PathBuf>,
+    #[serde(default = "default_webhook_content_type")]
+    pub content_type: String,
+    // Header thêm vào request, vd. Authorization
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "default_webhook_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_webhook_content_type() -> String {
+    "application/json".to_string()
+}
+
+fn default_webhook_timeout() -> u64 {
+    5
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CloudWatchConfig {
+    // vd. "ap-southeast-1"
+    pub region: String,
+    #[serde(default = "default_cloudwatch_namespace")]
+    pub namespace: String,
+    #[serde(default = "default_cloudwatch_flush_interval")]
+    pub flush_interval_secs: u64,
+    // Bỏ trống: lấy từ biến môi trường AWS_*, ECS task role hoặc EC2 instance profile
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+    // Ghi đè endpoint (vd. LocalStack), mặc định https://monitoring.<region>.amazonaws.com
+    #[serde(default)]
+    pub endpoint: Option<String>,
+}
+
+fn default_cloudwatch_namespace() -> String {
+    "LoadBalancer".to_string()
+}
+
+fn default_cloudwatch_flush_interval() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphiteConfig {
+    // host:port của carbon (plaintext, TCP)
+    pub address: String,
+    pub prefix: String,
+    pub flush_interval_secs: u64,
+}
+
+impl Default for GraphiteConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:2003".to_string(),
+            prefix: "lb".to_string(),
+            flush_interval_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSamplingConfig {
+    // Mặc định tắt: bật thì log INFO / DEBUG / TRACE lặp lại bị bỏ bớt (trừ access log, luôn được ghi)
+    pub enabled: bool,
+    // Số dòng tối đa mỗi giây của một lệnh log (INFO / DEBUG / TRACE; WARN / ERROR luôn được ghi)
+    pub max_per_second: u32,
+}
+
+impl Default for LogSamplingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_per_second: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyslogConfig {
+    // "udp://host:514", "tcp://host:514" hoặc "unix:///dev/log"
+    pub address: String,
+    pub facility: SyslogFacility,
+    // APP-NAME trong mỗi message
+    pub app_name: String,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            address: "udp://127.0.0.1:514".to_string(),
+            facility: SyslogFacility::Daemon,
+            app_name: "load_balancer".to_string(),
+        }
+    }
+}
+
+// Facility theo RFC 5424 (giá trị là mã số của facility)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogFacility {
+    Kern = 0,
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    Authpriv = 10,
+    Ftp = 11,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InfluxVersion {
+    // /write?db=... (InfluxDB 1.x)
+    V1,
+    // /api/v2/write?org=...&bucket=... với token (InfluxDB 2.x / Cloud)
+    #[default]
+    V2,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InfluxConfig {
+    // URL gốc, vd. "http://influxdb:8086"
+    pub url: String,
+    #[serde(default)]
+    pub version: InfluxVersion,
+    // v1
+    #[serde(default)]
+    pub database: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    // v2
+    #[serde(default)]
+    pub org: Option<String>,
+    #[serde(default)]
+    pub bucket: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default = "default_influx_flush_interval")]
+    pub flush_interval_secs: u64,
+    // Số dòng tối đa mỗi request ghi
+    #[serde(default = "default_influx_batch_size")]
+    pub batch_size: usize,
+}
+
+fn default_influx_flush_interval() -> u64 {
+    10
+}
+
+fn default_influx_batch_size() -> usize {
+    5000
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DashboardConfig {
+    // Thư mục chứa dashboard.html thay cho template mặc định (đọc lại khi restart)
+    pub template_dir: Option<PathBuf>,
+    pub title: String,
+    // Các cột của bảng, theo thứ tự hiển thị
+    pub columns: Vec<String>,
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        Self {
+            template_dir: None,
+            title: "Load Balancer Dashboard (Rust/Axum)".to_string(),
+            columns: crate::dashboard::COLUMNS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UptimeConfig {
+    // File lưu lịch sử health check theo giờ cho báo cáo uptime (bỏ trống = chỉ giữ trong bộ nhớ)
+    pub persist_file: Option<PathBuf>,
+    pub persist_interval_secs: u64,
+}
+
+impl Default for UptimeConfig {
+    fn default() -> Self {
+        Self {
+            persist_file: None,
+            persist_interval_secs: 300,
+        }
+    }
+}
+
+// Cách xác định "cùng một client" cho sticky session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientKey {
+    // Chỉ IP (ổn định khi trình duyệt tự cập nhật User-Agent)
+    Ip,
+    // IP + User-Agent (mặc định, giống bản Node.js)
+    #[default]
+    IpUa,
+    // Giá trị của một header (vd. X-User-Id do gateway phía trước gắn vào)
+    Header,
+    // Giá trị của một cookie (vd. session id) - tốt nhất khi nhiều user chung IP (CGNAT)
+    Cookie,
+}
+
+// Cơ chế giữ client ở lại một backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AffinityMode {
+    // Nhớ client -> backend trong sticky_map (mặc định)
+    #[default]
+    StickyMap,
+    // Rendezvous (HRW) hashing: tính backend từ client key + danh sách backend sống,
+    // không cần lưu gì trong bộ nhớ
+    Rendezvous,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AffinityConfig {
+    pub mode: AffinityMode,
+    pub key: ClientKey,
+    // Tên header / cookie khi key = "header" / "cookie".
+    // Request không có header/cookie đó thì dùng IP.
+    pub name: Option<String>,
+    // Client IPv6 được nhận diện theo prefix (thiết bị đổi địa chỉ tạm thời trong cùng /64)
+    pub ipv6_prefix_len: u8,
+}
+
+impl Default for AffinityConfig {
+    fn default() -> Self {
+        Self {
+            mode: AffinityMode::default(),
+            key: ClientKey::default(),
+            name: None,
+            ipv6_prefix_len: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    // Chứng chỉ (PEM, có thể kèm chain) và private key của listener (bỏ trống khi cert lấy từ [vault.pki])
+    #[serde(default)]
+    pub cert_file: Option<PathBuf>,
+    #[serde(default)]
+    pub key_file: Option<PathBuf>,
+    // CA dùng để xác thực chứng chỉ client (mTLS). Bỏ trống = không yêu cầu client cert
+    pub client_ca_file: Option<PathBuf>,
+    // true: client không gửi cert vẫn được kết nối (cert gửi lên thì vẫn phải hợp lệ)
+    #[serde(default)]
+    pub client_cert_optional: bool,
+    // Header chứa subject của client cert khi chuyển request lên backend
+    #[serde(default = "default_client_cert_header")]
+    pub client_cert_header: String,
+    // OCSP stapling: lấy OCSP response cho cert đang phục vụ (URL responder trong AIA của cert, issuer là cert thứ 2
+    // của chain) và gửi kèm trong handshake, client không phải tự hỏi CA
+    #[serde(default)]
+    pub ocsp_stapling: bool,
+    // Chu kỳ lấy lại OCSP response
+    #[serde(default = "default_ocsp_refresh_secs")]
+    pub ocsp_refresh_secs: u64,
+    // Chu kỳ kiểm tra cert_file / key_file có thay đổi (cert-manager, certbot gia hạn) để nạp lại
+    // mà không restart; 0 = tắt
+    #[serde(default = "default_tls_reload_secs")]
+    pub reload_secs: u64,
+    // Cert thêm, chọn theo SNI client gửi trong handshake; không khớp tên nào thì dùng cert ở trên
+    #[serde(default)]
+    pub certificates: Vec<SniCertConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SniCertConfig {
+    // Tên miền phục vụ bằng cert này, hỗ trợ wildcard "*.example.com"; bỏ trống = lấy các tên DNS trong SAN của cert
+    #[serde(default)]
+    pub server_names: Vec<String>,
+    pub cert_file: PathBuf,
+    pub key_file: PathBuf,
+}
+
+fn default_client_cert_header() -> String {
+    "x-client-cert-subject".to_string()
+}
+
+fn default_ocsp_refresh_secs() -> u64 {
+    3600
+}
+
+fn default_tls_reload_secs() -> u64 {
+    30
+}
+
+// Chính sách header bảo mật trên response trả về client
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityHeadersConfig {
+    // Xóa các header này khỏi response của backend (mặc định giữ hành vi cũ:
+    // bỏ CSP/X-Frame-Options để trình duyệt hiển thị được trang được proxy)
+    pub remove: Vec<String>,
+    // Thêm bộ header chuẩn (X-Content-Type-Options, Referrer-Policy, HSTS khi bật TLS...)
+    pub inject_defaults: bool,
+    // Header tự định nghĩa, chỉ thêm khi response chưa có (ghi đè bộ chuẩn nếu trùng tên).
+    // Muốn thay hẳn giá trị của backend thì cho tên header đó vào cả `remove`.
+    pub add: BTreeMap<String, String>,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            remove: vec!["content-security-policy".to_string(), "x-frame-options".to_string()],
+            inject_defaults: false,
+            add: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OidcConfig {
+    // Issuer của provider, vd. "https://accounts.google.com" (dùng để lấy .well-known/openid-configuration)
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    // URL công khai của load balancer, callback sẽ là {external_url}/load-balancer/oidc/callback
+    pub external_url: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    #[serde(default = "default_oidc_cookie")]
+    pub cookie_name: String,
+    #[serde(default = "default_oidc_session_ttl")]
+    pub session_ttl_secs: u64,
+    // Claim -> header gửi lên backend (mặc định sub/email/name)
+    #[serde(default = "default_claim_headers")]
+    pub claim_headers: BTreeMap<String, String>,
+    // Các path prefix không cần đăng nhập (vd. "/static/")
+    #[serde(default)]
+    pub public_paths: Vec<String>,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".to_string(), "email".to_string(), "profile".to_string()]
+}
+
+fn default_oidc_cookie() -> String {
+    "lb_session".to_string()
+}
+
+fn default_oidc_session_ttl() -> u64 {
+    8 * 3600
+}
+
+fn default_claim_headers() -> BTreeMap<String, String> {
+    [("sub", "x-auth-subject"), ("email", "x-auth-email"), ("name", "x-auth-name")]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JwtConfig {
+    pub jwks_url: String,
+    pub issuer: String,
+    pub audience: String,
+    // Path prefix cần JWT (mặc định: tất cả)
+    #[serde(default = "default_jwt_protected_paths")]
+    pub protected_paths: Vec<String>,
+    // Claim -> header gửi lên backend
+    #[serde(default = "default_claim_headers")]
+    pub claim_headers: BTreeMap<String, String>,
+    // Header chứa toàn bộ claims (JSON, base64url) nếu backend cần thêm claim khác
+    #[serde(default)]
+    pub claims_json_header: Option<String>,
+    // Chu kỳ tải lại JWKS (giây); kid lạ cũng kích hoạt tải lại
+    #[serde(default = "default_jwks_refresh")]
+    pub jwks_refresh_secs: u64,
+    // Độ lệch đồng hồ cho phép khi kiểm tra exp/nbf (giây)
+    #[serde(default = "default_jwt_leeway")]
+    pub leeway_secs: u64,
+    // Thuật toán chữ ký được chấp nhận; "alg" trong header của token chỉ được dùng khi nằm trong danh sách này
+    // (và khớp "alg" của khoá trong JWKS nếu có). Chỉ thuật toán khoá công khai, không nhận HS*
+    #[serde(default = "default_jwt_algorithms")]
+    pub algorithms: Vec<String>,
+}
+
+fn default_jwt_algorithms() -> Vec<String> {
+    ["RS256", "RS384", "RS512", "PS256", "PS384", "PS512", "ES256", "ES384", "EdDSA"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_jwt_protected_paths() -> Vec<String> {
+    vec!["/".to_string()]
+}
+
+fn default_jwks_refresh() -> u64 {
+    300
+}
+
+fn default_jwt_leeway() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    // Token bí mật, nên lấy từ "env:" / "file:" thay vì ghi thẳng
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BasicAuthConfig {
+    // Path prefix cần đăng nhập, vd. ["/staging/"]
+    pub paths: Vec<String>,
+    #[serde(default = "default_basic_auth_realm")]
+    pub realm: String,
+    // File htpasswd (bcrypt "$2y$", apr1 "$apr1$" hoặc "{SHA}")
+    pub htpasswd_file: Option<PathBuf>,
+    // Hoặc khai báo trực tiếp user -> hash (cùng định dạng với htpasswd)
+    #[serde(default)]
+    pub users: BTreeMap<String, String>,
+    // Header chứa username đã đăng nhập khi gửi lên backend
+    #[serde(default = "default_remote_user_header")]
+    pub user_header: String,
+}
+
+fn default_basic_auth_realm() -> String {
+    "Restricted".to_string()
+}
+
+fn default_remote_user_header() -> String {
+    "x-remote-user".to_string()
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WafConfig {
+    pub rules: Vec<WafRule>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WafAction {
+    // Trả 403 ngay
+    #[default]
+    Block,
+    // Chỉ ghi log, vẫn proxy
+    Log,
+    // Cho qua tối đa rate_limit request khớp luật / rate_window_secs cho mỗi IP, vượt thì 429
+    RateLimit,
+}
+
+// Một luật khớp khi TẤT CẢ điều kiện được khai báo đều khớp
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WafRule {
+    pub name: String,
+    // Regex trên path (đã giải mã %xx, khớp bản gốc hoặc bản đã chuẩn hoá dot-segment)
+    pub path: Option<String>,
+    // Regex trên query string (đã giải mã %xx)
+    pub query: Option<String>,
+    // Header cần kiểm tra và regex trên giá trị của nó
+    pub header: Option<String>,
+    pub header_pattern: Option<String>,
+    // Query string trông giống SQL injection (bộ mẫu có sẵn)
+    #[serde(default)]
+    pub sqli: bool,
+    // Body lớn hơn giá trị này (Content-Length, hoặc đếm khi stream body chunked) -> 413 nếu action = "block"
+    pub max_body_bytes: Option<u64>,
+    // Regex trên request body (chỉ áp dụng cho route có body = "buffer")
+    pub body: Option<String>,
+    #[serde(default)]
+    pub action: WafAction,
+    #[serde(default = "default_waf_rate_limit")]
+    pub rate_limit: u32,
+    #[serde(default = "default_waf_rate_window_secs")]
+    pub rate_window_secs: u64,
+}
+
+fn default_waf_rate_limit() -> u32 {
+    60
+}
+
+fn default_waf_rate_window_secs() -> u64 {
+    60
+}
+
+// Xử lý request không có (hoặc rỗng) User-Agent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyUaAction {
+    #[default]
+    Allow,
+    Block,
+    // Trả trang JS đặt cookie rồi tải lại; trình duyệt thật qua được, script đơn giản thì không
+    Challenge,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotsConfig {
+    // User-Agent trùng khớp hoàn toàn
+    pub deny_exact: Vec<String>,
+    // User-Agent bắt đầu bằng (không phân biệt hoa thường)
+    pub deny_prefix: Vec<String>,
+    // Regex trên User-Agent
+    pub deny_regex: Vec<String>,
+    pub empty_ua: EmptyUaAction,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathNormalizationConfig {
+    pub mode: PathMode,
+    // Gộp "/" liên tiếp ("//a///b" -> "/a/b")
+    pub merge_slashes: bool,
+}
+
+impl Default for PathNormalizationConfig {
+    fn default() -> Self {
+        Self {
+            mode: PathMode::Normalize,
+            merge_slashes: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CanonicalizationConfig {
+    // "Example.COM" -> "example.com"
+    pub lowercase_host: bool,
+    // "example.com:80" (HTTP) / "example.com:443" (HTTPS) -> "example.com"
+    pub strip_default_port: bool,
+    // "/a/b/" -> "/a/b" (giữ "/")
+    pub strip_trailing_slash: bool,
+    // Sắp xếp query param theo tên
+    pub sort_query: bool,
+    // Query param bị bỏ, "*" ở cuối để khớp tiền tố (vd. ["utm_*", "fbclid", "gclid"])
+    pub strip_query_params: Vec<String>,
+}
+
+impl Default for CanonicalizationConfig {
+    fn default() -> Self {
+        Self {
+            lowercase_host: true,
+            strip_default_port: true,
+            strip_trailing_slash: false,
+            sort_query: false,
+            strip_query_params: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathMode {
+    // Sửa path rồi xử lý tiếp (backend nhận path đã chuẩn hoá)
+    #[default]
+    Normalize,
+    // Path chưa chuẩn thì trả 400
+    Reject,
+    // Giữ nguyên path (vẫn từ chối path che giấu ".." qua %2f / "\")
+    Off,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MalformedRequestsConfig {
+    pub enabled: bool,
+    // Từ chối cả giá trị header chứa byte ngoài ASCII (obs-text, vd. UTF-8 chưa mã hoá)
+    pub reject_obs_text: bool,
+}
+
+impl Default for MalformedRequestsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            reject_obs_text: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlowClientsConfig {
+    // Thời gian tối đa để nhận đủ header của request (và TLS handshake)
+    pub header_read_timeout_secs: u64,
+    // Thời gian tối đa chờ phần tiếp theo của body request
+    pub body_chunk_timeout_secs: u64,
+    // Tốc độ gửi body tối thiểu (byte/giây), tính sau min_rate_grace_secs; 0 = tắt
+    pub min_body_rate_bytes_per_sec: u64,
+    pub min_rate_grace_secs: u64,
+    // Client không nhận response (ghi bị nghẽn) quá thời gian này thì đóng kết nối
+    pub write_stall_timeout_secs: u64,
+    // Tốc độ nhận response tối thiểu (byte/giây) tính trên thời gian ghi bị nghẽn, sau min_rate_grace_secs;
+    // chặn client đọc nhỏ giọt vừa đủ để không chạm write_stall_timeout_secs. 0 = tắt
+    pub min_response_rate_bytes_per_sec: u64,
+}
+
+impl Default for SlowClientsConfig {
+    fn default() -> Self {
+        Self {
+            header_read_timeout_secs: 15,
+            body_chunk_timeout_secs: 60,
+            min_body_rate_bytes_per_sec: 0,
+            min_rate_grace_secs: 10,
+            write_stall_timeout_secs: 60,
+            min_response_rate_bytes_per_sec: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionsConfig {
+    // HTTP/1.1 keep-alive; false thì đóng kết nối sau mỗi response
+    pub keep_alive: bool,
+    // Kết nối không có request nào đang xử lý quá thời gian này thì đóng (HTTP/1.1 và HTTP/2); 0 = không giới hạn
+    pub keep_alive_timeout_secs: u64,
+    // Đủ số request thì đóng kết nối sau response cuối (client mở kết nối mới); 0 = không giới hạn
+    pub max_requests_per_connection: u64,
+    // Tắt Nagle: gửi response ngay, không gom gói nhỏ
+    pub tcp_nodelay: bool,
+    // Số kết nối chờ accept tối đa trong kernel (listen backlog)
+    pub backlog: i32,
+    // TCP keepalive: gửi probe sau chừng này giây không có dữ liệu (phát hiện client mất kết nối im lặng); 0 = tắt
+    pub tcp_keepalive_secs: u64,
+    // Khoảng cách giữa các probe và số probe trước khi bỏ kết nối; không đặt = mặc định của hệ điều hành
+    pub tcp_keepalive_interval_secs: Option<u64>,
+    pub tcp_keepalive_retries: Option<u32>,
+}
+
+impl Default for ConnectionsConfig {
+    fn default() -> Self {
+        Self {
+            keep_alive: true,
+            keep_alive_timeout_secs: 75,
+            max_requests_per_connection: 0,
+            tcp_nodelay: true,
+            backlog: 1024,
+            tcp_keepalive_secs: 0,
+            tcp_keepalive_interval_secs: None,
+            tcp_keepalive_retries: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Http2Config {
+    // Quảng bá "h2" qua ALPN trên listener TLS (client không hỗ trợ vẫn dùng HTTP/1.1)
+    pub enabled: bool,
+    // Số stream (request) đồng thời tối đa trên một kết nối, vượt quá thì client phải chờ
+    pub max_concurrent_streams: u32,
+    // Flow control: cửa sổ nhận của mỗi stream và của cả kết nối
+    pub initial_stream_window_kb: u32,
+    pub initial_connection_window_kb: u32,
+    // Gửi PING định kỳ để phát hiện kết nối chết, không nhận được ACK sau keep_alive_timeout_secs thì đóng; 0 = tắt
+    pub keep_alive_interval_secs: u64,
+    pub keep_alive_timeout_secs: u64,
+}
+
+impl Default for Http2Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_concurrent_streams: 128,
+            initial_stream_window_kb: 1024,
+            initial_connection_window_kb: 1024,
+            keep_alive_interval_secs: 0,
+            keep_alive_timeout_secs: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RequestLimitsConfig {
+    // Tổng kích thước header (tên + giá trị) của một request, vượt thì trả 431
+    pub max_header_bytes: usize,
+    // Số header tối đa của một request, vượt thì trả 431
+    pub max_headers: usize,
+    // Độ dài tối đa của URI (path + query), vượt thì trả 414
+    pub max_uri_length: usize,
+}
+
+impl Default for RequestLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_header_bytes: 32 * 1024,
+            max_headers: 100,
+            max_uri_length: 8192,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientLimitsConfig {
+    // Số kết nối TCP mở cùng lúc tối đa của một IP (0 = không giới hạn)
+    pub max_connections_per_ip: usize,
+    // Số request đang xử lý cùng lúc tối đa của một IP (0 = không giới hạn)
+    pub max_requests_per_ip: usize,
+    // Client IPv6 được tính theo cả dải /ipv6_prefix (một máy thường có cả /64 để đổi địa chỉ)
+    pub ipv6_prefix: u8,
+}
+
+impl Default for ClientLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_connections_per_ip: 0,
+            max_requests_per_ip: 0,
+            ipv6_prefix: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BanConfig {
+    pub enabled: bool,
+    // Số lần vi phạm trong window_secs thì bị cấm
+    pub threshold: u32,
+    pub window_secs: u64,
+    // Lần cấm đầu tiên; mỗi lần tái phạm thời gian cấm gấp đôi, tối đa max_ban_secs
+    pub base_ban_secs: u64,
+    pub max_ban_secs: u64,
+    // Không tái phạm trong khoảng này (tính từ lúc hết cấm) thì quên lịch sử
+    pub forget_after_secs: u64,
+    // Client IPv6 bị tính và bị cấm theo cả dải /ipv6_prefix
+    pub ipv6_prefix: u8,
+}
+
+impl Default for BanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 10,
+            window_secs: 60,
+            base_ban_secs: 60,
+            max_ban_secs: 86400,
+            forget_after_secs: 86400,
+            ipv6_prefix: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegionConfig {
+    // Region "nhà" (khớp với trường region trong servers.json); bỏ trống = không ưu tiên
+    pub home: Option<String>,
+    // Header chứa region của client (vd. do CDN / GeoIP phía trước gắn), ưu tiên hơn home
+    pub client_header: Option<String>,
+    // Backend có thời gian health check lớn hơn ngưỡng này (ms) coi như quá tải
+    pub overload_latency_ms: Option<u128>,
+    pub spillover: Spillover,
+}
+
+// Khi region ưu tiên hết backend thì tràn sang đâu
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Spillover {
+    // Mọi backend healthy của các region khác
+    #[default]
+    Any,
+    // Region có độ trễ health check trung bình thấp nhất (đo liên tục, tự thích nghi khi region chậm đi)
+    Latency,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoutingConfig {
+    // Pool cho request không khớp route nào (mặc định: pool "default", hoặc pool đầu tiên)
+    pub default_pool: Option<String>,
+    // Cách gửi request body cho request không khớp route nào
+    pub default_body: BodyMode,
+    // Body lớn hơn giới hạn này ở chế độ "buffer" bị từ chối 413
+    pub max_buffered_body_bytes: u64,
+    // Xét theo thứ tự, route đầu tiên khớp thắng
+    pub routes: Vec<Route>,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        Self {
+            default_pool: None,
+            default_body: BodyMode::Stream,
+            max_buffered_body_bytes: 10 * 1024 * 1024,
+            routes: Vec::new(),
+        }
+    }
+}
+
+// Cách gửi request body lên backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyMode {
+    // Chuyển tiếp từng chunk ngay khi nhận (upload lớn, không tốn RAM).
+    // Body đã gửi một phần thì không failover được, WAF không xem được body
+    #[default]
+    Stream,
+    // Đọc hết body trước khi gửi: failover được cả khi backend đã đọc body (POST có Idempotency-Key)
+    // và áp dụng được luật WAF theo body
+    Buffer,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Route {
+    pub pool: String,
+    pub host: Option<String>,
+    pub path_prefix: Option<String>,
+    // Mã quốc gia ISO của client (cần [geoip]), vd. ["VN", "TH"]
+    pub countries: Option<Vec<String>>,
+    #[serde(default)]
+    pub body: BodyMode,
+    // Dung lượng upload tối đa (byte) của route, vượt thì trả 413 (mặc định: không giới hạn)
+    #[serde(default)]
+    pub max_upload_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExperimentConfig {
+    pub name: String,
+    // Chỉ áp dụng cho request có path bắt đầu bằng (mặc định: mọi request)
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    // Cookie lưu variant đã gán cho client
+    #[serde(default = "default_experiment_cookie")]
+    pub cookie_name: String,
+    #[serde(default = "default_experiment_cookie_max_age")]
+    pub cookie_max_age_secs: u64,
+    // Header báo cho backend biết variant của request
+    #[serde(default = "default_experiment_header")]
+    pub variant_header: String,
+    pub variants: Vec<VariantConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VariantConfig {
+    pub name: String,
+    pub pool: String,
+    // Tỉ lệ client mới được gán vào variant (tổng các variant = 100)
+    pub percent: u32,
+}
+
+fn default_experiment_cookie() -> String {
+    "lb_variant".to_string()
+}
+
+fn default_experiment_cookie_max_age() -> u64 {
+    30 * 24 * 3600
+}
+
+fn default_experiment_header() -> String {
+    "x-lb-variant".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShadowConfig {
+    pub path_prefix: String,
+    // URL gốc của service nhận bản sao, vd. "http://search-v2:8080"
+    pub target: String,
+    // Phần trăm request được sao chép (0-100)
+    pub percent: f64,
+    // Timeout riêng của request bản sao (không ảnh hưởng request thật)
+    #[serde(default = "default_shadow_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_shadow_timeout_ms() -> u64 {
+    2000
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HedgingConfig {
+    pub path_prefix: String,
+    // Chờ bao lâu trước khi gửi thêm request thứ hai (ms)
+    pub delay_ms: u64,
+    // Có thì delay = percentile latency gần đây của route (vd. 95), delay_ms chỉ dùng khi chưa đủ mẫu
+    pub percentile: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SloConfig {
+    pub name: String,
+    pub pool: String,
+    // Chỉ tính request tới một backend (URL như trong servers.json), mặc định cả pool
+    #[serde(default)]
+    pub backend: Option<String>,
+    // Mục tiêu latency: `latency_objective`% request xong trong `latency_ms`
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    #[serde(default = "default_slo_objective")]
+    pub latency_objective: f64,
+    // % request không lỗi (5xx / không tới được backend)
+    #[serde(default = "default_slo_objective")]
+    pub error_objective: f64,
+    // Cửa sổ tính mức tuân thủ (mặc định 30 ngày)
+    #[serde(default = "default_slo_window")]
+    pub window_secs: u64,
+    // Cảnh báo khi tốc độ đốt error budget trong `burn_window_secs` vượt ngưỡng
+    #[serde(default = "default_slo_burn_window")]
+    pub burn_window_secs: u64,
+    #[serde(default = "default_slo_burn_threshold")]
+    pub burn_rate_threshold: f64,
+    // POST cảnh báo (JSON) tới URL này, ngoài việc ghi log
+    #[serde(default)]
+    pub webhook: Option<String>,
+}
+
+fn default_slo_objective() -> f64 {
+    99.0
+}
+
+fn default_slo_window() -> u64 {
+    30 * 24 * 3600
+}
+
+fn default_slo_burn_window() -> u64 {
+    300
+}
+
+// Ngưỡng chuẩn: đốt 2% budget 30 ngày trong 1 giờ
+fn default_slo_burn_threshold() -> f64 {
+    14.4
+}
+
+pub fn load(path: &Path) -> Result<Config, String> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(format!("không đọc được {}: {}", path.display(), e)),
+    };
+
+    let mut config: Config =
+        crate::diagnostics::toml(&data).map_err(|e| format!("{} không hợp lệ: {}", path.display(), e))?;
+    resolve_secrets(&mut config).map_err(|e| format!("{}: {}", path.display(), e))?;
+    validate(&config).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(config)
+}
+
+// Thay tham chiếu env:/file:/exec: trong các field bí mật bằng giá trị thật (xem secrets.rs)
+fn resolve_secrets(config: &mut Config) -> Result<(), String> {
+    use crate::secrets::resolve_in_place;
+
+    if let Some(oidc) = &mut config.oidc {
+        resolve_in_place("oidc.client_secret", &mut oidc.client_secret)?;
+    }
+    if let Some(admin) = &mut config.admin {
+        resolve_in_place("admin.token", &mut admin.token)?;
+    }
+    for rule in &mut config.basic_auth {
+        for (user, hash) in &mut rule.users {
+            resolve_in_place(&format!("basic_auth.users.{}", user), hash)?;
+        }
+    }
+    for webhook in &mut config.webhooks {
+        // URL webhook Slack/Discord chứa token
+        resolve_in_place("webhooks.url", &mut webhook.url)?;
+        for (name, value) in &mut webhook.headers {
+            resolve_in_place(&format!("webhooks.headers.{}", name), value)?;
+        }
+    }
+    if let Some(influx) = &mut config.influxdb {
+        for (field, value) in [("influxdb.password", &mut influx.password), ("influxdb.token", &mut influx.token)] {
+            if let Some(value) = value {
+                resolve_in_place(field, value)?;
+            }
+        }
+    }
+    if let Some(vault) = &mut config.vault {
+        resolve_in_place("vault.token", &mut vault.token)?;
+    }
+    if let Some(cw) = &mut config.cloudwatch {
+        for (field, value) in [
+            ("cloudwatch.access_key_id", &mut cw.access_key_id),
+            ("cloudwatch.secret_access_key", &mut cw.secret_access_key),
+        ] {
+            if let Some(value) = value {
+                resolve_in_place(field, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate(config: &Config) -> Result<(), String> {
+    let affinity = &config.affinity;
+    if matches!(affinity.key, ClientKey::Header | ClientKey::Cookie)
+        && affinity.name.as_deref().is_none_or(str::is_empty)
+    {
+        return Err("affinity.name là bắt buộc khi affinity.key = \"header\" hoặc \"cookie\"".to_string());
+    }
+    if affinity.ipv6_prefix_len == 0 || affinity.ipv6_prefix_len > 128 {
+        return Err("affinity.ipv6_prefix_len phải trong khoảng 1-128".to_string());
+    }
+
+    if config.listen.addresses.is_empty() {
+        return Err("listen.addresses không được rỗng".to_string());
+    }
+    for listener in &config.listeners {
+        if listener.addresses.is_empty() {
+            return Err("listeners: addresses không được rỗng".to_string());
+        }
+        if listener.tls && config.tls.is_none() {
+            return Err(format!("listeners {:?}: tls = true cần mục [tls]", listener.addresses));
+        }
+    }
+    // Qua TLS thì HTTP/2 được chọn bằng ALPN ([http2])
+    if let Some(listener) = config.listeners().iter().find(|l| l.h2c && l.tls) {
+        return Err(format!("listeners {:?}: h2c chỉ dùng cho listener không TLS", listener.addresses));
+    }
+
+    // Token ngắn đoán được bằng brute force
+    if config.admin.as_ref().is_some_and(|admin| admin.token.len() < 16) {
+        return Err("admin.token phải dài ít nhất 16 ký tự".to_string());
+    }
+
+    let security = &config.security_headers;
+    for name in security.remove.iter().chain(security.add.keys()) {
+        axum::http::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("security_headers: tên header không hợp lệ: {}", name))?;
+    }
+    for (name, value) in &security.add {
+        axum::http::HeaderValue::from_str(value)
+            .map_err(|_| format!("security_headers.add: giá trị không hợp lệ cho {}", name))?;
+    }
+
+    if let Some(oidc) = &config.oidc {
+        if !oidc.external_url.starts_with("http://") && !oidc.external_url.starts_with("https://") {
+            return Err(format!("oidc.external_url phải là URL đầy đủ: {}", oidc.external_url));
+        }
+        for name in oidc.claim_headers.values() {
+            axum::http::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("oidc.claim_headers: tên header không hợp lệ: {}", name))?;
+        }
+    }
+
+    if let Some(jwt) = &config.jwt {
+        for name in jwt.claim_headers.values().chain(jwt.claims_json_header.iter()) {
+            axum::http::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("jwt: tên header không hợp lệ: {}", name))?;
+        }
+        if jwt.algorithms.is_empty() {
+            return Err("jwt.algorithms không được rỗng".to_string());
+        }
+        for alg in &jwt.algorithms {
+            match alg.parse::<jsonwebtoken::Algorithm>() {
+                Ok(jsonwebtoken::Algorithm::HS256 | jsonwebtoken::Algorithm::HS384 | jsonwebtoken::Algorithm::HS512) => {
+                    return Err(format!("jwt.algorithms: {} dùng khoá bí mật, không kiểm tra được bằng JWKS công khai", alg));
+                }
+                Ok(_) => {}
+                Err(_) => return Err(format!("jwt.algorithms: thuật toán không hợp lệ: {}", alg)),
+            }
+        }
+    }
+
+    for rule in &config.basic_auth {
+        if rule.htpasswd_file.is_none() && rule.users.is_empty() {
+            return Err(format!("basic_auth {:?}: cần htpasswd_file hoặc users", rule.paths));
+        }
+        axum::http::HeaderName::from_bytes(rule.user_header.as_bytes())
+            .map_err(|_| format!("basic_auth.user_header không hợp lệ: {}", rule.user_header))?;
+    }
+
+    for rule in &config.waf.rules {
+        let has_condition = rule.path.is_some()
+            || rule.query.is_some()
+            || rule.header.is_some()
+            || rule.sqli
+            || rule.max_body_bytes.is_some()
+            || rule.body.is_some();
+        if !has_condition {
+            return Err(format!("waf.rules '{}': cần ít nhất một điều kiện", rule.name));
+        }
+        if rule.header.is_some() != rule.header_pattern.is_some() {
+            return Err(format!("waf.rules '{}': header và header_pattern phải đi cùng nhau", rule.name));
+        }
+        if let Some(name) = &rule.header {
+            axum::http::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("waf.rules '{}': tên header không hợp lệ: {}", rule.name, name))?;
+        }
+        for pattern in [&rule.path, &rule.query, &rule.header_pattern, &rule.body].into_iter().flatten() {
+            regex::Regex::new(pattern).map_err(|e| format!("waf.rules '{}': regex không hợp lệ: {}", rule.name, e))?;
+        }
+        if rule.action == WafAction::RateLimit && rule.rate_window_secs == 0 {
+            return Err(format!("waf.rules '{}': rate_window_secs phải > 0", rule.name));
+        }
+    }
+
+    for pattern in &config.bots.deny_regex {
+        regex::Regex::new(pattern).map_err(|e| format!("bots.deny_regex không hợp lệ ({}): {}", pattern, e))?;
+    }
+
+    let slow = &config.slow_clients;
+    if slow.header_read_timeout_secs == 0 || slow.body_chunk_timeout_secs == 0 || slow.write_stall_timeout_secs == 0 {
+        return Err("slow_clients: các timeout phải > 0".to_string());
+    }
+
+    let connections = &config.connections;
+    if connections.backlog <= 0 {
+        return Err("connections.backlog phải > 0".to_string());
+    }
+    if connections.tcp_keepalive_secs == 0
+        && (connections.tcp_keepalive_interval_secs.is_some() || connections.tcp_keepalive_retries.is_some())
+    {
+        return Err("connections: tcp_keepalive_interval_secs / tcp_keepalive_retries cần tcp_keepalive_secs > 0".to_string());
+    }
+    if connections.tcp_keepalive_interval_secs == Some(0) || connections.tcp_keepalive_retries == Some(0) {
+        return Err("connections: tcp_keepalive_interval_secs và tcp_keepalive_retries phải > 0".to_string());
+    }
+
+    let http2 = &config.http2;
+    if http2.max_concurrent_streams == 0 {
+        return Err("http2.max_concurrent_streams phải > 0".to_string());
+    }
+    // Giới hạn của HTTP/2: cửa sổ từ 64KB tới 2^31-1 byte
+    for (name, kb) in [("initial_stream_window_kb", http2.initial_stream_window_kb), ("initial_connection_window_kb", http2.initial_connection_window_kb)] {
+        if !(64..=2_097_151).contains(&kb) {
+            return Err(format!("http2.{} phải trong khoảng 64..2097151", name));
+        }
+    }
+    if http2.keep_alive_interval_secs > 0 && http2.keep_alive_timeout_secs == 0 {
+        return Err("http2.keep_alive_timeout_secs phải > 0".to_string());
+    }
+    if let Some(canonicalization) = &config.canonicalization {
+        if canonicalization.strip_query_params.iter().any(|p| p.is_empty() || p == "*") {
+            return Err("canonicalization.strip_query_params: mẫu rỗng hoặc \"*\" sẽ bỏ mọi query param".to_string());
+        }
+    }
+    let request_limits = &config.request_limits;
+    if request_limits.max_header_bytes < 1024 || request_limits.max_headers == 0 || request_limits.max_uri_length < 256 {
+        return Err("request_limits: cần max_header_bytes >= 1024, max_headers > 0, max_uri_length >= 256".to_string());
+    }
+    if request_limits.max_header_bytes + request_limits.max_uri_length > 16 * 1024 * 1024 {
+        return Err("request_limits: max_header_bytes + max_uri_length không được vượt 16MB".to_string());
+    }
+
+    if config.ban.enabled && (config.ban.threshold == 0 || config.ban.window_secs == 0 || config.ban.base_ban_secs == 0) {
+        return Err("ban: threshold, window_secs, base_ban_secs phải > 0".to_string());
+    }
+    for (section, prefix) in [("ban", config.ban.ipv6_prefix), ("client_limits", config.client_limits.ipv6_prefix)] {
+        if prefix == 0 || prefix > 128 {
+            return Err(format!("{}.ipv6_prefix phải trong khoảng 1-128", section));
+        }
+    }
+
+    if let Some(name) = &config.region.client_header {
+        axum::http::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("region.client_header không hợp lệ: {}", name))?;
+    }
+
+    if let Some(exp) = &config.experiment {
+        if exp.variants.is_empty() || exp.variants.iter().map(|v| v.percent).sum::<u32>() != 100 {
+            return Err(format!("experiment '{}': cần ít nhất 1 variant và tổng percent = 100", exp.name));
+        }
+        for v in &exp.variants {
+            if v.name.is_empty() || !v.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(format!("experiment '{}': tên variant không hợp lệ: {}", exp.name, v.name));
+            }
+        }
+        axum::http::HeaderName::from_bytes(exp.variant_header.as_bytes())
+            .map_err(|_| format!("experiment.variant_header không hợp lệ: {}", exp.variant_header))?;
+    }
+
+    for shadow in &config.shadow {
+        if !(0.0..=100.0).contains(&shadow.percent) {
+            return Err(format!("shadow {}: percent phải trong khoảng 0-100", shadow.path_prefix));
+        }
+        reqwest::Url::parse(&shadow.target).map_err(|e| format!("shadow.target không hợp lệ ({}): {}", shadow.target, e))?;
+    }
+
+    if let Some(dns) = &config.dns {
+        if dns.min_ttl_secs > dns.max_ttl_secs {
+            return Err("dns: min_ttl_secs phải <= max_ttl_secs".to_string());
+        }
+    }
+
+    if let Some(geoip) = &config.geoip {
+        if geoip.reload_secs == 0 {
+            return Err("geoip.reload_secs phải > 0".to_string());
+        }
+        if !geoip.deny_countries.is_empty() && !geoip.allow_countries.is_empty() {
+            return Err("geoip: chỉ dùng một trong deny_countries / allow_countries".to_string());
+        }
+    }
+    if config.response_buffering.as_ref().is_some_and(|b| b.threshold_kb == 0) {
+        return Err("response_buffering.threshold_kb phải > 0".to_string());
+    }
+    if let Some(deadline) = &config.deadline {
+        axum::http::HeaderName::from_bytes(deadline.header.as_bytes())
+            .map_err(|_| format!("deadline.header không hợp lệ: {}", deadline.header))?;
+    }
+    if let Some(streaming) = &config.streaming {
+        if streaming.high_watermark_kb == 0 || streaming.low_watermark_kb >= streaming.high_watermark_kb {
+            return Err("streaming: cần 0 <= low_watermark_kb < high_watermark_kb".to_string());
+        }
+    }
+    if config.routing.max_buffered_body_bytes == 0 {
+        return Err("routing.max_buffered_body_bytes phải > 0".to_string());
+    }
+    if config.routing.routes.iter().any(|r| r.max_upload_bytes == Some(0)) {
+        return Err("routing: max_upload_bytes phải > 0".to_string());
+    }
+    if config.geoip.is_none() && config.routing.routes.iter().any(|r| r.countries.is_some()) {
+        return Err("routing: route theo countries cần mục [geoip]".to_string());
+    }
+
+    for hedging in &config.hedging {
+        if hedging.delay_ms == 0 {
+            return Err(format!("hedging {}: delay_ms phải > 0", hedging.path_prefix));
+        }
+        if hedging.percentile.is_some_and(|p| !(p > 0.0 && p < 100.0)) {
+            return Err(format!("hedging {}: percentile phải trong khoảng (0, 100)", hedging.path_prefix));
+        }
+    }
+
+    for column in &config.dashboard.columns {
+        if !crate::dashboard::COLUMNS.contains(&column.as_str()) {
+            return Err(format!(
+                "dashboard.columns: cột không hợp lệ {} (chọn trong {})",
+                column,
+                crate::dashboard::COLUMNS.join(", ")
+            ));
+        }
+    }
+
+    if let Some(influx) = &config.influxdb {
+        reqwest::Url::parse(&influx.url).map_err(|e| format!("influxdb.url không hợp lệ: {}", e))?;
+        let missing = match influx.version {
+            InfluxVersion::V1 => influx.database.is_none().then_some("database"),
+            InfluxVersion::V2 => [("org", &influx.org), ("bucket", &influx.bucket), ("token", &influx.token)]
+                .into_iter()
+                .find(|(_, v)| v.is_none())
+                .map(|(name, _)| name),
+        };
+        if let Some(field) = missing {
+            return Err(format!("influxdb: thiếu {} cho version {:?}", field, influx.version));
+        }
+        if influx.batch_size == 0 {
+            return Err("influxdb.batch_size phải > 0".to_string());
+        }
+    }
+
+    if let Some(cw) = &config.cloudwatch {
+        if cw.access_key_id.is_some() != cw.secret_access_key.is_some() {
+            return Err("cloudwatch: access_key_id và secret_access_key phải đi cùng nhau".to_string());
+        }
+        if let Some(endpoint) = &cw.endpoint {
+            reqwest::Url::parse(endpoint).map_err(|e| format!("cloudwatch.endpoint không hợp lệ: {}", e))?;
+        }
+    }
+
+    for webhook in &config.webhooks {
+        reqwest::Url::parse(&webhook.url).map_err(|e| format!("webhook {}: URL không hợp lệ: {}", webhook.url, e))?;
+        if webhook.template.is_some() && webhook.template_file.is_some() {
+            return Err(format!("webhook {}: chỉ dùng một trong template / template_file", webhook.url));
+        }
+        for name in webhook.headers.keys() {
+            axum::http::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("webhook {}: tên header không hợp lệ: {}", webhook.url, name))?;
+        }
+    }
+
+    let mut slo_names = std::collections::HashSet::new();
+    for slo in &config.slo {
+        if !slo_names.insert(slo.name.as_str()) {
+            return Err(format!("slo {}: tên bị trùng", slo.name));
+        }
+        for objective in [slo.latency_objective, slo.error_objective] {
+            if !(objective > 0.0 && objective < 100.0) {
+                return Err(format!("slo {}: objective phải trong khoảng (0, 100)", slo.name));
+            }
+        }
+        if slo.burn_window_secs < 60 || slo.window_secs < slo.burn_window_secs {
+            return Err(format!("slo {}: cần 60 <= burn_window_secs <= window_secs", slo.name));
+        }
+        if slo.burn_rate_threshold <= 0.0 {
+            return Err(format!("slo {}: burn_rate_threshold phải > 0", slo.name));
+        }
+        if let Some(url) = &slo.webhook {
+            reqwest::Url::parse(url).map_err(|e| format!("slo {}: webhook không hợp lệ: {}", slo.name, e))?;
+        }
+    }
+
+    let vault_pki = config.vault.as_ref().is_some_and(|v| v.pki.is_some());
+    if let Some(tls) = &config.tls {
+        axum::http::HeaderName::from_bytes(tls.client_cert_header.as_bytes())
+            .map_err(|_| format!("tls.client_cert_header không phải tên header hợp lệ: {}", tls.client_cert_header))?;
+        let files = tls.cert_file.is_some() || tls.key_file.is_some();
+        if vault_pki && files {
+            return Err("tls: cert lấy từ [vault.pki] thì bỏ cert_file / key_file".to_string());
+        }
+        if !vault_pki && (tls.cert_file.is_none() || tls.key_file.is_none()) {
+            return Err("tls: cần cert_file và key_file (hoặc mục [vault.pki])".to_string());
+        }
+        if tls.ocsp_stapling && tls.ocsp_refresh_secs == 0 {
+            return Err("tls.ocsp_refresh_secs phải > 0".to_string());
+        }
+        let mut names = std::collections::HashSet::new();
+        for name in tls.certificates.iter().flat_map(|c| &c.server_names) {
+            let host = name.strip_prefix("*.").unwrap_or(name);
+            if host.is_empty() || host.contains(['*', ':', '/']) {
+                return Err(format!("tls.certificates: server_names không hợp lệ: {}", name));
+            }
+            if !names.insert(name.to_ascii_lowercase()) {
+                return Err(format!("tls.certificates: server_names {} khai báo nhiều lần", name));
+            }
+        }
+    } else if vault_pki {
+        return Err("vault.pki cần mục [tls]".to_string());
+    }
+    if config.log_sampling.enabled && config.log_sampling.max_per_second == 0 {
+        return Err("log_sampling.max_per_second phải > 0 (hoặc enabled = false)".to_string());
+    }
+    if let Some(syslog) = &config.syslog {
+        crate::syslog::Target::parse(&syslog.address).map_err(|e| format!("syslog.address: {}", e))?;
+        if syslog.app_name.is_empty() || !syslog.app_name.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(format!("syslog.app_name không hợp lệ: {:?}", syslog.app_name));
+        }
+    }
+    if let Some(vault) = &config.vault {
+        reqwest::Url::parse(&vault.address).map_err(|e| format!("vault.address không hợp lệ: {}", e))?;
+        if vault.refresh_secs == 0 {
+            return Err("vault.refresh_secs phải > 0".to_string());
+        }
+    }
+    let runtime = &config.runtime;
+    if runtime.worker_threads == Some(0) {
+        return Err("runtime.worker_threads phải > 0".to_string());
+    }
+    if runtime.max_blocking_threads == 0 {
+        return Err("runtime.max_blocking_threads phải > 0".to_string());
+    }
+    if runtime.event_interval == 0 || runtime.global_queue_interval == Some(0) {
+        return Err("runtime.event_interval / global_queue_interval phải > 0".to_string());
+    }
+    if let Some(handoff) = &config.handoff {
+        if cfg!(not(unix)) {
+            return Err("[handoff] chỉ hỗ trợ trên Unix".to_string());
+        }
+        if handoff.ready_timeout_secs == 0 {
+            return Err("handoff.ready_timeout_secs phải > 0".to_string());
+        }
+        if let Some(binary) = handoff.upgrade_binaries.iter().find(|b| !b.is_absolute()) {
+            return Err(format!("handoff.upgrade_binaries: cần đường dẫn tuyệt đối: {}", binary.display()));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+// Chạy nền (--daemon) cho Unix: fork, ghi pid file, chuyển stdout/stderr vào file log.
+// Phải gọi TRƯỚC khi tạo tokio runtime (fork không an toàn khi đã có nhiều thread).
+use daemonize::Daemonize;
+use std::{fs, fs::OpenOptions, path::Path};
+
+pub fn start(pid_file: &Path, log_dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(log_dir)
+        .map_err(|e| format!("không tạo được thư mục log {}: {}", log_dir.display(), e))?;
+
+    let open_log = |name: &str| {
+        let path = log_dir.join(name);
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("không mở được {}: {}", path.display(), e))
+    };
+    let stdout = open_log("load_balancer.out.log")?;
+    let stderr = open_log("load_balancer.err.log")?;
+
+    // Giữ nguyên thư mục làm việc hiện tại để vẫn đọc được servers.json
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+
+    Daemonize::new()
+        .pid_file(pid_file)
+        .working_directory(cwd)
+        .stdout(stdout)
+        .stderr(stderr)
+        .start()
+        .map_err(|e| e.to_string())
+}
+
+// Process mới do `load_balancer upgrade` chạy từ daemon cũ: đã chạy nền sẵn (kế thừa session và
+// stdout/stderr vào file log) nên không fork lại, chỉ ghi pid của mình vào pid file.
+// Daemonize lần nữa sẽ lỗi vì process cũ vẫn giữ lock pid file tới khi drain xong.
+pub fn adopt(pid_file: &Path) -> Result<(), String> {
+    fs::write(pid_file, format!("{}\n", std::process::id()))
+        .map_err(|e| format!("không ghi được pid file {}: {}", pid_file.display(), e))
+}
+
+// Xóa pid file khi thoát bình thường.
+// Sau khi bàn giao ([handoff] / upgrade) pid file đã là của process mới thì giữ nguyên.
+pub fn cleanup(pid_file: &Path) {
+    let ours = fs::read_to_string(pid_file).is_ok_and(|pid| pid.trim() == std::process::id().to_string());
+    if ours {
+        let _ = fs::remove_file(pid_file);
+    }
+}
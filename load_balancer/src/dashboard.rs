@@ -0,0 +1,45 @@
+// Trang dashboard render từ template minijinja. Template mặc định được nhúng vào binary,
+// deployer có thể đặt dashboard.html riêng trong [dashboard] template_dir để đổi giao diện.
+use crate::config::DashboardConfig;
+use minijinja::{context, Environment};
+use tracing::info;
+
+const TEMPLATE_NAME: &str = "dashboard.html";
+const DEFAULT_TEMPLATE: &str = include_str!("../templates/dashboard.html");
+
+// Các cột template mặc định biết cách hiển thị
+pub const COLUMNS: [&str; 9] = [
+    "url", "region", "pool", "health", "uptime", "response_time", "graph", "last_check", "actions",
+];
+
+pub struct Dashboard {
+    // Render sẵn một lần: nội dung động đều đi qua SSE / WebSocket
+    html: String,
+}
+
+impl Dashboard {
+    pub fn new(config: &DashboardConfig) -> Result<Self, String> {
+        let mut env = Environment::new();
+        match &config.template_dir {
+            Some(dir) => {
+                let path = dir.join(TEMPLATE_NAME);
+                let source = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Không đọc được {}: {}", path.display(), e))?;
+                env.add_template_owned(TEMPLATE_NAME, source)
+                    .map_err(|e| format!("Template {} lỗi: {}", path.display(), e))?;
+                info!("🎨 Dùng template dashboard: {}", path.display());
+            }
+            None => env.add_template(TEMPLATE_NAME, DEFAULT_TEMPLATE).map_err(|e| e.to_string())?,
+        }
+
+        let html = env
+            .get_template(TEMPLATE_NAME)
+            .and_then(|t| t.render(context! { title => &config.title, columns => &config.columns }))
+            .map_err(|e| format!("Render dashboard lỗi: {}", e))?;
+        Ok(Self { html })
+    }
+
+    pub fn html(&self) -> &str {
+        &self.html
+    }
+}
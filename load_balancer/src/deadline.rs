@@ -0,0 +1,102 @@
+// Báo backend thời gian còn lại trước khi load balancer bỏ chờ ([deadline] trong config.toml),
+// để backend dừng việc mà load balancer sắp timeout. Request gRPC dùng grpc-timeout, request khác dùng
+// header cấu hình (mặc định X-Request-Deadline). Client đã gửi deadline ngắn hơn thì giữ deadline của client
+// (trừ đi thời gian request đã nằm ở load balancer).
+use crate::config::{DeadlineConfig, DeadlineFormat};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const GRPC_TIMEOUT: &str = "grpc-timeout";
+// grpc-timeout cho phép tối đa 8 chữ số
+const GRPC_TIMEOUT_MAX: u128 = 99_999_999;
+
+fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/grpc"))
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
+}
+
+// vd. "100m", "5S" (xem PROTOCOL-HTTP2.md của gRPC). Tối đa 8 chữ số như spec, nên nhân theo đơn vị không tràn
+fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    let unit = value.chars().last()?;
+    let digits = &value[..value.len() - unit.len_utf8()];
+    if digits.is_empty() || digits.len() > 8 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = digits.parse().ok()?;
+    Some(match unit {
+        'H' => Duration::from_secs(amount * 3600),
+        'M' => Duration::from_secs(amount * 60),
+        'S' => Duration::from_secs(amount),
+        'm' => Duration::from_millis(amount),
+        'u' => Duration::from_micros(amount),
+        'n' => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
+
+// Deadline client gửi kèm request, tính từ lúc load balancer nhận request
+pub fn from_client(headers: &HeaderMap, config: &DeadlineConfig) -> Option<Duration> {
+    if is_grpc(headers) {
+        return headers.get(GRPC_TIMEOUT).and_then(|v| v.to_str().ok()).and_then(parse_grpc_timeout);
+    }
+    let value: u64 = headers.get(config.header.as_str())?.to_str().ok()?.trim().parse().ok()?;
+    match config.format {
+        DeadlineFormat::RemainingMs => Some(Duration::from_millis(value)),
+        DeadlineFormat::UnixMs => {
+            let left = (value as u128).saturating_sub(now_ms());
+            Some(Duration::from_millis(left as u64))
+        }
+    }
+}
+
+// timeout: thời gian load balancer chờ response header của lần gửi này (None = không giới hạn)
+pub fn apply(
+    headers: &mut HeaderMap,
+    config: &DeadlineConfig,
+    timeout: Option<Duration>,
+    client: Option<Duration>,
+    started: Instant,
+) {
+    let client = client.map(|d| d.saturating_sub(started.elapsed()));
+    let remaining = match (timeout, client) {
+        (Some(timeout), Some(client)) => Some(timeout.min(client)),
+        (timeout, client) => timeout.or(client),
+    };
+    let Some(remaining) = remaining else {
+        return;
+    };
+
+    let (name, value) = if is_grpc(headers) {
+        let ms = remaining.as_millis().clamp(1, GRPC_TIMEOUT_MAX);
+        (HeaderName::from_static(GRPC_TIMEOUT), format!("{}m", ms))
+    } else {
+        let value = match config.format {
+            DeadlineFormat::RemainingMs => remaining.as_millis(),
+            DeadlineFormat::UnixMs => now_ms() + remaining.as_millis(),
+        };
+        (HeaderName::from_bytes(config.header.as_bytes()).unwrap(), value.to_string())
+    };
+    headers.insert(name, HeaderValue::from_str(&value).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_grpc_timeout;
+    use std::time::Duration;
+
+    #[test]
+    fn grpc_timeout_is_limited_to_eight_digits() {
+        assert_eq!(parse_grpc_timeout("100m"), Some(Duration::from_millis(100)));
+        assert_eq!(parse_grpc_timeout("99999999H"), Some(Duration::from_secs(99_999_999 * 3600)));
+        assert_eq!(parse_grpc_timeout("100000000H"), None);
+        assert_eq!(parse_grpc_timeout("18446744073709551615H"), None);
+        assert_eq!(parse_grpc_timeout("+5S"), None);
+        assert_eq!(parse_grpc_timeout("S"), None);
+    }
+}
@@ -0,0 +1,90 @@
+// Debug tracing theo từng request: ghi lại toàn bộ quá trình chọn backend
+// (client id, quyết định sticky/round robin, các ứng viên, backend được chọn, thời gian).
+// Bật cho 1 request bằng header `x-lb-debug: <admin.token>`, hoặc bật cho mọi request qua
+// PUT /load-balancer/api/debug/trace. Xem lại qua /load-balancer/api/debug/requests/:id (cần admin token).
+use crate::{admin_auth, config::AdminConfig};
+use axum::http::HeaderMap;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+// Header client gửi lên (giá trị là admin token) để bật trace cho request đó
+pub const DEBUG_HEADER: &str = "x-lb-debug";
+// Header trả về chứa id của trace
+pub const DEBUG_ID_HEADER: &str = "x-lb-debug-id";
+
+// Số trace tối đa giữ lại trong bộ nhớ
+const MAX_TRACES: usize = 200;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestTrace {
+    pub id: String,
+    pub started_at: String,
+    pub method: String,
+    pub path: String,
+    pub client_id: String,
+    // Pool backend mà request được đưa vào
+    pub pool: Option<String>,
+    // Các bước quyết định theo thứ tự
+    pub steps: Vec<String>,
+    // Các backend đủ điều kiện tại thời điểm chọn
+    pub candidates: Vec<String>,
+    pub backend: Option<String>,
+    pub upstream_status: Option<u16>,
+    pub error: Option<String>,
+    pub timings: Timings,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timings {
+    // Thời gian chọn backend (micro giây)
+    pub select_us: u128,
+    // Thời gian chờ backend trả header (ms)
+    pub upstream_ms: Option<u128>,
+    // Tổng thời gian tới khi bắt đầu trả response (ms)
+    pub total_ms: u128,
+}
+
+impl RequestTrace {
+    pub fn step(&mut self, msg: impl Into<String>) {
+        self.steps.push(msg.into());
+    }
+}
+
+// Header chỉ có hiệu lực khi mang đúng admin token: client bất kỳ bật được trace thì cũng
+// đẩy được trace thật ra khỏi bộ nhớ. Không cấu hình [admin] thì bỏ qua header
+pub fn requested(admin: Option<&AdminConfig>, headers: &HeaderMap) -> bool {
+    let token = headers.get(DEBUG_HEADER).and_then(|v| v.to_str().ok());
+    matches!((admin, token), (Some(admin), Some(token)) if admin_auth::token_matches(admin, token.trim()))
+}
+
+#[derive(Default)]
+pub struct TraceStore {
+    // Bật trace cho mọi request (toggle từ admin API)
+    pub enabled: bool,
+    entries: VecDeque<RequestTrace>,
+}
+
+// Id ngẫu nhiên 128 bit, không đoán được trace của request khác
+pub fn new_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+impl TraceStore {
+    pub fn push(&mut self, trace: RequestTrace) {
+        if self.entries.len() >= MAX_TRACES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(trace);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&RequestTrace> {
+        self.entries.iter().find(|t| t.id == id)
+    }
+
+    // Danh sách id gần nhất (mới nhất trước)
+    pub fn recent_ids(&self) -> Vec<&str> {
+        self.entries.iter().rev().map(|t| t.id.as_str()).collect()
+    }
+}
@@ -0,0 +1,85 @@
+// Thông báo lỗi khi đọc config.toml / servers.json: key bị lỗi (vd. routing.routes[0].pool),
+// dòng / cột, lý do (kiểu mong đợi...) và gợi ý sửa khi đoán được (gõ sai tên key, số viết trong ngoặc kép).
+use serde::de::DeserializeOwned;
+
+pub fn toml<T: DeserializeOwned>(data: &str) -> Result<T, String> {
+    serde_path_to_error::deserialize(toml::Deserializer::new(data)).map_err(|e| {
+        let position = e.inner().span().map(|span| line_column(data, span.start));
+        describe(&e.path().to_string(), e.inner().message(), position)
+    })
+}
+
+pub fn json<T: DeserializeOwned>(data: &str) -> Result<T, String> {
+    let deserializer = &mut serde_json::Deserializer::from_str(data);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let inner = e.inner();
+        let position = (inner.line() > 0).then(|| (inner.line(), inner.column()));
+        // serde_json tự thêm " at line x column y" vào cuối message
+        let message = inner.to_string();
+        let message = message.split(" at line ").next().unwrap_or(&message).to_string();
+        describe(&e.path().to_string(), &message, position)
+    })
+}
+
+fn line_column(data: &str, offset: usize) -> (usize, usize) {
+    let before = &data[..offset.min(data.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
+fn describe(path: &str, message: &str, position: Option<(usize, usize)>) -> String {
+    let mut text = String::new();
+    if path != "." && !path.is_empty() {
+        text.push_str(&format!("`{}`: ", path));
+    }
+    text.push_str(message.trim());
+    if let Some((line, column)) = position {
+        text.push_str(&format!(" (dòng {}, cột {})", line, column));
+    }
+    if let Some(hint) = suggestion(message) {
+        text.push_str(&format!(" — gợi ý: {}", hint));
+    }
+    text
+}
+
+fn suggestion(message: &str) -> Option<String> {
+    // "unknown field `adress`, expected one of `address`, `token`" / "unknown variant `roundrobin`, expected ..."
+    if let Some(rest) = message.strip_prefix("unknown field ").or_else(|| message.strip_prefix("unknown variant ")) {
+        let mut names = rest.split('`').skip(1).step_by(2);
+        let unknown = names.next()?;
+        let closest = names
+            .map(|name| (distance(&unknown.to_ascii_lowercase(), name), name))
+            .filter(|(d, name)| *d <= 2.max(name.len() / 3))
+            .min_by_key(|(d, _)| *d)?;
+        return Some(format!("ý bạn là `{}`?", closest.1));
+    }
+    if message.starts_with("invalid type: string") {
+        let expected = message.rsplit("expected ").next().unwrap_or("");
+        if ["u8", "u16", "u32", "u64", "usize", "i64", "f64", "integer", "float"].iter().any(|t| expected.contains(t)) {
+            return Some("giá trị là số, bỏ dấu ngoặc kép".to_string());
+        }
+        if expected.contains("bool") {
+            return Some("dùng true / false không có dấu ngoặc kép".to_string());
+        }
+    }
+    if message.starts_with("invalid type: integer") && message.ends_with("a string") {
+        return Some("giá trị là chuỗi, đặt trong dấu ngoặc kép".to_string());
+    }
+    None
+}
+
+// Khoảng cách Levenshtein
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            current[j + 1] = (previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,115 @@
+// Cache DNS cho hostname của backend ([dns] trong config.toml): mỗi host chỉ phân giải lại khi hết TTL
+// của bản ghi (kẹp trong [min_ttl_secs, max_ttl_secs]), thay vì phân giải theo từng kết nối.
+// Lỗi phân giải cũng được nhớ trong negative_ttl_secs để không dội resolver khi DNS có sự cố,
+// và được health check báo lại như lý do backend DOWN.
+use crate::config::DnsConfig;
+use hickory_resolver::TokioAsyncResolver;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+struct Entry {
+    result: Result<Arc<Vec<IpAddr>>, String>,
+    expires: Instant,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryReport {
+    pub host: String,
+    pub addresses: Vec<IpAddr>,
+    pub error: Option<String>,
+    pub expires_in_secs: u64,
+}
+
+struct Inner {
+    resolver: TokioAsyncResolver,
+    config: DnsConfig,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+// Dùng chung giữa các reqwest client (upstream, health check)
+#[derive(Clone)]
+pub struct Cache(Arc<Inner>);
+
+impl Cache {
+    pub fn new(config: &DnsConfig) -> Result<Self, String> {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf().map_err(|e| e.to_string())?;
+        Ok(Self(Arc::new(Inner {
+            resolver,
+            config: config.clone(),
+            entries: Mutex::new(HashMap::new()),
+        })))
+    }
+
+    pub async fn resolve(&self, host: &str) -> Result<Arc<Vec<IpAddr>>, String> {
+        if let Some(entry) = self.0.entries.lock().unwrap().get(host).filter(|e| e.expires > Instant::now()) {
+            return entry.result.clone();
+        }
+
+        let config = &self.0.config;
+        let now = Instant::now();
+        let (result, ttl) = match self.0.resolver.lookup_ip(host).await {
+            Ok(lookup) => {
+                let ttl = lookup.valid_until().saturating_duration_since(now).clamp(
+                    Duration::from_secs(config.min_ttl_secs),
+                    Duration::from_secs(config.max_ttl_secs),
+                );
+                let addresses: Vec<IpAddr> = lookup.iter().collect();
+                if addresses.is_empty() {
+                    (Err("không có bản ghi A / AAAA".to_string()), Duration::from_secs(config.negative_ttl_secs))
+                } else {
+                    debug!("🌐 {} -> {:?} (TTL {}s)", host, addresses, ttl.as_secs());
+                    (Ok(Arc::new(addresses)), ttl)
+                }
+            }
+            Err(e) => (Err(e.to_string()), Duration::from_secs(config.negative_ttl_secs)),
+        };
+        if let Err(e) = &result {
+            warn!("🌐 Không phân giải được {}: {}", host, e);
+        }
+
+        self.0.entries.lock().unwrap().insert(host.to_string(), Entry { result: result.clone(), expires: now + ttl });
+        result
+    }
+
+    // Lỗi phân giải đang được cache của host (None nếu phân giải được / chưa phân giải)
+    pub fn failure(&self, host: &str) -> Option<String> {
+        let entries = self.0.entries.lock().unwrap();
+        entries.get(host).and_then(|e| e.result.as_ref().err().cloned())
+    }
+
+    pub fn report(&self) -> Vec<EntryReport> {
+        let now = Instant::now();
+        let entries = self.0.entries.lock().unwrap();
+        let mut report: Vec<EntryReport> = entries
+            .iter()
+            .map(|(host, e)| EntryReport {
+                host: host.clone(),
+                addresses: e.result.as_ref().map(|a| a.to_vec()).unwrap_or_default(),
+                error: e.result.as_ref().err().cloned(),
+                expires_in_secs: e.expires.saturating_duration_since(now).as_secs(),
+            })
+            .collect();
+        report.sort_by(|a, b| a.host.cmp(&b.host));
+        report
+    }
+}
+
+impl Resolve for Cache {
+    fn resolve(&self, name: Name) -> Resolving {
+        let cache = self.clone();
+        Box::pin(async move {
+            let addresses = cache.resolve(name.as_str()).await?;
+            // reqwest tự gán port của URL vào địa chỉ trả về
+            let addrs: Addrs = Box::new(addresses.iter().map(|ip| SocketAddr::new(*ip, 0)).collect::<Vec<_>>().into_iter());
+            Ok(addrs)
+        })
+    }
+}
@@ -0,0 +1,133 @@
+// Drain: khi tắt load balancer hoặc tắt một backend (PUT /load-balancer/api/backends enabled=false),
+// chờ các request / stream (SSE...) đang gửi tới backend chạy xong, tối đa [drain] timeout_secs.
+// Quá thời gian thì cắt ngang các response còn đang stream.
+use crate::{AppState, ServerStatus, SharedState};
+use axum::{body::Bytes, BoxError};
+use futures::Stream;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{atomic::Ordering, Arc},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::sync::{futures::OwnedNotified, Notify};
+use tracing::{info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// Chu kỳ in tiến độ ra log
+const LOG_INTERVAL: Duration = Duration::from_secs(2);
+
+// Chờ tới khi `remaining` về 0 (hoặc trả None = không cần drain nữa), hết timeout thì gọi `close`
+async fn wait(
+    state: &SharedState,
+    what: &str,
+    timeout: Duration,
+    remaining: impl Fn(&AppState) -> Option<usize>,
+    close: impl Fn(&AppState),
+) {
+    let started = Instant::now();
+    let mut last_log = started;
+    loop {
+        let left = {
+            let r = state.read().unwrap();
+            match remaining(&r) {
+                Some(left) => left,
+                None => return,
+            }
+        };
+        if left == 0 {
+            info!("✅ Drain {} xong sau {:.1}s", what, started.elapsed().as_secs_f64());
+            return;
+        }
+        if started.elapsed() >= timeout {
+            warn!("⏱️ Hết {}s drain {}: cắt {} request còn lại", timeout.as_secs(), what, left);
+            close(&state.read().unwrap());
+            return;
+        }
+        if last_log.elapsed() >= LOG_INTERVAL {
+            info!("⏳ Drain {}: còn {} request, đã chờ {}s", what, left, started.elapsed().as_secs());
+            last_log = Instant::now();
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn active(s: &ServerStatus) -> usize {
+    s.active.load(Ordering::Relaxed)
+}
+
+fn servers(r: &AppState) -> impl Iterator<Item = &ServerStatus> {
+    r.pools.iter().flat_map(|p| p.servers.iter())
+}
+
+fn find<'a>(r: &'a AppState, pool_index: usize, url: &str) -> Option<&'a ServerStatus> {
+    r.pools.get(pool_index)?.servers.iter().find(|s| s.url == url)
+}
+
+// Backend vừa bị tắt: dừng drain nếu backend được bật lại trong lúc chờ
+pub async fn backend(state: SharedState, pool_index: usize, url: String, timeout: Duration) {
+    wait(
+        &state,
+        &url,
+        timeout,
+        |r| find(r, pool_index, &url).filter(|s| s.disabled).map(active),
+        |r| {
+            if let Some(s) = find(r, pool_index, &url) {
+                s.close.notify_waiters();
+            }
+        },
+    )
+    .await;
+}
+
+// Load balancer đang tắt (đã ngừng nhận kết nối mới)
+pub async fn shutdown(state: &SharedState, timeout: Duration) {
+    wait(
+        state,
+        "khi tắt load balancer",
+        timeout,
+        |r| Some(servers(r).map(active).sum()),
+        |r| servers(r).for_each(|s| s.close.notify_waiters()),
+    )
+    .await;
+}
+
+// Response body từ backend, kết thúc bằng lỗi (client thấy kết nối bị cắt) khi backend bị đóng sau drain
+pub fn until_closed<S, E>(body: S, close: Arc<Notify>) -> UntilClosed<S>
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Into<BoxError>,
+{
+    UntilClosed {
+        body: Box::pin(body),
+        // Tạo trước khi stream để không lỡ notify_waiters() xảy ra trước lần poll đầu
+        closed: Some(Box::pin(close.notified_owned())),
+    }
+}
+
+// Stream tự viết (thay cho unfold + select!): không tạo future mới cho mỗi chunk
+pub struct UntilClosed<S> {
+    body: Pin<Box<S>>,
+    // None: đã bị đóng
+    closed: Option<Pin<Box<OwnedNotified>>>,
+}
+
+impl<S, E> Stream for UntilClosed<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Into<BoxError>,
+{
+    type Item = Result<Bytes, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Some(closed) = self.closed.as_mut() else {
+            return Poll::Ready(None);
+        };
+        if closed.as_mut().poll(cx).is_ready() {
+            self.closed = None;
+            return Poll::Ready(Some(Err("backend bị đóng sau khi hết thời gian drain".into())));
+        }
+        self.body.as_mut().poll_next(cx).map(|chunk| chunk.map(|c| c.map_err(Into::into)))
+    }
+}
@@ -0,0 +1,129 @@
+// Thử nghiệm A/B: client mới được gán ngẫu nhiên vào một variant theo tỉ lệ cấu hình,
+// variant được lưu trong cookie để các request sau đi cùng một nhóm backend (pool).
+use crate::config::ExperimentConfig;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use serde::Serialize;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tracing::info;
+
+#[derive(Default)]
+struct VariantStats {
+    requests: AtomicU64,
+    // Response 5xx hoặc không tới được backend
+    errors: AtomicU64,
+    latency_ms_total: AtomicU64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantReport {
+    pub name: String,
+    pub pool: String,
+    pub percent: u32,
+    pub requests: u64,
+    pub errors: u64,
+    pub avg_latency_ms: u64,
+}
+
+// Variant của một request; `new` = vừa gán, cần gửi Set-Cookie
+pub struct Assignment {
+    pub index: usize,
+    pub new: bool,
+}
+
+pub struct Experiment {
+    config: ExperimentConfig,
+    header: HeaderName,
+    stats: Vec<VariantStats>,
+}
+
+impl Experiment {
+    pub fn new(config: ExperimentConfig) -> Self {
+        info!("🧪 Thử nghiệm '{}': {} variant", config.name, config.variants.len());
+        Self {
+            // Đã kiểm tra trong config::validate
+            header: HeaderName::from_bytes(config.variant_header.as_bytes()).unwrap(),
+            stats: config.variants.iter().map(|_| VariantStats::default()).collect(),
+            config,
+        }
+    }
+
+    pub fn assign(&self, path: &str, headers: &HeaderMap) -> Option<Assignment> {
+        if self.config.path_prefix.as_deref().is_some_and(|p| !path.starts_with(p)) {
+            return None;
+        }
+
+        let existing = crate::get_cookie(headers, &self.config.cookie_name)
+            .and_then(|name| self.config.variants.iter().position(|v| v.name == name));
+        if let Some(index) = existing {
+            return Some(Assignment { index, new: false });
+        }
+
+        // Chọn theo tỉ lệ phần trăm
+        let roll = rand::random::<u32>() % 100;
+        let mut acc = 0;
+        let index = self
+            .config
+            .variants
+            .iter()
+            .position(|v| {
+                acc += v.percent;
+                roll < acc
+            })
+            .unwrap_or(0);
+        Some(Assignment { index, new: true })
+    }
+
+    pub fn pool(&self, index: usize) -> &str {
+        &self.config.variants[index].pool
+    }
+
+    // Header gửi lên backend: (tên header, tên variant)
+    pub fn variant_header(&self, index: usize) -> (HeaderName, HeaderValue) {
+        let value = HeaderValue::from_str(&self.config.variants[index].name).unwrap();
+        (self.header.clone(), value)
+    }
+
+    pub fn set_cookie(&self, index: usize) -> HeaderValue {
+        let cookie = format!(
+            "{}={}; Path=/; Max-Age={}; SameSite=Lax",
+            self.config.cookie_name, self.config.variants[index].name, self.config.cookie_max_age_secs
+        );
+        HeaderValue::from_str(&cookie).unwrap()
+    }
+
+    pub fn record(&self, index: usize, status: u16, elapsed: Duration) {
+        let stats = &self.stats[index];
+        stats.requests.fetch_add(1, Ordering::Relaxed);
+        if status >= 500 {
+            stats.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        stats.latency_ms_total.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn report(&self) -> Vec<VariantReport> {
+        self.config
+            .variants
+            .iter()
+            .zip(&self.stats)
+            .map(|(v, s)| {
+                let requests = s.requests.load(Ordering::Relaxed);
+                VariantReport {
+                    name: v.name.clone(),
+                    pool: v.pool.clone(),
+                    percent: v.percent,
+                    requests,
+                    errors: s.errors.load(Ordering::Relaxed),
+                    avg_latency_ms: s.latency_ms_total.load(Ordering::Relaxed) / requests.max(1),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,258 @@
+// Failover: chỉ gửi lại request sang backend khác khi việc gửi lại là an toàn.
+// - Method an toàn (GET/HEAD/OPTIONS) hoặc có header Idempotency-Key
+// - Body chưa được đẩy đi byte nào (tránh backend xử lý 2 lần một request ghi dữ liệu),
+//   hoặc body đã được đọc hết vào bộ nhớ (route có body = "buffer")
+use axum::{
+    body::{Body, BodyDataStream, Bytes},
+    http::{header, HeaderMap, Method, StatusCode},
+};
+use futures::stream::{Stream, StreamExt};
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+// Số backend tối đa thử cho 1 request (tính cả lần đầu)
+pub const MAX_ATTEMPTS: usize = 3;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+pub fn is_replay_safe(method: &Method, headers: &HeaderMap) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+        || headers.contains_key(IDEMPOTENCY_KEY_HEADER)
+}
+
+// Body của request gửi lên backend, có thể lấy lại nếu backend chưa đọc tới
+pub enum UpstreamBody {
+    // Request không có body (không Content-Length / Transfer-Encoding)
+    Empty,
+    // Body stream, chỉ bị lấy ra khi reqwest thực sự đọc
+    Stream(Arc<Mutex<Option<BodyDataStream>>>),
+    // Body đã đọc hết, gửi lại bao nhiêu lần cũng được
+    Buffered(Bytes),
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+}
+
+// Đọc hết body (route có body = "buffer"): quá `limit` byte -> 413, client gửi lỗi / quá chậm -> 400
+pub async fn buffer(body: Body, headers: &HeaderMap, limit: u64) -> Result<Bytes, StatusCode> {
+    let expected = content_length(headers).unwrap_or(0);
+    if expected > limit {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    // Không cấp phát trước theo Content-Length do client tự khai (khai lớn rồi không gửi body)
+    let mut buffered = Vec::with_capacity(expected.min(64 * 1024) as usize);
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|_| StatusCode::BAD_REQUEST)?;
+        if (buffered.len() + chunk.len()) as u64 > limit {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        buffered.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(buffered))
+}
+
+// Upload của route có max_upload_bytes: Content-Length lớn hơn giới hạn -> 413 ngay (client gửi
+// "Expect: 100-continue" không phải gửi body), body chunked vượt giới hạn thì cắt và đánh dấu `exceeded`
+pub fn cap_upload(body: Body, headers: &HeaderMap, limit: u64, exceeded: Arc<AtomicBool>) -> Result<Body, StatusCode> {
+    if content_length(headers).is_some_and(|len| len > limit) {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    let mut received = 0u64;
+    Ok(Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let bytes = chunk?;
+        received += bytes.len() as u64;
+        if received > limit {
+            exceeded.store(true, Ordering::Relaxed);
+            return Err(axum::Error::new(format!("upload vượt {} byte", limit)));
+        }
+        Ok(bytes)
+    })))
+}
+
+// Header mô tả body gửi backend, gọi sau hop_by_hop::strip_request (Transfer-Encoding của client đã bị bỏ).
+// Body đã đọc hết: Content-Length là độ dài thật (client có thể đã gửi chunked). Body stream giữ nguyên
+// Content-Length của client, không có thì hyper gửi chunked. Content-Type (boundary của multipart) không bị đụng tới
+pub fn frame(headers: &mut HeaderMap, body: Option<&reqwest::Body>) {
+    if let Some(bytes) = body.and_then(reqwest::Body::as_bytes) {
+        headers.insert(header::CONTENT_LENGTH, bytes.len().into());
+    }
+}
+
+impl UpstreamBody {
+    pub fn new(body: Body, headers: &HeaderMap) -> Self {
+        let has_body = headers.contains_key(header::TRANSFER_ENCODING)
+            || content_length(headers).is_some_and(|len| len > 0);
+
+        if has_body {
+            UpstreamBody::Stream(Arc::new(Mutex::new(Some(body.into_data_stream()))))
+        } else {
+            UpstreamBody::Empty
+        }
+    }
+
+    pub fn buffered(body: Bytes) -> Self {
+        if body.is_empty() {
+            UpstreamBody::Empty
+        } else {
+            UpstreamBody::Buffered(body)
+        }
+    }
+
+    // Tạo body cho một lần gửi; None nếu body đã bị đọc ở lần gửi trước
+    pub fn attempt(&self) -> Option<Option<reqwest::Body>> {
+        match self {
+            UpstreamBody::Empty => Some(None),
+            UpstreamBody::Stream(slot) => {
+                if slot.lock().unwrap().is_none() {
+                    return None;
+                }
+                Some(Some(reqwest::Body::wrap_stream(LazyBody {
+                    slot: slot.clone(),
+                    stream: None,
+                })))
+            }
+            UpstreamBody::Buffered(body) => Some(Some(reqwest::Body::from(body.clone()))),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, UpstreamBody::Empty)
+    }
+
+    // Body chưa bị đọc byte nào -> gửi lại được
+    pub fn is_replayable(&self) -> bool {
+        match self {
+            UpstreamBody::Empty | UpstreamBody::Buffered(_) => true,
+            UpstreamBody::Stream(slot) => slot.lock().unwrap().is_some(),
+        }
+    }
+}
+
+// Stream chỉ lấy body ra khỏi slot ở lần poll đầu tiên.
+// Nếu backend lỗi trước khi kịp đọc (vd. connection refused) thì body vẫn còn trong slot.
+struct LazyBody {
+    slot: Arc<Mutex<Option<BodyDataStream>>>,
+    stream: Option<BodyDataStream>,
+}
+
+impl Stream for LazyBody {
+    type Item = Result<axum::body::Bytes, axum::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.stream.is_none() {
+            let taken = self.slot.lock().unwrap().take();
+            self.stream = taken;
+        }
+        match self.stream.as_mut() {
+            Some(stream) => Pin::new(stream).poll_next(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{buffer, cap_upload, frame, UpstreamBody};
+    use crate::hop_by_hop;
+    use axum::{
+        body::{Body, Bytes},
+        http::{header, HeaderMap, HeaderValue},
+    };
+    use futures::StreamExt;
+    use http_body_util::BodyExt;
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    const CONTENT_TYPE: &str = "multipart/form-data; boundary=----lb7MA4YWxkTrZu0gW";
+    const MULTIPART: &str = "------lb7MA4YWxkTrZu0gW\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\
+        Content-Type: application/octet-stream\r\n\r\nhello\r\n------lb7MA4YWxkTrZu0gW--\r\n";
+
+    // Body chunked từ client, cắt ngang boundary
+    fn chunked_body() -> Body {
+        let chunks: Vec<Result<Bytes, std::io::Error>> =
+            MULTIPART.as_bytes().chunks(7).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+        Body::from_stream(futures::stream::iter(chunks))
+    }
+
+    fn client_headers(chunked: bool) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+        headers.insert(header::CONNECTION, HeaderValue::from_static("keep-alive"));
+        if chunked {
+            headers.insert(header::TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+        } else {
+            headers.insert(header::CONTENT_LENGTH, MULTIPART.len().into());
+        }
+        headers
+    }
+
+    async fn collect(body: reqwest::Body) -> Bytes {
+        body.collect().await.unwrap().to_bytes()
+    }
+
+    #[tokio::test]
+    async fn buffered_multipart_gets_its_real_length() {
+        let headers = client_headers(true);
+        let bytes = buffer(chunked_body(), &headers, 1 << 20).await.unwrap();
+        assert_eq!(bytes, MULTIPART.as_bytes());
+
+        let body = UpstreamBody::buffered(bytes);
+        // Failover: lần gửi nào cũng đủ body và cùng header
+        for _ in 0..2 {
+            let attempt = body.attempt().unwrap();
+            let mut upstream = headers.clone();
+            hop_by_hop::strip_request(&mut upstream);
+            frame(&mut upstream, attempt.as_ref());
+            assert_eq!(upstream[header::CONTENT_TYPE], CONTENT_TYPE);
+            assert_eq!(upstream[header::CONTENT_LENGTH], MULTIPART.len().to_string().as_str());
+            assert!(!upstream.contains_key(header::TRANSFER_ENCODING));
+            assert!(!upstream.contains_key(header::CONNECTION));
+            assert_eq!(collect(attempt.unwrap()).await, MULTIPART.as_bytes());
+        }
+    }
+
+    #[tokio::test]
+    async fn streamed_multipart_keeps_client_framing() {
+        for chunked in [false, true] {
+            let headers = client_headers(chunked);
+            let body = UpstreamBody::new(chunked_body(), &headers);
+            let attempt = body.attempt().unwrap();
+            let mut upstream = headers.clone();
+            hop_by_hop::strip_request(&mut upstream);
+            frame(&mut upstream, attempt.as_ref());
+            assert_eq!(upstream[header::CONTENT_TYPE], CONTENT_TYPE);
+            assert!(!upstream.contains_key(header::TRANSFER_ENCODING));
+            // Có Content-Length thì giữ, chunked thì để hyper tự gửi chunked
+            assert_eq!(upstream.get(header::CONTENT_LENGTH).is_some(), !chunked);
+            assert_eq!(collect(attempt.unwrap()).await, MULTIPART.as_bytes());
+            // Body stream đã bị đọc: không failover
+            assert!(!body.is_replayable());
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_cap_passes_bytes_through_and_cuts_oversized() {
+        let headers = client_headers(true);
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let capped = cap_upload(chunked_body(), &headers, MULTIPART.len() as u64, exceeded.clone()).unwrap();
+        assert_eq!(capped.collect().await.unwrap().to_bytes(), MULTIPART.as_bytes());
+        assert!(!exceeded.load(std::sync::atomic::Ordering::Relaxed));
+
+        let capped = cap_upload(chunked_body(), &headers, 10, exceeded.clone()).unwrap();
+        let chunks: Vec<_> = StreamExt::collect(capped.into_data_stream()).await;
+        assert!(chunks.last().unwrap().is_err());
+        assert!(exceeded.load(std::sync::atomic::Ordering::Relaxed));
+
+        assert!(cap_upload(chunked_body(), &client_headers(false), 10, exceeded).is_err());
+    }
+}
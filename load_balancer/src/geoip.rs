@@ -0,0 +1,139 @@
+// Tra quốc gia / vùng của client từ database MaxMind GeoLite2 ([geoip] trong config.toml).
+// Kết quả dùng để route theo quốc gia ([[routing.routes]] countries), chặn theo deny/allow list,
+// gửi kèm lên backend (X-Geo-Country / X-Geo-Region) và thống kê theo quốc gia trên dashboard.
+// File database được nạp lại khi thay đổi (vd. geoipupdate chạy định kỳ), không cần restart.
+use crate::config::GeoIpConfig;
+use maxminddb::{geoip2, Reader};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+
+// Header gửi lên backend (header cùng tên do client tự gửi bị xoá)
+pub const COUNTRY_HEADER: &str = "x-geo-country";
+pub const REGION_HEADER: &str = "x-geo-region";
+// Nhãn thống kê cho IP không tra được quốc gia (IP nội bộ, chưa có trong database...)
+const UNKNOWN: &str = "unknown";
+
+#[derive(Debug, Clone, Default)]
+pub struct Location {
+    // Mã ISO 3166-1, vd. "VN"
+    pub country: Option<String>,
+    // Mã vùng (subdivision) ISO 3166-2 không kèm quốc gia, vd. "HN" (chỉ có với database City)
+    pub region: Option<String>,
+}
+
+#[derive(Default)]
+struct Counts {
+    requests: u64,
+    denied: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountryStats {
+    pub country: String,
+    pub requests: u64,
+    pub denied: u64,
+}
+
+struct Database {
+    reader: Reader<Vec<u8>>,
+    modified: Option<SystemTime>,
+}
+
+pub struct Locator {
+    config: GeoIpConfig,
+    database: RwLock<Arc<Database>>,
+    counts: Mutex<HashMap<String, Counts>>,
+}
+
+fn open(config: &GeoIpConfig) -> Result<Database, String> {
+    let modified = std::fs::metadata(&config.database).and_then(|m| m.modified()).ok();
+    let reader = Reader::open_readfile(&config.database).map_err(|e| format!("{}: {}", config.database.display(), e))?;
+    Ok(Database { reader, modified })
+}
+
+impl Locator {
+    pub fn new(config: &GeoIpConfig) -> Result<Self, String> {
+        let database = open(config)?;
+        info!(
+            "🌍 Nạp GeoIP database {} ({})",
+            config.database.display(),
+            database.reader.metadata.database_type
+        );
+        Ok(Self {
+            config: config.clone(),
+            database: RwLock::new(Arc::new(database)),
+            counts: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Location {
+        let database = self.database.read().unwrap().clone();
+        let Ok(city) = database.reader.lookup::<geoip2::City>(ip.to_canonical()) else {
+            return Location::default();
+        };
+        Location {
+            country: city.country.and_then(|c| c.iso_code).map(str::to_string),
+            region: city
+                .subdivisions
+                .and_then(|s| s.into_iter().next())
+                .and_then(|s| s.iso_code)
+                .map(str::to_string),
+        }
+    }
+
+    // true nếu request từ quốc gia này bị chặn theo deny_countries / allow_countries
+    pub fn denied(&self, country: Option<&str>) -> bool {
+        let listed = |list: &[String]| country.is_some_and(|c| list.iter().any(|l| l.eq_ignore_ascii_case(c)));
+        if !self.config.allow_countries.is_empty() {
+            return !listed(&self.config.allow_countries);
+        }
+        listed(&self.config.deny_countries)
+    }
+
+    pub fn record(&self, country: Option<&str>, denied: bool) {
+        let mut counts = self.counts.lock().unwrap();
+        let entry = counts.entry(country.unwrap_or(UNKNOWN).to_string()).or_default();
+        entry.requests += 1;
+        if denied {
+            entry.denied += 1;
+        }
+    }
+
+    // Nhiều request nhất trước
+    pub fn stats(&self) -> Vec<CountryStats> {
+        let counts = self.counts.lock().unwrap();
+        let mut stats: Vec<CountryStats> = counts
+            .iter()
+            .map(|(country, c)| CountryStats { country: country.clone(), requests: c.requests, denied: c.denied })
+            .collect();
+        stats.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.country.cmp(&b.country)));
+        stats
+    }
+
+    // Nạp lại database khi file thay đổi; file lỗi (đang ghi dở...) thì giữ database cũ
+    pub async fn watch(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.reload_secs));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let modified = std::fs::metadata(&self.config.database).and_then(|m| m.modified()).ok();
+            if modified.is_none() || modified == self.database.read().unwrap().modified {
+                continue;
+            }
+            match open(&self.config) {
+                Ok(database) => {
+                    info!("🌍 Nạp lại GeoIP database {}", self.config.database.display());
+                    *self.database.write().unwrap() = Arc::new(database);
+                }
+                Err(e) => warn!("⚠️ Không nạp lại được GeoIP database: {}", e),
+            }
+        }
+    }
+}
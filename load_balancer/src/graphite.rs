@@ -0,0 +1,67 @@
+// Gửi metrics tới Graphite (carbon) bằng plaintext protocol: "<path> <value> <timestamp>\n" qua TCP.
+// Mỗi chu kỳ flush mở một kết nối, gửi số liệu request cộng dồn và trạng thái backend.
+use crate::{
+    config::GraphiteConfig,
+    metrics::{path_component, BackendSample, TrafficWindow},
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+use tracing::{info, warn};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Exporter {
+    config: GraphiteConfig,
+    traffic: TrafficWindow,
+}
+
+impl Exporter {
+    pub fn new(config: GraphiteConfig) -> Self {
+        info!("📈 Gửi metrics Graphite tới {} mỗi {}s", config.address, config.flush_interval_secs);
+        Self { config, traffic: TrafficWindow::default() }
+    }
+
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_secs(self.config.flush_interval_secs.max(1))
+    }
+
+    pub fn record(&self, pool: &str, backend: Option<&str>, status: u16, elapsed: Duration) {
+        self.traffic.record(pool, backend, status, elapsed);
+    }
+
+    fn lines(&self, backends: &[BackendSample]) -> String {
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let prefix = &self.config.prefix;
+        let mut out = String::new();
+
+        for t in self.traffic.take() {
+            let path = format!("{}.requests.{}.{}.{}xx", prefix, path_component(&t.pool), path_component(&t.backend), t.status_class);
+            out.push_str(&format!("{}.count {} {}\n", path, t.count, ts));
+            out.push_str(&format!("{}.latency_ms_avg {:.2} {}\n", path, t.latency_ms_avg, ts));
+            out.push_str(&format!("{}.latency_ms_max {} {}\n", path, t.latency_ms_max, ts));
+        }
+        for b in backends {
+            let path = format!("{}.backend.{}.{}", prefix, path_component(&b.pool), path_component(&b.url));
+            out.push_str(&format!("{}.up {} {}\n", path, b.up as u8, ts));
+            out.push_str(&format!("{}.active_requests {} {}\n", path, b.active, ts));
+            if let Some(ms) = b.response_time {
+                out.push_str(&format!("{}.health_latency_ms {} {}\n", path, ms, ts));
+            }
+        }
+        out
+    }
+
+    pub async fn flush(&self, backends: &[BackendSample]) {
+        let payload = self.lines(backends);
+        let result = async {
+            let mut stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&self.config.address))
+                .await
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+            stream.write_all(payload.as_bytes()).await?;
+            stream.shutdown().await
+        };
+        if let Err(e) = result.await {
+            warn!("⚠️ Không gửi được metrics tới Graphite {}: {}", self.config.address, e);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// HTTP/2 cleartext (h2c) qua "Upgrade: h2c" (RFC 7540 mục 3.2), cho listener bật "h2c = true".
+// hyper không hỗ trợ upgrade phía server: trả 101 rồi đưa cho HTTP/2 server một luồng byte như thể client
+// kết nối bằng prior knowledge - preface, SETTINGS (gộp HTTP2-Settings của request upgrade với SETTINGS đầu tiên
+// của client) và HEADERS của chính request upgrade trên stream 1, để response của nó đi qua HTTP/2 như RFC yêu cầu.
+// Chỉ nhận upgrade với request không có body, còn lại bỏ qua header Upgrade và trả lời bằng HTTP/1.1.
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode, Uri, Version},
+    response::Response,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hyper::upgrade::OnUpgrade;
+use hyper_util::rt::TokioIo;
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+const PREFACE: &[u8; 24] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+// SETTINGS_MAX_FRAME_SIZE mặc định, HTTP/2 server chưa báo giá trị khác khi nhận các frame dựng sẵn
+const MAX_FRAME: usize = 16_384;
+const HEADERS: u8 = 0x1;
+const SETTINGS: u8 = 0x4;
+const CONTINUATION: u8 = 0x9;
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const HTTP2_SETTINGS: &str = "http2-settings";
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub struct Upgrade {
+    on_upgrade: OnUpgrade,
+    // Payload SETTINGS lấy từ header HTTP2-Settings
+    settings: Vec<u8>,
+    // HEADERS (+ CONTINUATION) của request upgrade trên stream 1
+    headers: Vec<u8>,
+}
+
+fn has_token(headers: &HeaderMap, name: header::HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|t| t.trim().eq_ignore_ascii_case(token))
+}
+
+// Request xin upgrade lên h2c hợp lệ thì giữ lại để chuyển sang HTTP/2 sau khi trả 101
+pub fn take<B>(req: &mut Request<B>) -> Option<Upgrade> {
+    let headers = req.headers();
+    if req.version() != Version::HTTP_11
+        || !has_token(headers, header::UPGRADE, "h2c")
+        || !has_token(headers, header::CONNECTION, "upgrade")
+        || !has_token(headers, header::CONNECTION, HTTP2_SETTINGS)
+        || headers.contains_key(header::TRANSFER_ENCODING)
+        || headers.get(header::CONTENT_LENGTH).is_some_and(|v| v != "0")
+    {
+        return None;
+    }
+    let mut values = headers.get_all(HTTP2_SETTINGS).iter();
+    let (Some(value), None) = (values.next(), values.next()) else {
+        return None;
+    };
+    let settings = URL_SAFE_NO_PAD.decode(value.to_str().ok()?.trim_end_matches('=')).ok()?;
+    // Mỗi setting 6 byte; giới hạn để SETTINGS gộp vẫn nằm trong một frame
+    if !settings.len().is_multiple_of(6) || settings.len() > 1024 {
+        return None;
+    }
+    let headers = header_frames(req.method(), req.uri(), req.headers());
+    Some(Upgrade { on_upgrade: hyper::upgrade::on(req), settings, headers })
+}
+
+pub fn switching_protocols() -> Response {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    response.headers_mut().insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+    response.headers_mut().insert(header::UPGRADE, HeaderValue::from_static("h2c"));
+    response
+}
+
+fn frame(out: &mut Vec<u8>, kind: u8, flags: u8, stream: u32, payload: &[u8]) {
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+    out.push(kind);
+    out.push(flags);
+    out.extend_from_slice(&stream.to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+// HPACK: số nguyên với prefix 7 bit, chuỗi không nén Huffman
+fn hpack_string(out: &mut Vec<u8>, value: &[u8]) {
+    let mut len = value.len();
+    if len < 127 {
+        out.push(len as u8);
+    } else {
+        out.push(127);
+        len -= 127;
+        while len >= 128 {
+            out.push((len % 128) as u8 | 0x80);
+            len /= 128;
+        }
+        out.push(len as u8);
+    }
+    out.extend_from_slice(value);
+}
+
+// Literal không đánh index: không đụng tới dynamic table của decoder
+fn hpack_field(out: &mut Vec<u8>, name: &[u8], value: &[u8]) {
+    out.push(0);
+    hpack_string(out, name);
+    hpack_string(out, value);
+}
+
+fn header_frames(method: &Method, uri: &Uri, headers: &HeaderMap) -> Vec<u8> {
+    let authority = headers
+        .get(header::HOST)
+        .map(|v| v.as_bytes())
+        .or_else(|| uri.authority().map(|a| a.as_str().as_bytes()))
+        .unwrap_or_default();
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+
+    let mut block = Vec::new();
+    hpack_field(&mut block, b":method", method.as_str().as_bytes());
+    hpack_field(&mut block, b":scheme", b"http");
+    hpack_field(&mut block, b":authority", authority);
+    hpack_field(&mut block, b":path", path.as_bytes());
+    // HTTP/2 không cho phép header theo kết nối (kể cả các header được liệt kê trong Connection)
+    let connection_tokens: Vec<String> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|t| t.trim().to_ascii_lowercase())
+        .collect();
+    for (name, value) in headers {
+        let hop_by_hop = matches!(
+            name.as_str(),
+            "connection" | "upgrade" | "host" | "keep-alive" | "proxy-connection" | "transfer-encoding" | HTTP2_SETTINGS
+        ) || (name == header::TE && value != "trailers")
+            || connection_tokens.iter().any(|t| t == name.as_str());
+        if !hop_by_hop {
+            hpack_field(&mut block, name.as_str().as_bytes(), value.as_bytes());
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut chunks = block.chunks(MAX_FRAME).peekable();
+    let mut kind = HEADERS;
+    let mut flags = END_STREAM;
+    while let Some(chunk) = chunks.next() {
+        let last = if chunks.peek().is_none() { END_HEADERS } else { 0 };
+        frame(&mut out, kind, flags | last, 1, chunk);
+        kind = CONTINUATION;
+        flags = 0;
+    }
+    out
+}
+
+impl Upgrade {
+    // Chờ hyper gửi xong 101, đọc preface + SETTINGS đầu tiên của client rồi dựng lại luồng byte cho HTTP/2 server
+    pub async fn accept(self) -> io::Result<Prefixed> {
+        let upgraded = self.on_upgrade.await.map_err(io::Error::other)?;
+        let mut io = TokioIo::new(upgraded);
+        let mut preface = [0u8; 24];
+        io.read_exact(&mut preface).await?;
+        if preface != *PREFACE {
+            return Err(invalid("thiếu HTTP/2 preface sau khi upgrade h2c"));
+        }
+        let mut head = [0u8; 9];
+        io.read_exact(&mut head).await?;
+        let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+        let stream = u32::from_be_bytes([head[5], head[6], head[7], head[8]]);
+        if head[3] != SETTINGS || head[4] & ACK != 0 || stream != 0 || !len.is_multiple_of(6) || len > MAX_FRAME - self.settings.len() {
+            return Err(invalid("frame đầu tiên sau preface phải là SETTINGS"));
+        }
+        let mut settings = self.settings;
+        let start = settings.len();
+        settings.resize(start + len, 0);
+        io.read_exact(&mut settings[start..]).await?;
+
+        let mut prefix = PREFACE.to_vec();
+        // Setting đứng sau ghi đè setting đứng trước: SETTINGS của client được ưu tiên hơn HTTP2-Settings
+        frame(&mut prefix, SETTINGS, 0, 0, &settings);
+        prefix.extend_from_slice(&self.headers);
+        Ok(Prefixed { prefix, read: 0, inner: io })
+    }
+}
+
+// Trả các byte dựng sẵn trước rồi mới đọc tiếp từ kết nối
+pub struct Prefixed {
+    prefix: Vec<u8>,
+    read: usize,
+    inner: TokioIo<hyper::upgrade::Upgraded>,
+}
+
+impl AsyncRead for Prefixed {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        if self.read < self.prefix.len() {
+            let n = buf.remaining().min(self.prefix.len() - self.read);
+            buf.put_slice(&self.prefix[self.read..self.read + n]);
+            self.read += n;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Prefixed {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[io::IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
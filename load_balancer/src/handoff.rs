@@ -0,0 +1,464 @@
+// Restart không rớt kết nối ([handoff], Unix). Process mới chạy cùng config kết nối tới control socket
+// của process đang chạy, nhận fd của các listener (SCM_RIGHTS) cùng sticky map / health state,
+// mở listener từ các fd đó rồi báo "ready". Process cũ ngừng accept, drain request đang chạy và thoát.
+// Hai process dùng chung một socket listen nên kết nối đang chờ trong hàng đợi accept không bị mất.
+//
+// Giao thức (mỗi dòng kết thúc bằng '\n'):
+//   mới -> cũ: "takeover"
+//   cũ -> mới: 1 byte kèm fd của các listener, sau đó state dạng JSON
+//   mới -> cũ: "ready <pid>" (đóng kết nối trước đó = huỷ, process cũ phục vụ tiếp)
+//
+// `load_balancer upgrade` (hot upgrade binary, giống hot restart của HAProxy / Envoy):
+//   lệnh -> cũ: "upgrade <đường dẫn binary>"
+//   process cũ chạy binary đó với cùng tham số, process mới xin bàn giao như trên
+//   cũ -> lệnh: "ok <pid mới>" hoặc "error <lý do>"
+use crate::{config::HandoffConfig, pools::Pool, sticky, uptime, HealthSample, SharedState};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::{fs::PermissionsExt, net::UnixStream},
+    },
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt},
+    sync::{watch, Notify},
+};
+use tracing::{error, info, warn};
+
+// Biến môi trường đánh dấu process được `upgrade` chạy (giá trị: pid của process cũ)
+pub const UPGRADE_ENV: &str = "LB_UPGRADED_FROM";
+// Số listener tối đa nhận trong một lần bàn giao
+const MAX_FDS: usize = 64;
+// Thời gian chờ bên kia gửi lệnh / trả lời
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize)]
+struct BackendState {
+    pool: String,
+    url: String,
+    healthy: bool,
+    disabled: bool,
+    response_time: Option<u128>,
+    last_check: Option<String>,
+    last_error: Option<String>,
+    uptime: u64,
+    downtime: u64,
+    history: Vec<HealthSample>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct State {
+    sticky: sticky::PoolMaps,
+    backends: Vec<BackendState>,
+    uptime: uptime::History,
+}
+
+fn snapshot(state: &SharedState) -> State {
+    let r = state.read().unwrap();
+    State {
+        sticky: r.pools.iter().map(|p| (p.name.clone(), p.sticky_map.clone())).collect(),
+        backends: r
+            .pools
+            .iter()
+            .flat_map(|p| p.servers.iter())
+            .map(|s| BackendState {
+                pool: s.pool.clone(),
+                url: s.url.clone(),
+                healthy: s.healthy,
+                disabled: s.disabled,
+                response_time: s.response_time,
+                last_check: s.last_check.clone(),
+                last_error: s.last_error.clone(),
+                uptime: s.uptime,
+                downtime: s.downtime,
+                history: s.history.clone(),
+            })
+            .collect(),
+        uptime: r.uptime.clone(),
+    }
+}
+
+// Phía process mới: listener và state nhận được, giữ kết nối để báo "ready"
+pub struct Takeover {
+    stream: UnixStream,
+    listeners: Vec<TcpListener>,
+    state: State,
+}
+
+// Xin bàn giao từ process đang chạy. Ok(None): không có process nào nghe ở control socket
+pub fn request(config: &HandoffConfig) -> Result<Option<Takeover>, String> {
+    let stream = match UnixStream::connect(&config.socket) {
+        Ok(stream) => stream,
+        // Chưa có file, hoặc file còn sót lại của process đã chết
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused) => return Ok(None),
+        Err(e) => return Err(format!("không kết nối được control socket {}: {}", config.socket.display(), e)),
+    };
+    let fail = |e: io::Error| format!("bàn giao từ process đang chạy thất bại: {}", e);
+    stream.set_read_timeout(Some(REPLY_TIMEOUT)).map_err(fail)?;
+    (&stream).write_all(b"takeover\n").map_err(fail)?;
+    let listeners = recv_fds(&stream).map_err(fail)?.into_iter().map(TcpListener::from).collect();
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).map_err(fail)?;
+    let state = serde_json::from_str(&line).map_err(|e| format!("state bàn giao không hợp lệ: {}", e))?;
+    Ok(Some(Takeover { stream, listeners, state }))
+}
+
+impl Takeover {
+    // Ghi đè sticky map, health state và lịch sử uptime bằng dữ liệu của process cũ
+    // (backend mới thêm vào servers.json giữ trạng thái mặc định)
+    pub fn apply(&mut self, pools: &mut [Pool], uptime: &mut uptime::History) {
+        let mut sticky = std::mem::take(&mut self.state.sticky);
+        let mut restored = 0;
+        for pool in pools.iter_mut() {
+            if let Some(map) = sticky.remove(&pool.name) {
+                pool.sticky_map = map;
+            }
+            restored += pool.sticky_map.len();
+        }
+        let mut backends = 0;
+        for b in self.state.backends.drain(..) {
+            let Some(s) = pools
+                .iter_mut()
+                .filter(|p| p.name == b.pool)
+                .flat_map(|p| p.servers.iter_mut())
+                .find(|s| s.url == b.url)
+            else {
+                continue;
+            };
+            s.healthy = b.healthy;
+            s.disabled = b.disabled;
+            s.response_time = b.response_time;
+            s.last_check = b.last_check;
+            s.last_error = b.last_error;
+            s.uptime = b.uptime;
+            s.downtime = b.downtime;
+            s.history = b.history;
+            backends += 1;
+        }
+        *uptime = std::mem::take(&mut self.state.uptime);
+        info!(
+            "🔁 Nhận bàn giao: {} listener, {} sticky session, trạng thái {} backend",
+            self.listeners.len(),
+            restored,
+            backends
+        );
+    }
+
+    // Listener của process cũ đang mở ở `addr` (nếu có)
+    pub fn listener(&mut self, addr: SocketAddr) -> Option<io::Result<TcpListener>> {
+        let index = self.listeners.iter().position(|l| l.local_addr().is_ok_and(|a| a == addr))?;
+        let listener = self.listeners.swap_remove(index);
+        Some(listener.set_nonblocking(true).map(|_| listener))
+    }
+
+    // Báo process cũ ngừng accept. Listener không còn trong config mới bị đóng tại đây
+    pub fn finish(self) {
+        if let Err(e) = (&self.stream).write_all(format!("ready {}\n", std::process::id()).as_bytes()) {
+            warn!("⚠️ Không báo được process cũ ngừng nhận kết nối: {}", e);
+        }
+    }
+}
+
+// Phía process đang chạy
+struct Control {
+    state: SharedState,
+    listeners: Vec<OwnedFd>,
+    ready_timeout: Duration,
+    // Đã canonicalize, xem allowed_binaries
+    upgrade_binaries: Vec<PathBuf>,
+    // Đang (hoặc đã) bàn giao: từ chối yêu cầu thứ hai
+    busy: AtomicBool,
+    // pid của process mới sau khi bàn giao xong
+    handed: watch::Sender<Option<u32>>,
+    done: Arc<Notify>,
+}
+
+// Chờ process mới xin bàn giao / lệnh upgrade. Bàn giao xong thì báo `done` để ngừng accept và drain
+pub async fn serve(state: SharedState, config: HandoffConfig, listeners: Vec<OwnedFd>, done: Arc<Notify>) {
+    // File cũ: của process vừa bàn giao cho mình, hoặc của process đã chết
+    let _ = std::fs::remove_file(&config.socket);
+    let socket = match tokio::net::UnixListener::bind(&config.socket) {
+        Ok(socket) => socket,
+        Err(e) => {
+            error!("❌ Không mở được control socket {}: {}", config.socket.display(), e);
+            return;
+        }
+    };
+    // Chỉ user chạy load balancer được kết nối (quyền mặc định theo umask thường cho cả group / other)
+    if let Err(e) = std::fs::set_permissions(&config.socket, std::fs::Permissions::from_mode(0o600)) {
+        error!("❌ Không đặt được quyền 0600 cho control socket {}: {}", config.socket.display(), e);
+        return;
+    }
+    info!("🔁 Nhận yêu cầu bàn giao (restart không rớt kết nối) tại {}", config.socket.display());
+
+    let control = Arc::new(Control {
+        state,
+        listeners,
+        ready_timeout: Duration::from_secs(config.ready_timeout_secs),
+        upgrade_binaries: allowed_binaries(&config),
+        busy: AtomicBool::new(false),
+        handed: watch::channel(None).0,
+        done,
+    });
+    loop {
+        match socket.accept().await {
+            Ok((stream, _)) => match stream.peer_cred() {
+                // Chỉ nhận lệnh từ cùng user với process này hoặc root (bàn giao trao cả fd listener lẫn state)
+                // SAFETY: geteuid không nhận tham số và luôn thành công
+                Ok(cred) if cred.uid() == 0 || cred.uid() == unsafe { libc::geteuid() } => {
+                    tokio::spawn(handle(stream, control.clone()));
+                }
+                Ok(cred) => warn!(
+                    "⚠️ Từ chối kết nối control socket từ uid {} (pid {:?})",
+                    cred.uid(),
+                    cred.pid()
+                ),
+                Err(e) => warn!("⚠️ Không lấy được thông tin process kết nối control socket: {}", e),
+            },
+            Err(e) => warn!("⚠️ Lỗi accept control socket: {}", e),
+        }
+    }
+}
+
+async fn handle(stream: tokio::net::UnixStream, control: Arc<Control>) {
+    let mut reader = tokio::io::BufReader::new(stream);
+    let mut line = String::new();
+    match tokio::time::timeout(REPLY_TIMEOUT, reader.read_line(&mut line)).await {
+        Ok(Ok(n)) if n > 0 => {}
+        _ => return,
+    }
+    let stream = reader.into_inner();
+    match line.trim() {
+        "takeover" => take_over(stream, &control).await,
+        command => match command.strip_prefix("upgrade ") {
+            Some(binary) => {
+                let reply = match upgrade(&control, binary).await {
+                    Ok(pid) => format!("ok {}\n", pid),
+                    Err(e) => {
+                        warn!("⚠️ Nâng cấp thất bại: {}", e);
+                        format!("error {}\n", e)
+                    }
+                };
+                let mut stream = stream;
+                let _ = stream.write_all(reply.as_bytes()).await;
+            }
+            None => warn!("⚠️ Lệnh không hợp lệ trên control socket: {:?}", command),
+        },
+    }
+}
+
+async fn take_over(stream: tokio::net::UnixStream, control: &Arc<Control>) {
+    if control.busy.swap(true, Ordering::SeqCst) {
+        warn!("⚠️ Đang bàn giao cho process khác, từ chối yêu cầu mới");
+        return;
+    }
+    let handed = match stream.into_std() {
+        Ok(stream) => {
+            let c = control.clone();
+            tokio::task::spawn_blocking(move || hand_over(stream, &c.state, &c.listeners, c.ready_timeout)).await
+        }
+        Err(e) => Ok(Err(e)),
+    };
+    match handed {
+        Ok(Ok(Some(pid))) => {
+            control.handed.send_replace(Some(pid));
+            control.done.notify_one();
+            // Giữ busy = true: process này không còn gì để bàn giao
+            return;
+        }
+        Ok(Ok(None)) => warn!("⚠️ Process mới không sẵn sàng, huỷ bàn giao và phục vụ tiếp"),
+        Ok(Err(e)) => warn!("⚠️ Huỷ bàn giao: {}", e),
+        Err(e) => error!("❌ Lỗi khi bàn giao: {}", e),
+    }
+    control.busy.store(false, Ordering::SeqCst);
+}
+
+// Some(pid): process mới đã nhận listener và sẵn sàng
+fn hand_over(
+    stream: UnixStream,
+    state: &SharedState,
+    listeners: &[OwnedFd],
+    ready_timeout: Duration,
+) -> io::Result<Option<u32>> {
+    stream.set_nonblocking(false)?;
+    info!("🔁 Process mới xin bàn giao, gửi {} listener và state", listeners.len());
+    send_fds(&stream, listeners)?;
+    let mut data = serde_json::to_vec(&snapshot(state))?;
+    data.push(b'\n');
+    (&stream).write_all(&data)?;
+
+    stream.set_read_timeout(Some(ready_timeout))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    Ok(line.trim().strip_prefix("ready ").and_then(|pid| pid.parse().ok()))
+}
+
+// Binary được phép nâng cấp lên: binary đang chạy (đã thay file tại chỗ) + handoff.upgrade_binaries.
+// So sánh sau canonicalize để symlink / đường dẫn tương đối không lách được
+fn allowed_binaries(config: &HandoffConfig) -> Vec<PathBuf> {
+    std::env::current_exe()
+        .into_iter()
+        .chain(config.upgrade_binaries.iter().cloned())
+        .filter_map(|path| match std::fs::canonicalize(&path) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                warn!("⚠️ Bỏ qua binary nâng cấp {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+// Chạy binary mới với cùng tham số + thư mục làm việc, chờ nó nhận bàn giao. Trả về pid của process mới
+async fn upgrade(control: &Control, binary: &str) -> Result<u32, String> {
+    let mut handed = control.handed.subscribe();
+    if control.busy.load(Ordering::SeqCst) {
+        return Err("đang bàn giao cho process khác".to_string());
+    }
+    let allowed = std::fs::canonicalize(binary)
+        .map(|path| control.upgrade_binaries.contains(&path))
+        .unwrap_or(false);
+    if !allowed {
+        return Err(format!("{} không phải binary đang chạy hoặc trong handoff.upgrade_binaries", binary));
+    }
+    let mut child = tokio::process::Command::new(binary)
+        .args(std::env::args_os().skip(1))
+        .env(UPGRADE_ENV, std::process::id().to_string())
+        .stdin(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("không chạy được {}: {}", binary, e))?;
+    info!("⬆️ Nâng cấp: chạy {} (pid {})", binary, child.id().unwrap_or_default());
+
+    let deadline = tokio::time::sleep(control.ready_timeout + REPLY_TIMEOUT);
+    tokio::pin!(deadline);
+    let mut exited = false;
+    loop {
+        tokio::select! {
+            pid = handed.wait_for(Option::is_some) => {
+                return pid.ok().and_then(|pid| *pid).ok_or_else(|| "control socket đã đóng".to_string());
+            }
+            status = child.wait(), if !exited => match status {
+                // --daemon: process con đã fork ra process chạy nền và thoát, chờ tiếp
+                Ok(status) if status.success() => exited = true,
+                Ok(status) => return Err(format!("process mới thoát ({})", status)),
+                Err(e) => return Err(e.to_string()),
+            },
+            _ = &mut deadline => {
+                let _ = child.start_kill();
+                return Err("process mới không nhận bàn giao kịp".to_string());
+            }
+        }
+    }
+}
+
+// Lệnh `load_balancer upgrade`: yêu cầu process đang chạy nâng cấp lên `binary`
+pub fn request_upgrade(config: &HandoffConfig, binary: &Path) -> Result<u32, String> {
+    let stream = UnixStream::connect(&config.socket)
+        .map_err(|e| format!("không kết nối được process đang chạy qua {}: {}", config.socket.display(), e))?;
+    // Chờ process mới khởi động xong (tối đa ready_timeout_secs) + thời gian trao đổi
+    stream
+        .set_read_timeout(Some(Duration::from_secs(config.ready_timeout_secs) + REPLY_TIMEOUT * 2))
+        .map_err(|e| e.to_string())?;
+    (&stream)
+        .write_all(format!("upgrade {}\n", binary.display()).as_bytes())
+        .map_err(|e| e.to_string())?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).map_err(|e| e.to_string())?;
+    let line = line.trim();
+    if let Some(pid) = line.strip_prefix("ok ") {
+        return pid.parse().map_err(|_| format!("trả lời không hợp lệ: {:?}", line));
+    }
+    Err(match line.strip_prefix("error ") {
+        Some(reason) => reason.to_string(),
+        None if line.is_empty() => "process đang chạy đóng kết nối".to_string(),
+        None => format!("trả lời không hợp lệ: {:?}", line),
+    })
+}
+
+// Gửi 1 byte kèm các fd (SCM_RIGHTS)
+fn send_fds(stream: &UnixStream, fds: &[OwnedFd]) -> io::Result<()> {
+    let raw: Vec<RawFd> = fds.iter().map(AsRawFd::as_raw_fd).collect();
+    let payload = [0u8];
+    let mut iov = libc::iovec { iov_base: payload.as_ptr() as *mut libc::c_void, iov_len: payload.len() };
+    let data_len = std::mem::size_of_val(raw.as_slice()) as libc::c_uint;
+    // SAFETY: CMSG_SPACE chỉ tính kích thước
+    let space = unsafe { libc::CMSG_SPACE(data_len) } as usize;
+    // Dùng u64 để buffer căn lề đúng cho cmsghdr
+    let mut control = vec![0u64; space.div_ceil(8)];
+    // SAFETY: msghdr toàn số / con trỏ, giá trị 0 là hợp lệ
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    if !raw.is_empty() {
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = space as _;
+        // SAFETY: buffer control đủ chỗ cho một cmsghdr chứa raw.len() fd
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(data_len) as _;
+            std::ptr::copy_nonoverlapping(raw.as_ptr(), libc::CMSG_DATA(cmsg).cast::<RawFd>(), raw.len());
+        }
+    }
+    // SAFETY: msg trỏ tới iov / control còn sống tới hết hàm
+    if unsafe { libc::sendmsg(stream.as_raw_fd(), &msg, 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn recv_fds(stream: &UnixStream) -> io::Result<Vec<OwnedFd>> {
+    let mut payload = [0u8];
+    let mut iov = libc::iovec { iov_base: payload.as_mut_ptr().cast(), iov_len: payload.len() };
+    // SAFETY: CMSG_SPACE chỉ tính kích thước
+    let space = unsafe { libc::CMSG_SPACE((MAX_FDS * std::mem::size_of::<RawFd>()) as libc::c_uint) } as usize;
+    let mut control = vec![0u64; space.div_ceil(8)];
+    // SAFETY: msghdr toàn số / con trỏ, giá trị 0 là hợp lệ
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = space as _;
+    // SAFETY: msg trỏ tới iov / control còn sống tới hết hàm
+    let received = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut msg, 0) };
+    if received < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if received == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "process đang chạy đóng kết nối"));
+    }
+
+    let mut fds = Vec::new();
+    // SAFETY: duyệt các cmsghdr do kernel ghi vào buffer control; fd nhận được thuộc về process này
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg).cast::<RawFd>();
+                let count = ((*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize) / std::mem::size_of::<RawFd>();
+                for i in 0..count {
+                    let fd = std::ptr::read_unaligned(data.add(i));
+                    // Không để process con (exec health check...) thừa kế socket
+                    libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                    fds.push(OwnedFd::from_raw_fd(fd));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+        return Err(io::Error::other(format!("quá nhiều listener (tối đa {})", MAX_FDS)));
+    }
+    Ok(fds)
+}
@@ -0,0 +1,117 @@
+// Request hedging: với route chỉ đọc (GET/HEAD/OPTIONS, không có body), nếu backend đầu tiên
+// chưa trả response sau "hedge delay" thì gửi thêm request tới backend thứ hai,
+// dùng response nào về trước và huỷ request còn lại.
+//
+// Delay cố định (delay_ms) hoặc theo percentile latency gần đây của route (vd. p95):
+// chỉ khoảng 5% request chậm nhất bị gửi hai lần.
+use crate::config::HedgingConfig;
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+// Số mẫu latency gần nhất giữ lại để tính percentile
+const LATENCY_SAMPLES: usize = 1000;
+// Chưa đủ mẫu thì dùng delay_ms
+const MIN_SAMPLES: usize = 20;
+
+pub struct Rule {
+    config: HedgingConfig,
+    // Latency (ms) tới lúc nhận response header, mới nhất ở cuối
+    latencies: Mutex<VecDeque<u64>>,
+    requests: AtomicU64,
+    hedged: AtomicU64,
+    // Request thứ hai trả lời trước
+    hedge_wins: AtomicU64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleStats {
+    pub path_prefix: String,
+    pub delay_ms: u64,
+    pub requests: u64,
+    pub hedged: u64,
+    pub hedge_wins: u64,
+}
+
+impl Rule {
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms())
+    }
+
+    fn delay_ms(&self) -> u64 {
+        let Some(percentile) = self.config.percentile else {
+            return self.config.delay_ms;
+        };
+        let mut samples: Vec<u64> = self.latencies.lock().unwrap().iter().copied().collect();
+        if samples.len() < MIN_SAMPLES {
+            return self.config.delay_ms;
+        }
+        samples.sort_unstable();
+        let rank = ((percentile / 100.0) * samples.len() as f64).ceil() as usize;
+        samples[rank.clamp(1, samples.len()) - 1].max(1)
+    }
+
+    // Ghi nhận request đã có response (hedge hay không)
+    pub fn record(&self, latency: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.len() == LATENCY_SAMPLES {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency.as_millis() as u64);
+    }
+
+    // Đã gửi request thứ hai; won = request thứ hai trả lời trước
+    pub fn hedged(&self, won: bool) {
+        self.hedged.fetch_add(1, Ordering::Relaxed);
+        if won {
+            self.hedge_wins.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+pub struct Hedger {
+    rules: Vec<Rule>,
+}
+
+impl Hedger {
+    pub fn new(configs: &[HedgingConfig]) -> Self {
+        Self {
+            rules: configs
+                .iter()
+                .map(|c| Rule {
+                    config: c.clone(),
+                    latencies: Mutex::new(VecDeque::new()),
+                    requests: AtomicU64::new(0),
+                    hedged: AtomicU64::new(0),
+                    hedge_wins: AtomicU64::new(0),
+                })
+                .collect(),
+        }
+    }
+
+    // Rule đầu tiên khớp path (không tính query string)
+    pub fn rule(&self, path: &str) -> Option<&Rule> {
+        self.rules.iter().find(|r| path.starts_with(r.config.path_prefix.as_str()))
+    }
+
+    pub fn stats(&self) -> Vec<RuleStats> {
+        self.rules
+            .iter()
+            .map(|r| RuleStats {
+                path_prefix: r.config.path_prefix.clone(),
+                delay_ms: r.delay_ms(),
+                requests: r.requests.load(Ordering::Relaxed),
+                hedged: r.hedged.load(Ordering::Relaxed),
+                hedge_wins: r.hedge_wins.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
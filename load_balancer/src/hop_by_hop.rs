@@ -0,0 +1,47 @@
+// Header theo từng chặng (RFC 9110 mục 7.6.1): chỉ có nghĩa trên một kết nối nên không được chuyển tiếp
+// qua proxy, theo cả hai chiều client -> backend và backend -> client. Gồm các header cố định bên dưới và
+// mọi header được liệt kê trong Connection. hyper tự sinh lại Connection / Transfer-Encoding cho từng chặng.
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue};
+
+const FIXED: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    HeaderName::from_static("proxy-connection"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+fn has_token(headers: &HeaderMap, name: &HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|t| t.trim().eq_ignore_ascii_case(token))
+}
+
+pub fn strip(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|t| HeaderName::from_bytes(t.trim().as_bytes()).ok())
+        .collect();
+    for name in listed.iter().chain(FIXED.iter()) {
+        headers.remove(name);
+    }
+}
+
+// Request gửi backend: bỏ header theo chặng nhưng giữ "TE: trailers" nếu client nhận được trailer
+// (gRPC bắt buộc có header này)
+pub fn strip_request(headers: &mut HeaderMap) {
+    let trailers = has_token(headers, &header::TE, "trailers");
+    strip(headers);
+    if trailers {
+        headers.insert(header::TE, HeaderValue::from_static("trailers"));
+    }
+}
@@ -0,0 +1,506 @@
+// `load_balancer import --from nginx.conf`: chuyển cấu hình nginx / HAProxy sang config.toml + servers.json.
+// Hỗ trợ các cấu trúc hay gặp:
+//   nginx:   upstream { server; least_conn / ip_hash / hash / random }, server { listen; server_name;
+//            ssl_certificate(_key); location <prefix> { proxy_pass } }
+//   HAProxy: frontend / backend / listen với bind (kể cả ssl crt), server, balance, option httpchk,
+//            acl path_beg / hdr(host) + use_backend, default_backend
+// Directive không chuyển được (weight, backup, location regex, rewrite...) được liệt kê thành cảnh báo.
+// Sau khi ghi file, kết quả được kiểm tra như `load_balancer check`.
+use crate::check;
+use clap::ValueEnum;
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    net::{SocketAddr, ToSocketAddrs},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Nginx,
+    Haproxy,
+}
+
+#[derive(Default)]
+struct Pool {
+    servers: Vec<String>,
+    strategy: Option<&'static str>,
+    health_path: Option<String>,
+}
+
+struct Route {
+    pool: String,
+    host: Option<String>,
+    path_prefix: Option<String>,
+}
+
+#[derive(Default)]
+struct Imported {
+    listen: Vec<SocketAddr>,
+    tls_listen: Vec<SocketAddr>,
+    cert_file: Option<String>,
+    key_file: Option<String>,
+    pools: BTreeMap<String, Pool>,
+    routes: Vec<Route>,
+    default_pool: Option<String>,
+    warnings: Vec<String>,
+}
+
+impl Imported {
+    fn warn(&mut self, message: String) {
+        self.warnings.push(message);
+    }
+
+    // "80", "*:80", "127.0.0.1:8080", "[::]:443", "localhost:80"
+    fn listen_address(&mut self, value: &str, tls: bool) {
+        let value = value.trim_start_matches("*:").trim_start_matches(':');
+        let address = match value.parse::<u16>() {
+            Ok(port) => Some(SocketAddr::from(([0, 0, 0, 0], port))),
+            Err(_) => value.parse().ok().or_else(|| value.to_socket_addrs().ok().and_then(|mut a| a.next())),
+        };
+        match address {
+            Some(address) if tls => self.tls_listen.push(address),
+            Some(address) => self.listen.push(address),
+            None => self.warn(format!("không hiểu địa chỉ listen {}", value)),
+        }
+    }
+
+    // Pool cho một URL proxy thẳng tới backend (không qua upstream / backend có tên)
+    fn pool_for_url(&mut self, url: &str) -> String {
+        let host = url.split("://").nth(1).unwrap_or(url).split('/').next().unwrap_or(url);
+        let name: String = host.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        let pool = self.pools.entry(name.clone()).or_default();
+        let base = url.split('/').take(3).collect::<Vec<_>>().join("/");
+        if !pool.servers.contains(&base) {
+            pool.servers.push(base);
+        }
+        name
+    }
+}
+
+fn backend_url(address: &str, tls: bool) -> String {
+    if address.contains("://") || address.starts_with("unix:") {
+        return address.to_string();
+    }
+    format!("{}://{}", if tls { "https" } else { "http" }, address)
+}
+
+// --- nginx ---
+
+struct Directive {
+    name: String,
+    args: Vec<String>,
+    block: Vec<Directive>,
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars();
+    let mut current = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '"' | '\'' => {
+                for q in chars.by_ref() {
+                    if q == c {
+                        break;
+                    }
+                    current.push(q);
+                }
+            }
+            '{' | '}' | ';' => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+                tokens.push(c.to_string());
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn parse_block(tokens: &mut std::vec::IntoIter<String>) -> Vec<Directive> {
+    let mut directives = Vec::new();
+    let mut words = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            ";" => {
+                if !words.is_empty() {
+                    let name = words.remove(0);
+                    directives.push(Directive { name, args: std::mem::take(&mut words), block: Vec::new() });
+                }
+            }
+            "{" => {
+                let block = parse_block(tokens);
+                let name = if words.is_empty() { String::new() } else { words.remove(0) };
+                directives.push(Directive { name, args: std::mem::take(&mut words), block });
+            }
+            "}" => break,
+            _ => words.push(token),
+        }
+    }
+    directives
+}
+
+fn import_nginx(text: &str, out: &mut Imported) {
+    let mut tokens = tokenize(text).into_iter();
+    let root = parse_block(&mut tokens);
+    // Các directive nằm trong http { } (hoặc ở gốc nếu file chỉ là một phần cấu hình)
+    let mut directives: Vec<&Directive> = Vec::new();
+    for d in &root {
+        match d.name.as_str() {
+            "http" => directives.extend(d.block.iter()),
+            "events" | "stream" => {}
+            _ => directives.push(d),
+        }
+    }
+
+    for d in directives.iter().filter(|d| d.name == "upstream") {
+        let Some(name) = d.args.first() else { continue };
+        let mut pool = Pool::default();
+        for item in &d.block {
+            match item.name.as_str() {
+                "server" => {
+                    let Some(address) = item.args.first() else { continue };
+                    for option in &item.args[1..] {
+                        out.warn(format!("upstream {}: bỏ qua \"{}\" của server {}", name, option, address));
+                    }
+                    pool.servers.push(backend_url(address, false));
+                }
+                "least_conn" => pool.strategy = Some("least_conn"),
+                "ip_hash" | "hash" => pool.strategy = Some("consistent_hash"),
+                "random" => pool.strategy = Some("random"),
+                other => out.warn(format!("upstream {}: bỏ qua directive {}", name, other)),
+            }
+        }
+        out.pools.insert(name.clone(), pool);
+    }
+
+    for d in directives.iter().filter(|d| d.name == "include") {
+        out.warn(format!("không đọc file include {} (gộp nội dung vào một file rồi chạy lại)", d.args.join(" ")));
+    }
+
+    for server in directives.iter().filter(|d| d.name == "server") {
+        let mut hosts = Vec::new();
+        for item in &server.block {
+            match item.name.as_str() {
+                "listen" => {
+                    let Some(address) = item.args.first() else { continue };
+                    let tls = item.args.iter().any(|a| a == "ssl");
+                    out.listen_address(address, tls);
+                }
+                "server_name" => hosts.extend(item.args.iter().filter(|h| *h != "_" && !h.is_empty()).cloned()),
+                "ssl_certificate" => out.cert_file = item.args.first().cloned(),
+                "ssl_certificate_key" => out.key_file = item.args.first().cloned(),
+                _ => {}
+            }
+        }
+        for location in server.block.iter().filter(|d| d.name == "location") {
+            let (modifier, prefix) = match location.args.as_slice() {
+                [prefix] => (None, prefix.clone()),
+                [modifier, prefix] => (Some(modifier.as_str()), prefix.clone()),
+                _ => continue,
+            };
+            let Some(target) = location.block.iter().find(|d| d.name == "proxy_pass").and_then(|d| d.args.first()) else {
+                continue;
+            };
+            match modifier {
+                Some("~") | Some("~*") => {
+                    out.warn(format!("location {} {}: location regex không hỗ trợ, bỏ qua", modifier.unwrap_or(""), prefix));
+                    continue;
+                }
+                Some("=") => out.warn(format!("location = {}: chuyển thành path prefix", prefix)),
+                _ => {}
+            }
+            let rest = target.split("://").nth(1).unwrap_or(target);
+            let upstream = rest.split('/').next().unwrap_or(rest);
+            if rest.len() > upstream.len() && &rest[upstream.len()..] != "/" {
+                out.warn(format!("location {}: proxy_pass {} có URI, path gửi backend sẽ không bị thay", prefix, target));
+            }
+            let pool = if out.pools.contains_key(upstream) {
+                upstream.to_string()
+            } else {
+                out.pool_for_url(target)
+            };
+            let path_prefix = (prefix != "/").then_some(prefix);
+            if hosts.is_empty() {
+                out.routes.push(Route { pool, host: None, path_prefix });
+            } else {
+                for host in &hosts {
+                    out.routes.push(Route { pool: pool.clone(), host: Some(host.clone()), path_prefix: path_prefix.clone() });
+                }
+            }
+        }
+    }
+}
+
+// --- HAProxy ---
+
+fn import_haproxy(text: &str, out: &mut Imported) {
+    // Section hiện tại: (loại, tên)
+    let mut section: Option<(String, String)> = None;
+    // acl tên -> (host, path prefix) của frontend hiện tại
+    let mut acls: HashMap<String, (Option<String>, Option<String>)> = HashMap::new();
+
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&keyword, args)) = words.split_first() else { continue };
+
+        if matches!(keyword, "global" | "defaults" | "frontend" | "backend" | "listen" | "resolvers" | "peers" | "userlist") {
+            let name = args.first().map_or_else(String::new, |n| n.to_string());
+            acls.clear();
+            if matches!(keyword, "backend" | "listen") {
+                out.pools.entry(name.clone()).or_default();
+            }
+            if keyword == "listen" {
+                out.default_pool.get_or_insert_with(|| name.clone());
+                // listen <tên> <địa chỉ> (cú pháp cũ)
+                if let Some(address) = args.get(1) {
+                    out.listen_address(address, false);
+                }
+            }
+            section = Some((keyword.to_string(), name));
+            continue;
+        }
+        let Some((kind, name)) = section.clone() else { continue };
+        if matches!(kind.as_str(), "global" | "defaults" | "resolvers" | "peers" | "userlist") {
+            continue;
+        }
+
+        match (keyword, args) {
+            ("bind", [address, options @ ..]) => {
+                let tls = options.contains(&"ssl");
+                if let Some(i) = options.iter().position(|o| *o == "crt") {
+                    // HAProxy dùng một file PEM gồm cả cert và key
+                    if let Some(pem) = options.get(i + 1) {
+                        out.cert_file = Some(pem.to_string());
+                        out.key_file = Some(pem.to_string());
+                    }
+                }
+                out.listen_address(address, tls);
+            }
+            ("server", [_, address, options @ ..]) if matches!(kind.as_str(), "backend" | "listen") => {
+                let tls = options.contains(&"ssl");
+                for option in options.iter().filter(|o| matches!(**o, "weight" | "backup")) {
+                    out.warn(format!("backend {}: bỏ qua \"{}\" của server {}", name, option, address));
+                }
+                out.pools.entry(name.clone()).or_default().servers.push(backend_url(address, tls));
+            }
+            ("balance", [algorithm, ..]) => {
+                let strategy = match *algorithm {
+                    "roundrobin" | "static-rr" => None,
+                    "leastconn" => Some("least_conn"),
+                    "source" | "uri" | "url_param" | "hdr" => Some("consistent_hash"),
+                    "random" => Some("random"),
+                    other => {
+                        out.warn(format!("backend {}: balance {} không hỗ trợ, dùng mặc định", name, other));
+                        None
+                    }
+                };
+                out.pools.entry(name.clone()).or_default().strategy = strategy;
+            }
+            ("option", ["httpchk", rest @ ..]) => {
+                // option httpchk [<method>] <uri>  |  option httpchk <method> <uri> <version>
+                let path = rest.iter().find(|a| a.starts_with('/')).map(|p| p.to_string());
+                out.pools.entry(name.clone()).or_default().health_path = path;
+            }
+            ("acl", [acl, criterion, rest @ ..]) => {
+                let values: Vec<&str> = rest.iter().copied().filter(|v| !v.starts_with('-')).collect();
+                let entry = acls.entry(acl.to_string()).or_default();
+                match (*criterion, values.as_slice()) {
+                    ("path_beg", [prefix, ..]) => entry.1 = Some(prefix.to_string()),
+                    ("hdr(host)" | "hdr_dom(host)" | "req.hdr(host)", [host, ..]) => entry.0 = Some(host.to_string()),
+                    _ => out.warn(format!("{} {}: acl {} {} không hỗ trợ", kind, name, acl, criterion)),
+                }
+            }
+            ("use_backend", [backend, condition, names @ ..]) if matches!(*condition, "if") => {
+                let mut host = None;
+                let mut path_prefix = None;
+                for acl in names {
+                    match acls.get(*acl) {
+                        Some((h, p)) => {
+                            host = host.or_else(|| h.clone());
+                            path_prefix = path_prefix.or_else(|| p.clone());
+                        }
+                        None => out.warn(format!("use_backend {}: điều kiện {} không hỗ trợ", backend, acl)),
+                    }
+                }
+                out.routes.push(Route { pool: backend.to_string(), host, path_prefix });
+            }
+            ("use_backend", [backend, ..]) => {
+                out.warn(format!("use_backend {}: chỉ hỗ trợ \"if <acl> ...\"", backend));
+            }
+            ("default_backend", [backend, ..]) => out.default_pool = Some(backend.to_string()),
+            ("mode", ["tcp", ..]) => out.warn(format!("{} {}: mode tcp không hỗ trợ (chỉ HTTP)", kind, name)),
+            _ => {}
+        }
+    }
+}
+
+// --- Ghi kết quả ---
+
+// Chuỗi TOML (JSON string cũng là basic string hợp lệ của TOML)
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+fn addresses(list: &[SocketAddr]) -> String {
+    let items: Vec<String> = list.iter().map(|a| quote(&a.to_string())).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn render_config(source: &Path, imported: &Imported) -> String {
+    let mut toml = String::new();
+    let _ = writeln!(toml, "# Chuyển từ {} bằng `load_balancer import`", source.display());
+    let tls = imported.cert_file.is_some() && !imported.tls_listen.is_empty();
+    match (imported.listen.is_empty(), tls) {
+        (false, true) => {
+            let _ = writeln!(toml, "\n[[listeners]]\naddresses = {}", addresses(&imported.listen));
+            let _ = writeln!(toml, "\n[[listeners]]\naddresses = {}\ntls = true", addresses(&imported.tls_listen));
+        }
+        (true, true) => {
+            let _ = writeln!(toml, "\n[listen]\naddresses = {}", addresses(&imported.tls_listen));
+        }
+        (false, false) => {
+            let _ = writeln!(toml, "\n[listen]\naddresses = {}", addresses(&imported.listen));
+        }
+        (true, false) => {}
+    }
+    if tls {
+        let _ = writeln!(toml, "\n[tls]");
+        if let Some(cert) = &imported.cert_file {
+            let _ = writeln!(toml, "cert_file = {}", quote(cert));
+        }
+        if let Some(key) = &imported.key_file {
+            let _ = writeln!(toml, "key_file = {}", quote(key));
+        }
+    }
+
+    if imported.default_pool.is_none() && imported.routes.is_empty() {
+        return toml;
+    }
+    let _ = writeln!(toml, "\n[routing]");
+    if let Some(pool) = &imported.default_pool {
+        let _ = writeln!(toml, "default_pool = {}", quote(pool));
+    }
+    // Route đầu tiên khớp được chọn: route có host trước, path prefix dài trước (giống nginx chọn prefix dài nhất)
+    let mut routes: Vec<&Route> = imported.routes.iter().collect();
+    routes.sort_by_key(|r| (r.host.is_none(), std::cmp::Reverse(r.path_prefix.as_ref().map_or(0, String::len))));
+    for route in routes {
+        // Route bắt mọi request (không host, không path) = pool mặc định
+        if route.host.is_none() && route.path_prefix.is_none() && imported.default_pool.is_none() {
+            let _ = writeln!(toml, "default_pool = {}", quote(&route.pool));
+            continue;
+        }
+        let _ = writeln!(toml, "\n[[routing.routes]]\npool = {}", quote(&route.pool));
+        if let Some(host) = &route.host {
+            let _ = writeln!(toml, "host = {}", quote(host));
+        }
+        if let Some(prefix) = &route.path_prefix {
+            let _ = writeln!(toml, "path_prefix = {}", quote(prefix));
+        }
+    }
+    toml
+}
+
+fn render_servers(imported: &Imported) -> String {
+    let pools: serde_json::Map<String, serde_json::Value> = imported
+        .pools
+        .iter()
+        .map(|(name, pool)| {
+            let mut value = json!({ "servers": pool.servers.iter().map(|url| json!({ "url": url })).collect::<Vec<_>>() });
+            if let Some(strategy) = pool.strategy {
+                value["strategy"] = json!(strategy);
+            }
+            if let Some(path) = &pool.health_path {
+                value["health"] = json!({ "path": path });
+            }
+            (name.clone(), value)
+        })
+        .collect();
+    serde_json::to_string_pretty(&pools).unwrap()
+}
+
+fn detect(text: &str) -> Format {
+    // Section HAProxy không kết thúc bằng ";" / "{" như directive nginx (vd. "listen 80;")
+    let haproxy = text.lines().any(|l| {
+        let l = l.split('#').next().unwrap_or("").trim();
+        ["frontend ", "backend ", "defaults", "listen "].iter().any(|k| l.starts_with(k))
+            && !l.ends_with(';')
+            && !l.ends_with('{')
+    });
+    if haproxy {
+        Format::Haproxy
+    } else {
+        Format::Nginx
+    }
+}
+
+// Trả về exit code
+pub fn run(from: &Path, format: Option<Format>, out_dir: &Path, force: bool) -> i32 {
+    let text = match std::fs::read_to_string(from) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("❌ Không đọc được {}: {}", from.display(), e);
+            return 1;
+        }
+    };
+    let config_path = out_dir.join("config.toml");
+    let servers_path = out_dir.join("servers.json");
+    if !force {
+        if let Some(existing) = [&config_path, &servers_path].into_iter().find(|p| p.exists()) {
+            eprintln!("❌ {} đã tồn tại (dùng --force để ghi đè)", existing.display());
+            return 1;
+        }
+    }
+
+    let mut imported = Imported::default();
+    match format.unwrap_or_else(|| detect(&text)) {
+        Format::Nginx => import_nginx(&text, &mut imported),
+        Format::Haproxy => import_haproxy(&text, &mut imported),
+    }
+    if imported.cert_file.is_none() && !imported.tls_listen.is_empty() {
+        imported.warn("listen ssl nhưng không có certificate, bỏ qua các địa chỉ TLS".to_string());
+    }
+    for route in &imported.routes {
+        if !imported.pools.contains_key(&route.pool) {
+            imported.warnings.push(format!("route tới {} nhưng không có upstream / backend cùng tên", route.pool));
+        }
+    }
+
+    if let Err(e) = std::fs::create_dir_all(out_dir)
+        .and_then(|_| std::fs::write(&config_path, render_config(from, &imported)))
+        .and_then(|_| std::fs::write(&servers_path, render_servers(&imported)))
+    {
+        eprintln!("❌ Không ghi được kết quả vào {}: {}", out_dir.display(), e);
+        return 1;
+    }
+    println!(
+        "📝 Đã ghi {} và {} ({} pool, {} route)",
+        config_path.display(),
+        servers_path.display(),
+        imported.pools.len(),
+        imported.routes.len()
+    );
+    for warning in &imported.warnings {
+        println!("⚠️  {}", warning);
+    }
+    check::run(&config_path, &servers_path)
+}
@@ -0,0 +1,133 @@
+// Đẩy metrics lên InfluxDB bằng line protocol (v1 hoặc v2).
+// Request được cộng dồn trong mỗi chu kỳ flush, trạng thái backend được lấy mẫu lúc flush.
+use crate::{
+    config::{InfluxConfig, InfluxVersion},
+    metrics::{BackendSample, TrafficWindow},
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+// InfluxDB không nhận được thì giữ lại tối đa chừng này dòng để gửi lại lần sau
+const MAX_PENDING_LINES: usize = 100_000;
+
+pub struct Exporter {
+    config: InfluxConfig,
+    client: reqwest::Client,
+    traffic: TrafficWindow,
+    // Dòng chưa gửi được (InfluxDB lỗi / mất mạng)
+    pending: tokio::sync::Mutex<Vec<String>>,
+}
+
+// Escape tag key / value theo line protocol
+fn tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+impl Exporter {
+    pub fn new(config: InfluxConfig) -> Self {
+        info!("📈 Đẩy metrics lên InfluxDB {} mỗi {}s", config.url, config.flush_interval_secs);
+        Self {
+            config,
+            client: reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap(),
+            traffic: TrafficWindow::default(),
+            pending: tokio::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_secs(self.config.flush_interval_secs.max(1))
+    }
+
+    pub fn record(&self, pool: &str, backend: Option<&str>, status: u16, elapsed: Duration) {
+        self.traffic.record(pool, backend, status, elapsed);
+    }
+
+    fn lines(&self, backends: &[BackendSample]) -> Vec<String> {
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let mut lines: Vec<String> = self
+            .traffic
+            .take()
+            .into_iter()
+            .map(|t| {
+                format!(
+                    "lb_requests,pool={},backend={},status={}xx count={}i,latency_ms_avg={:.2},latency_ms_max={}i {}",
+                    tag(&t.pool),
+                    tag(&t.backend),
+                    t.status_class,
+                    t.count,
+                    t.latency_ms_avg,
+                    t.latency_ms_max,
+                    ts
+                )
+            })
+            .collect();
+
+        lines.extend(backends.iter().map(|b| {
+            let latency = b.response_time.map(|ms| format!(",health_latency_ms={}i", ms)).unwrap_or_default();
+            format!(
+                "lb_backend,pool={},backend={} up={}i,active_requests={}i{} {}",
+                tag(&b.pool),
+                tag(&b.url),
+                b.up as u8,
+                b.active,
+                latency,
+                ts
+            )
+        }));
+        lines
+    }
+
+    fn write_request(&self, body: String) -> reqwest::RequestBuilder {
+        let base = self.config.url.trim_end_matches('/');
+        match self.config.version {
+            InfluxVersion::V1 => {
+                let mut query = vec![
+                    ("db", self.config.database.clone().unwrap_or_default()),
+                    ("precision", "ms".to_string()),
+                ];
+                if let (Some(u), Some(p)) = (&self.config.username, &self.config.password) {
+                    query.push(("u", u.clone()));
+                    query.push(("p", p.clone()));
+                }
+                self.client.post(format!("{}/write", base)).query(&query).body(body)
+            }
+            InfluxVersion::V2 => self
+                .client
+                .post(format!("{}/api/v2/write", base))
+                .query(&[
+                    ("org", self.config.org.as_deref().unwrap_or_default()),
+                    ("bucket", self.config.bucket.as_deref().unwrap_or_default()),
+                    ("precision", "ms"),
+                ])
+                .header("Authorization", format!("Token {}", self.config.token.as_deref().unwrap_or_default()))
+                .body(body),
+        }
+    }
+
+    // Gửi số liệu của chu kỳ vừa qua (kèm các dòng lần trước chưa gửi được) theo từng batch
+    pub async fn flush(&self, backends: &[BackendSample]) {
+        let mut pending = self.pending.lock().await;
+        pending.extend(self.lines(backends));
+
+        while !pending.is_empty() {
+            let n = pending.len().min(self.config.batch_size);
+            let body = pending[..n].join("\n");
+            let result = self.write_request(body).send().await.and_then(|r| r.error_for_status());
+            match result {
+                Ok(_) => {
+                    pending.drain(..n);
+                }
+                Err(e) => {
+                    warn!("⚠️ Không ghi được metrics lên InfluxDB ({} dòng chờ): {}", pending.len(), e);
+                    break;
+                }
+            }
+        }
+
+        if pending.len() > MAX_PENDING_LINES {
+            let dropped = pending.len() - MAX_PENDING_LINES;
+            pending.drain(..dropped);
+            warn!("⚠️ Bỏ {} dòng metrics InfluxDB cũ nhất", dropped);
+        }
+    }
+}
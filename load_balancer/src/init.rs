@@ -0,0 +1,177 @@
+// `load_balancer init`: tạo config.toml + servers.json khởi đầu. Thiếu tham số (--backend...) và đang chạy
+// trong terminal thì hỏi lần lượt. URL backend được kiểm tra ngay khi nhập: sai cú pháp thì nhập lại,
+// không kết nối được thì chỉ cảnh báo (backend có thể chưa chạy).
+use crate::check;
+use serde_json::json;
+use std::{
+    io::{BufRead, IsTerminal, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub struct Options {
+    pub listen: Option<SocketAddr>,
+    pub admin: Option<SocketAddr>,
+    pub backends: Vec<String>,
+    pub health_path: Option<String>,
+    pub out_dir: PathBuf,
+    pub force: bool,
+}
+
+const DEFAULT_LISTEN: &str = "0.0.0.0:8080";
+const DEFAULT_HEALTH_PATH: &str = "/healthz";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Lỗi cú pháp (Err) hoặc cảnh báo khi không kết nối được (Ok(Some))
+fn validate_backend(url: &str) -> Result<Option<String>, String> {
+    if let Some(path) = crate::upstream::unix_socket(url) {
+        return Ok((!Path::new(path).exists()).then(|| format!("chưa có socket {}", path)));
+    }
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("URL không hợp lệ: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("chỉ hỗ trợ http://, https:// hoặc unix:/đường/dẫn.sock".to_string());
+    }
+    let host = parsed.host_str().ok_or("URL thiếu host")?;
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    let reachable = (host, port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .is_some_and(|address| TcpStream::connect_timeout(&address, PROBE_TIMEOUT).is_ok());
+    Ok((!reachable).then(|| format!("không kết nối được {}:{} (backend chưa chạy?)", host, port)))
+}
+
+fn prompt(input: &mut impl BufRead, question: &str, default: Option<&str>) -> String {
+    match default {
+        Some(default) => print!("{} [{}]: ", question, default),
+        None => print!("{}: ", question),
+    }
+    let _ = std::io::stdout().flush();
+    let mut line = String::new();
+    let _ = input.read_line(&mut line);
+    let line = line.trim();
+    if line.is_empty() {
+        default.unwrap_or("").to_string()
+    } else {
+        line.to_string()
+    }
+}
+
+fn ask(options: &mut Options) {
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    while options.listen.is_none() {
+        match prompt(&mut input, "Địa chỉ nhận request", Some(DEFAULT_LISTEN)).parse() {
+            Ok(address) => options.listen = Some(address),
+            Err(_) => println!("❌ Cần dạng ip:port, vd. 0.0.0.0:8080"),
+        }
+    }
+    loop {
+        let answer = prompt(&mut input, "Dashboard / API trên cổng riêng (vd. 127.0.0.1:9000, bỏ trống = chung cổng)", None);
+        if answer.is_empty() {
+            break;
+        }
+        match answer.parse() {
+            Ok(address) => {
+                options.admin = Some(address);
+                break;
+            }
+            Err(_) => println!("❌ Cần dạng ip:port"),
+        }
+    }
+    println!("Nhập URL backend, mỗi dòng một backend (bỏ trống để kết thúc):");
+    loop {
+        let url = prompt(&mut input, "  Backend", None);
+        if url.is_empty() {
+            if options.backends.is_empty() {
+                println!("❌ Cần ít nhất một backend");
+                continue;
+            }
+            break;
+        }
+        match validate_backend(&url) {
+            Ok(warning) => {
+                if let Some(warning) = warning {
+                    println!("  ⚠️  {}", warning);
+                }
+                options.backends.push(url);
+            }
+            Err(e) => println!("  ❌ {}", e),
+        }
+    }
+    if options.health_path.is_none() {
+        options.health_path = Some(prompt(&mut input, "Path health check", Some(DEFAULT_HEALTH_PATH)));
+    }
+}
+
+fn render_config(listen: SocketAddr, admin: Option<SocketAddr>) -> String {
+    let mut toml = String::from(
+        "# Tạo bởi `load_balancer init`. Kiểm tra sau khi sửa: `load_balancer check`\n\
+         # Backend và health check khai báo trong servers.json\n",
+    );
+    match admin {
+        Some(admin) => toml.push_str(&format!(
+            "\n[[listeners]]\naddresses = [\"{}\"]\nroutes = \"proxy\"\n\n\
+             # Dashboard (/load-balancer/dashboard), API, /metrics\n\
+             [[listeners]]\naddresses = [\"{}\"]\nroutes = \"admin\"\n",
+            listen, admin
+        )),
+        None => toml.push_str(&format!("\n[listen]\naddresses = [\"{}\"]\n", listen)),
+    }
+    toml.push_str(
+        "\n# Giữ client ở lại backend cũ qua các lần restart\n\
+         [sticky]\npersist_file = \"sticky.json\"\n",
+    );
+    toml
+}
+
+// Trả về exit code
+pub fn run(mut options: Options) -> i32 {
+    let config_path = options.out_dir.join("config.toml");
+    let servers_path = options.out_dir.join("servers.json");
+    if !options.force {
+        if let Some(existing) = [&config_path, &servers_path].into_iter().find(|p| p.exists()) {
+            eprintln!("❌ {} đã tồn tại (dùng --force để ghi đè)", existing.display());
+            return 1;
+        }
+    }
+
+    // Backend truyền qua --backend: sai cú pháp thì dừng luôn
+    for url in &options.backends {
+        match validate_backend(url) {
+            Ok(Some(warning)) => println!("⚠️  {}: {}", url, warning),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("❌ {}: {}", url, e);
+                return 1;
+            }
+        }
+    }
+    if options.backends.is_empty() {
+        if !std::io::stdin().is_terminal() {
+            eprintln!("❌ Cần ít nhất một --backend (hoặc chạy trong terminal để nhập từng bước)");
+            return 1;
+        }
+        ask(&mut options);
+    }
+
+    let listen = options.listen.unwrap_or_else(|| DEFAULT_LISTEN.parse().unwrap());
+    let health_path = options.health_path.unwrap_or_else(|| DEFAULT_HEALTH_PATH.to_string());
+    let servers = json!({
+        crate::pools::DEFAULT_POOL: {
+            "servers": options.backends.iter().map(|url| json!({ "url": url })).collect::<Vec<_>>(),
+            "health": { "path": health_path },
+        }
+    });
+
+    if let Err(e) = std::fs::create_dir_all(&options.out_dir)
+        .and_then(|_| std::fs::write(&config_path, render_config(listen, options.admin)))
+        .and_then(|_| std::fs::write(&servers_path, serde_json::to_string_pretty(&servers).unwrap()))
+    {
+        eprintln!("❌ Không ghi được vào {}: {}", options.out_dir.display(), e);
+        return 1;
+    }
+    println!("📝 Đã tạo {} và {}", config_path.display(), servers_path.display());
+    check::run(&config_path, &servers_path)
+}
@@ -0,0 +1,114 @@
+// Ghi log thẳng vào journald khi chạy dưới systemd (stdout nối vào journal), mỗi event là một entry có field
+// riêng: MESSAGE, PRIORITY và các field của event viết hoa (vd. BACKEND, STATUS, LATENCY của request đã xử lý),
+// lọc được bằng `journalctl -u load_balancer STATUS=502`. Dùng native protocol qua unix datagram socket.
+use crate::syslog::severity;
+use std::{
+    os::unix::net::UnixDatagram,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+};
+use tracing::{field::Field, Event, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+
+const SOCKET: &str = "/run/systemd/journal/socket";
+
+// Số entry không gửi được (quá lớn cho một datagram, journald quá tải...), xuất ra /load-balancer/metrics
+// (None nếu không ghi vào journald)
+static DROPPED: OnceLock<AtomicU64> = OnceLock::new();
+
+pub fn dropped_total() -> Option<u64> {
+    DROPPED.get().map(|d| d.load(Ordering::Relaxed))
+}
+
+// systemd đặt JOURNAL_STREAM=<dev>:<inode> khi nối stdout / stderr vào journal; so với stdout để không nhầm
+// khi process con thừa kế biến này nhưng stdout đã bị chuyển hướng
+pub fn available() -> bool {
+    let Some(stream) = std::env::var_os("JOURNAL_STREAM") else {
+        return false;
+    };
+    // SAFETY: libc::stat là struct C chỉ gồm số nguyên, toàn bit 0 là giá trị hợp lệ
+    let mut stat = unsafe { std::mem::zeroed::<libc::stat>() };
+    // SAFETY: stat là con trỏ hợp lệ tới struct đủ lớn, sống qua lời gọi; fd không hợp lệ thì fstat chỉ trả -1
+    if unsafe { libc::fstat(libc::STDOUT_FILENO, &mut stat) } != 0 {
+        return false;
+    }
+    stream.to_str() == Some(&format!("{}:{}", stat.st_dev, stat.st_ino)) && Path::new(SOCKET).exists()
+}
+
+pub struct JournaldLayer {
+    socket: UnixDatagram,
+}
+
+impl JournaldLayer {
+    pub fn new() -> std::io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        DROPPED.get_or_init(|| AtomicU64::new(0));
+        Ok(Self { socket })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for JournaldLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut entry = Vec::new();
+        push_field(&mut entry, "PRIORITY", &severity(event.metadata().level()).to_string());
+        push_field(&mut entry, "SYSLOG_IDENTIFIER", "load_balancer");
+        push_field(&mut entry, "TARGET", event.metadata().target());
+        event.record(&mut FieldVisitor(&mut entry));
+        // Entry quá lớn cho một datagram thì bị bỏ và được đếm
+        if self.socket.send_to(&entry, SOCKET).is_err() {
+            if let Some(dropped) = DROPPED.get() {
+                dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// "NAME=value\n", hoặc dạng nhị phân (NAME\n, độ dài u64 little-endian, value\n) khi value có xuống dòng
+fn push_field(entry: &mut Vec<u8>, name: &str, value: &str) {
+    entry.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
+
+// Tên field journald: chữ hoa, số và "_", không bắt đầu bằng "_" (dành cho field do journald tự thêm) hay chữ số
+fn field_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    match name.trim_start_matches(|c: char| c == '_' || c.is_ascii_digit()) {
+        "" => "FIELD".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut Vec<u8>);
+
+impl FieldVisitor<'_> {
+    fn record(&mut self, field: &Field, value: &str) {
+        let name = match field.name() {
+            "message" => "MESSAGE".to_string(),
+            name => field_name(name),
+        };
+        push_field(self.0, &name, value);
+    }
+}
+
+impl tracing::field::Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record(field, &format!("{:?}", value));
+    }
+}
@@ -0,0 +1,231 @@
+// Kiểm tra "Authorization: Bearer <JWT>" (chữ ký theo JWKS, iss, aud, exp) trước khi proxy.
+// Token không hợp lệ bị chặn ngay với 401, claims đã xác thực được gửi lên backend qua header.
+use crate::{config::JwtConfig, normalize, oidc::claims_to_headers};
+use axum::{
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    jwk::{AlgorithmParameters, Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use serde_json::{Map, Value};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+// Không tải lại JWKS dồn dập khi gặp kid lạ
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct Validator {
+    config: JwtConfig,
+    // jwt.algorithms, đã kiểm tra trong config::validate
+    algorithms: Vec<Algorithm>,
+    client: reqwest::Client,
+    // JWKS đã tải và thời điểm tải
+    jwks: RwLock<Option<(JwkSet, Instant)>>,
+}
+
+impl Validator {
+    pub fn new(config: JwtConfig) -> Self {
+        Self {
+            algorithms: config.algorithms.iter().filter_map(|alg| alg.parse().ok()).collect(),
+            config,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    // Các header do validator đặt, phải xóa khỏi request của client để không bị giả mạo
+    pub fn identity_header_names(&self) -> impl Iterator<Item = &str> {
+        self.config
+            .claim_headers
+            .values()
+            .chain(self.config.claims_json_header.iter())
+            .map(String::as_str)
+    }
+
+    pub async fn authenticate(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Result<Vec<(HeaderName, HeaderValue)>, Response> {
+        // So theo path đã chuẩn hoá và theo từng segment: "/api" bảo vệ "/api/x" và "/./api", không phải "/apix".
+        // Path không chuẩn hoá được (traversal ẩn) thì coi như được bảo vệ
+        let protected = normalize::canonical_path(path)
+            .is_none_or(|path| self.config.protected_paths.iter().any(|p| normalize::under(&path, p)));
+        if !protected {
+            return Ok(Vec::new());
+        }
+
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer ").or_else(|| v.strip_prefix("bearer ")))
+            .map(str::trim);
+
+        let Some(token) = token else {
+            return Err(unauthorized("thiếu Bearer token"));
+        };
+
+        let claims = self.verify(token).await.map_err(|e| {
+            debug!("JWT bị từ chối: {}", e);
+            unauthorized(&e)
+        })?;
+
+        let mut identity = claims_to_headers(&self.config.claim_headers, &claims);
+        if let Some(name) = &self.config.claims_json_header {
+            let encoded = URL_SAFE_NO_PAD.encode(Value::Object(claims).to_string());
+            if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(&encoded)) {
+                identity.push((name, value));
+            }
+        }
+        Ok(identity)
+    }
+
+    async fn verify(&self, token: &str) -> Result<Map<String, Value>, String> {
+        let header = jsonwebtoken::decode_header(token).map_err(|e| e.to_string())?;
+
+        let jwk = match self.find_key(header.kid.as_deref(), false).await? {
+            Some(jwk) => jwk,
+            // kid lạ: có thể provider vừa xoay khoá -> tải lại JWKS rồi thử lần nữa
+            None => self
+                .find_key(header.kid.as_deref(), true)
+                .await?
+                .ok_or("không tìm thấy khoá (kid) trong JWKS")?,
+        };
+
+        // Thuật toán lấy từ khoá (alg của JWK, hoặc loại khoá) và jwt.algorithms, không tin header:
+        // token tự khai alg khác (vd. HS256 với public key RSA) bị từ chối trước khi kiểm tra chữ ký
+        let allowed = self.allowed_algorithms(&jwk);
+        if !allowed.contains(&header.alg) {
+            return Err(format!("thuật toán {:?} không được phép cho khoá này", header.alg));
+        }
+        let key = DecodingKey::from_jwk(&jwk).map_err(|e| e.to_string())?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = allowed;
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.audience]);
+        validation.leeway = self.config.leeway_secs;
+
+        jsonwebtoken::decode::<Map<String, Value>>(token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| e.to_string())
+    }
+
+    fn allowed_algorithms(&self, jwk: &Jwk) -> Vec<Algorithm> {
+        // JWK khai "alg" là thuật toán mã hoá (vd. RSA-OAEP) thì không dùng để kiểm tra chữ ký
+        let declared = jwk.common.key_algorithm.map(|alg| alg.to_string().parse::<Algorithm>().ok());
+        self.algorithms
+            .iter()
+            .copied()
+            .filter(|alg| match declared {
+                Some(declared) => declared == Some(*alg),
+                None => fits_key(*alg, &jwk.algorithm),
+            })
+            .collect()
+    }
+
+    async fn find_key(&self, kid: Option<&str>, force_refresh: bool) -> Result<Option<Jwk>, String> {
+        self.refresh_if_needed(force_refresh).await?;
+
+        let guard = self.jwks.read().await;
+        let Some((jwks, _)) = guard.as_ref() else {
+            return Ok(None);
+        };
+
+        let jwk = match kid {
+            Some(kid) => jwks.find(kid),
+            // Token không có kid: chỉ chấp nhận khi JWKS có đúng 1 khoá
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        };
+
+        Ok(jwk.cloned())
+    }
+
+    async fn refresh_if_needed(&self, force: bool) -> Result<(), String> {
+        let refresh_every = Duration::from_secs(self.config.jwks_refresh_secs);
+        let needs_refresh = |cached: &Option<(JwkSet, Instant)>| match cached {
+            None => true,
+            Some((_, fetched)) if force => fetched.elapsed() >= MIN_REFRESH_INTERVAL,
+            Some((_, fetched)) => fetched.elapsed() >= refresh_every,
+        };
+
+        if !needs_refresh(&*self.jwks.read().await) {
+            return Ok(());
+        }
+
+        let mut guard = self.jwks.write().await;
+        // Request khác có thể đã tải xong trong lúc chờ lock
+        if !needs_refresh(&guard) {
+            return Ok(());
+        }
+
+        match self.fetch_jwks().await {
+            Ok(jwks) => {
+                *guard = Some((jwks, Instant::now()));
+                Ok(())
+            }
+            // Giữ JWKS cũ nếu còn, tránh chặn toàn bộ traffic chỉ vì provider chập chờn
+            Err(e) if guard.is_some() => {
+                warn!("⚠️ Không tải lại được JWKS, dùng bản cũ: {}", e);
+                Ok(())
+            }
+            Err(e) => Err(format!("không tải được JWKS: {}", e)),
+        }
+    }
+
+    async fn fetch_jwks(&self) -> Result<JwkSet, String> {
+        self.client
+            .get(&self.config.jwks_url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .error_for_status()
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Thuật toán dùng được với loại khoá (JWK không khai "alg")
+fn fits_key(alg: Algorithm, key: &AlgorithmParameters) -> bool {
+    match key {
+        AlgorithmParameters::RSA(_) => matches!(
+            alg,
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 | Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512
+        ),
+        AlgorithmParameters::EllipticCurve(_) => matches!(alg, Algorithm::ES256 | Algorithm::ES384),
+        AlgorithmParameters::OctetKeyPair(_) => alg == Algorithm::EdDSA,
+        AlgorithmParameters::OctetKey(_) => false,
+    }
+}
+
+fn unauthorized(reason: &str) -> Response {
+    let mut resp = (StatusCode::UNAUTHORIZED, format!("JWT không hợp lệ: {}", reason)).into_response();
+    resp.headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fits_key;
+    use jsonwebtoken::{jwk::Jwk, Algorithm};
+
+    #[test]
+    fn key_type_limits_algorithm() {
+        let rsa: Jwk = serde_json::from_str(r#"{"kty":"RSA","n":"AQAB","e":"AQAB"}"#).unwrap();
+        assert!(fits_key(Algorithm::RS256, &rsa.algorithm));
+        assert!(fits_key(Algorithm::PS512, &rsa.algorithm));
+        assert!(!fits_key(Algorithm::HS256, &rsa.algorithm));
+        assert!(!fits_key(Algorithm::ES256, &rsa.algorithm));
+    }
+}
@@ -0,0 +1,400 @@
+// Khởi tạo logging (tracing). Mặc định level "info", ghi đè được bằng RUST_LOG.
+use crate::{cli::LogFormat, config::Config, syslog::SyslogLayer};
+use std::{
+    collections::HashMap,
+    io::IsTerminal,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, OnceLock, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tracing::{info, Event, Level, Metadata, Subscriber};
+use tracing_subscriber::{fmt, layer::Context, prelude::*, reload, EnvFilter, Layer, Registry};
+
+// Các level cho phép đổi lúc runtime qua API
+pub const LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+// Handle để đổi filter lúc đang chạy (PUT /load-balancer/api/log-level)
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+// Đếm log theo từng dòng lệnh log (callsite) khi bật [log_sampling]
+static SAMPLER: OnceLock<Arc<Sampler>> = OnceLock::new();
+
+// Ghi access log cho mỗi request (access_log = true trong config.toml hoặc đang ghi vào journald)
+static ACCESS_LOG: AtomicBool = AtomicBool::new(false);
+
+// Chu kỳ ghi một dòng tóm tắt số log đã bỏ
+const SUPPRESSED_REPORT: Duration = Duration::from_secs(10);
+
+pub enum Output {
+    // Ghi ra stdout như bình thường (text hoặc JSON theo --log-format)
+    Stdout(LogFormat),
+    // Ghi vào Windows Event Log (khi chạy dưới dạng service)
+    #[cfg(windows)]
+    EventLog,
+}
+
+// config (None khi chưa đọc được config.toml): [syslog] gửi thêm mọi log tới syslog song song với output chính,
+// [log_sampling] giới hạn số dòng log lặp lại
+pub fn init(output: Output, config: Option<&Config>) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = FILTER_HANDLE.set(handle);
+    let syslog = config.and_then(|c| c.syslog.as_ref()).and_then(|config| {
+        SyslogLayer::new(config)
+            .inspect_err(|e| eprintln!("⚠️ Không bật được syslog: {}", e))
+            .ok()
+    });
+    let sampling = config.filter(|c| c.log_sampling.enabled).map(|c| {
+        let sampler = SAMPLER.get_or_init(|| Arc::new(Sampler::new(c.log_sampling.max_per_second)));
+        SamplingLayer(sampler.clone())
+    });
+    let registry = tracing_subscriber::registry().with(filter).with(sampling).with(syslog);
+    ACCESS_LOG.store(config.is_some_and(|c| c.access_log), Ordering::Relaxed);
+
+    match output {
+        // Chạy dưới systemd: ghi thẳng vào journald để giữ field riêng (PRIORITY, BACKEND, STATUS...)
+        #[cfg(unix)]
+        Output::Stdout(LogFormat::Text) if crate::journald::available() => match crate::journald::JournaldLayer::new() {
+            Ok(journald) => {
+                ACCESS_LOG.store(true, Ordering::Relaxed);
+                registry.with(journald).init()
+            }
+            Err(_) => registry.with(fmt::layer().with_target(false).with_ansi(false)).init(),
+        },
+        Output::Stdout(LogFormat::Text) => {
+            // Không in mã màu ANSI khi stdout là file (vd. chạy --daemon)
+            let ansi = std::io::stdout().is_terminal();
+            registry.with(fmt::layer().with_target(false).with_ansi(ansi)).init()
+        }
+        Output::Stdout(LogFormat::Json) => registry.with(json::JsonLayer).init(),
+        #[cfg(windows)]
+        Output::EventLog => registry.with(eventlog::EventLogLayer::new(crate::service::SERVICE_NAME)).init(),
+    }
+}
+
+pub fn access_log() -> bool {
+    ACCESS_LOG.load(Ordering::Relaxed)
+}
+
+// Filter đang áp dụng (vd. "info" hoặc nội dung RUST_LOG)
+pub fn current_level() -> Option<String> {
+    FILTER_HANDLE.get()?.with_current(|f| f.to_string()).ok()
+}
+
+pub fn set_level(level: &str) -> Result<(), String> {
+    let level = level.trim().to_ascii_lowercase();
+    if !LEVELS.contains(&level.as_str()) {
+        return Err(format!("level không hợp lệ: {} (chọn một trong {:?})", level, LEVELS));
+    }
+
+    let handle = FILTER_HANDLE.get().ok_or("logging chưa được khởi tạo")?;
+    handle.reload(EnvFilter::new(&level)).map_err(|e| e.to_string())
+}
+
+// Mỗi dòng lệnh log (vd. "✅ Đã chọn server" khi traffic tăng vọt) chỉ ghi tối đa max_per_second dòng mỗi giây,
+// phần còn lại bị bỏ và được đếm. WARN / ERROR và access log (target "access") luôn được ghi.
+struct Sampler {
+    max_per_second: u32,
+    started: Instant,
+    // Bộ đếm riêng của từng callsite: chỉ lấy write lock khi gặp callsite lần đầu,
+    // còn lại đọc chung và cập nhật bằng atomic
+    lines: RwLock<HashMap<usize, Line>>,
+    suppressed_total: AtomicU64,
+}
+
+struct Line {
+    metadata: &'static Metadata<'static>,
+    // Giây hiện tại (32 bit cao) + số dòng đã ghi trong giây đó (32 bit thấp)
+    window: AtomicU64,
+    // Số log bị bỏ từ lần tóm tắt trước
+    suppressed: AtomicU64,
+}
+
+// Target của log không bao giờ bị bỏ
+pub const ACCESS_TARGET: &str = "access";
+
+impl Sampler {
+    fn new(max_per_second: u32) -> Self {
+        Self {
+            max_per_second,
+            started: Instant::now(),
+            lines: RwLock::new(HashMap::new()),
+            suppressed_total: AtomicU64::new(0),
+        }
+    }
+
+    fn allow(&self, metadata: &'static Metadata<'static>) -> bool {
+        if *metadata.level() <= Level::WARN || metadata.target() == ACCESS_TARGET {
+            return true;
+        }
+        let second = self.started.elapsed().as_secs() as u32;
+        let key = metadata as *const Metadata<'static> as usize;
+        if let Some(line) = self.lines.read().unwrap().get(&key) {
+            return self.count(line, second);
+        }
+        let mut lines = self.lines.write().unwrap();
+        let line = lines.entry(key).or_insert(Line {
+            metadata,
+            window: AtomicU64::new(u64::from(second) << 32),
+            suppressed: AtomicU64::new(0),
+        });
+        self.count(line, second)
+    }
+
+    fn count(&self, line: &Line, second: u32) -> bool {
+        // Sang giây mới thì đếm lại từ 1 (thread đọc đồng hồ trễ hơn vẫn đếm vào giây mới nhất)
+        let next = |window: u64| {
+            if (window >> 32) as u32 >= second {
+                (window & !u64::from(u32::MAX)) | u64::from((window as u32).saturating_add(1))
+            } else {
+                (u64::from(second) << 32) | 1
+            }
+        };
+        let previous = line
+            .window
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |window| Some(next(window)))
+            .unwrap();
+        if next(previous) as u32 <= self.max_per_second {
+            return true;
+        }
+        line.suppressed.fetch_add(1, Ordering::Relaxed);
+        self.suppressed_total.fetch_add(1, Ordering::Relaxed);
+        false
+    }
+}
+
+struct SamplingLayer(Arc<Sampler>);
+
+impl<S: Subscriber> Layer<S> for SamplingLayer {
+    fn event_enabled(&self, event: &Event<'_>, _ctx: Context<'_, S>) -> bool {
+        self.0.allow(event.metadata())
+    }
+}
+
+// Tổng số log đã bị bỏ (None nếu tắt [log_sampling]), xuất ra /load-balancer/metrics
+pub fn suppressed_total() -> Option<u64> {
+    SAMPLER.get().map(|s| s.suppressed_total.load(Ordering::Relaxed))
+}
+
+// Định kỳ ghi số log đã bỏ của từng dòng lệnh log (ghi ngoài lúc giữ lock của Sampler)
+pub async fn report_suppressed() {
+    let Some(sampler) = SAMPLER.get() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(SUPPRESSED_REPORT);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let suppressed: Vec<(&'static Metadata<'static>, u64)> = sampler
+            .lines
+            .read()
+            .unwrap()
+            .values()
+            .map(|line| (line.metadata, line.suppressed.swap(0, Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        for (metadata, count) in suppressed {
+            info!(
+                "🔇 Bỏ qua {} log lặp lại trong {}s tại {}:{}",
+                count,
+                SUPPRESSED_REPORT.as_secs(),
+                metadata.file().unwrap_or("?"),
+                metadata.line().unwrap_or(0)
+            );
+        }
+    }
+}
+
+// Mỗi log event một dòng JSON:
+// {"timestamp":"...","level":"INFO","target":"...","fields":{"message":"...", ...}}
+mod json {
+    use serde_json::{Map, Value};
+    use std::io::Write as _;
+    use tracing::{field::Field, Event, Subscriber};
+    use tracing_subscriber::{layer::Context, Layer};
+
+    pub struct JsonLayer;
+
+    impl<S: Subscriber> Layer<S> for JsonLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let mut fields = FieldVisitor(Map::new());
+            event.record(&mut fields);
+
+            let metadata = event.metadata();
+            let mut line = Map::new();
+            line.insert(
+                "timestamp".to_string(),
+                chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true).into(),
+            );
+            line.insert("level".to_string(), metadata.level().as_str().into());
+            line.insert("target".to_string(), metadata.target().into());
+            line.insert("fields".to_string(), Value::Object(fields.0));
+
+            let mut stdout = std::io::stdout().lock();
+            let _ = serde_json::to_writer(&mut stdout, &line);
+            let _ = stdout.write_all(b"\n");
+        }
+    }
+
+    // Giữ kiểu số / bool của field, còn lại ghi dạng chuỗi
+    struct FieldVisitor(Map<String, Value>);
+
+    impl tracing::field::Visit for FieldVisitor {
+        fn record_i64(&mut self, field: &Field, value: i64) {
+            self.0.insert(field.name().to_string(), value.into());
+        }
+
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.insert(field.name().to_string(), value.into());
+        }
+
+        fn record_f64(&mut self, field: &Field, value: f64) {
+            self.0.insert(field.name().to_string(), value.into());
+        }
+
+        fn record_bool(&mut self, field: &Field, value: bool) {
+            self.0.insert(field.name().to_string(), value.into());
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.into());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().to_string(), format!("{:?}", value).into());
+        }
+    }
+}
+
+#[cfg(windows)]
+mod eventlog {
+    use std::fmt::Write as _;
+    use tracing::{field::Field, Event, Level, Subscriber};
+    use tracing_subscriber::{layer::Context, Layer};
+    use windows_sys::Win32::{
+        Foundation::HANDLE,
+        System::EventLog::{
+            DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+            EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE,
+        },
+    };
+
+    // Layer đẩy từng log event vào Event Log (mục Application)
+    pub struct EventLogLayer {
+        handle: HANDLE,
+    }
+
+    // SAFETY: handle của event source dùng được từ nhiều thread (theo tài liệu Win32)
+    unsafe impl Send for EventLogLayer {}
+    unsafe impl Sync for EventLogLayer {}
+
+    impl EventLogLayer {
+        pub fn new(source: &str) -> Self {
+            let name = to_wide(source);
+            let handle = unsafe { RegisterEventSourceW(std::ptr::null(), name.as_ptr()) };
+            Self { handle }
+        }
+    }
+
+    impl Drop for EventLogLayer {
+        fn drop(&mut self) {
+            if !self.handle.is_null() {
+                unsafe { DeregisterEventSource(self.handle) };
+            }
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for EventLogLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            if self.handle.is_null() {
+                return;
+            }
+
+            let event_type = match *event.metadata().level() {
+                Level::ERROR => EVENTLOG_ERROR_TYPE,
+                Level::WARN => EVENTLOG_WARNING_TYPE,
+                _ => EVENTLOG_INFORMATION_TYPE,
+            };
+
+            let mut visitor = MessageVisitor(String::new());
+            event.record(&mut visitor);
+
+            let message = to_wide(&visitor.0);
+            let strings = [message.as_ptr()];
+            unsafe {
+                ReportEventW(
+                    self.handle,
+                    event_type,
+                    0,
+                    0,
+                    std::ptr::null_mut(),
+                    1,
+                    0,
+                    strings.as_ptr(),
+                    std::ptr::null(),
+                );
+            }
+        }
+    }
+
+    // Gom message + các field còn lại thành 1 dòng text
+    struct MessageVisitor(String);
+
+    impl tracing::field::Visit for MessageVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if !self.0.is_empty() {
+                self.0.push(' ');
+            }
+            if field.name() == "message" {
+                let _ = write!(self.0, "{:?}", value);
+            } else {
+                let _ = write!(self.0, "{}={:?}", field.name(), value);
+            }
+        }
+    }
+
+    fn to_wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sampler, SamplingLayer, ACCESS_TARGET};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tracing::{info, Event, Subscriber};
+    use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+    struct Counter(Arc<AtomicUsize>);
+
+    impl<S: Subscriber> Layer<S> for Counter {
+        fn on_event(&self, _event: &Event<'_>, _ctx: Context<'_, S>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Log thường bị giới hạn theo từng callsite, access log luôn được ghi
+    #[test]
+    fn access_log_is_never_sampled() {
+        let sampler = Arc::new(Sampler::new(3));
+        let written = Arc::new(AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::registry()
+            .with(SamplingLayer(sampler.clone()))
+            .with(Counter(written.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..10 {
+                info!("lặp lại");
+            }
+            for _ in 0..10 {
+                info!(target: ACCESS_TARGET, status = 200, "↩️ Đã trả response");
+            }
+        });
+        assert_eq!(written.load(Ordering::Relaxed), 13);
+        assert_eq!(sampler.suppressed_total.load(Ordering::Relaxed), 7);
+    }
+}
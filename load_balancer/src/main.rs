@@ -1,11 +1,14 @@
 use axum::{
     body::Body,
-    extract::{ConnectInfo, Request, State},
-    response::{Html, IntoResponse, Response, Sse},
-    routing::{get, any},
+    extract::{ConnectInfo, Path, Query, Request, State},
+    Extension,
+    http::StatusCode,
+    response::{Html, IntoResponse, Json, Response, Sse},
+    routing::{delete, get, any, put},
     Router,
 };
 use axum::response::sse::{Event, KeepAlive};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use futures::stream::{Stream, StreamExt}; // Import Stream trait
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -18,6 +21,8 @@ use std::{
 };
 use tokio::sync::broadcast;
 use tower_http::cors::CorsLayer;
+use tracing::{error, info, warn};
+use debug_trace::{RequestTrace, TraceStore};
 // Import thư viện tạo bảng
 use comfy_table::{presets::UTF8_FULL, Table};
 use crossterm::{
@@ -27,155 +32,87 @@ use crossterm::{
 };
 // use std::io::Write;
 
-const PORT: u16 = 8080;
-
-const DASHBOARD_HTML: &str = r#"
-<!DOCTYPE html>
-<html lang="vi">
-  <head>
-    <meta charset="UTF-8" />
-    <title>Load Balancer Status</title>
-    <style>
-      body {
-        font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto,
-          sans-serif;
-        margin: 2em;
-        background-color: #f8f9fa;
-      }
-      h1 {
-        color: #343a40;
-      }
-      table {
-        border-collapse: collapse;
-        width: 100%;
-        background-color: #fff;
-        box-shadow: 0 2px 4px rgba(0, 0, 0, 0.1);
-      }
-      th,
-      td {
-        border: 1px solid #dee2e6;
-        padding: 12px;
-        text-align: left;
-      }
-      th {
-        background-color: #f1f3f5;
-      }
-    </style>
-  </head>
-  <body>
-    <h1>Load Balancer Dashboard (Rust/Axum)</h1>
-    <table>
-      <thead>
-        <tr>
-          <th>URL</th>
-          <th>Region</th>
-          <th>Health</th>
-          <th>Uptime (%)</th>
-          <th>Resp (ms)</th>
-          <th>Latency Graph</th>
-          <th>Last Check</th>
-        </tr>
-      </thead>
-      <tbody id="dashboard-tbody"></tbody>
-    </table>
-
-    <script>
-      const tbody = document.getElementById("dashboard-tbody");
-
-      // Hàm tạo graph
-      function createGraph(values) {
-        const numericValues = values.filter((v) => typeof v === "number");
-        const max = numericValues.length > 0 ? Math.max(...numericValues) : 1;
-
-        let graphHtml =
-          '<div style="display: flex; align-items: flex-end; justify-content: center; gap: 1px; height: 20px; min-width: 60px;">';
-
-        graphHtml += values
-          .map((v) => {
-            if (typeof v !== "number") {
-              return '<div style="width: .5rem; height: 1px; background-color: #e9ecef; border-radius: 1px;"></div>';
-            }
-            if (v === 0) {
-              return '<div style="width: .5rem; height: 2px; background-color: #dc3545; border-radius: 1px;" title="DOWN"></div>';
-            }
-            const height = Math.max(1, (v / max) * 20);
-            // Lưu ý: Đã bỏ dấu \ trước ${}
-            return `<div style="width: .5rem; height: ${height}px; background-color: #007bff; border-radius: 1px;" title="${v}ms"></div>`;
-          })
-          .join("");
-
-        graphHtml += "</div>";
-        return graphHtml;
-      }
-
-      // Hàm cập nhật nội dung bảng
-      function updateTable(servers) {
-        let tableRows = "";
-        servers.forEach((s) => {
-          const uptimePercent = (
-            (s.uptime / (s.uptime + s.downtime + 1)) *
-            100
-          ).toFixed(1);
-
-          const healthStatus = s.healthy
-            ? '<span style="color: green;">🟢 ALIVE</span>'
-            : '<span style="color: red;">🔴 DOWN</span>';
-
-          const graph = createGraph(s.history);
-
-          // Lưu ý: Đã bỏ dấu \ trước ${}
-          tableRows += `
-          <tr>
-            <td>${s.url}</td>
-            <td>${s.region || "-"}</td>
-            <td>${healthStatus}</td>
-            <td>${uptimePercent} %</td>
-            <td>${s.responseTime || "-"}</td>
-            <td>${graph}</td>
-            <td>${s.lastCheck || "-"}</td>
-          </tr>
-        `;
-        });
-        tbody.innerHTML = tableRows;
-      }
-
-      // Hàm kết nối SSE
-      function connect() {
-        // Kết nối đến route SSE của server Rust
-        const evtSource = new EventSource("/load-balancer/events");
-
-        evtSource.onopen = () => {
-          console.log("SSE Connection established!");
-        };
+mod admin_auth;
+mod assets;
+mod backpressure;
+mod ban;
+mod basic_auth;
+mod bots;
+mod check;
+mod cli;
+mod client_limits;
+mod cloudwatch;
+mod config;
+#[cfg(unix)]
+mod daemon;
+mod dashboard;
+mod deadline;
+mod diagnostics;
+mod debug_trace;
+mod dns;
+mod drain;
+mod experiment;
+mod geoip;
+mod failover;
+mod graphite;
+mod h2c;
+#[cfg(unix)]
+mod handoff;
+mod hedging;
+mod hop_by_hop;
+mod import;
+mod influx;
+mod init;
+#[cfg(unix)]
+mod journald;
+mod jwt_auth;
+mod logging;
+mod malformed;
+mod metrics;
+mod normalize;
+mod ocsp;
+mod oidc;
+mod pools;
+mod proxy_protocol;
+mod response_buffering;
+#[cfg(windows)]
+mod service;
+mod secrets;
+mod security_headers;
+mod server;
+mod shadow;
+mod slo;
+mod slow_clients;
+mod statsd;
+mod sticky;
+mod syslog;
+mod synthetic;
+mod systemd;
+mod tls;
+mod trusted_proxies;
+mod upstream;
+mod uptime;
+mod vault;
+mod waf;
+mod webhooks;
 
-        evtSource.onmessage = (event) => {
-          try {
-            const servers = JSON.parse(event.data);
-            updateTable(servers);
-          } catch (e) {
-            console.error("Error parsing SSE data", e);
-          }
-        };
-
-        evtSource.onerror = (err) => {
-          console.error("EventSource error:", err);
-          // EventSource tự động reconnect, không cần code thêm logic
-        };
-      }
+// --- 1. Cấu trúc dữ liệu ---
 
-      // Bắt đầu kết nối khi trang được tải
-      connect();
-    </script>
-  </body>
-</html>
-"#;
+// Số lần health check gần nhất giữ lại cho biểu đồ
+const HISTORY_LEN: usize = 20;
 
-// --- 1. Cấu trúc dữ liệu ---
+// Thời gian giữ các lần health check chi tiết cho export CSV
+const SAMPLE_RETENTION_SECS: u64 = 24 * 3600;
 
+// Một lần health check
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct ServerConfig {
-    url: String,
-    region: Option<String>,
+#[serde(rename_all = "camelCase")]
+struct HealthSample {
+    // Unix timestamp (giây)
+    at: u64,
+    healthy: bool,
+    // None khi check lỗi
+    latency_ms: Option<u128>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -185,20 +122,114 @@ struct ServerConfig {
 struct ServerStatus {
     url: String,
     region: String,
+    pool: String,
     healthy: bool,
+    // Tắt thủ công từ dashboard / API: không nhận request dù health check vẫn OK
+    disabled: bool,
     response_time: Option<u128>,
     last_check: Option<String>,
+    // Lý do lần health check gần nhất thất bại (HTTP 503, DNS, timeout...)
+    last_error: Option<String>,
     uptime: u64,
     downtime: u64,
-    history: Vec<Option<u128>>,
+    // % health check UP trong 24 giờ gần nhất (từ lịch sử uptime theo giờ)
+    availability_24h: Option<f64>,
+    // Các lần health check gần nhất (tối đa HISTORY_LEN), cũ trước mới sau
+    history: Vec<HealthSample>,
+    // Mọi lần health check trong SAMPLE_RETENTION_SECS gần nhất (cho /load-balancer/api/history.csv)
+    #[serde(skip)]
+    samples: std::collections::VecDeque<HealthSample>,
+    // Số request đang gửi tới backend (least_conn)
+    #[serde(skip)]
+    active: Arc<std::sync::atomic::AtomicUsize>,
+    // Báo các response đang stream từ backend đóng lại (hết thời gian drain)
+    #[serde(skip)]
+    close: Arc<tokio::sync::Notify>,
+    // [timeouts] của config.toml, đã áp dụng phần ghi đè trong servers.json
+    #[serde(skip)]
+    timeouts: config::UpstreamTimeouts,
+    // Header xác thực gửi kèm request tới backend ("auth" trong servers.json), rỗng nếu không có
+    #[serde(skip)]
+    auth: axum::http::HeaderMap,
+    // Khai báo "auth" gốc (tham chiếu env:/file:/vault:...), để đọc lại credential từ Vault
+    #[serde(skip)]
+    auth_source: Option<pools::UpstreamAuth>,
+    // "protocol" trong servers.json
+    protocol: upstream::Protocol,
+    // "tls" trong servers.json: xác minh cert của backend (None = chấp nhận mọi cert)
+    #[serde(skip)]
+    tls: Option<upstream::BackendTls>,
+    // Giao thức của response gần nhất từ backend (kết quả ALPN với protocol = "auto"), vd. "HTTP/2.0"
+    negotiated: Option<String>,
+}
+
+impl ServerStatus {
+    // Có thể nhận request: health check OK và không bị tắt thủ công
+    fn is_available(&self) -> bool {
+        self.healthy && !self.disabled
+    }
 }
 
 struct AppState {
-    servers: Vec<ServerStatus>,
-    sticky_map: HashMap<String, String>,
-    rr_index: usize,
+    // Các pool backend (servers.json), mỗi pool có backend, sticky map và round robin riêng
+    pools: Vec<pools::Pool>,
     // Đưa channel vào trong AppState để dễ quản lý
     tx: broadcast::Sender<String>,
+    // true nếu servers.json được đọc và parse thành công (dùng cho /readyz)
+    config_loaded: bool,
+    // Debug trace của các request gần đây
+    traces: TraceStore,
+    config: config::Config,
+    // OIDC gateway (khi cấu hình [oidc])
+    oidc: Option<Arc<oidc::Gateway>>,
+    // Kiểm tra JWT (khi cấu hình [jwt])
+    jwt: Option<Arc<jwt_auth::Validator>>,
+    // Basic auth theo path (khi cấu hình [[basic_auth]])
+    basic_auth: Option<Arc<basic_auth::Guard>>,
+    // WAF (khi có [[waf.rules]])
+    waf: Option<Arc<waf::Engine>>,
+    // Chặn bot theo User-Agent (khi cấu hình [bots])
+    bots: Option<Arc<bots::Filter>>,
+    // Từ chối request sai định dạng (khi bật [malformed_requests])
+    malformed: Option<Arc<malformed::Validator>>,
+    // Giới hạn kết nối / request đồng thời mỗi IP (khi cấu hình [client_limits])
+    client_limits: Option<Arc<client_limits::Limiter>>,
+    // IP bị cấm tạm thời (khi bật [ban])
+    bans: Option<Arc<ban::BanList>>,
+    // Lấy IP client từ X-Forwarded-For khi peer là proxy tin cậy (khi cấu hình trusted_proxies)
+    trusted_proxies: Option<Arc<trusted_proxies::TrustedProxies>>,
+    // Quốc gia / vùng của client theo GeoIP (khi cấu hình [geoip])
+    geoip: Option<Arc<geoip::Locator>>,
+    // Gom response nhỏ để gửi kèm Content-Length (khi cấu hình [response_buffering])
+    response_buffering: Option<Arc<response_buffering::Buffering>>,
+    // Đệm body theo watermark giữa client và backend (khi cấu hình [streaming])
+    streaming: Option<Arc<backpressure::Watermarks>>,
+    // Thử nghiệm A/B (khi cấu hình [experiment])
+    experiment: Option<Arc<experiment::Experiment>>,
+    // Sao chép traffic sang service khác (khi cấu hình [[shadow]])
+    shadow: Option<Arc<shadow::Mirror>>,
+    // Gửi thêm request tới backend thứ hai khi backend đầu chậm (khi cấu hình [[hedging]])
+    hedging: Option<Arc<hedging::Hedger>>,
+    // Client gọi backend dùng chung (giữ kết nối keep-alive), theo connect timeout
+    upstream: upstream::Clients,
+    // Cache DNS cho hostname của backend (khi cấu hình [dns])
+    dns: Option<dns::Cache>,
+    // Lịch sử health check theo giờ cho báo cáo uptime
+    uptime: uptime::History,
+    // Gửi metrics tới StatsD (khi cấu hình [statsd])
+    statsd: Option<Arc<statsd::Sink>>,
+    // Đẩy metrics lên InfluxDB (khi cấu hình [influxdb])
+    influx: Option<Arc<influx::Exporter>>,
+    // Gửi metrics tới Graphite (khi cấu hình [graphite])
+    graphite: Option<Arc<graphite::Exporter>>,
+    // Đẩy metrics lên CloudWatch (khi cấu hình [cloudwatch])
+    cloudwatch: Option<Arc<cloudwatch::Exporter>>,
+    // Webhook khi backend UP <-> DOWN (khi cấu hình [[webhooks]])
+    webhooks: Option<Arc<webhooks::Notifier>>,
+    // Trang dashboard đã render từ template
+    dashboard: Arc<dashboard::Dashboard>,
+    // SLO và cảnh báo burn rate (khi cấu hình [[slo]])
+    slo: Option<Arc<slo::Tracker>>,
 }
 
 type SharedState = Arc<RwLock<AppState>>;
@@ -206,26 +237,53 @@ type SharedState = Arc<RwLock<AppState>>;
 // --- 2. Helper Functions ---
 
 // Hàm vẽ biểu đồ ASCII từ lịch sử response time
-fn ascii_graph(history: &[Option<u128>]) -> String {
+fn ascii_graph(history: &[HealthSample]) -> String {
     // Các ký tự block để vẽ độ cao
-    let chars = vec![' ', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let chars = [' ', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
     
     // Tìm giá trị lớn nhất để scale biểu đồ
-    let valid_values: Vec<u128> = history.iter().filter_map(|&v| v).collect();
-    let max = *valid_values.iter().max().unwrap_or(&1); // Tránh chia cho 0
+    let max = history.iter().filter_map(|s| s.latency_ms).max().unwrap_or(1).max(1); // Tránh chia cho 0
+    let gap = gap_threshold(history);
 
-    history.iter().map(|val| {
-        match val {
-            None => '·', // Chưa có dữ liệu (null)
-            Some(0) => 'x', // Server chết hoặc lỗi
+    let mut cells = Vec::new();
+    for (i, sample) in history.iter().enumerate() {
+        if i > 0 && gap.is_some_and(|g| sample.at.saturating_sub(history[i - 1].at) > g) {
+            cells.push('┊'); // Mất dữ liệu giữa 2 lần check
+        }
+        cells.push(match sample.latency_ms {
+            None => 'x', // Server chết hoặc lỗi
             Some(v) => {
                 // Tính toán độ cao tương đối
-                let ratio = *v as f64 / max as f64;
+                let ratio = v as f64 / max as f64;
                 let idx = (ratio * (chars.len() - 1) as f64).round() as usize;
-                chars[idx]
+                chars[idx.max(1)]
             }
+        });
+    }
+    if let (Some(last), Some(g)) = (history.last(), gap) {
+        if unix_now().saturating_sub(last.at) > g {
+            cells.push('┊');
         }
-    }).collect()
+    }
+
+    // Chưa có dữ liệu: '·' bên trái
+    let shown = &cells[cells.len().saturating_sub(HISTORY_LEN)..];
+    std::iter::repeat_n('·', HISTORY_LEN - shown.len()).chain(shown.iter().copied()).collect()
+}
+
+// Khoảng cách giữa 2 lần check lớn hơn ngưỡng này (gấp đôi khoảng cách thường gặp)
+// được coi là mất dữ liệu: load balancer restart, health check bị treo...
+fn gap_threshold(history: &[HealthSample]) -> Option<u64> {
+    let mut deltas: Vec<u64> = history.windows(2).map(|w| w[1].at.saturating_sub(w[0].at)).collect();
+    if deltas.is_empty() {
+        return None;
+    }
+    deltas.sort_unstable();
+    Some(deltas[deltas.len() / 2].max(1) * 2)
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs()
 }
 
 // Hàm in bảng trạng thái ra terminal
@@ -241,37 +299,34 @@ fn print_status_table(state: &SharedState) {
         MoveTo(0, 0)             // Đưa con trỏ về góc trái trên
     ).unwrap();
 
+    let port = r.config.listeners().iter()
+        .find(|l| l.routes != config::ListenerRoutes::Proxy)
+        .map_or(0, |l| l.addresses[0].port());
     println!("=== SERVER STATUS ===");
-    println!("=== http://localhost:{} ===", PORT);
-    println!("=== http://localhost:{}/load-balancer/dashboard ===\n", PORT);
+    println!("=== http://localhost:{} ===", port);
+    println!("=== http://localhost:{}/load-balancer/dashboard ===\n", port);
 
     let mut table = Table::new();
     table.load_preset(UTF8_FULL)
          .set_content_arrangement(comfy_table::ContentArrangement::Dynamic);
 
     table.set_header(vec![
-        "(index)", "URL", "REGION", "HEALTH", "UPTIME (%)", "RESP (ms)", "GRAPH", "LAST CHECK"
+        "(index)", "POOL", "URL", "REGION", "HEALTH", "UPTIME 24H (%)", "RESP (ms)", "GRAPH", "LAST CHECK"
     ]);
 
-    for (i, s) in r.servers.iter().enumerate() {
-        let health_icon = if s.healthy { "🟢" } else { "🔴" };
+    for (i, s) in r.pools.iter().flat_map(|p| p.servers.iter()).enumerate() {
+        let health_icon = if s.disabled { "⏸️" } else if s.healthy { "🟢" } else { "🔴" };
         
-        let total_checks = s.uptime + s.downtime;
-        let uptime_pct = if total_checks > 0 {
-            (s.uptime as f64 / total_checks as f64) * 100.0
-        } else {
-            0.0
-        };
-
         let resp_str = s.response_time.map(|t| t.to_string()).unwrap_or("-".to_string());
         let last_check = s.last_check.clone().unwrap_or("-".to_string());
 
         table.add_row(vec![
             i.to_string(),
+            s.pool.clone(),
             s.url.clone(),
             s.region.clone(),
             health_icon.to_string(),
-            format!("{:.1}", uptime_pct),
+            s.availability_24h.map_or("-".to_string(), |pct| format!("{:.2}", pct)),
             resp_str,
             ascii_graph(&s.history),
             last_check,
@@ -282,292 +337,2496 @@ fn print_status_table(state: &SharedState) {
 }
 // server
 
-fn load_servers() -> Vec<ServerStatus> {
-    // Đọc file servers.json
-    let data = std::fs::read_to_string("servers.json").unwrap_or_else(|_| {
-        println!("⚠️ Không tìm thấy servers.json, dùng danh sách rỗng.");
-        "[]".to_string()
-    });
-    
-    let configs: Vec<ServerConfig> = serde_json::from_str(&data).unwrap_or_else(|_| Vec::new());
-
-    configs.into_iter().map(|s| ServerStatus {
-        url: s.url,
-        region: s.region.unwrap_or_else(|| "-".to_string()),
-        healthy: false,
-        response_time: None,
-        last_check: None,
-        uptime: 0,
-        downtime: 0,
-        history: vec![None; 20],
-    }).collect()
-}
-
-fn get_client_id(ip: SocketAddr, headers: &axum::http::HeaderMap) -> String {
-    let ua = headers.get("user-agent").and_then(|v| v.to_str().ok()).unwrap_or("");
-    let raw = format!("{}{}", ip.ip(), ua);
+fn get_client_id(ip: SocketAddr, headers: &axum::http::HeaderMap, affinity: &config::AffinityConfig) -> String {
+    let name = affinity.name.as_deref().unwrap_or("");
+    let client_ip = client_ip_key(ip.ip(), affinity.ipv6_prefix_len);
+    let raw = match affinity.key {
+        config::ClientKey::Ip => client_ip,
+        config::ClientKey::IpUa => {
+            let ua = headers.get("user-agent").and_then(|v| v.to_str().ok()).unwrap_or("");
+            format!("{}{}", client_ip, ua)
+        }
+        config::ClientKey::Header => match headers.get(name).and_then(|v| v.to_str().ok()) {
+            Some(v) => format!("header:{}", v),
+            None => client_ip,
+        },
+        config::ClientKey::Cookie => match get_cookie(headers, name) {
+            Some(v) => format!("cookie:{}", v),
+            None => client_ip,
+        },
+    };
     format!("{:x}", md5::compute(raw))
 }
 
-fn choose_server(state: &mut AppState, client_id: &str) -> Option<String> {
-    // 1. Kiểm tra Sticky Session
-    if let Some(url) = state.sticky_map.get(client_id) {
-        if let Some(s) = state.servers.iter().find(|s| s.url == *url && s.healthy) {
-            println!("🎯 Sticky Hit: {}", s.url);
-            return Some(s.url.clone());
-        } else {
-            println!("⚠️ Sticky Server ({}) đã chết hoặc không tồn tại. Chuyển sang Round Robin.", url);
+// IPv4 giữ nguyên; IPv6 lấy prefix (vd. /64) vì thiết bị tự đổi phần interface ID (privacy extensions)
+fn client_ip_key(ip: std::net::IpAddr, ipv6_prefix_len: u8) -> String {
+    match ip.to_canonical() {
+        std::net::IpAddr::V6(v6) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(ipv6_prefix_len)).unwrap_or(0);
+            format!("{}/{}", std::net::Ipv6Addr::from(u128::from(v6) & mask), ipv6_prefix_len)
         }
+        v4 => v4.to_string(),
     }
+}
+
+fn get_cookie<'a>(headers: &'a axum::http::HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all("cookie")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+// Region của client lấy từ header cấu hình (chỉ dùng khi pool có backend thuộc region đó)
+fn client_region(pool: &pools::Pool, config: &config::Config, headers: &axum::http::HeaderMap) -> Option<String> {
+    let name = config.region.client_header.as_deref()?;
+    let region = headers.get(name)?.to_str().ok()?.trim();
+    pool.servers.iter()
+        .find(|s| s.region.eq_ignore_ascii_case(region))
+        .map(|s| s.region.clone())
+}
 
-    // 2. Lọc danh sách các server đang sống (Healthy = true)
-    let alive_indices: Vec<usize> = state.servers.iter()
+// Backend sống (chưa bị loại) được phép chọn: ưu tiên region của client / region nhà,
+// chỉ tràn sang region khác khi region đó không còn backend healthy và không quá tải
+fn candidate_indices(
+    pool: &pools::Pool,
+    config: &config::Config,
+    region: Option<&str>,
+    exclude: &[String],
+    trace: &mut Option<&mut RequestTrace>,
+) -> Vec<usize> {
+    let alive: Vec<usize> = pool.servers.iter()
         .enumerate()
-        .filter(|(_, s)| s.healthy)
+        .filter(|(_, s)| s.is_available() && !exclude.contains(&s.url))
         .map(|(i, _)| i)
         .collect();
 
+    let Some(preferred) = region.or(config.region.home.as_deref()) else {
+        return alive;
+    };
+
+    let overload = config.region.overload_latency_ms;
+    let local: Vec<usize> = alive.iter().copied()
+        .filter(|&i| {
+            let s = &pool.servers[i];
+            s.region.eq_ignore_ascii_case(preferred)
+                && overload.is_none_or(|max| s.response_time.is_none_or(|t| t <= max))
+        })
+        .collect();
+
+    if !local.is_empty() {
+        if let Some(t) = trace.as_mut() {
+            t.step(format!("ưu tiên region {}: {} backend", preferred, local.len()));
+        }
+        return local;
+    }
+
+    if alive.is_empty() {
+        return alive;
+    }
+
+    if config.region.spillover == config::Spillover::Latency {
+        let latencies = region_latencies(&pool.servers);
+        let fastest = alive.iter()
+            .map(|&i| pool.servers[i].region.as_str())
+            .filter(|r| !r.eq_ignore_ascii_case(preferred))
+            .min_by_key(|r| latencies.get(*r).copied().unwrap_or(u128::MAX));
+        if let Some(fastest) = fastest {
+            warn!("🌏 Region {} không còn backend khả dụng, chuyển sang region nhanh nhất: {}", preferred, fastest);
+            if let Some(t) = trace.as_mut() {
+                t.step(format!("region {} hết backend khả dụng -> region nhanh nhất {}", preferred, fastest));
+            }
+            return alive.iter().copied().filter(|&i| pool.servers[i].region == fastest).collect();
+        }
+    }
+
+    warn!("🌏 Region {} không còn backend khả dụng, chuyển sang region khác", preferred);
+    if let Some(t) = trace.as_mut() {
+        t.step(format!("region {} hết backend khả dụng -> dùng region khác", preferred));
+    }
+    alive
+}
+
+// Độ trễ health check trung bình (ms) của từng region, tính trên các lần đo gần nhất
+// của các backend đang healthy
+fn region_latencies(servers: &[ServerStatus]) -> HashMap<String, u128> {
+    let mut samples: HashMap<String, (u128, u128)> = HashMap::new();
+    for s in servers.iter().filter(|s| s.is_available()) {
+        // Lần check lỗi không có latency -> bỏ qua
+        for t in s.history.iter().filter_map(|h| h.latency_ms.as_ref()) {
+            let entry = samples.entry(s.region.clone()).or_default();
+            entry.0 += t;
+            entry.1 += 1;
+        }
+    }
+    samples.into_iter().map(|(region, (sum, n))| (region, sum / n)).collect()
+}
+
+// Ghi nhớ giao thức backend thực sự dùng (kết quả ALPN), chỉ ghi và log khi thay đổi
+fn remember_protocol(state: &SharedState, pool_index: usize, url: &str, version: axum::http::Version) {
+    let negotiated = format!("{:?}", version);
+    let known = |s: &ServerStatus| s.url == url && s.negotiated.as_deref() == Some(negotiated.as_str());
+    if state.read().unwrap().pools[pool_index].servers.iter().any(known) {
+        return;
+    }
+    let mut w = state.write().unwrap();
+    if let Some(server) = w.pools[pool_index].servers.iter_mut().find(|s| s.url == url) {
+        info!("🔀 Backend {} dùng {} (protocol = {:?})", url, negotiated, server.protocol);
+        server.negotiated = Some(negotiated);
+    }
+}
+
+// exclude: các backend đã thử và lỗi trong request này (failover)
+// region: region của client (nếu biết), ưu tiên hơn region nhà trong config
+// trace = Some(..) khi request đang bật debug tracing: ghi lại từng bước quyết định
+fn choose_server(
+    pool: &mut pools::Pool,
+    config: &config::Config,
+    client_id: &str,
+    region: Option<&str>,
+    exclude: &[String],
+    mut trace: Option<&mut RequestTrace>,
+) -> Option<String> {
+    // Lọc danh sách các server đang sống (Healthy = true), theo region ưu tiên
+    let alive_indices = candidate_indices(pool, config, region, exclude, &mut trace);
+
+    let strategy = pool.strategy.unwrap_or(match config.affinity.mode {
+        config::AffinityMode::StickyMap => pools::Strategy::RoundRobin,
+        config::AffinityMode::Rendezvous => pools::Strategy::ConsistentHash,
+    });
+    match strategy {
+        pools::Strategy::ConsistentHash => return choose_rendezvous(pool, client_id, &alive_indices, trace),
+        pools::Strategy::LeastConn | pools::Strategy::Random => {
+            return choose_unsticky(pool, strategy, &alive_indices, trace)
+        }
+        pools::Strategy::RoundRobin => {}
+    }
+
+    // 1. Kiểm tra Sticky Session
+    if let Some(url) = pool.sticky_map.get(client_id) {
+        if let Some(s) = alive_indices.iter().map(|&i| &pool.servers[i]).find(|s| s.url == *url) {
+            info!("🎯 Sticky Hit: {}", s.url);
+            if let Some(t) = trace.as_mut() {
+                t.step(format!("sticky hit: {}", s.url));
+                t.candidates = vec![s.url.clone()];
+            }
+            return Some(s.url.clone());
+        } else {
+            warn!("⚠️ Sticky Server ({}) đã chết, ngoài region ưu tiên hoặc không tồn tại. Chuyển sang Round Robin.", url);
+            if let Some(t) = trace.as_mut() {
+                t.step(format!("sticky miss: {} không healthy, ngoài region ưu tiên hoặc đã bị xóa", url));
+            }
+        }
+    } else if let Some(t) = trace.as_mut() {
+        t.step("không có sticky session");
+    }
+
+    // 2. Danh sách ứng viên
+    if let Some(t) = trace.as_mut() {
+        t.candidates = alive_indices.iter().map(|&i| pool.servers[i].url.clone()).collect();
+    }
+
     // --- DEBUG LOG ---
     if alive_indices.is_empty() {
-        println!("❌ LỖI: Không có server nào sống!");
-        println!("--- Trạng thái hiện tại ---");
-        for s in &state.servers {
-            println!(" - {}: Healthy={}", s.url, s.healthy);
+        error!("❌ LỖI: Không có server nào sống trong pool {}!", pool.name);
+        for s in &pool.servers {
+            error!(" - {}: Healthy={} Disabled={}", s.url, s.healthy, s.disabled);
+        }
+        if let Some(t) = trace.as_mut() {
+            t.step("không có backend healthy -> 503");
         }
-        println!("---------------------------");
         return None; // Trả về None -> Gây ra lỗi 503 "No backend servers alive"
     }
 
     // 3. Round Robin
-    state.rr_index = (state.rr_index + 1) % alive_indices.len();
-    let chosen_index = alive_indices[state.rr_index];
+    pool.rr_index = (pool.rr_index + 1) % alive_indices.len();
+    let chosen_index = alive_indices[pool.rr_index];
     
-    let chosen_url = state.servers[chosen_index].url.clone();
-    state.sticky_map.insert(client_id.to_string(), chosen_url.clone());
+    let chosen_url = pool.servers[chosen_index].url.clone();
+    pool.sticky_map.insert(client_id.to_string(), chosen_url.clone());
 
-    println!("✅ Đã chọn server: {}", chosen_url);
+    info!("✅ Đã chọn server: {}", chosen_url);
+    if let Some(t) = trace.as_mut() {
+        t.step(format!("round robin: index {} / {} ứng viên -> {}", pool.rr_index, alive_indices.len(), chosen_url));
+    }
     Some(chosen_url)
 }
 
+// least_conn / random: không dùng sticky session
+fn choose_unsticky(
+    pool: &mut pools::Pool,
+    strategy: pools::Strategy,
+    alive_indices: &[usize],
+    mut trace: Option<&mut RequestTrace>,
+) -> Option<String> {
+    if let Some(t) = trace.as_mut() {
+        t.candidates = alive_indices.iter().map(|&i| pool.servers[i].url.clone()).collect();
+    }
+    if alive_indices.is_empty() {
+        error!("❌ LỖI: Không có server nào sống trong pool {}!", pool.name);
+        if let Some(t) = trace.as_mut() {
+            t.step("không có backend healthy -> 503");
+        }
+        return None;
+    }
+
+    let chosen = if strategy == pools::Strategy::Random {
+        alive_indices[rand::random::<usize>() % alive_indices.len()]
+    } else {
+        // Xoay vòng điểm bắt đầu để các backend cùng số request được chia đều
+        pool.rr_index = (pool.rr_index + 1) % alive_indices.len();
+        let active = |i: &usize| pool.servers[*i].active.load(std::sync::atomic::Ordering::Relaxed);
+        alive_indices[pool.rr_index..].iter().chain(&alive_indices[..pool.rr_index])
+            .copied()
+            .min_by_key(active)
+            .unwrap()
+    };
+
+    let s = &pool.servers[chosen];
+    info!("✅ Đã chọn server ({:?}): {}", strategy, s.url);
+    if let Some(t) = trace.as_mut() {
+        t.step(format!("{:?}: {} ứng viên -> {} ({} request đang xử lý)", strategy, alive_indices.len(), s.url,
+            s.active.load(std::sync::atomic::Ordering::Relaxed)));
+    }
+    Some(s.url.clone())
+}
+
+// Rendezvous (HRW) hashing: mỗi backend sống được chấm điểm hash(client, backend),
+// backend điểm cao nhất thắng. Khi 1 backend chết chỉ client của nó bị chuyển đi.
+fn choose_rendezvous(
+    pool: &pools::Pool,
+    client_id: &str,
+    alive_indices: &[usize],
+    mut trace: Option<&mut RequestTrace>,
+) -> Option<String> {
+    let candidates: Vec<&ServerStatus> = alive_indices.iter().map(|&i| &pool.servers[i]).collect();
+
+    if let Some(t) = trace.as_mut() {
+        t.candidates = candidates.iter().map(|s| s.url.clone()).collect();
+    }
+
+    let Some(chosen) = candidates.iter().max_by_key(|s| rendezvous_score(client_id, &s.url)) else {
+        error!("❌ LỖI: Không có server nào sống trong pool {}!", pool.name);
+        if let Some(t) = trace.as_mut() {
+            t.step("không có backend healthy -> 503");
+        }
+        return None;
+    };
+
+    info!("✅ Đã chọn server (rendezvous): {}", chosen.url);
+    if let Some(t) = trace.as_mut() {
+        t.step(format!("rendezvous hash: {} ứng viên -> {}", candidates.len(), chosen.url));
+    }
+    Some(chosen.url.clone())
+}
+
+fn rendezvous_score(client_id: &str, url: &str) -> u64 {
+    let digest = md5::compute(format!("{}|{}", client_id, url));
+    u64::from_be_bytes(digest.0[..8].try_into().unwrap())
+}
+
 // --- 3. Background Task (Đã sửa lỗi check status) ---
 
-async fn health_check_task(state: SharedState) {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(2))
-        .user_agent("Mozilla/5.0 (Rust Load Balancer)")
-        .build()
-        .unwrap();
+// Gửi warmup.requests request tới mỗi path trong warmup.paths, dừng ở request đầu tiên lỗi hoặc không trả 2xx
+async fn warm_up(client: &Client, url: &str, auth: &axum::http::HeaderMap, warmup: &pools::WarmupConfig) -> Result<(), String> {
+    for path in &warmup.paths {
+        let target = format!("{}/{}", url.trim_end_matches('/'), path.trim_start_matches('/'));
+        for _ in 0..warmup.requests {
+            let response = client.get(&target).headers(auth.clone()).send().await.map_err(|e| format!("{}: {}", target, e))?;
+            if !response.status().is_success() {
+                return Err(format!("{} trả về {}", target, response.status()));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Health check bằng lệnh ngoài (check_type = "exec"): exit code 0 = UP.
+// Lệnh nhận thông tin backend qua biến môi trường LB_BACKEND_URL, LB_BACKEND_HOST, LB_BACKEND_PORT, LB_POOL;
+// chạy quá timeout_secs thì bị kill (chỉ tiến trình chính, script shell nên `exec` lệnh cuối) và tính DOWN.
+// Dòng cuối stderr được đưa vào lý do DOWN.
+async fn exec_check(health: &pools::HealthConfig, pool: &str, url: &str) -> Option<String> {
+    let Some((program, args)) = health.command.split_first() else {
+        return Some("exec: chưa khai báo command".to_string());
+    };
+    let mut command = tokio::process::Command::new(program);
+    command
+        .args(args)
+        .env("LB_BACKEND_URL", url)
+        .env("LB_POOL", pool)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true);
+    if let Ok(parsed) = reqwest::Url::parse(url) {
+        if let Some(host) = parsed.host_str() {
+            command.env("LB_BACKEND_HOST", host);
+        }
+        if let Some(port) = parsed.port_or_known_default() {
+            command.env("LB_BACKEND_PORT", port.to_string());
+        }
+    }
+    let child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => return Some(format!("exec {}: {}", program, e)),
+    };
+    match tokio::time::timeout(Duration::from_secs(health.timeout_secs), child.wait_with_output()).await {
+        Err(_) => Some(format!("exec {}: quá {}s", program, health.timeout_secs)),
+        Ok(Err(e)) => Some(format!("exec {}: {}", program, e)),
+        Ok(Ok(output)) if output.status.success() => None,
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match stderr.lines().map(str::trim).rfind(|l| !l.is_empty()) {
+                Some(line) => Some(format!("exec {}: {}", output.status, line)),
+                None => Some(format!("exec {}", output.status)),
+            }
+        }
+    }
+}
+
+// Mỗi pool một task health check, theo path / chu kỳ / timeout riêng của pool
+async fn health_check_task(state: SharedState, pool_index: usize) {
+    let (pool_name, health, dns) = {
+        let r = state.read().unwrap();
+        let pool = &r.pools[pool_index];
+        (pool.name.clone(), pool.health.clone(), r.dns.clone())
+    };
+    let client_for = |url: &str, protocol: upstream::Protocol, tls: Option<&upstream::BackendTls>| {
+        let mut builder = upstream::with_tls(Client::builder(), tls, protocol)
+            .timeout(Duration::from_secs(health.timeout_secs))
+            .user_agent("Mozilla/5.0 (Rust Load Balancer)");
+        if let Some(dns) = &dns {
+            builder = builder.dns_resolver(Arc::new(dns.clone()));
+        }
+        builder = match protocol {
+            upstream::Protocol::Auto => builder,
+            upstream::Protocol::Http1 => builder.http1_only(),
+            upstream::Protocol::Http2 => builder.http2_prior_knowledge(),
+        };
+        upstream::with_unix_socket(builder, url).build().unwrap()
+    };
+    // Mỗi (Unix socket, giao thức, xác minh cert) một client, backend TCP dùng chung
+    type HealthClientKey = (Option<String>, upstream::Protocol, Option<upstream::BackendTls>);
+    let mut clients: HashMap<HealthClientKey, Client> = HashMap::new();
 
     loop {
-        let servers_to_check: Vec<(usize, String)> = {
+        let servers_to_check: Vec<(usize, String, bool, axum::http::HeaderMap, upstream::Protocol, Option<upstream::BackendTls>)> = {
             let r = state.read().unwrap();
-            r.servers.iter().enumerate().map(|(i, s)| (i, s.url.clone())).collect()
+            r.pools[pool_index]
+                .servers
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (i, s.url.clone(), s.healthy, s.auth.clone(), s.protocol, s.tls.clone()))
+                .collect()
         };
 
         let mut updates = Vec::new();
 
-        for (idx, url) in servers_to_check {
-            let health_url = if url.ends_with('/') {
-                format!("{}healthz", url)
-            } else {
-                format!("{}/healthz", url)
-            };
+        for (idx, url, was_healthy, auth, protocol, tls) in servers_to_check {
+            let client = clients
+                .entry((upstream::unix_socket(&url).map(str::to_string), protocol, tls.clone()))
+                .or_insert_with(|| client_for(&url, protocol, tls.as_ref()))
+                .clone();
+            let base_url = upstream::http_base(&url);
+            let health_url = format!("{}/{}", base_url.trim_end_matches('/'), health.path.trim_start_matches('/'));
 
             let start = std::time::Instant::now();
-            
-            // Gửi request
-            let result = client.get(&health_url).send().await;
-            
+
+            // error = lý do DOWN (None = UP), hiển thị trong API và gửi kèm webhook
+            let error = match health.check_type {
+                pools::CheckType::Exec => exec_check(&health, &pool_name, &url).await,
+                pools::CheckType::Steps => synthetic::run(&client, base_url, &auth, &health.steps).await.err(),
+                // Kiểm tra kỹ: Phải kết nối được VÀ Status phải là 2xx (Success)
+                pools::CheckType::Http => match client.get(&health_url).headers(auth.clone()).send().await {
+                    // response.status().is_success() trả về true nếu mã là 200-299
+                    Ok(response) if response.status().is_success() => match &health.body {
+                        Some(expected) => match response.text().await {
+                            Ok(body) => expected.check(&body).err(),
+                            Err(e) => Some(e.to_string()),
+                        },
+                        None => None,
+                    },
+                    Ok(response) => Some(format!("HTTP {}", response.status())),
+                    // Lỗi kết nối mạng (Connection refused, Timeout...) hoặc không phân giải được hostname
+                    Err(e) => {
+                        let host = reqwest::Url::parse(&url).ok().and_then(|u| u.host_str().map(str::to_string));
+                        match dns.as_ref().zip(host).and_then(|(d, host)| d.failure(&host)) {
+                            Some(failure) => Some(format!("DNS: {}", failure)),
+                            // Kèm nguyên nhân, vd. cert không khớp pin (DNS trỏ nhầm hoặc bị MITM)
+                            None => Some(upstream::describe(&e)),
+                        }
+                    }
+                },
+            };
+
             let duration = start.elapsed().as_millis();
             let now_str = chrono::Local::now().format("%H:%M:%S").to_string();
 
-            // --- SỬA ĐOẠN NÀY ---
-            // Kiểm tra kỹ: Phải kết nối được VÀ Status phải là 2xx (Success)
-            let is_healthy = match result {
-                Ok(response) => {
-                    // response.status().is_success() trả về true nếu mã là 200-299
-                    response.status().is_success()
+            // Backend vừa hồi phục: warm-up xong mới đưa vào rotation, lỗi thì vẫn tính DOWN và thử lại lần sau
+            let error = match &health.warmup {
+                Some(warmup) if error.is_none() && !was_healthy => match warm_up(&client, base_url, &auth, warmup).await {
+                    Ok(()) => {
+                        info!("🔥 Warm-up {} xong", url);
+                        None
+                    }
+                    Err(e) => {
+                        warn!("⚠️ Warm-up {} thất bại: {}", url, e);
+                        Some(format!("warm-up: {}", e))
+                    }
                 },
-                Err(_) => false, // Lỗi kết nối mạng (Connection refused, Timeout...)
+                _ => error,
             };
 
-            updates.push((idx, is_healthy, duration, now_str));
+            updates.push((idx, error, duration, now_str));
         }
 
-        {
-            let mut w = state.write().unwrap();
-            for (idx, healthy, time, timestamp) in updates {
-                let s = &mut w.servers[idx];
+        let mut events = Vec::new();
+        let webhooks = {
+            let mut guard = state.write().unwrap();
+            let w = &mut *guard;
+            for (idx, error, time, timestamp) in updates {
+                let healthy = error.is_none();
+                let s = &mut w.pools[pool_index].servers[idx];
+                // Lần check đầu: chỉ báo khi backend DOWN (UP là trạng thái bình thường khi khởi động)
+                let transition = match s.last_check {
+                    None => !healthy,
+                    Some(_) => s.healthy != healthy,
+                };
                 s.last_check = Some(timestamp);
+                s.last_error = error;
+                w.uptime.record(&s.pool, &s.url, healthy);
+                s.availability_24h = w.uptime.availability(&s.pool, &s.url, 24);
                 
                 if healthy {
                     s.healthy = true;
                     s.response_time = Some(time);
                     s.uptime += 1;
-                    s.history.push(Some(time));
                 } else {
                     s.healthy = false;
                     s.response_time = None;
                     s.downtime += 1;
-                    s.history.push(Some(0));
                 }
-                if s.history.len() > 20 { s.history.remove(0); }
+                let sample = HealthSample { at: unix_now(), healthy, latency_ms: s.response_time };
+                while s.samples.front().is_some_and(|old| old.at + SAMPLE_RETENTION_SECS < sample.at) {
+                    s.samples.pop_front();
+                }
+                s.samples.push_back(sample.clone());
+                s.history.push(sample);
+                if s.history.len() > HISTORY_LEN { s.history.remove(0); }
+
+                if transition {
+                    events.push(webhooks::HealthEvent::new(&s.pool, &s.url, &s.region, s.response_time, s.last_error.as_deref()));
+                }
             }
             
-            let json_data = serde_json::to_string(&w.servers).unwrap();
+            let json_data = servers_json(w);
             let _ = w.tx.send(json_data);
+            w.webhooks.clone()
+        };
+        if let Some(notifier) = webhooks {
+            for event in &events {
+                notifier.notify(event);
+            }
         }
 
-        // --- THÊM DÒNG NÀY ĐỂ IN BẢNG ---
-        print_status_table(&state);
-        
+        tokio::time::sleep(Duration::from_secs(health.interval_secs.max(1))).await;
+    }
+}
+
+// Đọc lại credential backend lấy từ Vault để nhận giá trị đã xoay vòng
+async fn vault_refresh_task(state: SharedState, vault: Arc<vault::Client>) {
+    loop {
+        tokio::time::sleep(vault.refresh_interval()).await;
+        let sources: Vec<(usize, String, pools::UpstreamAuth)> = {
+            let r = state.read().unwrap();
+            r.pools
+                .iter()
+                .enumerate()
+                .flat_map(|(i, p)| {
+                    p.servers.iter().filter_map(move |s| {
+                        s.auth_source.as_ref().filter(|a| a.uses_vault()).map(|a| (i, s.url.clone(), a.clone()))
+                    })
+                })
+                .collect()
+        };
+        if sources.is_empty() {
+            return;
+        }
+        for (pool, url, source) in sources {
+            match source.headers(Some(&vault)).await {
+                Ok(headers) => {
+                    let mut w = state.write().unwrap();
+                    if let Some(s) = w.pools[pool].servers.iter_mut().find(|s| s.url == url) {
+                        s.auth = headers;
+                    }
+                }
+                Err(e) => warn!("⚠️ Không đọc lại được credential của {} từ Vault: {}", url, e),
+            }
+        }
+    }
+}
+
+// Danh sách backend của mọi pool (cho dashboard / SSE)
+fn servers_json(state: &AppState) -> String {
+    let servers: Vec<&ServerStatus> = state.pools.iter().flat_map(|p| p.servers.iter()).collect();
+    serde_json::to_string(&servers).unwrap()
+}
+
+// In bảng trạng thái ra terminal theo chu kỳ
+async fn status_table_task(state: SharedState) {
+    loop {
         tokio::time::sleep(Duration::from_secs(5)).await;
+        print_status_table(&state);
     }
 }
 
-// --- 4. Handlers ---
 
-async fn dashboard_handler() -> Html<&'static str> {
-    // Html(include_str!("dashboard.html"))
-    Html(DASHBOARD_HTML)
+// Thay cho bảng trạng thái khi không có terminal: mỗi phút một dòng log tóm tắt, kèm các backend đang down
+async fn status_log_task(state: SharedState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let r = state.read().unwrap();
+        let servers: Vec<&ServerStatus> = r.pools.iter().flat_map(|p| p.servers.iter()).collect();
+        let healthy = servers.iter().filter(|s| s.healthy && !s.disabled).count();
+        let disabled = servers.iter().filter(|s| s.disabled).count();
+        let down: Vec<&str> = servers.iter().filter(|s| !s.healthy && !s.disabled).map(|s| s.url.as_str()).collect();
+        if down.is_empty() {
+            info!("📊 Backend: {}/{} healthy, {} tắt thủ công", healthy, servers.len(), disabled);
+        } else {
+            warn!(
+                "📊 Backend: {}/{} healthy, {} tắt thủ công, down: {}",
+                healthy,
+                servers.len(),
+                disabled,
+                down.join(", ")
+            );
+        }
+    }
 }
 
-async fn sse_handler(
-    State(state): State<SharedState>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    // 1. Lấy receiver từ state
-    let (rx, initial_data) = {
-        let s = state.read().unwrap();
-        (s.tx.subscribe(), serde_json::to_string(&s.servers).unwrap())
+fn save_sticky_map(state: &SharedState) {
+    let (path, maps) = {
+        let r = state.read().unwrap();
+        let Some(path) = r.config.sticky.persist_file.clone() else {
+            return;
+        };
+        let maps: sticky::PoolMaps = r.pools.iter().map(|p| (p.name.clone(), p.sticky_map.clone())).collect();
+        (path, maps)
     };
 
-    // 2. Tạo stream từ broadcast receiver
-    let stream = tokio_stream::wrappers::BroadcastStream::new(rx)
-        .map(|msg| {
-            match msg {
-                Ok(data) => Event::default().data(data),
-                Err(_) => Event::default().comment("missed message"),
-            }
-        })
-        .map(Ok);
+    let total: usize = maps.values().map(HashMap::len).sum();
+    match sticky::save(&path, &maps) {
+        Ok(()) => info!("💾 Đã lưu {} sticky session vào {}", total, path.display()),
+        Err(e) => error!("❌ Không lưu được sticky map vào {}: {}", path.display(), e),
+    }
+}
 
-    // 3. Gửi ngay dữ liệu hiện tại (initial_data) trước khi stream bắt đầu
-    // Để người dùng không thấy bảng trắng khi mới F5
-    let initial_stream = tokio_stream::once(Ok(Event::default().data(initial_data)));
-    
-    // Nối stream khởi tạo với stream lắng nghe
-    let combined_stream = initial_stream.chain(stream);
+fn save_uptime_history(state: &SharedState) {
+    let r = state.read().unwrap();
+    let Some(path) = &r.config.uptime.persist_file else {
+        return;
+    };
+    if let Err(e) = r.uptime.save(path) {
+        error!("❌ Không lưu được lịch sử uptime vào {}: {}", path.display(), e);
+    }
+}
 
-    Sse::new(combined_stream).keep_alive(KeepAlive::default())
+async fn uptime_persist_task(state: SharedState) {
+    let interval = {
+        let r = state.read().unwrap();
+        if r.config.uptime.persist_file.is_none() {
+            return;
+        }
+        Duration::from_secs(r.config.uptime.persist_interval_secs.max(1))
+    };
+
+    loop {
+        tokio::time::sleep(interval).await;
+        save_uptime_history(&state);
+    }
 }
 
-async fn proxy_handler(
-    State(state): State<SharedState>,
-    ConnectInfo(ip): ConnectInfo<SocketAddr>,
-    headers: axum::http::HeaderMap, // Header gốc từ trình duyệt
-    req: Request,
-) -> Response {
-    let client_id = get_client_id(ip, &headers);
-    
-    let target_url = {
-        let mut w = state.write().unwrap();
-        choose_server(&mut w, &client_id)
+async fn sticky_persist_task(state: SharedState) {
+    let interval = {
+        let r = state.read().unwrap();
+        if r.config.sticky.persist_file.is_none() {
+            return;
+        }
+        Duration::from_secs(r.config.sticky.persist_interval_secs.max(1))
+    };
+
+    loop {
+        tokio::time::sleep(interval).await;
+        save_sticky_map(&state);
+    }
+}
+
+// --- 4. Handlers ---
+
+async fn dashboard_handler(State(state): State<SharedState>) -> Html<String> {
+    let dashboard = state.read().unwrap().dashboard.clone();
+    Html(dashboard.html().to_string())
+}
+
+// Liveness: process còn chạy và phục vụ được request là đủ
+async fn livez_handler() -> &'static str {
+    "ok"
+}
+
+// Readiness: đã load config và pool nào cũng có ít nhất 1 backend healthy
+async fn readyz_handler(State(state): State<SharedState>) -> Response {
+    let (config_loaded, pools) = {
+        let r = state.read().unwrap();
+        let pools: serde_json::Map<String, serde_json::Value> = r.pools.iter()
+            .map(|p| (p.name.clone(), p.servers.iter().filter(|s| s.is_available()).count().into()))
+            .collect();
+        (r.config_loaded, pools)
     };
 
-    match target_url {
-        Some(base_url) => {
-            let path = req.uri().path();
-            let query = req.uri().query().map(|q| format!("?{}", q)).unwrap_or_default();
-            let final_url = format!("{}{}{}", base_url.trim_end_matches('/'), path, query);
+    let healthy_backends: u64 = pools.values().filter_map(|v| v.as_u64()).sum();
+    let ready = config_loaded && !pools.is_empty() && pools.values().all(|v| v.as_u64() > Some(0));
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
 
-            // 1. Parse URL đích để lấy Hostname (ví dụ: p.dh74.io.vn)
-            let parsed_url = reqwest::Url::parse(&base_url).unwrap();
-            let target_host = parsed_url.host_str().unwrap_or("");
+    (status, Json(serde_json::json!({
+        "ready": ready,
+        "configLoaded": config_loaded,
+        "healthyBackends": healthy_backends,
+        "pools": pools,
+    }))).into_response()
+}
 
-            let client = Client::builder()
-                // Quan trọng: Tắt verify SSL nếu server đích dùng self-signed hoặc lỗi cert
-                // Nhưng với p.dh74.io.vn thì không cần dòng này cũng được
-                .danger_accept_invalid_certs(true) 
-                .build()
-                .unwrap();
+#[derive(Deserialize)]
+struct LogLevelRequest {
+    level: String,
+}
 
-            let method = req.method().clone();
-            let body = req.into_body(); 
+async fn get_log_level_handler() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "level": logging::current_level() }))
+}
 
-            // 2. Tạo bộ Header mới để gửi đi
-            let mut new_headers = headers.clone();
-            
-            // --- SỬA QUAN TRỌNG Ở ĐÂY ---
-            // Thay thế Host: localhost:8080 bằng Host: p.dh74.io.vn
-            new_headers.insert("host", target_host.parse().unwrap());
-            // Thêm Referer để server đích không chặn
-            new_headers.insert("referer", base_url.parse().unwrap());
-
-            // Xóa header nén (gzip/br) để tránh lỗi decode khi proxy trả về
-            new_headers.remove("accept-encoding"); 
-
-            println!("Proxying to: {} (Host: {})", final_url, target_host);
-
-            match client.request(method, &final_url)
-                .headers(new_headers) // Dùng header đã sửa
-                .body(reqwest::Body::wrap_stream(body.into_data_stream()))
-                .send()
-                .await 
-            {
-                Ok(res) => {
-                    let mut response_builder = Response::builder().status(res.status());
-                    *response_builder.headers_mut().unwrap() = res.headers().clone();
-                    
-                    // Xóa các header bảo mật cors/frame của server đích để trình duyệt local hiển thị được
-                    // (Tùy chọn, nhưng hữu ích khi proxy trang web khác)
-                    response_builder.headers_mut().unwrap().remove("content-security-policy");
-                    response_builder.headers_mut().unwrap().remove("x-frame-options");
-
-                    response_builder.body(Body::from_stream(res.bytes_stream())).unwrap()
-                },
-                Err(e) => {
-                    println!("Proxy Error: {}", e);
-                    (axum::http::StatusCode::BAD_GATEWAY, format!("Bad Gateway: {}", e)).into_response()
-                }
-            }
-        },
-        None => (axum::http::StatusCode::SERVICE_UNAVAILABLE, "No backend servers alive").into_response()
+// Đổi log level lúc runtime, không cần restart (giữ nguyên sticky/health state)
+async fn put_log_level_handler(Json(body): Json<LogLevelRequest>) -> Response {
+    match logging::set_level(&body.level) {
+        Ok(()) => {
+            warn!("🔧 Đã đổi log level sang: {}", body.level);
+            Json(serde_json::json!({ "level": logging::current_level() })).into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
     }
 }
 
-// --- 5. Main ---
+#[derive(Deserialize)]
+struct DebugTraceToggle {
+    enabled: bool,
+}
 
-#[tokio::main]
-async fn main() {
-    // Tạo channel broadcast
-    let (tx, _rx) = broadcast::channel::<String>(100);
+async fn get_debug_trace_handler(State(state): State<SharedState>) -> Json<serde_json::Value> {
+    let r = state.read().unwrap();
+    Json(serde_json::json!({
+        "enabled": r.traces.enabled,
+        "recent": r.traces.recent_ids(),
+    }))
+}
 
-    // Khởi tạo State
-    let shared_state = Arc::new(RwLock::new(AppState {
-        servers: load_servers(),
-        sticky_map: HashMap::new(),
-        rr_index: 0,
-        tx, // Lưu tx vào state luôn
-    }));
+// Bật/tắt trace cho mọi request (không cần header x-lb-debug)
+async fn put_debug_trace_handler(
+    State(state): State<SharedState>,
+    Json(body): Json<DebugTraceToggle>,
+) -> Json<serde_json::Value> {
+    state.write().unwrap().traces.enabled = body.enabled;
+    warn!("🔧 Debug trace cho mọi request: {}", if body.enabled { "BẬT" } else { "TẮT" });
+    Json(serde_json::json!({ "enabled": body.enabled }))
+}
 
-    // Chạy Health Check
-    let state_clone = shared_state.clone();
-    tokio::spawn(async move {
-        health_check_task(state_clone).await;
-    });
+async fn debug_request_handler(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    let r = state.read().unwrap();
+    match r.traces.get(&id) {
+        Some(t) => Json(t).into_response(),
+        None => (StatusCode::NOT_FOUND, "Không tìm thấy trace (đã hết hạn hoặc id sai)").into_response(),
+    }
+}
 
-    println!("🚀 Load balancer (Rust) đang chạy tại http://localhost:{}", PORT);
-    println!("📊 Dashboard: http://localhost:{}/load-balancer/dashboard", PORT);
+#[derive(Deserialize)]
+struct DebugStateQuery {
+    // Số entry sticky map đưa vào mẫu của mỗi pool (mặc định 10, tối đa 100)
+    sample: Option<usize>,
+}
 
-    // Router đơn giản hơn (Dùng chung 1 State)
-    let app = Router::new()
-        .route("/load-balancer/dashboard", get(dashboard_handler))
-        .route("/load-balancer/events", get(sse_handler))
-        .fallback(any(proxy_handler))
-        .layer(CorsLayer::permissive())
-        .with_state(shared_state);
+// Bỏ user:password trong URL backend (nếu có)
+fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) if !parsed.username().is_empty() || parsed.password().is_some() => {
+            let _ = parsed.set_username("***");
+            let _ = parsed.set_password(None);
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
 
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", PORT)).await.unwrap();
-    
-    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+// Ảnh chụp toàn bộ trạng thái routing để debug: pool, backend, sticky map, round robin...
+// Không bao giờ trả giá trị credential (header auth, token, secret), chỉ cho biết có cấu hình hay không.
+async fn debug_state_handler(State(state): State<SharedState>, Query(query): Query<DebugStateQuery>) -> Json<serde_json::Value> {
+    let sample_size = query.sample.unwrap_or(10).min(100);
+    let r = state.read().unwrap();
+    let config = &r.config;
+    let pools: Vec<_> = r
+        .pools
+        .iter()
+        .map(|pool| {
+            let strategy = pool.strategy.unwrap_or(match config.affinity.mode {
+                config::AffinityMode::StickyMap => pools::Strategy::RoundRobin,
+                config::AffinityMode::Rendezvous => pools::Strategy::ConsistentHash,
+            });
+            // Client id là md5 của IP / header / cookie, chỉ giữ 8 ký tự đầu để đối chiếu với log
+            let mut sticky: Vec<_> = pool
+                .sticky_map
+                .iter()
+                .map(|(client, url)| (client.chars().take(8).collect::<String>() + "…", redact_url(url)))
+                .collect();
+            sticky.sort();
+            sticky.truncate(sample_size);
+            let mut per_backend: HashMap<&str, usize> = HashMap::new();
+            for url in pool.sticky_map.values() {
+                *per_backend.entry(url.as_str()).or_default() += 1;
+            }
+            let servers: Vec<_> = pool
+                .servers
+                .iter()
+                .map(|s| {
+                    serde_json::json!({
+                        "url": redact_url(&s.url),
+                        "region": s.region,
+                        "healthy": s.healthy,
+                        "disabled": s.disabled,
+                        "available": s.is_available(),
+                        "active": s.active.load(std::sync::atomic::Ordering::Relaxed),
+                        "stickyClients": per_backend.get(s.url.as_str()).copied().unwrap_or(0),
+                        "responseTime": s.response_time,
+                        "lastCheck": s.last_check,
+                        "lastError": s.last_error,
+                        "uptime": s.uptime,
+                        "downtime": s.downtime,
+                        "timeouts": s.timeouts,
+                        "auth": (!s.auth.is_empty()).then_some("redacted"),
+                        "protocol": s.protocol,
+                        "negotiatedProtocol": s.negotiated,
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "name": pool.name,
+                "strategy": strategy,
+                "strategyConfigured": pool.strategy.is_some(),
+                "rrIndex": pool.rr_index,
+                "requests": pool.requests,
+                "health": {
+                    "type": pool.health.check_type,
+                    "path": pool.health.path,
+                    "intervalSecs": pool.health.interval_secs,
+                    "timeoutSecs": pool.health.timeout_secs,
+                },
+                "stickyMap": {
+                    "size": pool.sticky_map.len(),
+                    "sample": sticky.into_iter().map(|(client, url)| serde_json::json!({ "client": client, "backend": url })).collect::<Vec<_>>(),
+                },
+                "servers": servers,
+            })
+        })
+        .collect();
+    Json(serde_json::json!({
+        "configLoaded": r.config_loaded,
+        "affinity": {
+            "mode": config.affinity.mode,
+            "key": config.affinity.key,
+        },
+        "defaultPool": config.routing.default_pool,
+        "traceAll": r.traces.enabled,
+        "features": {
+            "oidc": r.oidc.is_some(),
+            "jwt": r.jwt.is_some(),
+            "basicAuth": r.basic_auth.is_some(),
+            "waf": r.waf.is_some(),
+            "bots": r.bots.is_some(),
+            "malformedRequests": r.malformed.is_some(),
+            "clientLimits": r.client_limits.is_some(),
+            "ban": r.bans.is_some(),
+            "trustedProxies": r.trusted_proxies.is_some(),
+            "geoip": r.geoip.is_some(),
+            "responseBuffering": r.response_buffering.is_some(),
+            "streaming": r.streaming.is_some(),
+            "experiment": r.experiment.is_some(),
+            "shadow": r.shadow.is_some(),
+            "hedging": r.hedging.is_some(),
+            "dns": r.dns.is_some(),
+            "statsd": r.statsd.is_some(),
+            "influxdb": r.influx.is_some(),
+            "graphite": r.graphite.is_some(),
+            "cloudwatch": r.cloudwatch.is_some(),
+            "webhooks": r.webhooks.is_some(),
+            "slo": r.slo.is_some(),
+        },
+        "pools": pools,
+    }))
+}
+
+async fn metrics_handler(State(state): State<SharedState>) -> Response {
+    let body = metrics::render(&state.read().unwrap());
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response()
+}
+
+async fn list_bans_handler(State(state): State<SharedState>) -> Response {
+    let bans = state.read().unwrap().bans.clone();
+    match bans {
+        Some(b) => Json(serde_json::json!({ "bans": b.list() })).into_response(),
+        None => (StatusCode::NOT_FOUND, "Chưa bật [ban] trong config.toml").into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct BackendStateRequest {
+    // Bỏ trống: tìm backend theo URL trong mọi pool
+    pool: Option<String>,
+    url: String,
+    enabled: bool,
+}
+
+// Bật / tắt backend thủ công, ghi đè kết quả health check
+async fn backend_state_handler(State(state): State<SharedState>, Json(req): Json<BackendStateRequest>) -> Response {
+    let mut w = state.write().unwrap();
+    let mut found = 0;
+    let mut draining = Vec::new();
+    for (pool_index, pool) in w.pools.iter_mut().enumerate()
+        .filter(|(_, p)| req.pool.as_deref().is_none_or(|name| p.name == name))
+    {
+        for s in pool.servers.iter_mut().filter(|s| s.url == req.url) {
+            if !req.enabled && !s.disabled {
+                draining.push(pool_index);
+            }
+            s.disabled = !req.enabled;
+            found += 1;
+        }
+    }
+    if found == 0 {
+        return (StatusCode::NOT_FOUND, "Không tìm thấy backend").into_response();
+    }
+
+    // Request đang chạy trên backend vừa tắt được chờ tối đa [drain] timeout_secs
+    let timeout = Duration::from_secs(w.config.drain.timeout_secs);
+    for pool_index in draining {
+        tokio::spawn(drain::backend(state.clone(), pool_index, req.url.clone(), timeout));
+    }
+
+    if req.enabled {
+        warn!("▶️ Bật lại backend thủ công: {}", req.url);
+    } else {
+        warn!("⏸️ Tắt backend thủ công: {}", req.url);
+    }
+    let json_data = servers_json(&w);
+    let _ = w.tx.send(json_data);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn unban_handler(State(state): State<SharedState>, Path(ip): Path<String>) -> Response {
+    let Ok(ip) = ip.parse::<std::net::IpAddr>() else {
+        return (StatusCode::BAD_REQUEST, "IP không hợp lệ").into_response();
+    };
+    let bans = state.read().unwrap().bans.clone();
+    match bans {
+        Some(b) if b.unban(ip) => {
+            warn!("🔧 Gỡ cấm thủ công: {}", ip);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Some(_) => (StatusCode::NOT_FOUND, "IP không có trong danh sách").into_response(),
+        None => (StatusCode::NOT_FOUND, "Chưa bật [ban] trong config.toml").into_response(),
+    }
+}
+
+async fn experiment_handler(State(state): State<SharedState>) -> Response {
+    let experiment = state.read().unwrap().experiment.clone();
+    match experiment {
+        Some(e) => Json(serde_json::json!({ "name": e.name(), "variants": e.report() })).into_response(),
+        None => (StatusCode::NOT_FOUND, "Chưa cấu hình [experiment] trong config.toml").into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct HistoryCsvQuery {
+    // URL backend (mặc định: mọi backend)
+    server: Option<String>,
+    // Unix timestamp (giây) hoặc RFC 3339
+    from: Option<String>,
+    to: Option<String>,
+}
+
+fn parse_time(value: &str) -> Option<u64> {
+    value.parse().ok().or_else(|| {
+        chrono::DateTime::parse_from_rfc3339(value).ok().and_then(|t| u64::try_from(t.timestamp()).ok())
+    })
+}
+
+// Export các lần health check ra CSV để phân tích bằng spreadsheet / công cụ ngoài
+async fn history_csv_handler(State(state): State<SharedState>, Query(query): Query<HistoryCsvQuery>) -> Response {
+    let mut range = [0, u64::MAX];
+    for (bound, value) in range.iter_mut().zip([&query.from, &query.to]) {
+        if let Some(value) = value {
+            match parse_time(value) {
+                Some(t) => *bound = t,
+                None => {
+                    return (StatusCode::BAD_REQUEST, format!("Thời gian không hợp lệ: {} (dùng unix timestamp hoặc RFC 3339)", value))
+                        .into_response();
+                }
+            }
+        }
+    }
+    let [from, to] = range;
+
+    let mut csv = String::from("pool,server,timestamp,unix_time,healthy,latency_ms\n");
+    {
+        let r = state.read().unwrap();
+        let servers = r.pools.iter()
+            .flat_map(|p| p.servers.iter())
+            .filter(|s| query.server.as_deref().is_none_or(|url| s.url == url));
+        for s in servers {
+            for sample in s.samples.iter().filter(|h| h.at >= from && h.at <= to) {
+                let timestamp = chrono::DateTime::from_timestamp(sample.at as i64, 0)
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default();
+                let latency = sample.latency_ms.map(|ms| ms.to_string()).unwrap_or_default();
+                csv.push_str(&format!(
+                    "{},{},{},{},{},{}\n",
+                    csv_field(&s.pool), csv_field(&s.url), timestamp, sample.at, sample.healthy, latency
+                ));
+            }
+        }
+    }
+
+    (
+        [
+            (axum::http::header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (axum::http::header::CONTENT_DISPOSITION, "attachment; filename=\"history.csv\""),
+        ],
+        csv,
+    ).into_response()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(Deserialize)]
+struct ReportQuery {
+    format: Option<String>,
+}
+
+// Báo cáo uptime 24 giờ / 7 ngày / 30 ngày: JSON, hoặc HTML khi ?format=html / trình duyệt yêu cầu
+async fn uptime_report_handler(
+    State(state): State<SharedState>,
+    Query(query): Query<ReportQuery>,
+    headers: axum::http::HeaderMap,
+) -> Response {
+    let reports = {
+        let r = state.read().unwrap();
+        r.uptime.report(&r.pools)
+    };
+    let wants_html = match query.format.as_deref() {
+        Some(format) => format == "html",
+        None => headers
+            .get(axum::http::header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("text/html")),
+    };
+    if wants_html {
+        Html(uptime::render_html(&reports)).into_response()
+    } else {
+        Json(serde_json::json!({ "backends": reports })).into_response()
+    }
+}
+
+async fn slo_handler(State(state): State<SharedState>) -> Response {
+    let slo = state.read().unwrap().slo.clone();
+    match slo {
+        Some(t) => Json(serde_json::json!({ "slos": t.report() })).into_response(),
+        None => (StatusCode::NOT_FOUND, "Chưa cấu hình [[slo]] trong config.toml").into_response(),
+    }
+}
+
+async fn dns_handler(State(state): State<SharedState>) -> Response {
+    let dns = state.read().unwrap().dns.clone();
+    match dns {
+        Some(d) => Json(serde_json::json!({ "entries": d.report() })).into_response(),
+        None => (StatusCode::NOT_FOUND, "Chưa cấu hình [dns] trong config.toml").into_response(),
+    }
+}
+
+// Số request theo quốc gia của client (dashboard hiển thị bảng này nếu có [geoip])
+async fn geo_stats_handler(State(state): State<SharedState>) -> Response {
+    let geoip = state.read().unwrap().geoip.clone();
+    match geoip {
+        Some(g) => Json(serde_json::json!({ "countries": g.stats() })).into_response(),
+        None => (StatusCode::NOT_FOUND, "Chưa cấu hình [geoip] trong config.toml").into_response(),
+    }
+}
+
+async fn hedging_stats_handler(State(state): State<SharedState>) -> Json<serde_json::Value> {
+    let hedging = state.read().unwrap().hedging.clone();
+    Json(serde_json::json!({ "rules": hedging.map(|h| h.stats()).unwrap_or_default() }))
+}
+
+async fn shadow_stats_handler(State(state): State<SharedState>) -> Json<serde_json::Value> {
+    let shadow = state.read().unwrap().shadow.clone();
+    Json(serde_json::json!({ "rules": shadow.map(|s| s.stats()).unwrap_or_default() }))
+}
+
+async fn waf_stats_handler(State(state): State<SharedState>) -> Json<serde_json::Value> {
+    let waf = state.read().unwrap().waf.clone();
+    Json(serde_json::json!({ "rules": waf.map(|w| w.stats()).unwrap_or_default() }))
+}
+
+async fn oidc_callback_handler(
+    State(state): State<SharedState>,
+    Query(query): Query<oidc::CallbackQuery>,
+    headers: axum::http::HeaderMap,
+) -> Response {
+    let oidc = state.read().unwrap().oidc.clone();
+    match oidc {
+        Some(gateway) => gateway.callback(query, &headers).await,
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn oidc_logout_handler(State(state): State<SharedState>, headers: axum::http::HeaderMap) -> Response {
+    let oidc = state.read().unwrap().oidc.clone();
+    match oidc {
+        Some(gateway) => gateway.logout(&headers),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn sse_handler(
+    State(state): State<SharedState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    // 1. Lấy receiver từ state
+    let (rx, initial_data) = {
+        let s = state.read().unwrap();
+        (s.tx.subscribe(), servers_json(&s))
+    };
+
+    // 2. Tạo stream từ broadcast receiver
+    let stream = tokio_stream::wrappers::BroadcastStream::new(rx)
+        .map(|msg| {
+            match msg {
+                Ok(data) => Event::default().data(data),
+                Err(_) => Event::default().comment("missed message"),
+            }
+        })
+        .map(Ok);
+
+    // 3. Gửi ngay dữ liệu hiện tại (initial_data) trước khi stream bắt đầu
+    // Để người dùng không thấy bảng trắng khi mới F5
+    let initial_stream = tokio_stream::once(Ok(Event::default().data(initial_data)));
+    
+    // Nối stream khởi tạo với stream lắng nghe
+    let combined_stream = initial_stream.chain(stream);
+
+    Sse::new(combined_stream).keep_alive(KeepAlive::default())
+}
+
+// Cùng dữ liệu với SSE nhưng qua WebSocket
+async fn ws_handler(State(state): State<SharedState>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| ws_session(state, socket))
+}
+
+async fn ws_session(state: SharedState, mut socket: WebSocket) {
+    let (mut rx, initial_data) = {
+        let s = state.read().unwrap();
+        (s.tx.subscribe(), servers_json(&s))
+    };
+    if socket.send(Message::Text(initial_data)).await.is_err() {
+        return;
+    }
+
+    // Ping định kỳ để proxy không đóng kết nối nhàn rỗi
+    let mut keep_alive = tokio::time::interval(Duration::from_secs(15));
+    loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Ok(data) => {
+                    if socket.send(Message::Text(data)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Client không gửi gì ngoài ping/pong
+                Some(Ok(_)) => {}
+            },
+            _ = keep_alive.tick() => {
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn proxy_handler(
+    State(state): State<SharedState>,
+    ConnectInfo(ip): ConnectInfo<SocketAddr>,
+    Extension(client_cert): Extension<Option<tls::ClientCertSubject>>,
+    headers: axum::http::HeaderMap, // Header gốc từ trình duyệt
+    req: Request,
+) -> Response {
+    let started = std::time::Instant::now();
+    // Sau CDN / reverse proxy tin cậy: IP client thật lấy từ X-Forwarded-For
+    // (dùng cho sticky, giới hạn request, ban, WAF...)
+    let trusted = state.read().unwrap().trusted_proxies.clone();
+    let ip = trusted.map_or(ip, |t| t.client_addr(ip, &headers));
+    let (client_id, client_cert_header, oidc, jwt, basic_auth, waf, bots, slow_clients, client_limits, bans) = {
+        let r = state.read().unwrap();
+        (
+            get_client_id(ip, &headers, &r.config.affinity),
+            r.config.tls.as_ref().map(|t| t.client_cert_header.clone()),
+            r.oidc.clone(),
+            r.jwt.clone(),
+            r.basic_auth.clone(),
+            r.waf.clone(),
+            r.bots.clone(),
+            r.config.slow_clients.clone(),
+            r.client_limits.clone(),
+            r.bans.clone(),
+        )
+    };
+
+    let malformed = state.read().unwrap().malformed.clone();
+    if let Some(reason) = malformed.as_ref().and_then(|m| m.check(req.uri(), &headers)) {
+        warn!("🚫 Từ chối request sai định dạng từ {} ({})", ip.ip(), reason.as_str());
+        if let Some(b) = &bans {
+            b.strike(ip.ip(), "request sai định dạng");
+        }
+        return (StatusCode::BAD_REQUEST, reason.message()).into_response();
+    }
+
+    // Expect: chỉ hỗ trợ 100-continue. hyper trả 100 Continue ở lần đầu body được đọc, tức là khi reqwest đã gửi xong
+    // header lên backend và bắt đầu đẩy body; request bị chặn trước đó thì client không phải gửi body.
+    // Header Expect vẫn được chuyển tiếp, nhưng reqwest không đợi / chuyển tiếp 100 Continue của backend.
+    if headers
+        .get_all(axum::http::header::EXPECT)
+        .iter()
+        .any(|v| !v.as_bytes().eq_ignore_ascii_case(b"100-continue"))
+    {
+        return (StatusCode::EXPECTATION_FAILED, "Chỉ hỗ trợ Expect: 100-continue").into_response();
+    }
+
+    if let Some(remaining) = bans.as_ref().and_then(|b| b.banned_for(ip.ip())) {
+        let mut resp = (StatusCode::FORBIDDEN, "IP đang bị cấm tạm thời").into_response();
+        resp.headers_mut().insert(axum::http::header::RETRY_AFTER, (remaining.as_secs() + 1).into());
+        return resp;
+    }
+
+    // GeoIP: quốc gia / vùng của client, chặn theo deny_countries / allow_countries
+    let geoip = state.read().unwrap().geoip.clone();
+    let location = geoip.as_ref().map(|g| g.lookup(ip.ip())).unwrap_or_default();
+    if let Some(g) = &geoip {
+        let denied = g.denied(location.country.as_deref());
+        g.record(location.country.as_deref(), denied);
+        if denied {
+            return (StatusCode::FORBIDDEN, "Không phục vụ request từ khu vực này").into_response();
+        }
+    }
+
+    // Giữ chỗ tới khi có response từ backend
+    let _request_permit = match client_limits.as_ref().map(|l| l.try_request(ip.ip())) {
+        Some(None) => {
+            if let Some(b) = &bans {
+                b.strike(ip.ip(), "vượt số request đồng thời");
+            }
+            return (StatusCode::TOO_MANY_REQUESTS, "Quá nhiều request đồng thời từ IP này").into_response();
+        }
+        permit => permit.flatten(),
+    };
+
+    if let Some(resp) = bots.as_ref().and_then(|b| b.check(ip.ip(), &headers)) {
+        return resp;
+    }
+
+    // WAF: kiểm tra trước mọi bước khác
+    if let Some(resp) = waf.as_ref().and_then(|w| w.evaluate(ip.ip(), req.uri(), &headers)) {
+        if let Some(b) = &bans {
+            b.strike(ip.ip(), "WAF");
+        }
+        return resp;
+    }
+    // Luật WAF theo kích thước body: body chunked (không có Content-Length) bị đếm và cắt khi stream
+    let waf_body_limit = waf.as_ref().and_then(|w| w.body_limit(req.uri(), &headers));
+
+    // Basic auth: chặn path được bảo vệ khi thiếu/sai user/password
+    let remote_user = match &basic_auth {
+        Some(guard) => match guard.check(req.uri().path(), &headers).await {
+            Ok(user) => user,
+            Err(resp) => return resp,
+        },
+        None => None,
+    };
+
+    // OIDC gateway: chưa đăng nhập thì redirect tới provider, đã đăng nhập thì lấy claims
+    let mut identity_headers = match &oidc {
+        Some(gateway) => match gateway.authenticate(ip.ip(), req.method(), req.uri(), &headers).await {
+            Ok(identity) => identity,
+            Err(resp) => return resp,
+        },
+        None => Vec::new(),
+    };
+
+    // JWT: chặn token không hợp lệ ngay tại load balancer
+    if let Some(validator) = &jwt {
+        match validator.authenticate(req.uri().path(), &headers).await {
+            Ok(identity) => identity_headers.extend(identity),
+            Err(resp) => return resp,
+        }
+    }
+
+    // Debug trace: bật theo header của request (mang admin token) hoặc theo toggle toàn cục
+    let trace_requested = debug_trace::requested(state.read().unwrap().config.admin.as_ref(), &headers);
+
+    let host = headers.get(axum::http::header::HOST).and_then(|v| v.to_str().ok());
+
+    let (target_url, mut trace, pool_index, region, experiment, assignment, body_mode, upload_cap) = {
+        let mut guard = state.write().unwrap();
+        let w = &mut *guard;
+        let routed = pools::route(&w.config.routing, host, req.uri().path(), location.country.as_deref());
+        let body_mode = routed.map_or(w.config.routing.default_body, |r| r.body);
+        let upload_cap = routed.and_then(|r| r.max_upload_bytes).into_iter().chain(waf_body_limit).min();
+        let mut pool_index = pools::select(&w.pools, &w.config.routing, routed);
+
+        // A/B: variant quyết định pool
+        let experiment = w.experiment.clone();
+        let assignment = experiment.as_ref().and_then(|e| e.assign(req.uri().path(), &headers));
+        if let (Some(e), Some(a)) = (&experiment, &assignment) {
+            if let Some(i) = w.pools.iter().position(|p| p.name == e.pool(a.index)) {
+                pool_index = Some(i);
+            }
+        }
+
+        let mut trace = (trace_requested || w.traces.enabled).then(|| RequestTrace {
+            id: debug_trace::new_id(),
+            started_at: chrono::Local::now().to_rfc3339(),
+            method: req.method().to_string(),
+            path: req.uri().to_string(),
+            client_id: client_id.clone(),
+            ..Default::default()
+        });
+
+        let select_start = std::time::Instant::now();
+        let (target_url, region) = match pool_index {
+            Some(i) => {
+                let pool = &mut w.pools[i];
+                pool.requests += 1;
+                if let Some(t) = trace.as_mut() {
+                    t.pool = Some(pool.name.clone());
+                }
+                let region = client_region(pool, &w.config, &headers);
+                (choose_server(pool, &w.config, &client_id, region.as_deref(), &[], trace.as_mut()), region)
+            }
+            None => {
+                if let Some(t) = trace.as_mut() {
+                    t.step("không có pool nào cho request này");
+                }
+                (None, None)
+            }
+        };
+        if let Some(t) = trace.as_mut() {
+            t.timings.select_us = select_start.elapsed().as_micros();
+            t.backend = target_url.clone();
+        }
+        (target_url, trace, pool_index, region, experiment, assignment, body_mode, upload_cap)
+    };
+    let variant = experiment.as_ref().zip(assignment.as_ref());
+
+    // Có backend thì luôn có pool (backend được chọn trong pool)
+    let (Some(mut base_url), Some(pool_index)) = (target_url, pool_index) else {
+        let response = finish_variant(variant, started,
+            (StatusCode::SERVICE_UNAVAILABLE, "No backend servers alive").into_response());
+        record_request(&state, pool_index, None, &response, started);
+        return finish_trace(&state, trace, started, response);
+    };
+
+    let path_and_query = req.uri().path_and_query().map(|pq| pq.as_str().to_string()).unwrap_or_default();
+
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    let replay_safe = failover::is_replay_safe(&method, &headers);
+    let mut body = slow_clients::guard_body(req.into_body(), &slow_clients);
+    let upload_exceeded = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    if let Some(limit) = upload_cap {
+        body = match failover::cap_upload(body, &headers, limit, upload_exceeded.clone()) {
+            Ok(body) => body,
+            Err(status) => {
+                if let Some(t) = trace.as_mut() {
+                    t.step(format!("upload vượt giới hạn {} byte (max_upload_bytes / WAF)", limit));
+                }
+                let response = finish_variant(variant, started, (status, "Upload vượt dung lượng cho phép").into_response());
+                record_request(&state, Some(pool_index), None, &response, started);
+                return finish_trace(&state, trace, started, response);
+            }
+        };
+    }
+    let shadow = state.read().unwrap().shadow.clone();
+    if let Some(mirror) = &shadow {
+        body = mirror.mirror(&method, &path_and_query, &headers, body);
+    }
+    let streaming = state.read().unwrap().streaming.clone();
+    let body = match body_mode {
+        config::BodyMode::Stream => {
+            let body = match &streaming {
+                Some(w) => Body::from_stream(w.wrap(body.into_data_stream(), backpressure::Direction::Request)),
+                None => body,
+            };
+            failover::UpstreamBody::new(body, &headers)
+        }
+        // Đọc hết body trước khi gửi: luật WAF theo body, failover sau khi backend đã đọc body
+        config::BodyMode::Buffer => {
+            let limit = state.read().unwrap().config.routing.max_buffered_body_bytes;
+            let limit = upload_cap.map_or(limit, |cap| cap.min(limit));
+            let buffered = match failover::buffer(body, &headers, limit).await {
+                Ok(bytes) => match waf.as_ref().and_then(|w| w.evaluate_body(ip.ip(), &uri, &headers, &bytes)) {
+                    Some(resp) => {
+                        if let Some(b) = &bans {
+                            b.strike(ip.ip(), "WAF");
+                        }
+                        Err(resp)
+                    }
+                    None => Ok(failover::UpstreamBody::buffered(bytes)),
+                },
+                Err(status) => Err((status, "Không đọc được request body").into_response()),
+            };
+            match buffered {
+                Ok(body) => body,
+                Err(response) => {
+                    if let Some(t) = trace.as_mut() {
+                        t.step(format!("request body bị từ chối: {}", response.status()));
+                    }
+                    let response = finish_variant(variant, started, response);
+                    record_request(&state, Some(pool_index), None, &response, started);
+                    return finish_trace(&state, trace, started, response);
+                }
+            }
+        }
+    };
+
+    // Request gửi lên backend `base_url` (dùng cho lần gửi đầu, failover và hedging)
+    // Timeout riêng, header xác thực của backend và client tương ứng
+    let upstream_for = |url: &str| {
+        let r = state.read().unwrap();
+        let server = r.pools[pool_index].servers.iter().find(|s| s.url == url);
+        let timeouts = server.map_or_else(|| r.config.timeouts.clone(), |s| s.timeouts.clone());
+        let auth = server.map(|s| s.auth.clone()).unwrap_or_default();
+        let protocol = server.map(|s| s.protocol).unwrap_or_default();
+        let tls = server.and_then(|s| s.tls.as_ref());
+        (r.upstream.get(url, &timeouts, protocol, tls), timeouts, auth)
+    };
+
+    let deadline_config = state.read().unwrap().config.deadline.clone();
+    let client_deadline = deadline_config.as_ref().and_then(|d| deadline::from_client(&headers, d));
+    let upstream_request = |base_url: &str, upstream_body: Option<reqwest::Body>| {
+        let (client, timeouts, auth) = upstream_for(base_url);
+        // Backend Unix socket: client đã gắn socket, URL chỉ còn path
+        let base_url = upstream::http_base(base_url);
+        let final_url = format!("{}{}", base_url.trim_end_matches('/'), path_and_query);
+
+        // 1. Parse URL đích để lấy Hostname (ví dụ: p.dh74.io.vn)
+        let parsed_url = reqwest::Url::parse(base_url).unwrap();
+        let target_host = parsed_url.host_str().unwrap_or("");
+
+        // 2. Tạo bộ Header mới để gửi đi
+        let mut new_headers = headers.clone();
+        // Bỏ header theo chặng của client (Connection, Keep-Alive, Upgrade, Proxy-*...)
+        hop_by_hop::strip_request(&mut new_headers);
+    
+        // --- SỬA QUAN TRỌNG Ở ĐÂY ---
+        // Thay thế Host: localhost:8080 bằng Host: p.dh74.io.vn
+        new_headers.insert("host", target_host.parse().unwrap());
+        // Thêm Referer để server đích không chặn
+        new_headers.insert("referer", base_url.parse().unwrap());
+
+        // Xóa header nén (gzip/br) để tránh lỗi decode khi proxy trả về
+        new_headers.remove("accept-encoding"); 
+        new_headers.remove(debug_trace::DEBUG_HEADER);
+
+        // Claims của user đã xác thực (OIDC/JWT), xóa header cùng tên do client tự gửi
+        for name in oidc.iter().flat_map(|g| g.identity_header_names())
+            .chain(jwt.iter().flat_map(|v| v.identity_header_names()))
+            .chain(basic_auth.iter().flat_map(|g| g.identity_header_names()))
+        {
+            new_headers.remove(name);
+        }
+        for (name, value) in &identity_headers {
+            new_headers.insert(name.clone(), value.clone());
+        }
+        // Mật khẩu basic auth chỉ dành cho load balancer, không gửi lên backend
+        if let Some((name, value)) = &remote_user {
+            new_headers.remove(axum::http::header::AUTHORIZATION);
+            new_headers.insert(name.clone(), value.clone());
+        }
+
+        // A/B: báo variant cho backend, không tin header do client tự gửi
+        if let Some(e) = &experiment {
+            let (name, value) = e.variant_header(assignment.as_ref().map_or(0, |a| a.index));
+            new_headers.remove(&name);
+            if assignment.is_some() {
+                new_headers.insert(name, value);
+            }
+        }
+
+        // GeoIP: báo quốc gia / vùng cho backend, không tin header do client tự gửi
+        if geoip.is_some() {
+            for (name, value) in [(geoip::COUNTRY_HEADER, &location.country), (geoip::REGION_HEADER, &location.region)] {
+                new_headers.remove(name);
+                if let Some(v) = value.as_deref().and_then(|v| v.parse().ok()) {
+                    new_headers.insert(name, v);
+                }
+            }
+        }
+
+        // mTLS: chỉ chuyển subject của cert đã xác thực, không tin header do client tự gửi
+        if let Some(name) = &client_cert_header {
+            new_headers.remove(name.as_str());
+            if let Some(v) = client_cert.as_ref().and_then(|c| c.0.parse().ok()) {
+                new_headers.insert(axum::http::HeaderName::from_bytes(name.as_bytes()).unwrap(), v);
+            }
+        }
+
+        // Credential service-to-service của backend thay cho header cùng tên do client gửi
+        for (name, value) in &auth {
+            new_headers.insert(name.clone(), value.clone());
+        }
+
+        // Thời gian còn lại trước khi load balancer bỏ chờ lần gửi này
+        if let Some(config) = &deadline_config {
+            let timeout = (timeouts.response_header_ms > 0).then(|| Duration::from_millis(timeouts.response_header_ms));
+            deadline::apply(&mut new_headers, config, timeout, client_deadline, started);
+        }
+
+        info!("Proxying to: {} (Host: {})", final_url, target_host);
+
+        // Content-Length theo body thật gửi đi (body đã đệm có thể đến từ request chunked)
+        failover::frame(&mut new_headers, upstream_body.as_ref());
+        let mut request = client.request(method.clone(), &final_url)
+            .headers(new_headers); // Dùng header đã sửa
+        if let Some(b) = upstream_body {
+            request = request.body(b);
+        }
+        async move { upstream::send(request, &timeouts).await }
+    };
+
+    // Giữ tới khi response body gửi xong (hoặc request lỗi)
+    let track = |url: &str| -> (Option<pools::InFlight>, Option<Arc<tokio::sync::Notify>>) {
+        let r = state.read().unwrap();
+        r.pools[pool_index].servers.iter()
+            .find(|s| s.url == url)
+            .map(|s| (pools::InFlight::new(s.active.clone()), s.close.clone()))
+            .unzip()
+    };
+
+    // Hedging chỉ cho request gửi lại được và không có body (chạy song song 2 bản)
+    let hedging = state.read().unwrap().hedging.clone();
+    let hedge_rule = hedging.as_ref()
+        .and_then(|h| h.rule(path_and_query.split('?').next().unwrap_or("")))
+        .filter(|_| replay_safe && body.is_empty());
+
+    let mut tried: Vec<String> = Vec::new();
+    let client_aborts = state.read().unwrap().pools[pool_index].client_aborts.clone();
+    let mut waiting = pools::AbortGuard::waiting(client_aborts.clone(), &format!("{} {}", method, path_and_query));
+
+    let response = loop {
+        // Body đã bị đọc ở lần trước (không thể xảy ra vì đã kiểm tra is_replayable)
+        let Some(upstream_body) = body.attempt() else {
+            break (StatusCode::BAD_GATEWAY, "Bad Gateway: request body đã được gửi đi").into_response();
+        };
+        let request = upstream_request(&base_url, upstream_body);
+
+        let (mut in_flight, mut close) = track(&base_url);
+
+        let upstream_start = std::time::Instant::now();
+        let result = match hedge_rule.filter(|_| tried.is_empty()) {
+            None => request.await,
+            Some(rule) => {
+                let delay = rule.delay();
+                let primary = request;
+                tokio::pin!(primary);
+                let result = match tokio::time::timeout(delay, &mut primary).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        let hedge_url = {
+                            let mut guard = state.write().unwrap();
+                            let w = &mut *guard;
+                            let pool = &mut w.pools[pool_index];
+                            let exclude = std::slice::from_ref(&base_url);
+                            pool.servers.iter().any(|s| s.is_available() && s.url != base_url)
+                                .then(|| choose_server(pool, &w.config, &client_id, region.as_deref(), exclude, None))
+                                .flatten()
+                        };
+                        match hedge_url {
+                            None => primary.await,
+                            Some(url) => {
+                                info!("🐇 Hedging: {} chưa trả lời sau {}ms, gửi thêm tới {}", base_url, delay.as_millis(), url);
+                                if let Some(t) = trace.as_mut() {
+                                    t.step(format!("hedging: {} chưa trả lời sau {}ms -> gửi thêm tới {}", base_url, delay.as_millis(), url));
+                                }
+                                let (hedge_in_flight, hedge_close) = track(&url);
+                                let secondary = upstream_request(&url, None);
+                                // Response thành công về trước thắng, request còn lại bị huỷ khi drop
+                                let first = async { (&mut primary).await.map(|res| (false, res)) };
+                                let second = async { secondary.await.map(|res| (true, res)) };
+                                match futures::future::select_ok([Box::pin(first) as std::pin::Pin<Box<dyn std::future::Future<Output = _> + Send>>, Box::pin(second)]).await {
+                                    Ok(((won, res), _)) => {
+                                        rule.hedged(won);
+                                        if won {
+                                            if let Some(t) = trace.as_mut() {
+                                                t.step(format!("hedging: {} trả lời trước", url));
+                                                t.backend = Some(url.clone());
+                                            }
+                                            base_url = url;
+                                            (in_flight, close) = (hedge_in_flight, hedge_close);
+                                        }
+                                        Ok(res)
+                                    }
+                                    Err(e) => {
+                                        rule.hedged(false);
+                                        tried.push(url);
+                                        Err(e)
+                                    }
+                                }
+                            }
+                        }
+                    }
+                };
+                if result.is_ok() {
+                    rule.record(upstream_start.elapsed());
+                }
+                result
+            }
+        };
+
+        if let Some(t) = trace.as_mut() {
+            t.timings.upstream_ms = Some(upstream_start.elapsed().as_millis());
+            match &result {
+                Ok(res) => t.upstream_status = Some(res.status().as_u16()),
+                Err(e) => t.error = Some(e.to_string()),
+            }
+        }
+
+        match result {
+            Ok(res) => {
+                remember_protocol(&state, pool_index, &base_url, res.version());
+                let status = res.status();
+                let mut response_builder = Response::builder().status(status);
+                *response_builder.headers_mut().unwrap() = res.headers().clone();
+                hop_by_hop::strip(response_builder.headers_mut().unwrap());
+                
+                // Xóa / thêm header bảo mật theo [security_headers]
+                // (mặc định xóa CSP/X-Frame-Options để trình duyệt local hiển thị được trang proxy)
+                let response_buffering = {
+                    let r = state.read().unwrap();
+                    security_headers::apply(
+                        response_builder.headers_mut().unwrap(),
+                        &r.config.security_headers,
+                        r.config.tls.is_some(),
+                    );
+                    r.response_buffering.clone()
+                };
+
+                let (body, trailers) = upstream::body_stream(res);
+                let body = upstream::idle_timeout(body, &upstream_for(&base_url).1);
+                let body = drain::until_closed(body, close.unwrap_or_default());
+                let body = match &streaming {
+                    Some(w) => futures::future::Either::Left(w.wrap(body, backpressure::Direction::Response)),
+                    None => futures::future::Either::Right(body),
+                };
+                // Response nhỏ: gom hết rồi gửi kèm Content-Length; còn lại stream từng chunk
+                let (prefix, body) = match response_buffering.filter(|b| b.applies(&method, status, response_builder.headers_ref().unwrap())) {
+                    Some(buffering) => match buffering.collect(body).await {
+                        response_buffering::Collected::Complete(bytes) => {
+                            let headers = response_builder.headers_mut().unwrap();
+                            headers.remove(axum::http::header::TRANSFER_ENCODING);
+                            headers.insert(axum::http::header::CONTENT_LENGTH, bytes.len().into());
+                            let body = futures::stream::once(async { Ok::<_, std::convert::Infallible>(bytes) });
+                            break response_builder.body(trailers.attach(body)).unwrap();
+                        }
+                        response_buffering::Collected::Partial(prefix, body) => (prefix, body),
+                    },
+                    None => (Vec::new(), body),
+                };
+                // Stream hết (hoặc body lỗi từ phía backend) thì không tính là client bỏ dở.
+                // Có Content-Length thì hyper ngừng poll ngay khi đủ byte, không đợi stream trả None
+                let mut remaining = response_builder.headers_ref().unwrap()
+                    .get(axum::http::header::CONTENT_LENGTH)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse::<usize>().ok());
+                let mut streaming = pools::AbortGuard::streaming(client_aborts.clone(), &base_url);
+                // Response không có body thì hyper không poll lần nào
+                if method == axum::http::Method::HEAD || matches!(status, StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED) || remaining == Some(0) {
+                    streaming.complete();
+                }
+                let mut body = futures::stream::iter(prefix).chain(body);
+                let stream = futures::stream::poll_fn(move |cx| {
+                    let _ = &in_flight;
+                    let chunk = body.poll_next_unpin(cx);
+                    let finished = match &chunk {
+                        std::task::Poll::Ready(Some(Ok(bytes))) => {
+                            remaining = remaining.map(|r| r.saturating_sub(bytes.len()));
+                            remaining == Some(0)
+                        }
+                        std::task::Poll::Ready(_) => true,
+                        std::task::Poll::Pending => false,
+                    };
+                    if finished {
+                        streaming.complete();
+                    }
+                    chunk
+                });
+                break response_builder.body(trailers.attach(stream)).unwrap();
+            },
+            Err(e) => {
+                error!("Proxy Error: {}", e);
+                tried.push(base_url.clone());
+
+                // Body bị cắt vì vượt max_upload_bytes của route / max_body_bytes của WAF: lỗi của client, không failover
+                if upload_exceeded.load(std::sync::atomic::Ordering::Relaxed) {
+                    if let Some(t) = trace.as_mut() {
+                        t.step("upload vượt giới hạn (max_upload_bytes / WAF), ngắt giữa chừng");
+                    }
+                    break (StatusCode::PAYLOAD_TOO_LARGE, "Upload vượt dung lượng cho phép").into_response();
+                }
+
+                // Chỉ failover khi gửi lại là an toàn: method idempotent và body chưa bị đọc
+                let can_retry = replay_safe && body.is_replayable() && tried.len() < failover::MAX_ATTEMPTS;
+                let next = if can_retry {
+                    let mut guard = state.write().unwrap();
+                    let w = &mut *guard;
+                    choose_server(&mut w.pools[pool_index], &w.config, &client_id, region.as_deref(), &tried, trace.as_mut())
+                } else {
+                    None
+                };
+
+                match next {
+                    Some(url) => {
+                        warn!("🔁 Failover: {} lỗi, thử lại với {}", base_url, url);
+                        if let Some(t) = trace.as_mut() {
+                            t.step(format!("failover: {} lỗi ({}) -> {}", base_url, e, url));
+                            t.backend = Some(url.clone());
+                        }
+                        base_url = url;
+                    }
+                    None => {
+                        if let Some(t) = trace.as_mut() {
+                            if !replay_safe {
+                                t.step("không failover: method không idempotent và không có Idempotency-Key");
+                            } else if !body.is_replayable() {
+                                t.step("không failover: body đã được gửi một phần lên backend");
+                            }
+                        }
+                        let status = e.status();
+                        break (status, format!("{}: {}", status.canonical_reason().unwrap_or_default(), e)).into_response();
+                    }
+                }
+            }
+        }
+    };
+
+    waiting.complete();
+
+    record_request(&state, Some(pool_index), Some(&base_url), &response, started);
+    let response = finish_variant(variant, started, response);
+    finish_trace(&state, trace, started, response)
+}
+
+// Ghi số liệu của request đã xử lý xong cho SLO và các exporter metrics.
+// Request không vào pool nào (pool_index = None) chỉ có access log, không tính vào pool nào
+fn record_request(state: &SharedState, pool_index: Option<usize>, backend: Option<&str>, response: &Response, started: std::time::Instant) {
+    let r = state.read().unwrap();
+    let pool = pool_index.and_then(|i| r.pools.get(i));
+    let (status, elapsed) = (response.status().as_u16(), started.elapsed());
+    if logging::access_log() {
+        // latency tính bằng ms; các field này thành BACKEND / STATUS / LATENCY trong journald, "fields" trong log JSON.
+        // Target "access" không bị [log_sampling] bỏ bớt
+        info!(
+            target: logging::ACCESS_TARGET,
+            pool = %pool.map_or("-", |p| p.name.as_str()),
+            backend = %backend.unwrap_or("-"),
+            status,
+            latency = elapsed.as_millis() as u64,
+            "↩️ Đã trả response"
+        );
+    }
+    let Some(pool) = pool else {
+        return;
+    };
+    if let Some(slo) = &r.slo {
+        slo.record(&pool.name, backend, status, elapsed);
+    }
+    if let Some(statsd) = &r.statsd {
+        statsd.request(&pool.name, backend, status, elapsed);
+    }
+    if let Some(influx) = &r.influx {
+        influx.record(&pool.name, backend, status, elapsed);
+    }
+    if let Some(graphite) = &r.graphite {
+        graphite.record(&pool.name, backend, status, elapsed);
+    }
+    if let Some(cloudwatch) = &r.cloudwatch {
+        cloudwatch.record(&pool.name, backend, status, elapsed);
+    }
+}
+
+// A/B: ghi số liệu của variant, gửi cookie cho client vừa được gán
+fn finish_variant(
+    variant: Option<(&Arc<experiment::Experiment>, &experiment::Assignment)>,
+    started: std::time::Instant,
+    mut response: Response,
+) -> Response {
+    if let Some((e, a)) = variant {
+        e.record(a.index, response.status().as_u16(), started.elapsed());
+        if a.new {
+            response.headers_mut().append(axum::http::header::SET_COOKIE, e.set_cookie(a.index));
+        }
+    }
+    response
+}
+
+// Lưu trace lại (nếu có) và trả id cho client để tra cứu
+fn finish_trace(
+    state: &SharedState,
+    trace: Option<RequestTrace>,
+    started: std::time::Instant,
+    mut response: Response,
+) -> Response {
+    if let Some(mut t) = trace {
+        t.timings.total_ms = started.elapsed().as_millis();
+        if let Ok(v) = t.id.parse() {
+            response.headers_mut().insert(debug_trace::DEBUG_ID_HEADER, v);
+        }
+        state.write().unwrap().traces.push(t);
+    }
+
+    response
+}
+
+// --- 5. Main ---
+
+fn main() {
+    let cli = <cli::Cli as clap::Parser>::parse();
+
+    if let Some(action) = cli.service {
+        run_service_action(action);
+        return;
+    }
+
+    match &cli.command {
+        Some(cli::Command::Check { servers }) => std::process::exit(check::run(&cli.config, servers)),
+        Some(cli::Command::Import { from, format, out_dir, force }) => {
+            std::process::exit(import::run(from, *format, out_dir, *force))
+        }
+        Some(cli::Command::Init { listen, admin, backends, health_path, out_dir, force }) => {
+            std::process::exit(init::run(init::Options {
+                listen: *listen,
+                admin: *admin,
+                backends: backends.clone(),
+                health_path: health_path.clone(),
+                out_dir: out_dir.clone(),
+                force: *force,
+            }))
+        }
+        Some(cli::Command::Upgrade { binary }) => std::process::exit(run_upgrade(&cli.config, binary.clone())),
+        None => {}
+    }
+
+    // Đọc config trước khi chạy nền để lỗi còn hiện ra terminal
+    let config = config::load(&cli.config).unwrap_or_else(|e| {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    });
+
+    if cli.daemon {
+        start_daemon(&cli);
+    }
+
+    logging::init(logging::Output::Stdout(cli.log_format), Some(&config));
+
+    let runtime = build_runtime(&config.runtime).unwrap_or_else(|e| {
+        error!("❌ Không tạo được tokio runtime: {}", e);
+        std::process::exit(1);
+    });
+    runtime.block_on(run(config, cli.console(), shutdown_signal()));
+
+    #[cfg(unix)]
+    if cli.daemon {
+        daemon::cleanup(&cli.pid_file);
+    }
+}
+
+#[cfg(unix)]
+fn start_daemon(cli: &cli::Cli) {
+    let upgraded = std::env::var_os(handoff::UPGRADE_ENV).is_some();
+    std::env::remove_var(handoff::UPGRADE_ENV);
+    let result = if upgraded {
+        daemon::adopt(&cli.pid_file)
+    } else {
+        daemon::start(&cli.pid_file, &cli.log_dir)
+    };
+    if let Err(e) = result {
+        eprintln!("❌ Không chạy nền được: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn start_daemon(_cli: &cli::Cli) {
+    eprintln!("❌ --daemon chỉ hỗ trợ trên Unix (dùng --service trên Windows)");
+    std::process::exit(2);
+}
+
+// `load_balancer upgrade`: process đang chạy (cùng config) chạy binary mới và bàn giao listener + state cho nó
+#[cfg(unix)]
+fn run_upgrade(config_path: &std::path::Path, binary: Option<std::path::PathBuf>) -> i32 {
+    let config = match config::load(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return 1;
+        }
+    };
+    let Some(handoff) = config.handoff else {
+        eprintln!("❌ Cần mục [handoff] trong {} để nâng cấp không rớt kết nối", config_path.display());
+        return 1;
+    };
+    let binary = match binary.map(Ok).unwrap_or_else(std::env::current_exe) {
+        Ok(binary) => binary,
+        Err(e) => {
+            eprintln!("❌ Không xác định được binary mới: {}", e);
+            return 1;
+        }
+    };
+    // Process đang chạy có thể ở thư mục khác
+    let binary = std::fs::canonicalize(&binary).unwrap_or(binary);
+    println!("⬆️ Nâng cấp lên {}...", binary.display());
+    match handoff::request_upgrade(&handoff, &binary) {
+        Ok(pid) => {
+            println!("✅ Process mới (pid {}) đã nhận listener, process cũ đang drain rồi thoát", pid);
+            0
+        }
+        Err(e) => {
+            eprintln!("❌ Nâng cấp thất bại: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn run_upgrade(_config_path: &std::path::Path, _binary: Option<std::path::PathBuf>) -> i32 {
+    eprintln!("❌ upgrade chỉ hỗ trợ trên Unix");
+    2
+}
+
+// Tokio runtime theo [runtime] trong config.toml
+fn build_runtime(config: &config::RuntimeConfig) -> std::io::Result<tokio::runtime::Runtime> {
+    let workers = config
+        .worker_threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder
+        .enable_all()
+        .worker_threads(workers)
+        .max_blocking_threads(config.max_blocking_threads)
+        .thread_keep_alive(Duration::from_secs(config.thread_keep_alive_secs))
+        .event_interval(config.event_interval);
+    if let Some(interval) = config.global_queue_interval {
+        builder.global_queue_interval(interval);
+    }
+    info!(
+        "⚙️ Tokio runtime: {} worker thread, tối đa {} blocking thread, event_interval {}",
+        workers, config.max_blocking_threads, config.event_interval
+    );
+    builder.build()
+}
+
+// Chờ Ctrl+C (hoặc SIGTERM trên Unix) để dừng gọn gàng
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[cfg(windows)]
+fn run_service_action(action: cli::ServiceAction) {
+    let result = match action {
+        cli::ServiceAction::Run => {
+            logging::init(logging::Output::EventLog, None);
+            service::run()
+        }
+        cli::ServiceAction::Install => service::install(),
+        cli::ServiceAction::Uninstall => service::uninstall(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("❌ Lỗi Windows service ({:?}): {}", action, e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(windows))]
+fn run_service_action(_action: cli::ServiceAction) {
+    eprintln!("❌ --service chỉ hỗ trợ trên Windows (dùng systemd trên Linux)");
+    std::process::exit(2);
+}
+
+// Socket systemd bind sẵn có khớp địa chỉ cấu hình không. Địa chỉ "mọi interface" (0.0.0.0 / [::]) khớp theo
+// port, vì ListenStream=8080 của systemd mặc định bind [::]:8080 nhận cả IPv4
+fn same_listen_addr(configured: SocketAddr, socket: SocketAddr) -> bool {
+    configured == socket
+        || (configured.ip().is_unspecified() && socket.ip().is_unspecified() && configured.port() == socket.port())
+}
+
+// Route của một listener: dashboard / API / metrics (admin), proxy tới backend, hoặc cả hai
+fn router(state: SharedState, routes: config::ListenerRoutes) -> Router {
+    let admin = Router::new()
+        .route("/load-balancer/dashboard", get(dashboard_handler))
+        .route("/load-balancer/assets/*path", get(assets::handler))
+        .route("/load-balancer/events", get(sse_handler))
+        .route("/load-balancer/ws", get(ws_handler))
+        .route("/load-balancer/api/waf", get(waf_stats_handler))
+        .route("/load-balancer/api/experiment", get(experiment_handler))
+        .route("/load-balancer/api/shadow", get(shadow_stats_handler))
+        .route("/load-balancer/api/hedging", get(hedging_stats_handler))
+        .route("/load-balancer/api/dns", get(dns_handler))
+        .route("/load-balancer/api/geo", get(geo_stats_handler))
+        .route("/load-balancer/api/slo", get(slo_handler))
+        .route("/load-balancer/api/reports/uptime", get(uptime_report_handler))
+        .route("/load-balancer/api/history.csv", get(history_csv_handler))
+        .route("/load-balancer/metrics", get(metrics_handler));
+
+    // API thay đổi trạng thái / debug: cần admin token, không CORS (trang web khác không gọi được từ trình duyệt)
+    let protected = Router::new()
+        .route("/load-balancer/api/backends", put(backend_state_handler))
+        .route("/load-balancer/api/log-level", put(put_log_level_handler).get(get_log_level_handler))
+        .route("/load-balancer/api/debug/trace", put(put_debug_trace_handler).get(get_debug_trace_handler))
+        .route("/load-balancer/api/debug/requests/:id", get(debug_request_handler))
+        .route("/load-balancer/api/debug/state", get(debug_state_handler))
+        .route("/load-balancer/api/bans", get(list_bans_handler))
+        .route("/load-balancer/api/bans/:ip", delete(unban_handler))
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), require_admin));
+
+    let proxy = Router::new()
+        .route(oidc::CALLBACK_PATH, get(oidc_callback_handler))
+        .route(oidc::LOGOUT_PATH, get(oidc_logout_handler))
+        .fallback(any(proxy_handler));
+
+    // Probe cho Kubernetes (livenessProbe / readinessProbe), có trên mọi listener
+    let probes = Router::new()
+        .route("/livez", get(livez_handler))
+        .route("/readyz", get(readyz_handler));
+
+    // API quản trị chỉ có trên listener routes = "admin" (không lộ trên cổng proxy mặc định)
+    let app = match routes {
+        config::ListenerRoutes::All => probes.merge(admin).merge(proxy).layer(CorsLayer::permissive()),
+        config::ListenerRoutes::Proxy => probes.merge(proxy).layer(CorsLayer::permissive()),
+        config::ListenerRoutes::Admin => probes.merge(admin).layer(CorsLayer::permissive()).merge(protected),
+    };
+    app.with_state(state)
+}
+
+async fn require_admin(State(state): State<SharedState>, req: Request, next: axum::middleware::Next) -> Response {
+    let rejected = admin_auth::check(state.read().unwrap().config.admin.as_ref(), req.headers());
+    match rejected {
+        Some(resp) => resp,
+        None => next.run(req).await,
+    }
+}
+
+// Chạy load balancer tới khi `shutdown` hoàn thành
+async fn run(config: config::Config, console: cli::Console, shutdown: impl std::future::Future<Output = ()>) {
+    // Tạo channel broadcast
+    let (tx, _rx) = broadcast::channel::<String>(100);
+
+    let (mut pools, config_loaded) = match pools::load(std::path::Path::new("servers.json"), &config.timeouts) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            error!("❌ {}", e);
+            return;
+        }
+    };
+    for route in &config.routing.routes {
+        if !pools.iter().any(|p| p.name == route.pool) {
+            warn!("⚠️ Route tới pool không tồn tại trong servers.json: {}", route.pool);
+        }
+    }
+    for variant in config.experiment.iter().flat_map(|e| e.variants.iter()) {
+        if !pools.iter().any(|p| p.name == variant.pool) {
+            warn!("⚠️ Variant {} dùng pool không tồn tại trong servers.json: {}", variant.name, variant.pool);
+        }
+    }
+    // Credential gửi lên backend ("auth" trong servers.json), có thể đọc từ Vault
+    let vault = config.vault.as_ref().map(|v| Arc::new(vault::Client::new(v)));
+    if let Err(e) = pools::resolve_auth(&mut pools, vault.as_deref()).await {
+        error!("❌ {}", e);
+        return;
+    }
+    let experiment = config.experiment.clone().map(|e| Arc::new(experiment::Experiment::new(e)));
+    let shadow = (!config.shadow.is_empty()).then(|| Arc::new(shadow::Mirror::new(&config.shadow)));
+    let hedging = (!config.hedging.is_empty()).then(|| Arc::new(hedging::Hedger::new(&config.hedging)));
+    let dns = match config.dns.as_ref().map(dns::Cache::new).transpose() {
+        Ok(dns) => dns,
+        Err(e) => {
+            error!("❌ Không khởi tạo được DNS resolver: {}", e);
+            return;
+        }
+    };
+    for slo in &config.slo {
+        if !pools.iter().any(|p| p.name == slo.pool) {
+            warn!("⚠️ SLO {} dùng pool không tồn tại trong servers.json: {}", slo.name, slo.pool);
+        }
+    }
+    let slo = (!config.slo.is_empty()).then(|| Arc::new(slo::Tracker::new(&config.slo)));
+
+    // Khôi phục sticky map từ lần chạy trước (nếu có cấu hình lưu)
+    if let Some(path) = &config.sticky.persist_file {
+        let mut maps = sticky::load(path, pools::DEFAULT_POOL);
+        let mut restored = 0;
+        for pool in &mut pools {
+            pool.sticky_map = maps.remove(&pool.name).unwrap_or_default();
+            restored += pool.sticky_map.len();
+        }
+        info!("📂 Khôi phục {} sticky session từ {}", restored, path.display());
+    }
+
+    // Chỉ bị sửa khi nhận bàn giao (Unix)
+    #[cfg_attr(not(unix), allow(unused_mut))]
+    let mut uptime = config.uptime.persist_file.as_deref().map(uptime::History::load).unwrap_or_default();
+
+    // Có process đang chạy cùng [handoff]: nhận listener + sticky / health state của nó thay vì bắt đầu từ đầu
+    #[cfg(unix)]
+    let mut takeover = match config.handoff.as_ref().map(handoff::request).transpose() {
+        Ok(takeover) => takeover.flatten(),
+        Err(e) => {
+            error!("❌ {}", e);
+            return;
+        }
+    };
+    #[cfg(unix)]
+    if let Some(takeover) = &mut takeover {
+        takeover.apply(&mut pools, &mut uptime);
+    }
+
+    let oidc = config.oidc.clone().map(|c| Arc::new(oidc::Gateway::new(c)));
+    let jwt = config.jwt.clone().map(|c| Arc::new(jwt_auth::Validator::new(c)));
+    let bans = config.ban.enabled.then(|| Arc::new(ban::BanList::new(config.ban.clone())));
+    let limits = &config.client_limits;
+    let client_limits = (limits.max_connections_per_ip > 0 || limits.max_requests_per_ip > 0)
+        .then(|| Arc::new(client_limits::Limiter::new(limits)));
+    let bots = bots::Filter::is_active(&config.bots).then(|| Arc::new(bots::Filter::new(&config.bots)));
+    let malformed = config.malformed_requests.enabled.then(|| Arc::new(malformed::Validator::new(&config.malformed_requests)));
+    let waf = (!config.waf.rules.is_empty()).then(|| Arc::new(waf::Engine::new(&config.waf)));
+    let basic_auth = if config.basic_auth.is_empty() {
+        None
+    } else {
+        match basic_auth::Guard::new(&config.basic_auth) {
+            Ok(guard) => Some(Arc::new(guard)),
+            Err(e) => {
+                error!("❌ Lỗi cấu hình basic auth: {}", e);
+                return;
+            }
+        }
+    };
+    let trusted_proxies = if config.trusted_proxies.is_empty() {
+        None
+    } else {
+        match trusted_proxies::TrustedProxies::new(&config.trusted_proxies) {
+            Ok(trusted) => Some(Arc::new(trusted)),
+            Err(e) => {
+                error!("❌ Lỗi cấu hình trusted_proxies: {}", e);
+                return;
+            }
+        }
+    };
+    let geoip = match &config.geoip {
+        Some(c) => match geoip::Locator::new(c) {
+            Ok(locator) => Some(Arc::new(locator)),
+            Err(e) => {
+                error!("❌ Không nạp được GeoIP database: {}", e);
+                return;
+            }
+        },
+        None => None,
+    };
+    let response_buffering = config.response_buffering.as_ref().map(|c| Arc::new(response_buffering::Buffering::new(c)));
+    let streaming = config.streaming.as_ref().map(|c| Arc::new(backpressure::Watermarks::new(c)));
+    let statsd = config.statsd.clone().map(|c| Arc::new(statsd::Sink::new(c)));
+    let influx = config.influxdb.clone().map(|c| Arc::new(influx::Exporter::new(c)));
+    let graphite = config.graphite.clone().map(|c| Arc::new(graphite::Exporter::new(c)));
+    let cloudwatch = config.cloudwatch.clone().map(|c| Arc::new(cloudwatch::Exporter::new(c)));
+    let webhooks = if config.webhooks.is_empty() {
+        None
+    } else {
+        match webhooks::Notifier::new(&config.webhooks) {
+            Ok(notifier) => Some(Arc::new(notifier)),
+            Err(e) => {
+                error!("❌ Lỗi cấu hình webhook: {}", e);
+                return;
+            }
+        }
+    };
+    let dashboard = match dashboard::Dashboard::new(&config.dashboard) {
+        Ok(dashboard) => Arc::new(dashboard),
+        Err(e) => {
+            error!("❌ Lỗi template dashboard: {}", e);
+            return;
+        }
+    };
+
+    // Khởi tạo State
+    let pool_count = pools.len();
+    let shared_state = Arc::new(RwLock::new(AppState {
+        pools,
+        tx, // Lưu tx vào state luôn
+        config_loaded,
+        traces: TraceStore::default(),
+        config,
+        oidc,
+        jwt,
+        basic_auth,
+        waf,
+        bots,
+        malformed,
+        client_limits: client_limits.clone(),
+        bans,
+        trusted_proxies,
+        geoip: geoip.clone(),
+        response_buffering,
+        streaming,
+        experiment,
+        shadow,
+        hedging,
+        upstream: upstream::Clients::new(dns.clone()),
+        dns,
+        uptime,
+        dashboard,
+        statsd: statsd.clone(),
+        influx: influx.clone(),
+        graphite: graphite.clone(),
+        cloudwatch: cloudwatch.clone(),
+        webhooks,
+        slo: slo.clone(),
+    }));
+
+    // Chạy Health Check cho từng pool
+    for pool_index in 0..pool_count {
+        let state_clone = shared_state.clone();
+        tokio::spawn(async move {
+            health_check_task(state_clone, pool_index).await;
+        });
+    }
+
+    // Tóm tắt số log lặp lại đã bị bỏ ([log_sampling])
+    tokio::spawn(logging::report_suppressed());
+
+    // Trạng thái backend ra console: bảng vẽ lại toàn màn hình, dòng log định kỳ, hoặc không in (service / --quiet)
+    match console {
+        cli::Console::Tui => {
+            let state_clone = shared_state.clone();
+            tokio::spawn(async move {
+                status_table_task(state_clone).await;
+            });
+        }
+        cli::Console::Log => {
+            tokio::spawn(status_log_task(shared_state.clone()));
+        }
+        cli::Console::Off => {}
+    }
+
+    // Nạp lại GeoIP database khi file thay đổi
+    if let Some(locator) = geoip {
+        tokio::spawn(locator.watch());
+    }
+
+    // Định kỳ kiểm tra burn rate của các SLO
+    if let Some(tracker) = slo {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(30));
+            loop {
+                interval.tick().await;
+                tracker.evaluate().await;
+            }
+        });
+    }
+
+    // Định kỳ gửi trạng thái backend tới StatsD
+    if let Some(sink) = statsd {
+        let state_clone = shared_state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(sink.gauge_interval());
+            loop {
+                interval.tick().await;
+                let samples = metrics::backend_samples(&state_clone.read().unwrap());
+                for backend in &samples {
+                    sink.backend_gauges(backend);
+                }
+            }
+        });
+    }
+
+    // Định kỳ đẩy metrics lên InfluxDB
+    if let Some(exporter) = influx {
+        let state_clone = shared_state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(exporter.flush_interval());
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let samples = metrics::backend_samples(&state_clone.read().unwrap());
+                exporter.flush(&samples).await;
+            }
+        });
+    }
+
+    // Định kỳ gửi metrics tới Graphite
+    if let Some(exporter) = graphite {
+        let state_clone = shared_state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(exporter.flush_interval());
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let samples = metrics::backend_samples(&state_clone.read().unwrap());
+                exporter.flush(&samples).await;
+            }
+        });
+    }
+
+    // Định kỳ đẩy metrics lên CloudWatch
+    if let Some(exporter) = cloudwatch {
+        let state_clone = shared_state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(exporter.flush_interval());
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let samples = metrics::backend_samples(&state_clone.read().unwrap());
+                exporter.flush(&samples).await;
+            }
+        });
+    }
+
+    // Định kỳ ghi lịch sử uptime xuống file
+    let state_clone = shared_state.clone();
+    tokio::spawn(async move {
+        uptime_persist_task(state_clone).await;
+    });
+
+    // Định kỳ ghi sticky map xuống file
+    let state_clone = shared_state.clone();
+    tokio::spawn(async move {
+        sticky_persist_task(state_clone).await;
+    });
+
+    // Định kỳ đọc lại credential backend lấy từ Vault
+    if let Some(vault) = vault.clone() {
+        let state_clone = shared_state.clone();
+        tokio::spawn(async move {
+            vault_refresh_task(state_clone, vault).await;
+        });
+    }
+
+    // Cert của listener TLS: từ file, hoặc xin từ Vault PKI và tự gia hạn
+    let (tls_config, vault_pki, http2) = {
+        let r = shared_state.read().unwrap();
+        (r.config.tls.clone(), r.config.vault.as_ref().and_then(|v| v.pki.clone()), r.config.http2.enabled)
+    };
+    let tls_acceptor = match tls_config {
+        None => None,
+        Some(tls_config) => {
+            let certs = match (&vault_pki, &vault) {
+                (Some(pki), Some(vault)) => vault.issue(pki).await.map(|(key, lifetime)| {
+                    info!("🔐 Nhận cert TLS từ Vault ({}, hạn {}s)", pki.common_name, lifetime.as_secs());
+                    let store = Arc::new(tls::CertStore::new(key));
+                    tokio::spawn(vault.clone().renew_certs(pki.clone(), store.clone(), lifetime));
+                    store
+                }),
+                _ => match (&tls_config.cert_file, &tls_config.key_file) {
+                    (Some(cert_file), Some(key_file)) => tls::watched_store(cert_file, key_file, tls_config.reload_secs),
+                    _ => Err("thiếu cert_file / key_file".to_string()),
+                },
+            };
+            // Thêm các cert chọn theo SNI trong [[tls.certificates]], cert trên là mặc định
+            let certs = certs.and_then(|default| tls::SniResolver::new(&tls_config, default).map(Arc::new));
+            if let (Ok(resolver), true) = (&certs, tls_config.ocsp_stapling) {
+                for store in resolver.stores() {
+                    tokio::spawn(ocsp::staple_task(store.clone(), Duration::from_secs(tls_config.ocsp_refresh_secs)));
+                }
+            }
+            match certs.and_then(|certs| tls::build_acceptor(&tls_config, certs, http2)) {
+                Ok(acceptor) => Some(acceptor),
+                Err(e) => {
+                    error!("❌ Lỗi cấu hình TLS: {}", e);
+                    return;
+                }
+            }
+        }
+    };
+
+    // Ưu tiên socket do systemd bind sẵn (socket activation), địa chỉ còn lại tự bind theo [listen] / [[listeners]]
+    let (listener_configs, settings) = {
+        let r = shared_state.read().unwrap();
+        let settings = server::Settings {
+            slow: r.config.slow_clients.clone(),
+            connections: r.config.connections.clone(),
+            http2: r.config.http2.clone(),
+            request_limits: r.config.request_limits.clone(),
+            path_normalization: r.config.path_normalization.clone(),
+            canonicalization: r.config.canonicalization.clone(),
+        };
+        (r.config.listeners(), settings)
+    };
+    let mut listeners = Vec::new();
+    // Socket systemd bind sẵn được ghép với listener cấu hình cùng địa chỉ (dùng routes / TLS / proxy_protocol
+    // của listener đó), socket không khớp listener nào thì không chạy để tránh lộ route ngoài ý muốn
+    let mut activated = Vec::new();
+    for std_listener in systemd::take_listeners() {
+        let Ok(local) = std_listener.local_addr() else {
+            error!("❌ systemd truyền sang socket không phải TCP");
+            return;
+        };
+        let find = |exact: bool| {
+            listener_configs.iter().find_map(|l| {
+                let mut addrs = l.addresses.iter();
+                let addr = if exact { addrs.find(|a| **a == local) } else { addrs.find(|a| same_listen_addr(**a, local)) };
+                addr.map(|a| (l, *a))
+            })
+        };
+        let matched = find(true).or_else(|| find(false));
+        let Some((l, addr)) = matched else {
+            error!("❌ systemd truyền sang socket {} không khớp địa chỉ nào trong [listen] / [[listeners]]", local);
+            return;
+        };
+        info!("🔌 Dùng socket được systemd truyền sang cho {} (socket activation)", addr);
+        activated.push(addr);
+        let listener = tokio::net::TcpListener::from_std(std_listener).unwrap();
+        listeners.push((listener, l.tls, l.routes, l.proxy_protocol, l.h2c));
+    }
+    let all: Vec<SocketAddr> = listener_configs.iter().flat_map(|l| l.addresses.iter().copied()).collect();
+    for l in &listener_configs {
+        for addr in l.addresses.iter().filter(|a| !activated.contains(a)) {
+            let only_v6 = all.iter().any(|a| a.is_ipv4() && a.port() == addr.port());
+            // Dùng lại socket của process cũ (nếu được bàn giao) để không mất kết nối đang chờ accept
+            #[cfg(unix)]
+            let inherited = takeover.as_mut().and_then(|t| t.listener(*addr));
+            #[cfg(not(unix))]
+            let inherited: Option<std::io::Result<std::net::TcpListener>> = None;
+            let bound = match inherited {
+                Some(listener) => listener.and_then(tokio::net::TcpListener::from_std),
+                None => server::bind(*addr, only_v6, settings.connections.backlog),
+            };
+            match bound {
+                Ok(listener) => listeners.push((listener, l.tls, l.routes, l.proxy_protocol, l.h2c)),
+                Err(e) => {
+                    error!("❌ Không bind được {}: {}", addr, e);
+                    return;
+                }
+            }
+        }
+    }
+
+    let mut dashboard_url = None;
+    for (listener, tls, routes, ..) in &listeners {
+        let Ok(addr) = listener.local_addr() else { continue };
+        let scheme = if *tls { "https" } else { "http" };
+        info!("🚀 Load balancer (Rust) đang chạy tại {}://{} ({:?})", scheme, addr, routes);
+        if *routes != config::ListenerRoutes::Proxy && dashboard_url.is_none() {
+            dashboard_url = Some(format!("{}://localhost:{}/load-balancer/dashboard", scheme, addr.port()));
+        }
+    }
+    if let Some(url) = dashboard_url {
+        info!("📊 Dashboard: {}", url);
+    }
+
+    // Process mới bàn giao xong (handoff) thì process này ngừng accept và drain
+    let handed_off = Arc::new(tokio::sync::Notify::new());
+    #[cfg(unix)]
+    {
+        let handoff_config = shared_state.read().unwrap().config.handoff.clone();
+        if let Some(handoff_config) = handoff_config {
+            if let Some(takeover) = takeover.take() {
+                takeover.finish();
+            }
+            use std::os::fd::AsFd;
+            let fds = listeners.iter().filter_map(|(l, ..)| l.as_fd().try_clone_to_owned().ok()).collect();
+            tokio::spawn(handoff::serve(shared_state.clone(), handoff_config, fds, handed_off.clone()));
+        }
+    }
+
+    // Báo cho systemd (Type=notify) là đã sẵn sàng nhận kết nối
+    systemd::notify("READY=1");
+
+    let server = futures::future::join_all(listeners.into_iter().map(|(listener, tls, routes, proxy_protocol, h2c)| {
+        let app = router(shared_state.clone(), routes);
+        let acceptor = if tls { tls_acceptor.clone() } else { None };
+        server::serve(listener, app, acceptor, proxy_protocol, h2c, settings.clone(), client_limits.clone())
+    }));
+
+    let mut handed_over = false;
+    tokio::select! {
+        _ = server => {},
+        _ = shutdown => info!("🛑 Nhận tín hiệu dừng, tắt load balancer"),
+        _ = handed_off.notified() => {
+            info!("🔁 Đã bàn giao listener cho process mới, ngừng nhận kết nối");
+            handed_over = true;
+        }
+    }
+
+    // Đã ngừng nhận kết nối mới, chờ các request đang chạy xong
+    systemd::notify("STOPPING=1");
+    let drain_timeout = Duration::from_secs(shared_state.read().unwrap().config.drain.timeout_secs);
+    drain::shutdown(&shared_state, drain_timeout).await;
+
+    // Đã bàn giao: sticky map / lịch sử uptime và control socket giờ thuộc về process mới
+    if handed_over {
+        return;
+    }
+    save_sticky_map(&shared_state);
+    save_uptime_history(&shared_state);
+    let handoff = shared_state.read().unwrap().config.handoff.clone();
+    if let Some(handoff) = handoff {
+        let _ = std::fs::remove_file(&handoff.socket);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,105 @@
+// Từ chối request sai định dạng trước khi proxy, tránh request smuggling qua load balancer:
+// - Content-Length lặp lại / không phải số, hoặc đi cùng Transfer-Encoding
+// - Giá trị header chứa ký tự điều khiển (tuỳ chọn: cả byte ngoài ASCII)
+// - URI dạng tuyệt đối (hoặc :authority của HTTP/2) trỏ tới host khác với header Host
+// Với HTTP/1.1, hyper đã tự trả 400 cho Content-Length khác nhau và ký tự điều khiển, gộp Content-Length trùng nhau,
+// bỏ Content-Length khi có Transfer-Encoding (và đóng kết nối sau response): các kiểm tra đó chủ yếu cho HTTP/2.
+use crate::config::MalformedRequestsConfig;
+use axum::http::{header, HeaderMap, Uri};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Reason {
+    Framing,
+    HeaderChars,
+    ForeignHost,
+}
+
+impl Reason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reason::Framing => "framing",
+            Reason::HeaderChars => "header_chars",
+            Reason::ForeignHost => "foreign_host",
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            Reason::Framing => "Content-Length / Transfer-Encoding không hợp lệ",
+            Reason::HeaderChars => "Header chứa ký tự không hợp lệ",
+            Reason::ForeignHost => "URI trỏ tới host khác với header Host",
+        }
+    }
+}
+
+// Số request bị từ chối theo từng lý do, xuất ra /load-balancer/metrics
+#[derive(Default)]
+pub struct Counters {
+    pub framing: AtomicU64,
+    pub header_chars: AtomicU64,
+    pub foreign_host: AtomicU64,
+}
+
+pub struct Validator {
+    reject_obs_text: bool,
+    pub counters: Counters,
+}
+
+impl Validator {
+    pub fn new(config: &MalformedRequestsConfig) -> Self {
+        Self {
+            reject_obs_text: config.reject_obs_text,
+            counters: Counters::default(),
+        }
+    }
+
+    pub fn check(&self, uri: &Uri, headers: &HeaderMap) -> Option<Reason> {
+        let reason = if !valid_framing(headers) {
+            Reason::Framing
+        } else if headers.values().any(|v| v.as_bytes().iter().any(|&b| self.invalid_byte(b))) {
+            Reason::HeaderChars
+        } else if !same_host(uri, headers) {
+            Reason::ForeignHost
+        } else {
+            return None;
+        };
+        let counter = match reason {
+            Reason::Framing => &self.counters.framing,
+            Reason::HeaderChars => &self.counters.header_chars,
+            Reason::ForeignHost => &self.counters.foreign_host,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        Some(reason)
+    }
+
+    // HTAB được phép, các ký tự điều khiển khác (kể cả DEL) thì không
+    fn invalid_byte(&self, b: u8) -> bool {
+        (b < 0x20 && b != b'\t') || b == 0x7f || (self.reject_obs_text && b >= 0x80)
+    }
+}
+
+// Đúng một Content-Length là số, và không đi cùng Transfer-Encoding
+fn valid_framing(headers: &HeaderMap) -> bool {
+    let mut lengths = headers.get_all(header::CONTENT_LENGTH).iter();
+    match (lengths.next(), lengths.next()) {
+        (None, _) => true,
+        (Some(value), None) => {
+            !headers.contains_key(header::TRANSFER_ENCODING)
+                && !value.is_empty()
+                && value.as_bytes().iter().all(u8::is_ascii_digit)
+        }
+        (Some(_), Some(_)) => false,
+    }
+}
+
+// Authority của URI (nếu có) phải trùng header Host, không phân biệt hoa thường
+fn same_host(uri: &Uri, headers: &HeaderMap) -> bool {
+    let Some(authority) = uri.authority() else {
+        return true;
+    };
+    headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|host| host.eq_ignore_ascii_case(authority.as_str()))
+}
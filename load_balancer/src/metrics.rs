@@ -0,0 +1,303 @@
+// Xuất số liệu dạng Prometheus text cho /load-balancer/metrics
+use crate::{backpressure::Direction, AppState};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{atomic::Ordering, Mutex},
+    time::Duration,
+};
+
+// (tên metric, kiểu, mô tả, hàm lấy giá trị)
+type MetricFamily<T, V = u64> = (&'static str, &'static str, &'static str, fn(&T) -> V);
+
+pub fn render(state: &AppState) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# HELP lb_backend_up Backend đang healthy (1) hay không (0)");
+    let _ = writeln!(out, "# TYPE lb_backend_up gauge");
+    for p in &state.pools {
+        for s in &p.servers {
+            let _ = writeln!(out, "lb_backend_up{{pool=\"{}\",backend=\"{}\"}} {}", escape(&p.name), escape(&s.url), s.healthy as u8);
+        }
+    }
+
+    let _ = writeln!(out, "# HELP lb_backend_disabled Backend bị tắt thủ công (1) hay không (0)");
+    let _ = writeln!(out, "# TYPE lb_backend_disabled gauge");
+    for p in &state.pools {
+        for s in &p.servers {
+            let _ = writeln!(out, "lb_backend_disabled{{pool=\"{}\",backend=\"{}\"}} {}", escape(&p.name), escape(&s.url), s.disabled as u8);
+        }
+    }
+
+    let _ = writeln!(out, "# HELP lb_backend_active_requests Số request đang gửi tới backend");
+    let _ = writeln!(out, "# TYPE lb_backend_active_requests gauge");
+    for p in &state.pools {
+        for s in &p.servers {
+            let active = s.active.load(Ordering::Relaxed);
+            let _ = writeln!(out, "lb_backend_active_requests{{pool=\"{}\",backend=\"{}\"}} {}", escape(&p.name), escape(&s.url), active);
+        }
+    }
+
+    let _ = writeln!(out, "# HELP lb_pool_requests_total Số request được đưa vào từng pool");
+    let _ = writeln!(out, "# TYPE lb_pool_requests_total counter");
+    for p in &state.pools {
+        let _ = writeln!(out, "lb_pool_requests_total{{pool=\"{}\"}} {}", escape(&p.name), p.requests);
+    }
+
+    let _ = writeln!(out, "# HELP lb_client_aborted_requests_total Request bị client đóng kết nối giữa chừng (request tới backend bị huỷ)");
+    let _ = writeln!(out, "# TYPE lb_client_aborted_requests_total counter");
+    for p in &state.pools {
+        for (phase, counter) in [("waiting", &p.client_aborts.waiting), ("streaming", &p.client_aborts.streaming)] {
+            let _ = writeln!(out, "lb_client_aborted_requests_total{{pool=\"{}\",phase=\"{}\"}} {}", escape(&p.name), phase, counter.load(Ordering::Relaxed));
+        }
+    }
+
+    let _ = writeln!(out, "# HELP lb_region_latency_ms Độ trễ health check trung bình của region (ms)");
+    let _ = writeln!(out, "# TYPE lb_region_latency_ms gauge");
+    for p in &state.pools {
+        let mut latencies: Vec<_> = crate::region_latencies(&p.servers).into_iter().collect();
+        latencies.sort();
+        for (region, ms) in latencies {
+            let _ = writeln!(out, "lb_region_latency_ms{{pool=\"{}\",region=\"{}\"}} {}", escape(&p.name), escape(&region), ms);
+        }
+    }
+
+    if let Some(experiment) = &state.experiment {
+        let name = escape(experiment.name());
+        let report = experiment.report();
+        let families: [MetricFamily<crate::experiment::VariantReport>; 3] = [
+            ("lb_experiment_requests_total", "counter", "Số request theo variant A/B", |v| v.requests),
+            ("lb_experiment_errors_total", "counter", "Số request lỗi (5xx) theo variant A/B", |v| v.errors),
+            ("lb_experiment_avg_latency_ms", "gauge", "Thời gian xử lý trung bình theo variant A/B", |v| v.avg_latency_ms),
+        ];
+        for (metric, kind, help, value) in families {
+            let _ = writeln!(out, "# HELP {} {}", metric, help);
+            let _ = writeln!(out, "# TYPE {} {}", metric, kind);
+            for v in &report {
+                let _ = writeln!(out, "{}{{experiment=\"{}\",variant=\"{}\"}} {}", metric, name, escape(&v.name), value(v));
+            }
+        }
+    }
+
+    if let Some(shadow) = &state.shadow {
+        let _ = writeln!(out, "# HELP lb_shadow_requests_total Request bản sao (shadow) theo kết quả");
+        let _ = writeln!(out, "# TYPE lb_shadow_requests_total counter");
+        for r in shadow.stats() {
+            let (prefix, target) = (escape(&r.path_prefix), escape(&r.target));
+            for (result, count) in [("sent", r.sent), ("failed", r.failed), ("dropped", r.dropped)] {
+                let _ = writeln!(out, "lb_shadow_requests_total{{path_prefix=\"{}\",target=\"{}\",result=\"{}\"}} {}", prefix, target, result, count);
+            }
+        }
+    }
+
+    if let Some(hedging) = &state.hedging {
+        let _ = writeln!(out, "# HELP lb_hedged_requests_total Request được gửi thêm tới backend thứ hai (hedging)");
+        let _ = writeln!(out, "# TYPE lb_hedged_requests_total counter");
+        for r in hedging.stats() {
+            let prefix = escape(&r.path_prefix);
+            for (winner, count) in [("primary", r.hedged.saturating_sub(r.hedge_wins)), ("hedge", r.hedge_wins)] {
+                let _ = writeln!(out, "lb_hedged_requests_total{{path_prefix=\"{}\",winner=\"{}\"}} {}", prefix, winner, count);
+            }
+        }
+        let _ = writeln!(out, "# HELP lb_hedging_delay_ms Delay hiện tại trước khi gửi request thứ hai");
+        let _ = writeln!(out, "# TYPE lb_hedging_delay_ms gauge");
+        for r in hedging.stats() {
+            let _ = writeln!(out, "lb_hedging_delay_ms{{path_prefix=\"{}\"}} {}", escape(&r.path_prefix), r.delay_ms);
+        }
+    }
+
+    if let Some(buffering) = &state.response_buffering {
+        let (buffered, streamed) = buffering.counts();
+        let _ = writeln!(out, "# HELP lb_responses_total Response gửi cho client theo cách: gom kèm Content-Length hoặc stream");
+        let _ = writeln!(out, "# TYPE lb_responses_total counter");
+        let _ = writeln!(out, "lb_responses_total{{mode=\"buffered\"}} {}", buffered);
+        let _ = writeln!(out, "lb_responses_total{{mode=\"streamed\"}} {}", streamed);
+    }
+
+    if let Some(streaming) = &state.streaming {
+        let _ = writeln!(out, "# HELP lb_stream_backpressure_pauses_total Số lần ngừng đọc phía gửi vì phía nhận chậm (đệm đủ high watermark)");
+        let _ = writeln!(out, "# TYPE lb_stream_backpressure_pauses_total counter");
+        let _ = writeln!(out, "lb_stream_backpressure_pauses_total{{direction=\"request\"}} {}", streaming.pauses(Direction::Request));
+        let _ = writeln!(out, "lb_stream_backpressure_pauses_total{{direction=\"response\"}} {}", streaming.pauses(Direction::Response));
+    }
+
+    if let Some(geoip) = &state.geoip {
+        let stats = geoip.stats();
+        let _ = writeln!(out, "# HELP lb_geo_requests_total Request theo quốc gia của client (GeoIP)");
+        let _ = writeln!(out, "# TYPE lb_geo_requests_total counter");
+        for c in &stats {
+            let _ = writeln!(out, "lb_geo_requests_total{{country=\"{}\"}} {}", escape(&c.country), c.requests);
+        }
+        let _ = writeln!(out, "# HELP lb_geo_denied_total Request bị chặn theo deny_countries / allow_countries");
+        let _ = writeln!(out, "# TYPE lb_geo_denied_total counter");
+        for c in &stats {
+            let _ = writeln!(out, "lb_geo_denied_total{{country=\"{}\"}} {}", escape(&c.country), c.denied);
+        }
+    }
+
+    if let Some(slo) = &state.slo {
+        let reports = slo.report();
+        let families: [MetricFamily<crate::slo::ObjectiveReport, f64>; 3] = [
+            ("lb_slo_compliance_percent", "gauge", "Phần trăm request đạt mục tiêu SLO trong cửa sổ", |o| o.compliance),
+            ("lb_slo_error_budget_remaining", "gauge", "Phần error budget còn lại (1 = chưa dùng)", |o| o.error_budget_remaining),
+            ("lb_slo_burn_rate", "gauge", "Tốc độ đốt error budget trong cửa sổ ngắn", |o| o.burn_rate),
+        ];
+        for (metric, kind, help, value) in families {
+            let _ = writeln!(out, "# HELP {} {}", metric, help);
+            let _ = writeln!(out, "# TYPE {} {}", metric, kind);
+            for r in &reports {
+                for o in &r.objectives {
+                    let _ = writeln!(out, "{}{{slo=\"{}\",pool=\"{}\",objective=\"{}\"}} {}", metric, escape(&r.name), escape(&r.pool), o.objective.as_str(), value(o));
+                }
+            }
+        }
+    }
+
+    if let Some(limits) = &state.client_limits {
+        let _ = writeln!(out, "# HELP lb_client_limit_rejected_total Kết nối / request bị từ chối do vượt giới hạn mỗi IP");
+        let _ = writeln!(out, "# TYPE lb_client_limit_rejected_total counter");
+        let _ = writeln!(out, "lb_client_limit_rejected_total{{kind=\"connection\"}} {}", limits.rejected_connections.load(Ordering::Relaxed));
+        let _ = writeln!(out, "lb_client_limit_rejected_total{{kind=\"request\"}} {}", limits.rejected_requests.load(Ordering::Relaxed));
+    }
+
+    if let Some(bans) = &state.bans {
+        let _ = writeln!(out, "# HELP lb_banned_clients Số IP đang bị cấm tạm thời");
+        let _ = writeln!(out, "# TYPE lb_banned_clients gauge");
+        let _ = writeln!(out, "lb_banned_clients {}", bans.list().len());
+    }
+
+    if let Some(malformed) = &state.malformed {
+        let c = &malformed.counters;
+        let _ = writeln!(out, "# HELP lb_malformed_requests_total Request sai định dạng bị từ chối (400)");
+        let _ = writeln!(out, "# TYPE lb_malformed_requests_total counter");
+        for (reason, counter) in [
+            ("framing", &c.framing),
+            ("header_chars", &c.header_chars),
+            ("foreign_host", &c.foreign_host),
+        ] {
+            let _ = writeln!(out, "lb_malformed_requests_total{{reason=\"{}\"}} {}", reason, counter.load(Ordering::Relaxed));
+        }
+    }
+
+    if let Some(suppressed) = crate::logging::suppressed_total() {
+        let _ = writeln!(out, "# HELP lb_log_suppressed_total Dòng log lặp lại bị bỏ do [log_sampling]");
+        let _ = writeln!(out, "# TYPE lb_log_suppressed_total counter");
+        let _ = writeln!(out, "lb_log_suppressed_total {}", suppressed);
+    }
+
+    #[cfg(unix)]
+    if let Some(dropped) = crate::journald::dropped_total() {
+        let _ = writeln!(out, "# HELP lb_journald_dropped_total Log entry không gửi được tới journald (vd. quá lớn cho một datagram)");
+        let _ = writeln!(out, "# TYPE lb_journald_dropped_total counter");
+        let _ = writeln!(out, "lb_journald_dropped_total {}", dropped);
+    }
+
+    if let Some(bots) = &state.bots {
+        let c = &bots.counters;
+        let _ = writeln!(out, "# HELP lb_bot_blocked_total Request bị chặn theo User-Agent");
+        let _ = writeln!(out, "# TYPE lb_bot_blocked_total counter");
+        for (reason, counter) in [
+            ("exact", &c.exact),
+            ("prefix", &c.prefix),
+            ("regex", &c.regex),
+            ("empty_ua", &c.empty_ua),
+        ] {
+            let _ = writeln!(out, "lb_bot_blocked_total{{reason=\"{}\"}} {}", reason, counter.load(Ordering::Relaxed));
+        }
+        let _ = writeln!(out, "# HELP lb_bot_challenged_total Request không có User-Agent bị trả trang challenge");
+        let _ = writeln!(out, "# TYPE lb_bot_challenged_total counter");
+        let _ = writeln!(out, "lb_bot_challenged_total {}", c.challenged.load(Ordering::Relaxed));
+    }
+
+    if let Some(waf) = &state.waf {
+        let _ = writeln!(out, "# HELP lb_waf_rule_hits_total Số request khớp từng luật WAF");
+        let _ = writeln!(out, "# TYPE lb_waf_rule_hits_total counter");
+        for rule in waf.stats() {
+            let _ = writeln!(out, "lb_waf_rule_hits_total{{rule=\"{}\"}} {}", escape(&rule.name), rule.hits);
+        }
+    }
+
+    out
+}
+
+// Trạng thái một backend tại thời điểm lấy mẫu, cho các exporter đẩy metrics (StatsD, InfluxDB...)
+pub struct BackendSample {
+    pub pool: String,
+    pub url: String,
+    pub up: bool,
+    pub active: usize,
+    pub response_time: Option<u128>,
+}
+
+pub fn backend_samples(state: &AppState) -> Vec<BackendSample> {
+    state
+        .pools
+        .iter()
+        .flat_map(|p| p.servers.iter())
+        .map(|s| BackendSample {
+            pool: s.pool.clone(),
+            url: s.url.clone(),
+            up: s.is_available(),
+            active: s.active.load(Ordering::Relaxed),
+            response_time: s.response_time,
+        })
+        .collect()
+}
+
+// Số liệu request cộng dồn trong một chu kỳ flush, theo (pool, backend, nhóm status)
+pub struct TrafficSample {
+    pub pool: String,
+    pub backend: String,
+    // 2 = 2xx, 5 = 5xx...
+    pub status_class: u16,
+    pub count: u64,
+    pub latency_ms_avg: f64,
+    pub latency_ms_max: u64,
+}
+
+#[derive(Default)]
+struct Traffic {
+    count: u64,
+    latency_ms_total: u64,
+    latency_ms_max: u64,
+}
+
+// Cho exporter đẩy metrics theo chu kỳ: không sinh một point cho mỗi request
+#[derive(Default)]
+pub struct TrafficWindow(Mutex<HashMap<(String, String, u16), Traffic>>);
+
+impl TrafficWindow {
+    pub fn record(&self, pool: &str, backend: Option<&str>, status: u16, elapsed: Duration) {
+        let key = (pool.to_string(), backend.unwrap_or("none").to_string(), status / 100);
+        let ms = elapsed.as_millis() as u64;
+        let mut traffic = self.0.lock().unwrap();
+        let entry = traffic.entry(key).or_default();
+        entry.count += 1;
+        entry.latency_ms_total += ms;
+        entry.latency_ms_max = entry.latency_ms_max.max(ms);
+    }
+
+    // Lấy số liệu từ lần gọi trước tới giờ và bắt đầu chu kỳ mới
+    pub fn take(&self) -> Vec<TrafficSample> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+            .into_iter()
+            .map(|((pool, backend, status_class), t)| TrafficSample {
+                pool,
+                backend,
+                status_class,
+                count: t.count,
+                latency_ms_avg: t.latency_ms_total as f64 / t.count as f64,
+                latency_ms_max: t.latency_ms_max,
+            })
+            .collect()
+    }
+}
+
+// Một đoạn trong tên metric dạng Graphite / StatsD (không được chứa '.', ':', '|', ' '...)
+pub fn path_component(value: &str) -> String {
+    value.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -0,0 +1,263 @@
+// Chuẩn hoá path trước khi routing ([path_normalization]) để luật theo path_prefix (routing, basic auth, WAF,
+// shadow...) không bị lách bằng "/public/../admin", "//admin" hay "%2e%2e": gộp "/" liên tiếp, xử lý "." / ".."
+// (kể cả dạng %2e). Path vẫn còn ".." sau khi giải mã hết (qua %2f, "\" hoặc mã hoá hai lần) thì luôn bị từ chối,
+// vì backend giải mã khác có thể hiểu thành đi ngược thư mục.
+// Tuỳ chọn: chuẩn hoá thêm URL ([canonicalization]) - host chữ thường, bỏ port mặc định, bỏ "/" cuối path,
+// bỏ / sắp xếp query param - để cùng một trang luôn ra cùng host + URI cho routing và mọi thứ tính theo URL.
+use crate::config::{CanonicalizationConfig, PathMode, PathNormalizationConfig};
+use axum::http::{
+    header,
+    uri::{Authority, PathAndQuery},
+    HeaderValue, Request, Uri,
+};
+use percent_encoding::percent_decode_str;
+use std::borrow::Cow;
+
+// Path có thể bị backend hiểu thành đi ngược thư mục, hoặc cần chuẩn hoá mà mode = "reject"
+#[derive(Debug)]
+pub struct Rejected;
+
+// Đoạn path là "." / ".." sau khi giải mã %2e
+fn dot_segment(segment: &str) -> Option<bool> {
+    let decoded = segment.replace("%2e", ".").replace("%2E", ".");
+    match decoded.as_str() {
+        "." => Some(false),
+        ".." => Some(true),
+        _ => None,
+    }
+}
+
+// Giải mã tới khi không đổi nữa (tối đa vài lần), coi "\" như "/" rồi tìm đoạn ".."
+fn hides_traversal(segment: &str) -> bool {
+    let mut current = Cow::Borrowed(segment);
+    for _ in 0..3 {
+        let decoded = percent_decode_str(&current).decode_utf8_lossy().into_owned();
+        if decoded == current {
+            break;
+        }
+        current = Cow::Owned(decoded);
+    }
+    current.split(['/', '\\']).any(|part| part == "..")
+}
+
+// Giải mã %XX của ký tự unreserved (RFC 3986 mục 6.2.2.2: chữ, số, "-", ".", "_", "~"), vd. "%61dmin" -> "admin",
+// để luật theo path_prefix không bị lách; các %XX khác giữ nguyên
+fn decode_unreserved(segment: &str) -> Cow<'_, str> {
+    if !segment.contains('%') {
+        return Cow::Borrowed(segment);
+    }
+    let bytes = segment.as_bytes();
+    let mut out = String::with_capacity(segment.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = (bytes[i] == b'%')
+            .then(|| segment.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .filter(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'));
+        match decoded {
+            Some(b) => {
+                out.push(b as char);
+                i += 3;
+            }
+            None => {
+                // Ký tự UTF-8 nhiều byte được chép nguyên
+                let len = segment[i..].chars().next().map_or(1, char::len_utf8);
+                out.push_str(&segment[i..i + len]);
+                i += len;
+            }
+        }
+    }
+    Cow::Owned(out)
+}
+
+pub fn path(path: &str, merge_slashes: bool) -> Result<Cow<'_, str>, Rejected> {
+    // "*" (OPTIONS *) hoặc path rỗng: không có gì để chuẩn hoá
+    let Some(rest) = path.strip_prefix('/') else {
+        return Ok(Cow::Borrowed(path));
+    };
+    let raw: Vec<&str> = rest.split('/').collect();
+    let mut segments: Vec<Cow<'_, str>> = Vec::with_capacity(raw.len());
+    // Path kết thúc bằng "/" (hoặc bằng "." / "..") thì giữ "/" ở cuối
+    let mut trailing = false;
+    for (i, segment) in raw.iter().enumerate() {
+        let last = i + 1 == raw.len();
+        match dot_segment(segment) {
+            Some(parent) => {
+                if parent {
+                    segments.pop();
+                }
+                trailing = last;
+            }
+            None if segment.is_empty() && (merge_slashes || last) => trailing = last,
+            None => {
+                if hides_traversal(segment) {
+                    return Err(Rejected);
+                }
+                segments.push(decode_unreserved(segment));
+            }
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len());
+    for segment in &segments {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if trailing || segments.is_empty() {
+        normalized.push('/');
+    }
+    if normalized == path {
+        Ok(Cow::Borrowed(path))
+    } else {
+        Ok(Cow::Owned(normalized))
+    }
+}
+
+// Path đã chuẩn hoá (gộp "/", xử lý "." / "..", giải mã unreserved) để so với path_prefix của các luật bảo vệ,
+// kể cả khi [path_normalization] mode = "off"; None nếu path che giấu ".."
+pub fn canonical_path(raw: &str) -> Option<Cow<'_, str>> {
+    path(raw, true).ok()
+}
+
+// path nằm dưới prefix theo ranh giới đoạn: "/public" khớp "/public" và "/public/x" nhưng không khớp "/publicity"
+pub fn under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'))
+}
+
+// URI mới nếu path được chuẩn hoá (giữ nguyên query), None nếu path đã chuẩn; Err nếu phải từ chối request
+pub fn uri(uri: &Uri, config: &PathNormalizationConfig) -> Result<Option<Uri>, Rejected> {
+    if config.mode == PathMode::Off {
+        // Giữ nguyên path nhưng vẫn từ chối ".." được che giấu (qua %2f, "\" hoặc mã hoá hai lần)
+        let hidden = uri.path().split('/').any(|segment| dot_segment(segment).is_none() && hides_traversal(segment));
+        return if hidden { Err(Rejected) } else { Ok(None) };
+    }
+    let normalized = match path(uri.path(), config.merge_slashes)? {
+        Cow::Borrowed(_) => return Ok(None),
+        Cow::Owned(normalized) => normalized,
+    };
+    if config.mode == PathMode::Reject {
+        return Err(Rejected);
+    }
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", normalized, query),
+        None => normalized,
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).map_err(|_| Rejected)?);
+    Uri::from_parts(parts).map(Some).map_err(|_| Rejected)
+}
+
+// Query param bị bỏ: trùng tên, hoặc khớp tiền tố với mẫu kết thúc bằng "*" (vd. "utm_*")
+fn stripped(name: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    })
+}
+
+fn canonical_query(query: &str, config: &CanonicalizationConfig) -> String {
+    let mut params: Vec<&str> = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .filter(|param| !stripped(param.split('=').next().unwrap_or_default(), &config.strip_query_params))
+        .collect();
+    if config.sort_query {
+        // Sắp xếp ổn định theo tên: các giá trị của cùng một param giữ nguyên thứ tự
+        params.sort_by_key(|param| param.split('=').next().unwrap_or_default());
+    }
+    params.join("&")
+}
+
+fn canonical_host(host: &str, config: &CanonicalizationConfig, secure: bool) -> String {
+    let mut host = if config.lowercase_host { host.to_ascii_lowercase() } else { host.to_string() };
+    if config.strip_default_port {
+        let default_port = if secure { ":443" } else { ":80" };
+        if let Some(stripped) = host.strip_suffix(default_port) {
+            host = stripped.to_string();
+        }
+    }
+    host
+}
+
+// Chuẩn hoá header Host (và authority của URI nếu có) cùng path + query của request, trước khi routing
+pub fn canonicalize<B>(req: &mut Request<B>, config: &CanonicalizationConfig, secure: bool) -> Result<(), Rejected> {
+    if let Some(host) = req.headers().get(header::HOST).and_then(|v| v.to_str().ok()) {
+        let canonical = canonical_host(host, config, secure);
+        if canonical != host {
+            let value = HeaderValue::from_str(&canonical).map_err(|_| Rejected)?;
+            req.headers_mut().insert(header::HOST, value);
+        }
+    }
+
+    let uri = req.uri();
+    let authority = uri.authority().map(|a| canonical_host(a.as_str(), config, secure));
+    let mut path = uri.path();
+    if config.strip_trailing_slash && path.len() > 1 {
+        path = path.trim_end_matches('/');
+        if path.is_empty() {
+            path = "/";
+        }
+    }
+    let query = uri.query().map(|q| canonical_query(q, config)).filter(|q| !q.is_empty());
+    let path_and_query = match &query {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let unchanged = authority.as_deref() == uri.authority().map(|a| a.as_str())
+        && uri.path_and_query().is_none_or(|p| p.as_str() == path_and_query);
+    if unchanged {
+        return Ok(());
+    }
+
+    let mut parts = uri.clone().into_parts();
+    if let Some(authority) = authority {
+        parts.authority = Some(Authority::try_from(authority).map_err(|_| Rejected)?);
+    }
+    if parts.path_and_query.is_some() {
+        parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).map_err(|_| Rejected)?);
+    }
+    *req.uri_mut() = Uri::from_parts(parts).map_err(|_| Rejected)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(mode: PathMode) -> PathNormalizationConfig {
+        PathNormalizationConfig { mode, merge_slashes: true }
+    }
+
+    #[test]
+    fn decodes_unreserved_characters() {
+        assert_eq!(path("/%61dmin/%7Euser", true).unwrap(), "/admin/~user");
+        // "%2F" và byte ngoài ASCII không phải unreserved
+        assert_eq!(path("/a%2Fb/%C3%A9", true).unwrap(), "/a%2Fb/%C3%A9");
+    }
+
+    #[test]
+    fn resolves_dot_segments() {
+        assert_eq!(path("/public/../admin", true).unwrap(), "/admin");
+        assert_eq!(path("//admin/%2e%2e/x", true).unwrap(), "/x");
+        assert!(path("/a/..%2fadmin", true).is_err());
+    }
+
+    #[test]
+    fn prefix_matches_whole_segments() {
+        assert!(under("/public", "/public"));
+        assert!(under("/public/a", "/public"));
+        assert!(!under("/publicity", "/public"));
+        assert!(under("/anything", "/"));
+    }
+
+    #[test]
+    fn off_mode_still_rejects_hidden_traversal() {
+        let off = config(PathMode::Off);
+        assert!(uri(&"/a/..%2fadmin".parse().unwrap(), &off).is_err());
+        assert!(uri(&"/a/..%5cadmin".parse().unwrap(), &off).is_err());
+        assert!(uri(&"/a/%252e%252e".parse().unwrap(), &off).is_err());
+        assert!(uri(&"/a/../b".parse().unwrap(), &off).unwrap().is_none());
+    }
+}
@@ -0,0 +1,147 @@
+// OCSP stapling cho cert của listener TLS: hỏi OCSP responder của CA (URL trong extension AIA của cert) rồi gắn
+// response vào cert trong CertStore để gửi kèm trong handshake. Lấy lại theo chu kỳ, và ngay khi cert bị thay
+// (Vault PKI gia hạn...). Response chỉ được kiểm tra responseStatus; chữ ký và trạng thái cert để client xác thực.
+use crate::tls::CertStore;
+use sha1::{Digest, Sha1};
+use std::{sync::Arc, time::Duration};
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tracing::{info, warn};
+use x509_parser::{
+    extensions::{GeneralName, ParsedExtension},
+    oid_registry::OID_PKIX_ACCESS_DESCRIPTOR_OCSP,
+};
+
+// Lấy response thất bại thì thử lại sau khoảng này (hoặc sau chu kỳ nếu ngắn hơn)
+const RETRY: Duration = Duration::from_secs(60);
+
+pub async fn staple_task(store: Arc<CertStore>, refresh: Duration) {
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap();
+    loop {
+        // Tạo trước khi hỏi responder để không lỡ lần thay cert xảy ra trong lúc đó
+        let changed = store.changed();
+        let key = store.current();
+        let Some(leaf) = key.cert.first() else {
+            changed.await;
+            continue;
+        };
+        let wait = match fetch(&client, leaf, key.cert.get(1)).await {
+            Ok(Some(response)) => {
+                let len = response.len();
+                if !store.staple(leaf, response) {
+                    continue;
+                }
+                info!("📜 Đã gắn OCSP response ({} bytes) vào cert TLS", len);
+                Some(refresh)
+            }
+            Ok(None) => {
+                info!("📜 Cert TLS không có OCSP responder (AIA), bỏ qua OCSP stapling");
+                None
+            }
+            Err(e) => {
+                warn!("⚠️ Không lấy được OCSP response: {}", e);
+                Some(RETRY.min(refresh))
+            }
+        };
+        match wait {
+            Some(wait) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = changed => {}
+                }
+            }
+            None => changed.await,
+        }
+    }
+}
+
+// None nếu cert không khai báo OCSP responder
+async fn fetch(
+    client: &reqwest::Client,
+    leaf: &CertificateDer<'_>,
+    issuer: Option<&CertificateDer<'_>>,
+) -> Result<Option<Vec<u8>>, String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(leaf).map_err(|e| format!("cert không hợp lệ: {}", e))?;
+    let Some(url) = responder_url(&cert) else {
+        return Ok(None);
+    };
+    let issuer = issuer.ok_or("cert_file thiếu cert của issuer (cần cả chain)")?;
+    let (_, issuer) = x509_parser::parse_x509_certificate(issuer).map_err(|e| format!("cert issuer không hợp lệ: {}", e))?;
+    let request = request(
+        cert.issuer().as_raw(),
+        &issuer.public_key().subject_public_key.data,
+        cert.raw_serial(),
+    );
+
+    let response = client
+        .post(&url)
+        .header("content-type", "application/ocsp-request")
+        .body(request)
+        .send()
+        .await
+        .map_err(|e| format!("{}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{}: HTTP {}", url, response.status()));
+    }
+    let body = response.bytes().await.map_err(|e| format!("{}: {}", url, e))?;
+    match response_status(&body) {
+        Some(0) => Ok(Some(body.to_vec())),
+        Some(status) => Err(format!("{}: responseStatus = {}", url, status)),
+        None => Err(format!("{}: response không hợp lệ", url)),
+    }
+}
+
+fn responder_url(cert: &x509_parser::certificate::X509Certificate<'_>) -> Option<String> {
+    cert.iter_extensions().find_map(|ext| match ext.parsed_extension() {
+        ParsedExtension::AuthorityInfoAccess(aia) => aia.iter().find_map(|desc| match desc.access_location {
+            GeneralName::URI(uri) if desc.access_method == OID_PKIX_ACCESS_DESCRIPTOR_OCSP => Some(uri.to_string()),
+            _ => None,
+        }),
+        _ => None,
+    })
+}
+
+// OCSPRequest (RFC 6960) cho một cert, CertID băm bằng SHA-1 như mọi responder đều hỗ trợ
+fn request(issuer_name: &[u8], issuer_key: &[u8], serial: &[u8]) -> Vec<u8> {
+    // AlgorithmIdentifier { id-sha1, NULL }
+    let sha1 = der(0x30, &[&der(0x06, &[0x2b, 0x0e, 0x03, 0x02, 0x1a])[..], &[0x05, 0x00]].concat());
+    let cert_id = der(
+        0x30,
+        &[
+            sha1,
+            der(0x04, &Sha1::digest(issuer_name)),
+            der(0x04, &Sha1::digest(issuer_key)),
+            der(0x02, serial),
+        ]
+        .concat(),
+    );
+    // OCSPRequest { TBSRequest { requestList { Request { CertID } } } }
+    der(0x30, &der(0x30, &der(0x30, &der(0x30, &cert_id))))
+}
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|&b| b == 0).collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+// responseStatus của OCSPResponse ::= SEQUENCE { responseStatus ENUMERATED, responseBytes [0] ... }
+fn response_status(body: &[u8]) -> Option<u8> {
+    let (&tag, rest) = body.split_first()?;
+    let (&len, rest) = rest.split_first()?;
+    let rest = match len {
+        0..0x80 => rest,
+        _ => rest.get(usize::from(len & 0x7f)..)?,
+    };
+    match (tag, rest) {
+        (0x30, [0x0a, 0x01, status, ..]) => Some(*status),
+        _ => None,
+    }
+}
@@ -0,0 +1,441 @@
+// OIDC gateway: request chưa đăng nhập bị chuyển tới OIDC provider (authorization code flow),
+// load balancer xử lý callback, cấp session cookie và gửi claims của user lên backend qua header.
+//
+// id_token lấy trực tiếp từ token endpoint qua TLS bằng client_secret nên theo OIDC Core 3.1.3.7
+// không bắt buộc kiểm tra chữ ký; vẫn kiểm tra iss/aud/exp/nonce.
+use crate::config::OidcConfig;
+use axum::{
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+pub const CALLBACK_PATH: &str = "/load-balancer/oidc/callback";
+pub const LOGOUT_PATH: &str = "/load-balancer/oidc/logout";
+
+// Thời gian tối đa để user hoàn tất đăng nhập ở provider
+const PENDING_TTL: Duration = Duration::from_secs(600);
+// Số lượt đăng nhập đang chờ tối đa (mỗi request chưa đăng nhập tạo một lượt), đầy thì bỏ lượt cũ nhất
+const MAX_PENDING: usize = 10_000;
+// Số lượt đang chờ tối đa của một client (IPv6 tính theo dải /64): client gửi dồn dập request chưa đăng nhập
+// nhận 429 thay vì đẩy lượt đăng nhập của user khác ra ngoài
+const MAX_PENDING_PER_CLIENT: usize = 20;
+const CLIENT_IPV6_PREFIX: u8 = 64;
+
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+struct Session {
+    claims: Map<String, Value>,
+    expires: Instant,
+}
+
+struct Pending {
+    return_to: String,
+    nonce: String,
+    created: Instant,
+    client: IpAddr,
+}
+
+// Lượt đăng nhập đang chờ theo state. `order` giữ state theo thứ tự tạo (cũng là thứ tự hết hạn) để bỏ lượt
+// hết hạn / cũ nhất từ đầu hàng đợi thay vì duyệt cả map; state đã dùng ở callback còn trong `order` thì bị bỏ qua
+#[derive(Default)]
+struct PendingLogins {
+    by_state: HashMap<String, Pending>,
+    order: VecDeque<String>,
+    per_client: HashMap<IpAddr, usize>,
+}
+
+impl PendingLogins {
+    fn insert(&mut self, state: String, pending: Pending) -> bool {
+        while let Some(oldest) = self.order.front() {
+            let expired = self.by_state.get(oldest).is_none_or(|p| p.created.elapsed() >= PENDING_TTL);
+            if !expired && self.by_state.len() < MAX_PENDING {
+                break;
+            }
+            if let Some(oldest) = self.order.pop_front() {
+                self.take(&oldest);
+            }
+        }
+        let count = self.per_client.entry(pending.client).or_default();
+        if *count >= MAX_PENDING_PER_CLIENT {
+            return false;
+        }
+        *count += 1;
+        self.order.push_back(state.clone());
+        self.by_state.insert(state, pending);
+        true
+    }
+
+    fn take(&mut self, state: &str) -> Option<Pending> {
+        let pending = self.by_state.remove(state)?;
+        if let Entry::Occupied(mut count) = self.per_client.entry(pending.client) {
+            *count.get_mut() -= 1;
+            if *count.get() == 0 {
+                count.remove();
+            }
+        }
+        Some(pending)
+    }
+}
+
+pub struct Gateway {
+    config: OidcConfig,
+    client: reqwest::Client,
+    discovery: OnceCell<Discovery>,
+    sessions: Mutex<HashMap<String, Session>>,
+    pending: Mutex<PendingLogins>,
+}
+
+impl Gateway {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+            discovery: OnceCell::new(),
+            sessions: Mutex::new(HashMap::new()),
+            pending: Mutex::new(PendingLogins::default()),
+        }
+    }
+
+    fn redirect_uri(&self) -> String {
+        format!("{}{}", self.config.external_url.trim_end_matches('/'), CALLBACK_PATH)
+    }
+
+    // Lấy (và cache) .well-known/openid-configuration của provider
+    async fn discovery(&self) -> Result<&Discovery, String> {
+        self.discovery
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.config.issuer.trim_end_matches('/'));
+                let res = self.client.get(&url).send().await.map_err(|e| e.to_string())?;
+                res.error_for_status()
+                    .map_err(|e| e.to_string())?
+                    .json::<Discovery>()
+                    .await
+                    .map_err(|e| e.to_string())
+            })
+            .await
+    }
+
+    // Các header chứa claims, phải xóa khỏi request của client để không bị giả mạo
+    pub fn identity_header_names(&self) -> impl Iterator<Item = &str> {
+        self.config.claim_headers.values().map(String::as_str)
+    }
+
+    // Ok(header danh tính để gửi lên backend) hoặc Err(response redirect/401 trả ngay cho client)
+    pub async fn authenticate(
+        &self,
+        client: IpAddr,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+    ) -> Result<Vec<(HeaderName, HeaderValue)>, Response> {
+        // So trên path đã chuẩn hoá và theo ranh giới đoạn ("/public" không khớp "/publicity")
+        let public = crate::normalize::canonical_path(uri.path())
+            .is_some_and(|path| self.config.public_paths.iter().any(|p| crate::normalize::under(&path, p)));
+        if public {
+            return Ok(Vec::new());
+        }
+
+        if let Some(sid) = crate::get_cookie(headers, &self.config.cookie_name) {
+            let sessions = self.sessions.lock().unwrap();
+            if let Some(session) = sessions.get(sid).filter(|s| s.expires > Instant::now()) {
+                return Ok(claims_to_headers(&self.config.claim_headers, &session.claims));
+            }
+        }
+
+        // API (POST/PUT...) không redirect được -> 401
+        if !matches!(*method, Method::GET | Method::HEAD) {
+            return Err((StatusCode::UNAUTHORIZED, "Chưa đăng nhập").into_response());
+        }
+
+        let return_to = safe_return_to(uri.path_and_query().map_or("/", |pq| pq.as_str()));
+        match self.login_redirect(client, return_to).await {
+            Ok(resp) => Err(resp),
+            Err(e) => {
+                warn!("⚠️ OIDC discovery lỗi: {}", e);
+                Err((StatusCode::BAD_GATEWAY, "Không kết nối được OIDC provider").into_response())
+            }
+        }
+    }
+
+    async fn login_redirect(&self, client: IpAddr, return_to: String) -> Result<Response, String> {
+        let discovery = self.discovery().await?;
+        let state = random_token();
+        let nonce = random_token();
+
+        let url = reqwest::Url::parse_with_params(
+            &discovery.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri().as_str()),
+                ("scope", self.config.scopes.join(" ").as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let state_cookie = self.state_cookie(&state, PENDING_TTL.as_secs());
+        let client = crate::client_limits::client_key(client, CLIENT_IPV6_PREFIX);
+        let pending = Pending { return_to, nonce, created: Instant::now(), client };
+        if !self.pending.lock().unwrap().insert(state, pending) {
+            return Ok((StatusCode::TOO_MANY_REQUESTS, "Quá nhiều lượt đăng nhập đang chờ").into_response());
+        }
+
+        let mut resp = Redirect::to(url.as_str()).into_response();
+        if let Ok(cookie) = HeaderValue::from_str(&state_cookie) {
+            resp.headers_mut().insert(header::SET_COOKIE, cookie);
+        }
+        Ok(resp)
+    }
+
+    pub async fn callback(&self, query: CallbackQuery, headers: &HeaderMap) -> Response {
+        if let Some(error) = query.error {
+            return (StatusCode::UNAUTHORIZED, format!("Đăng nhập thất bại: {}", error)).into_response();
+        }
+        let (Some(code), Some(state)) = (query.code, query.state) else {
+            return (StatusCode::BAD_REQUEST, "Thiếu code/state").into_response();
+        };
+        // state phải trùng cookie đặt cho chính trình duyệt này lúc chuyển sang provider (chống login CSRF:
+        // code + state của kẻ tấn công không dùng được trong trình duyệt của nạn nhân)
+        if crate::get_cookie(headers, &self.state_cookie_name()) != Some(state.as_str()) {
+            return (StatusCode::BAD_REQUEST, "state không khớp với trình duyệt").into_response();
+        }
+
+        let pending = self.pending.lock().unwrap().take(&state);
+        let Some(pending) = pending.filter(|p| p.created.elapsed() < PENDING_TTL) else {
+            return (StatusCode::BAD_REQUEST, "state không hợp lệ hoặc đã hết hạn").into_response();
+        };
+
+        let claims = match self.exchange_code(&code, &pending.nonce).await {
+            Ok(claims) => claims,
+            Err(e) => {
+                warn!("⚠️ OIDC callback lỗi: {}", e);
+                return (StatusCode::UNAUTHORIZED, format!("Đăng nhập thất bại: {}", e)).into_response();
+            }
+        };
+
+        let subject = claims.get("sub").and_then(Value::as_str).unwrap_or("-");
+        info!("🔐 OIDC đăng nhập: {}", subject);
+
+        let sid = random_token();
+        let ttl = Duration::from_secs(self.config.session_ttl_secs);
+        {
+            let mut sessions = self.sessions.lock().unwrap();
+            let now = Instant::now();
+            sessions.retain(|_, s| s.expires > now);
+            sessions.insert(sid.clone(), Session { claims, expires: now + ttl });
+        }
+
+        let mut resp = Redirect::to(&safe_return_to(&pending.return_to)).into_response();
+        for cookie in [self.session_cookie(&sid, ttl.as_secs()), self.state_cookie("", 0)] {
+            if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+                resp.headers_mut().append(header::SET_COOKIE, cookie);
+            }
+        }
+        resp
+    }
+
+    pub fn logout(&self, headers: &HeaderMap) -> Response {
+        if let Some(sid) = crate::get_cookie(headers, &self.config.cookie_name) {
+            self.sessions.lock().unwrap().remove(sid);
+        }
+
+        let mut resp = (StatusCode::OK, "Đã đăng xuất").into_response();
+        if let Ok(cookie) = HeaderValue::from_str(&self.session_cookie("", 0)) {
+            resp.headers_mut().insert(header::SET_COOKIE, cookie);
+        }
+        resp
+    }
+
+    async fn exchange_code(&self, code: &str, nonce: &str) -> Result<Map<String, Value>, String> {
+        let discovery = self.discovery().await?;
+        let redirect_uri = self.redirect_uri();
+
+        let token: TokenResponse = self
+            .client
+            .post(&discovery.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", redirect_uri.as_str()),
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .error_for_status()
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let claims = decode_jwt_payload(&token.id_token)?;
+        self.validate_claims(&claims, nonce)?;
+        Ok(claims)
+    }
+
+    fn validate_claims(&self, claims: &Map<String, Value>, nonce: &str) -> Result<(), String> {
+        let iss = claims.get("iss").and_then(Value::as_str).unwrap_or("");
+        if iss.trim_end_matches('/') != self.config.issuer.trim_end_matches('/') {
+            return Err(format!("iss không khớp: {}", iss));
+        }
+
+        let aud_ok = match claims.get("aud") {
+            Some(Value::String(aud)) => *aud == self.config.client_id,
+            Some(Value::Array(auds)) => auds.iter().any(|a| a.as_str() == Some(self.config.client_id.as_str())),
+            _ => false,
+        };
+        if !aud_ok {
+            return Err("aud không chứa client_id".to_string());
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if claims.get("exp").and_then(Value::as_u64).is_none_or(|exp| exp <= now) {
+            return Err("id_token đã hết hạn".to_string());
+        }
+
+        if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+            return Err("nonce không khớp".to_string());
+        }
+        Ok(())
+    }
+
+    fn secure(&self) -> &'static str {
+        if self.config.external_url.starts_with("https://") { "; Secure" } else { "" }
+    }
+
+    fn session_cookie(&self, value: &str, max_age: u64) -> String {
+        format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}{}",
+            self.config.cookie_name,
+            value,
+            max_age,
+            self.secure()
+        )
+    }
+
+    fn state_cookie_name(&self) -> String {
+        format!("{}_state", self.config.cookie_name)
+    }
+
+    // Cookie gắn state với trình duyệt, chỉ gửi kèm tới callback (SameSite=Lax vẫn gửi khi provider redirect về)
+    fn state_cookie(&self, value: &str, max_age: u64) -> String {
+        format!(
+            "{}={}; Path={}; HttpOnly; SameSite=Lax; Max-Age={}{}",
+            self.state_cookie_name(),
+            value,
+            CALLBACK_PATH,
+            max_age,
+            self.secure()
+        )
+    }
+}
+
+// Chuyển claims thành header theo bảng claim -> tên header (dùng chung cho OIDC và JWT)
+pub fn claims_to_headers(
+    mapping: &BTreeMap<String, String>,
+    claims: &Map<String, Value>,
+) -> Vec<(HeaderName, HeaderValue)> {
+    mapping
+        .iter()
+        .filter_map(|(claim, header)| {
+            let value = match claims.get(claim)? {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            Some((
+                HeaderName::from_bytes(header.as_bytes()).ok()?,
+                HeaderValue::from_bytes(value.as_bytes()).ok()?,
+            ))
+        })
+        .collect()
+}
+
+// Chỉ redirect về path trong cùng site: một "/" ở đầu, không theo sau bởi "/" hoặc "\" ("//evil.com" là URL
+// tuyệt đối theo scheme hiện tại)
+fn safe_return_to(path: &str) -> String {
+    let local = path.starts_with('/') && !path[1..].starts_with(['/', '\\']);
+    if local { path.to_string() } else { "/".to_string() }
+}
+
+fn decode_jwt_payload(token: &str) -> Result<Map<String, Value>, String> {
+    let payload = token.split('.').nth(1).ok_or("id_token không đúng định dạng JWT")?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&bytes).map_err(|e| e.to_string())
+}
+
+fn random_token() -> String {
+    rand::thread_rng().sample_iter(&Alphanumeric).take(43).map(char::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{safe_return_to, Pending, PendingLogins, MAX_PENDING_PER_CLIENT};
+    use std::{net::IpAddr, time::Instant};
+
+    fn pending(client: &str) -> Pending {
+        Pending {
+            return_to: "/".to_string(),
+            nonce: String::new(),
+            created: Instant::now(),
+            client: client.parse::<IpAddr>().unwrap(),
+        }
+    }
+
+    // Một client gửi dồn dập không đẩy được lượt đăng nhập của client khác ra ngoài
+    #[test]
+    fn pending_logins_are_limited_per_client() {
+        let mut logins = PendingLogins::default();
+        assert!(logins.insert("user".to_string(), pending("10.0.0.1")));
+        for i in 0..MAX_PENDING_PER_CLIENT {
+            assert!(logins.insert(format!("flood{}", i), pending("10.0.0.2")));
+        }
+        assert!(!logins.insert("flood".to_string(), pending("10.0.0.2")));
+        assert!(logins.take("user").is_some());
+
+        // Lượt đã xong trả lại chỗ cho client đó
+        assert!(logins.take("flood0").is_some());
+        assert!(logins.insert("flood".to_string(), pending("10.0.0.2")));
+    }
+
+    #[test]
+    fn return_to_stays_on_site() {
+        assert_eq!(safe_return_to("/app?x=1"), "/app?x=1");
+        assert_eq!(safe_return_to("//evil.com/x"), "/");
+        assert_eq!(safe_return_to("/\\evil.com"), "/");
+        assert_eq!(safe_return_to("https://evil.com"), "/");
+    }
+}
@@ -0,0 +1,488 @@
+// Backend được chia thành các pool có tên (vd. api, web, grpc), mỗi pool có danh sách backend,
+// cấu hình health check, sticky map và số liệu riêng. Request được đưa vào pool theo [routing].
+//
+// servers.json dạng cũ (mảng backend) vẫn dùng được: tất cả thuộc pool "default".
+// Dạng pool:
+//   { "api": { "servers": [{ "url": "...", "region": "vi" }], "health": { "path": "/healthz" } },
+//     "web": { "servers": [...], "strategy": "least_conn" } }
+// Warm-up trước khi đưa backend vừa hồi phục vào rotation:
+//   "health": { "warmup": { "paths": ["/", "/api/catalog"], "requests": 3 } }
+// Kiểm tra cả body health check (app trả 200 nhưng body báo lỗi), một trong:
+//   "health": { "body": { "equals": "OK" } }
+//   "health": { "body": { "contains": "healthy" } }
+//   "health": { "body": { "regex": "^(ok|degraded)$" } }
+//   "health": { "body": { "json": { "pointer": "/status", "value": "UP" } } }
+// Health check bằng lệnh ngoài (exit code 0 = UP), cho các kiểm tra HTTP không diễn tả được:
+//   "health": { "check_type": "exec", "command": ["/etc/lb/check-replication.sh", "--max-lag", "10"] }
+// Chuỗi request mô phỏng giao dịch thật (cookie giữ qua các bước, {{tên}} thay bằng giá trị capture):
+//   "health": { "check_type": "steps", "steps": [
+//     { "method": "POST", "path": "/login", "headers": { "content-type": "application/json" },
+//       "body": "{\"user\":\"probe\",\"password\":\"...\"}", "capture": { "token": "/access_token" } },
+//     { "path": "/api/me", "headers": { "authorization": "Bearer {{token}}" }, "expect_status": 200 } ] }
+// Timeout riêng của backend (ghi đè [timeouts] trong config.toml, đơn vị ms):
+//   { "url": "...", "timeouts": { "connect_ms": 500, "body_idle_ms": 30000 } }
+// Backend cùng máy qua Unix domain socket (không chiếm port TCP):
+//   { "url": "unix:/var/run/app.sock" }
+// Giao thức gọi backend (mặc định "auto": h2 / http/1.1 thương lượng qua ALPN khi dùng TLS):
+//   { "url": "https://api:8443", "protocol": "http1" }
+//   { "url": "http://grpc:50051", "protocol": "http2" }
+// Xác minh cert của backend HTTPS (không khai báo = chấp nhận mọi cert), bằng CA nội bộ hoặc CA hệ thống:
+//   { "url": "https://10.0.0.5:8443", "tls": { "ca_file": "/etc/lb/internal-ca.pem" } }
+//   { "url": "https://api.example.com", "tls": {} }
+// Credential service-to-service gửi kèm mọi request (kể cả health check) tới backend, một trong:
+//   { "url": "...", "auth": { "bearer": "eyJ..." } }
+//   { "url": "...", "auth": { "basic": { "username": "lb", "password": "..." } } }
+//   { "url": "...", "auth": { "header": { "name": "x-api-key", "value": "env:ORDERS_API_KEY" } } }
+// (giá trị có thể là tham chiếu env:/file:/exec: hoặc vault:<path>#<field> khi có [vault])
+use crate::{
+    config::{Route, RoutingConfig, UpstreamTimeouts},
+    secrets, upstream, vault, ServerStatus,
+};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tracing::{info, warn};
+
+pub const DEFAULT_POOL: &str = "default";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServerConfig {
+    url: String,
+    region: Option<String>,
+    #[serde(default)]
+    timeouts: TimeoutOverrides,
+    auth: Option<UpstreamAuth>,
+    #[serde(default)]
+    protocol: upstream::Protocol,
+    tls: Option<upstream::BackendTls>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum UpstreamAuth {
+    Bearer(String),
+    Basic { username: String, password: String },
+    Header { name: String, value: String },
+}
+
+// Giá trị bí mật: vault:... đọc từ Vault, còn lại theo secrets.rs
+async fn secret(value: &str, vault: Option<&vault::Client>) -> Result<String, String> {
+    match vault {
+        Some(vault) if value.starts_with(vault::PREFIX) => vault.read(value).await,
+        None if value.starts_with(vault::PREFIX) => Err("tham chiếu vault: cần mục [vault] trong config.toml".to_string()),
+        _ => secrets::resolve(value),
+    }
+}
+
+impl UpstreamAuth {
+    // Có giá trị lấy từ Vault (cần đọc lại định kỳ)
+    pub fn uses_vault(&self) -> bool {
+        let values = match self {
+            UpstreamAuth::Bearer(token) => vec![token],
+            UpstreamAuth::Basic { username, password } => vec![username, password],
+            UpstreamAuth::Header { value, .. } => vec![value],
+        };
+        values.into_iter().any(|v| v.starts_with(vault::PREFIX))
+    }
+
+    // Header ghi đè lên request gửi backend (đánh dấu sensitive để không lộ khi log)
+    pub async fn headers(&self, vault: Option<&vault::Client>) -> Result<HeaderMap, String> {
+        let (name, value) = match self {
+            UpstreamAuth::Bearer(token) => (header::AUTHORIZATION, format!("Bearer {}", secret(token, vault).await?)),
+            UpstreamAuth::Basic { username, password } => {
+                let credentials = format!("{}:{}", secret(username, vault).await?, secret(password, vault).await?);
+                (header::AUTHORIZATION, format!("Basic {}", STANDARD.encode(credentials)))
+            }
+            UpstreamAuth::Header { name, value } => {
+                let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("tên header \"{}\" không hợp lệ", name))?;
+                (name, secret(value, vault).await?)
+            }
+        };
+        let mut value = HeaderValue::from_str(&value).map_err(|_| format!("giá trị header {} không hợp lệ", name))?;
+        value.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value);
+        Ok(headers)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TimeoutOverrides {
+    connect_ms: Option<u64>,
+    response_header_ms: Option<u64>,
+    body_idle_ms: Option<u64>,
+}
+
+impl TimeoutOverrides {
+    fn apply(&self, defaults: &UpstreamTimeouts) -> UpstreamTimeouts {
+        UpstreamTimeouts {
+            connect_ms: self.connect_ms.unwrap_or(defaults.connect_ms),
+            response_header_ms: self.response_header_ms.unwrap_or(defaults.response_header_ms),
+            body_idle_ms: self.body_idle_ms.unwrap_or(defaults.body_idle_ms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    pub path: String,
+    pub interval_secs: u64,
+    pub timeout_secs: u64,
+    pub warmup: Option<WarmupConfig>,
+    pub body: Option<BodyMatch>,
+    pub check_type: CheckType,
+    // Chương trình + tham số cho check_type = "exec"
+    pub command: Vec<String>,
+    // Các bước cho check_type = "steps"
+    pub steps: Vec<ProbeStep>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            path: "/healthz".to_string(),
+            interval_secs: 5,
+            timeout_secs: 2,
+            warmup: None,
+            body: None,
+            check_type: CheckType::default(),
+            command: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+}
+
+// Gửi `requests` lần GET tới từng path, tất cả phải trả 2xx thì backend mới được tính là UP
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WarmupConfig {
+    pub paths: Vec<String>,
+    #[serde(default = "default_warmup_requests")]
+    pub requests: u32,
+}
+
+fn default_warmup_requests() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckType {
+    // GET `path`, status 2xx (+ `body` nếu có) = UP
+    #[default]
+    Http,
+    // Chạy `command`, exit code 0 = UP
+    Exec,
+    // Chạy lần lượt `steps`, mọi bước đạt = UP
+    Steps,
+}
+
+// Một bước của health check nhiều bước
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeStep {
+    #[serde(default = "default_step_method")]
+    pub method: String,
+    pub path: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+    // Không khai báo: chấp nhận mọi 2xx
+    pub expect_status: Option<u16>,
+    pub expect_body: Option<BodyMatch>,
+    // Tên biến -> JSON pointer trong body response, dùng lại ở các bước sau dạng {{tên}}
+    #[serde(default)]
+    pub capture: BTreeMap<String, String>,
+}
+
+fn default_step_method() -> String {
+    "GET".to_string()
+}
+
+// Điều kiện với body response health check, kiểm tra sau khi status là 2xx
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum BodyMatch {
+    Equals(String),
+    Contains(String),
+    Regex(#[serde(deserialize_with = "regex")] Regex),
+    // Giá trị tại JSON pointer (RFC 6901) phải bằng `value`
+    Json { pointer: String, value: serde_json::Value },
+}
+
+fn regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+// Body dài thì chỉ đưa phần đầu vào thông báo lỗi
+fn excerpt(body: &str) -> String {
+    const MAX: usize = 100;
+    match body.char_indices().nth(MAX) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+impl BodyMatch {
+    // Err = lý do backend bị tính DOWN
+    pub fn check(&self, body: &str) -> Result<(), String> {
+        let matched = match self {
+            BodyMatch::Equals(expected) => body.trim() == expected,
+            BodyMatch::Contains(needle) => body.contains(needle.as_str()),
+            BodyMatch::Regex(re) => re.is_match(body),
+            BodyMatch::Json { pointer, value } => {
+                let json: serde_json::Value =
+                    serde_json::from_str(body).map_err(|e| format!("body không phải JSON: {}", e))?;
+                return match json.pointer(pointer) {
+                    Some(actual) if actual == value => Ok(()),
+                    Some(actual) => Err(format!("body {} = {}, cần {}", pointer, actual, value)),
+                    None => Err(format!("body không có {}", pointer)),
+                };
+            }
+        };
+        if matched {
+            Ok(())
+        } else {
+            Err(format!("body không khớp: {:?}", excerpt(body)))
+        }
+    }
+}
+
+// Thuật toán chọn backend của pool (không khai báo thì theo [affinity] mode)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    // Sticky map + round robin
+    RoundRobin,
+    // Backend đang xử lý ít request nhất
+    LeastConn,
+    // Rendezvous hashing theo client key (hợp cho pool cache)
+    ConsistentHash,
+    Random,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PoolConfig {
+    servers: Vec<ServerConfig>,
+    #[serde(default)]
+    health: HealthConfig,
+    strategy: Option<Strategy>,
+}
+
+pub struct Pool {
+    pub name: String,
+    pub servers: Vec<ServerStatus>,
+    pub health: HealthConfig,
+    pub strategy: Option<Strategy>,
+    pub sticky_map: HashMap<String, String>,
+    pub rr_index: usize,
+    // Số request đã được đưa vào pool
+    pub requests: u64,
+    // Số request client bỏ dở (đóng kết nối trước khi nhận xong response)
+    pub client_aborts: Arc<ClientAborts>,
+}
+
+impl Pool {
+    fn new(
+        name: String,
+        servers: Vec<ServerConfig>,
+        health: HealthConfig,
+        strategy: Option<Strategy>,
+        timeouts: &UpstreamTimeouts,
+    ) -> Self {
+        let servers = servers
+            .into_iter()
+            .map(|s| ServerStatus {
+                url: s.url,
+                region: s.region.unwrap_or_else(|| "-".to_string()),
+                pool: name.clone(),
+                healthy: false,
+                disabled: false,
+                response_time: None,
+                last_check: None,
+                last_error: None,
+                uptime: 0,
+                downtime: 0,
+                availability_24h: None,
+                history: Vec::new(),
+                samples: Default::default(),
+                active: Arc::default(),
+                close: Arc::default(),
+                timeouts: s.timeouts.apply(timeouts),
+                auth: HeaderMap::new(),
+                auth_source: s.auth,
+                protocol: s.protocol,
+                tls: s.tls,
+                negotiated: None,
+            })
+            .collect();
+        Self {
+            name,
+            servers,
+            health,
+            strategy,
+            sticky_map: HashMap::new(),
+            rr_index: 0,
+            requests: 0,
+            client_aborts: Arc::default(),
+        }
+    }
+}
+
+// Trả về (danh sách pool, đã load servers.json thành công hay chưa). Thiếu file thì chạy với danh sách rỗng
+// (thêm backend sau), file sai thì báo lỗi để không âm thầm chạy không có backend nào.
+pub fn load(path: &Path, timeouts: &UpstreamTimeouts) -> Result<(Vec<Pool>, bool), String> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            warn!("⚠️ Không tìm thấy {}, dùng danh sách rỗng.", path.display());
+            return Ok((Vec::new(), false));
+        }
+        Err(e) => return Err(format!("không đọc được {}: {}", path.display(), e)),
+    };
+
+    let pools = parse(&data, timeouts).map_err(|e| format!("{} không hợp lệ: {}", path.display(), e))?;
+    Ok((pools, true))
+}
+
+// Nội dung servers.json: mảng backend (dạng cũ) hoặc object các pool
+pub fn parse(data: &str, timeouts: &UpstreamTimeouts) -> Result<Vec<Pool>, String> {
+    let pools = if data.trim_start().starts_with('[') {
+        let servers: Vec<ServerConfig> = crate::diagnostics::json(data)?;
+        vec![Pool::new(DEFAULT_POOL.to_string(), servers, HealthConfig::default(), None, timeouts)]
+    } else {
+        let pools: BTreeMap<String, PoolConfig> = crate::diagnostics::json(data)?;
+        pools
+            .into_iter()
+            .map(|(name, p)| Pool::new(name, p.servers, p.health, p.strategy, timeouts))
+            .collect()
+    };
+    for server in pools.iter().flat_map(|p| &p.servers) {
+        if let Some(tls) = &server.tls {
+            tls.validate().map_err(|e| format!("backend {}: {}", server.url, e))?;
+        }
+    }
+    Ok(pools)
+}
+
+// Tính header xác thực của các backend có "auth" (lúc khởi động, sau đó vault_refresh_task đọc lại
+// các giá trị từ Vault). Secret không đọc được thì lỗi như servers.json sai: không gọi backend thiếu credential.
+pub async fn resolve_auth(pools: &mut [Pool], vault: Option<&vault::Client>) -> Result<(), String> {
+    for server in pools.iter_mut().flat_map(|p| p.servers.iter_mut()) {
+        let Some(source) = &server.auth_source else {
+            continue;
+        };
+        server.auth = source
+            .headers(vault)
+            .await
+            .map_err(|e| format!("servers.json: auth của backend {} không hợp lệ: {}", server.url, e))?;
+    }
+    Ok(())
+}
+
+// Route đầu tiên khớp host + path prefix (+ quốc gia của client)
+pub fn route<'a>(routing: &'a RoutingConfig, host: Option<&str>, path: &str, country: Option<&str>) -> Option<&'a Route> {
+    // Bỏ port khỏi Host header ("example.com:8080", "[::1]:8080")
+    let host = host.map(|h| match h.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => h.split(':').next().unwrap_or(h),
+    });
+
+    routing.routes.iter().find(|r| {
+        r.host.as_deref().is_none_or(|h| host.is_some_and(|host| host.eq_ignore_ascii_case(h)))
+            && r.path_prefix.as_deref().is_none_or(|p| path.starts_with(p))
+            && r.countries.as_ref().is_none_or(|cs| country.is_some_and(|c| cs.iter().any(|x| x.eq_ignore_ascii_case(c))))
+    })
+}
+
+// Chọn pool cho request: pool của route khớp, không có thì pool mặc định
+pub fn select(pools: &[Pool], routing: &RoutingConfig, routed: Option<&Route>) -> Option<usize> {
+    let name = match routed {
+        Some(route) => route.pool.as_str(),
+        None => match &routing.default_pool {
+            Some(name) => name.as_str(),
+            None if pools.iter().any(|p| p.name == DEFAULT_POOL) => DEFAULT_POOL,
+            // Không khai báo pool mặc định: dùng pool đầu tiên
+            None => return (!pools.is_empty()).then_some(0),
+        },
+    };
+    pools.iter().position(|p| p.name == name)
+}
+
+#[derive(Default)]
+pub struct ClientAborts {
+    // Khi đang chờ response header từ backend
+    pub waiting: AtomicU64,
+    // Khi đang stream response body
+    pub streaming: AtomicU64,
+}
+
+// Bị drop khi chưa `complete()` nghĩa là client đã đóng kết nối: hyper huỷ future của handler
+// (hoặc response body), kéo theo request tới backend bị huỷ ngay thay vì để backend xử lý tiếp
+// một response không ai nhận
+pub struct AbortGuard {
+    aborts: Arc<ClientAborts>,
+    streaming: bool,
+    target: String,
+    armed: bool,
+}
+
+impl AbortGuard {
+    pub fn waiting(aborts: Arc<ClientAborts>, target: &str) -> Self {
+        Self { aborts, streaming: false, target: target.to_string(), armed: true }
+    }
+
+    pub fn streaming(aborts: Arc<ClientAborts>, target: &str) -> Self {
+        Self { aborts, streaming: true, target: target.to_string(), armed: true }
+    }
+
+    // Request kết thúc bình thường (hoặc do phía backend)
+    pub fn complete(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for AbortGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let (counter, phase) = if self.streaming {
+            (&self.aborts.streaming, "đang nhận response body")
+        } else {
+            (&self.aborts.waiting, "đang chờ backend trả lời")
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        info!("🚪 Client đóng kết nối khi {}: huỷ request tới {}", phase, self.target);
+    }
+}
+
+// Đếm request đang xử lý trên một backend (cho least_conn), tự giảm khi drop
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    pub fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,94 @@
+// PROXY protocol (v1 dạng text, v2 dạng binary) do L4 load balancer phía trước (HAProxy, AWS NLB...)
+// gửi ở đầu mỗi kết nối: lấy địa chỉ client thật thay cho địa chỉ của L4 balancer.
+// Bật theo listener ("proxy_protocol = true"), khi đó kết nối không có header hợp lệ bị đóng.
+// https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+// Độ dài tối đa của header v1, tính cả "\r\n"
+const V1_MAX_LEN: usize = 107;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// Đọc header ở đầu kết nối (đọc vừa đủ, không lấn sang dữ liệu HTTP phía sau).
+// None: header hợp lệ nhưng không mang địa chỉ client (v1 UNKNOWN, v2 LOCAL - vd. health check của L4 balancer)
+pub async fn read_header<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<SocketAddr>> {
+    let mut start = [0u8; 8];
+    stream.read_exact(&mut start).await?;
+    if start.starts_with(b"PROXY ") {
+        read_v1(stream, &start).await
+    } else if start == V2_SIGNATURE[..8] {
+        read_v2(stream).await
+    } else {
+        Err(invalid("thiếu PROXY protocol header"))
+    }
+}
+
+async fn read_v1<S: AsyncRead + Unpin>(stream: &mut S, start: &[u8]) -> io::Result<Option<SocketAddr>> {
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            return Err(invalid("PROXY v1 header quá dài"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2]).map_err(|_| invalid("PROXY v1 header không phải ASCII"))?;
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", family @ ("TCP4" | "TCP6"), src, _dst, src_port, _dst_port] => {
+            let ip: IpAddr = src.parse().map_err(|_| invalid(format!("địa chỉ nguồn không hợp lệ: {}", src)))?;
+            if ip.is_ipv4() != (*family == "TCP4") {
+                return Err(invalid(format!("địa chỉ {} không khớp {}", src, family)));
+            }
+            let port: u16 = src_port.parse().map_err(|_| invalid(format!("port nguồn không hợp lệ: {}", src_port)))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid(format!("PROXY v1 header không hợp lệ: {:?}", line))),
+    }
+}
+
+async fn read_v2<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<SocketAddr>> {
+    let mut rest = [0u8; 8];
+    stream.read_exact(&mut rest).await?;
+    if rest[..4] != V2_SIGNATURE[8..] {
+        return Err(invalid("PROXY v2 signature không hợp lệ"));
+    }
+    let (version_command, family) = (rest[4], rest[5]);
+    let len = u16::from_be_bytes([rest[6], rest[7]]) as usize;
+    // Đọc hết phần địa chỉ + TLV dù có dùng hay không
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).await?;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid(format!("PROXY protocol version {} không hỗ trợ", version_command >> 4)));
+    }
+    match version_command & 0x0F {
+        // LOCAL: kết nối do chính L4 balancer mở
+        0x0 => return Ok(None),
+        0x1 => {}
+        command => return Err(invalid(format!("PROXY v2 command {} không hỗ trợ", command))),
+    }
+    // Byte cao: họ địa chỉ (1 = IPv4, 2 = IPv6), byte thấp: giao thức (1 = TCP, 2 = UDP)
+    match family >> 4 {
+        0x1 if body.len() >= 12 => {
+            let ip = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let port = u16::from_be_bytes([body[8], body[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        0x2 if body.len() >= 36 => {
+            let octets: [u8; 16] = body[..16].try_into().unwrap();
+            let port = u16::from_be_bytes([body[32], body[33]]);
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(octets).into(), port)))
+        }
+        // AF_UNSPEC / AF_UNIX: không có địa chỉ IP để dùng
+        0x0 | 0x3 => Ok(None),
+        _ => Err(invalid("PROXY v2 header thiếu địa chỉ")),
+    }
+}
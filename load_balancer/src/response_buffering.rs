@@ -0,0 +1,105 @@
+// Gom response nhỏ ([response_buffering] trong config.toml): response kết thúc trong threshold_kb
+// được gửi một lần kèm Content-Length (thay vì chunked), response lớn hơn / SSE / trả chậm
+// quá max_wait_ms thì stream như bình thường. Đếm số response theo từng cách để theo dõi qua /metrics.
+use crate::config::ResponseBufferingConfig;
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, Method, StatusCode},
+    BoxError,
+};
+use futures::{Stream, StreamExt};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+pub enum Collected<S> {
+    // Body kết thúc trong ngưỡng
+    Complete(Bytes),
+    // Vượt ngưỡng, quá max_wait_ms hoặc lỗi: phần đã đọc + phần còn lại, stream tiếp
+    Partial(Vec<Result<Bytes, BoxError>>, S),
+}
+
+pub struct Buffering {
+    threshold: usize,
+    max_wait: Duration,
+    buffered: AtomicU64,
+    streamed: AtomicU64,
+}
+
+impl Buffering {
+    pub fn new(config: &ResponseBufferingConfig) -> Self {
+        Self {
+            threshold: config.threshold_kb as usize * 1024,
+            max_wait: Duration::from_millis(config.max_wait_ms),
+            buffered: AtomicU64::new(0),
+            streamed: AtomicU64::new(0),
+        }
+    }
+
+    // Biết trước là không gom được: Content-Length vượt ngưỡng hoặc Server-Sent Events.
+    // Response không có body (HEAD, 204, 304) giữ nguyên header của backend
+    pub fn applies(&self, method: &Method, status: StatusCode, headers: &HeaderMap) -> bool {
+        if method == Method::HEAD || status == StatusCode::NO_CONTENT || status == StatusCode::NOT_MODIFIED {
+            return false;
+        }
+        let too_large = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok())
+            .is_some_and(|len| len > self.threshold);
+        let event_stream = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        let applies = !too_large && !event_stream;
+        if !applies {
+            self.streamed.fetch_add(1, Ordering::Relaxed);
+        }
+        applies
+    }
+
+    pub async fn collect<S>(&self, mut body: S) -> Collected<S>
+    where
+        S: Stream<Item = Result<Bytes, BoxError>> + Unpin,
+    {
+        let mut chunks: Vec<Result<Bytes, BoxError>> = Vec::new();
+        let mut size = 0;
+        let deadline = tokio::time::sleep(self.max_wait);
+        tokio::pin!(deadline);
+        loop {
+            let chunk = tokio::select! {
+                chunk = body.next() => chunk,
+                _ = &mut deadline => break,
+            };
+            match chunk {
+                Some(Ok(bytes)) => {
+                    size += bytes.len();
+                    chunks.push(Ok(bytes));
+                    if size > self.threshold {
+                        break;
+                    }
+                }
+                Some(Err(e)) => {
+                    chunks.push(Err(e));
+                    break;
+                }
+                None => {
+                    self.buffered.fetch_add(1, Ordering::Relaxed);
+                    let mut buffered = Vec::with_capacity(size);
+                    for bytes in chunks.into_iter().flatten() {
+                        buffered.extend_from_slice(&bytes);
+                    }
+                    return Collected::Complete(Bytes::from(buffered));
+                }
+            }
+        }
+        self.streamed.fetch_add(1, Ordering::Relaxed);
+        Collected::Partial(chunks, body)
+    }
+
+    // (buffered, streamed)
+    pub fn counts(&self) -> (u64, u64) {
+        (self.buffered.load(Ordering::Relaxed), self.streamed.load(Ordering::Relaxed))
+    }
+}
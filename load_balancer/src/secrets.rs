@@ -0,0 +1,42 @@
+// Giá trị bí mật trong config.toml / servers.json (token, mật khẩu, key) có thể tham chiếu nguồn ngoài
+// thay vì ghi thẳng:
+//   "env:OIDC_CLIENT_SECRET"                 biến môi trường
+//   "file:/run/secrets/influx_token"         nội dung file (Docker/Kubernetes secret), bỏ xuống dòng cuối
+//   "exec:/usr/local/bin/get-secret lb/oidc" stdout của lệnh (cầu nối tới secret manager: AWS, GCP, 1Password...)
+// Riêng auth của backend trong servers.json còn nhận "vault:<path>#<field>" (xem vault.rs).
+// Giá trị không có tiền tố nào ở trên được dùng nguyên văn. Tham chiếu được giải khi nạp config,
+// lỗi (biến chưa đặt, file không đọc được, lệnh thất bại) làm việc nạp config thất bại.
+use std::process::Command;
+
+pub fn resolve(value: &str) -> Result<String, String> {
+    if value.starts_with(crate::vault::PREFIX) {
+        return Err("tham chiếu vault: chỉ dùng được trong auth của servers.json".to_string());
+    }
+    if let Some(name) = value.strip_prefix("env:") {
+        return std::env::var(name).map_err(|_| format!("biến môi trường {} chưa đặt", name));
+    }
+    if let Some(path) = value.strip_prefix("file:") {
+        return std::fs::read_to_string(path)
+            .map(|s| s.trim_end_matches(['\r', '\n']).to_string())
+            .map_err(|e| format!("không đọc được {}: {}", path, e));
+    }
+    if let Some(command) = value.strip_prefix("exec:") {
+        // Tách tham số theo khoảng trắng (không hỗ trợ quote)
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or("exec: thiếu lệnh")?;
+        let output = Command::new(program).args(parts).output().map_err(|e| format!("exec {}: {}", program, e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("exec {}: {} {}", program, output.status, stderr.trim()));
+        }
+        let stdout = String::from_utf8(output.stdout).map_err(|_| format!("exec {}: output không phải UTF-8", program))?;
+        return Ok(stdout.trim_end_matches(['\r', '\n']).to_string());
+    }
+    Ok(value.to_string())
+}
+
+// Giải tại chỗ, lỗi kèm tên field để dễ tìm trong config
+pub fn resolve_in_place(field: &str, value: &mut String) -> Result<(), String> {
+    *value = resolve(value).map_err(|e| format!("{}: {}", field, e))?;
+    Ok(())
+}
@@ -0,0 +1,42 @@
+// Áp dụng chính sách header bảo mật ([security_headers]) lên response của backend
+use crate::config::SecurityHeadersConfig;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+
+// Bộ header chuẩn khi bật inject_defaults
+const DEFAULT_HEADERS: [(&str, &str); 4] = [
+    ("x-content-type-options", "nosniff"),
+    ("referrer-policy", "strict-origin-when-cross-origin"),
+    ("x-permitted-cross-domain-policies", "none"),
+    ("cross-origin-opener-policy", "same-origin"),
+];
+
+// HSTS chỉ có ý nghĩa khi client kết nối qua HTTPS
+const HSTS: (&str, &str) = ("strict-transport-security", "max-age=31536000; includeSubDomains");
+
+pub fn apply(headers: &mut HeaderMap, config: &SecurityHeadersConfig, tls: bool) {
+    for name in &config.remove {
+        headers.remove(name.as_str());
+    }
+
+    // Header tự định nghĩa được ưu tiên hơn bộ chuẩn
+    for (name, value) in &config.add {
+        insert_if_missing(headers, name, value);
+    }
+
+    if config.inject_defaults {
+        for (name, value) in DEFAULT_HEADERS {
+            insert_if_missing(headers, name, value);
+        }
+        if tls {
+            insert_if_missing(headers, HSTS.0, HSTS.1);
+        }
+    }
+}
+
+fn insert_if_missing(headers: &mut HeaderMap, name: &str, value: &str) {
+    // Tên/giá trị đã được kiểm tra khi load config
+    let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) else {
+        return;
+    };
+    headers.entry(name).or_insert(value);
+}
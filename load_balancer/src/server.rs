@@ -0,0 +1,436 @@
+// Vòng accept kết nối tự viết (thay cho axum::serve) để xử lý TLS
+// và gắn thông tin theo từng kết nối (địa chỉ client, client cert) vào request.
+use crate::{
+    client_limits::Limiter,
+    h2c,
+    config::{CanonicalizationConfig, ConnectionsConfig, Http2Config, PathNormalizationConfig, RequestLimitsConfig, SlowClientsConfig},
+    normalize,
+    proxy_protocol,
+    slow_clients::StallGuard,
+    tls::{self, ClientCertSubject},
+};
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, HeaderValue, Request, StatusCode},
+    response::{IntoResponse, Response},
+    Router,
+};
+use http_body_util::BodyExt;
+use hyper::{
+    body::Incoming,
+    server::conn::http1,
+    service::{service_fn, Service},
+};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo, TokioTimer},
+    server::conn::auto::Builder,
+    service::TowerToHyperService,
+};
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+use std::{
+    convert::Infallible,
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{net::TcpListener, sync::Notify, time::Instant};
+use tokio_rustls::TlsAcceptor;
+use tower_http::add_extension::AddExtension;
+use tracing::{debug, warn};
+
+// only_v6: listener IPv6 chỉ nhận IPv6, dùng khi có listener IPv4 cùng port
+// (tránh "address in use" với "0.0.0.0" + "[::]" cùng port trên Linux)
+pub fn bind(addr: SocketAddr, only_v6: bool, backlog: i32) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(only_v6)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(backlog)?;
+    TcpListener::from_std(socket.into())
+}
+
+// Thiết lập chung cho mọi kết nối của các listener
+#[derive(Clone)]
+pub struct Settings {
+    pub slow: SlowClientsConfig,
+    pub connections: ConnectionsConfig,
+    pub http2: Http2Config,
+    pub request_limits: RequestLimitsConfig,
+    pub path_normalization: PathNormalizationConfig,
+    pub canonicalization: Option<CanonicalizationConfig>,
+}
+
+// Giao thức HTTP của kết nối
+#[derive(Clone, Copy)]
+enum Negotiated {
+    // TLS không chọn "h2" qua ALPN, hoặc cleartext không bật h2c
+    Http1,
+    // TLS chọn "h2" qua ALPN, hoặc đã upgrade h2c
+    Http2,
+    // Cleartext có bật h2c: HTTP/2 prior knowledge (nhận biết qua preface) hoặc "Upgrade: h2c"
+    H2c,
+}
+
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    tls: Option<TlsAcceptor>,
+    proxy_protocol: bool,
+    h2c: bool,
+    settings: Settings,
+    limits: Option<Arc<Limiter>>,
+) {
+    let connections = &settings.connections;
+    let header_timeout = Duration::from_secs(settings.slow.header_read_timeout_secs);
+    let keepalive = (connections.tcp_keepalive_secs > 0).then(|| {
+        let mut keepalive = TcpKeepalive::new().with_time(Duration::from_secs(connections.tcp_keepalive_secs));
+        if let Some(interval) = connections.tcp_keepalive_interval_secs {
+            keepalive = keepalive.with_interval(Duration::from_secs(interval));
+        }
+        if let Some(retries) = connections.tcp_keepalive_retries {
+            keepalive = keepalive.with_retries(retries);
+        }
+        keepalive
+    });
+    let settings = Arc::new(settings);
+    // IP đã vượt số kết nối cho phép: vẫn trả lời 429 (thay vì cắt ngang) rồi đóng kết nối
+    let too_many = Router::new().fallback(|| async {
+        (StatusCode::TOO_MANY_REQUESTS, [(header::CONNECTION, "close")], "Quá nhiều kết nối từ IP này")
+    });
+
+    loop {
+        let (mut stream, remote_addr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                // Lỗi tạm thời (vd. hết file descriptor): chờ chút rồi accept tiếp
+                warn!("⚠️ Lỗi accept kết nối: {}", e);
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let _ = stream.set_nodelay(settings.connections.tcp_nodelay);
+        if let Some(keepalive) = &keepalive {
+            if let Err(e) = SockRef::from(&stream).set_tcp_keepalive(keepalive) {
+                debug!("Không bật được TCP keepalive cho {}: {}", remote_addr, e);
+            }
+        }
+        let settings = settings.clone();
+        let limits = limits.clone();
+        let too_many = too_many.clone();
+        let app = app.clone();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            // Địa chỉ client thật trong PROXY protocol header (đọc header cũng tính vào thời gian chờ header)
+            let remote_addr = if proxy_protocol {
+                match tokio::time::timeout(header_timeout, proxy_protocol::read_header(&mut stream)).await {
+                    Ok(Ok(source)) => source.unwrap_or(remote_addr),
+                    Ok(Err(e)) => {
+                        debug!("PROXY protocol header không hợp lệ từ {}: {}", remote_addr, e);
+                        return;
+                    }
+                    Err(_) => {
+                        debug!("PROXY protocol header quá lâu từ {}", remote_addr);
+                        return;
+                    }
+                }
+            } else {
+                remote_addr
+            };
+            // Client IPv4 qua socket dual-stack có dạng ::ffff:a.b.c.d -> đưa về IPv4
+            let remote_addr = SocketAddr::new(remote_addr.ip().to_canonical(), remote_addr.port());
+
+            // Giữ chỗ tới khi kết nối đóng
+            let permit = limits.as_ref().map(|l| l.try_connection(remote_addr.ip()));
+            let app = match &permit {
+                Some(None) => {
+                    debug!("Từ chối kết nối từ {}: vượt giới hạn mỗi IP", remote_addr);
+                    too_many
+                }
+                _ => app,
+            };
+            let _permit = permit;
+            let stream = StallGuard::new(stream, &settings.slow);
+            match tls {
+                Some(acceptor) => {
+                    // Handshake cũng tính vào thời gian chờ header
+                    let stream = match tokio::time::timeout(header_timeout, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => stream,
+                        Ok(Err(e)) => {
+                            debug!("TLS handshake thất bại từ {}: {}", remote_addr, e);
+                            return;
+                        }
+                        Err(_) => {
+                            debug!("TLS handshake quá lâu từ {}", remote_addr);
+                            return;
+                        }
+                    };
+                    let session = stream.get_ref().1;
+                    let subject = tls::client_cert_subject(session.peer_certificates());
+                    let negotiated = match session.alpn_protocol() {
+                        Some(b"h2") => Negotiated::Http2,
+                        _ => Negotiated::Http1,
+                    };
+                    serve_connection(TokioIo::new(stream), app, remote_addr, subject, negotiated, true, &settings).await;
+                }
+                None if h2c => {
+                    let upgrade = serve_connection(TokioIo::new(stream), app.clone(), remote_addr, None, Negotiated::H2c, false, &settings).await;
+                    if let Some(upgrade) = upgrade {
+                        match tokio::time::timeout(header_timeout, upgrade.accept()).await {
+                            Ok(Ok(io)) => {
+                                serve_connection(TokioIo::new(io), app, remote_addr, None, Negotiated::Http2, false, &settings).await;
+                            }
+                            Ok(Err(e)) => debug!("Upgrade h2c thất bại từ {}: {}", remote_addr, e),
+                            Err(_) => debug!("Upgrade h2c quá lâu từ {}", remote_addr),
+                        }
+                    }
+                }
+                None => {
+                    serve_connection(TokioIo::new(stream), app, remote_addr, None, Negotiated::Http1, false, &settings).await;
+                }
+            }
+        });
+    }
+}
+
+// Theo dõi request trên một kết nối để đóng kết nối rảnh quá keep_alive_timeout_secs
+// hoặc đã phục vụ đủ max_requests_per_connection
+struct Usage {
+    state: Mutex<UsageState>,
+    changed: Notify,
+}
+
+struct UsageState {
+    requests: u64,
+    // Request chưa trả xong response (tính tới khi body response gửi hết)
+    in_flight: usize,
+    idle_since: Instant,
+}
+
+// Giảm in_flight khi body response bị drop (gửi xong hoặc client bỏ)
+struct InFlight(Arc<Usage>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.in_flight -= 1;
+        state.idle_since = Instant::now();
+        drop(state);
+        self.0.changed.notify_one();
+    }
+}
+
+impl Usage {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(UsageState { requests: 0, in_flight: 0, idle_since: Instant::now() }),
+            changed: Notify::new(),
+        })
+    }
+
+    fn start(self: &Arc<Self>) -> InFlight {
+        let mut state = self.state.lock().unwrap();
+        state.requests += 1;
+        state.in_flight += 1;
+        drop(state);
+        self.changed.notify_one();
+        InFlight(self.clone())
+    }
+
+    // Xong khi nên đóng kết nối
+    async fn exhausted(&self, idle_timeout: Option<Duration>, max_requests: Option<u64>) {
+        loop {
+            let changed = self.changed.notified();
+            let idle_deadline = {
+                let state = self.state.lock().unwrap();
+                if max_requests.is_some_and(|max| state.requests >= max) {
+                    return;
+                }
+                idle_timeout.filter(|_| state.in_flight == 0).map(|timeout| state.idle_since + timeout)
+            };
+            match idle_deadline {
+                Some(deadline) => tokio::select! {
+                    _ = tokio::time::sleep_until(deadline) => return,
+                    _ = changed => {}
+                },
+                None => changed.await,
+            }
+        }
+    }
+}
+
+// Trả về request "Upgrade: h2c" đã được chấp nhận (đã gửi 101): kết nối tiếp tục bằng HTTP/2
+async fn serve_connection<I>(
+    io: TokioIo<I>,
+    app: Router,
+    remote_addr: SocketAddr,
+    subject: Option<ClientCertSubject>,
+    negotiated: Negotiated,
+    // Kết nối TLS (port mặc định 443 thay vì 80)
+    secure: bool,
+    settings: &Settings,
+) -> Option<h2c::Upgrade>
+where
+    I: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    // Giống axum::serve: ConnectInfo cho handler, (tuỳ chọn) subject của client cert
+    let service = AddExtension::new(AddExtension::new(app, ConnectInfo(remote_addr)), subject);
+    let service = TowerToHyperService::new(service);
+    let usage = Usage::new();
+    let tracked = usage.clone();
+    let upgrade = Arc::new(Mutex::new(None));
+    let accepted = upgrade.clone();
+    let limits = settings.request_limits.clone();
+    let paths = settings.path_normalization.clone();
+    let canonicalization = settings.canonicalization.clone();
+    let service = service_fn(move |mut req: Request<Incoming>| {
+        let mut rejected = exceeds_limits(&req, &limits);
+        if let Some(status) = rejected {
+            debug!("Từ chối request từ {} ({}): vượt [request_limits]", remote_addr, status);
+        }
+        // Chuẩn hoá path trước khi routing (kể cả route admin / OIDC callback của axum)
+        if rejected.is_none() {
+            match normalize::uri(req.uri(), &paths) {
+                Ok(Some(uri)) => *req.uri_mut() = uri,
+                Ok(None) => {}
+                Err(normalize::Rejected) => {
+                    debug!("Từ chối request từ {}: path không hợp lệ {}", remote_addr, req.uri().path());
+                    rejected = Some(StatusCode::BAD_REQUEST);
+                }
+            }
+        }
+        // HTTP/2 gửi host trong :authority thay cho header Host: bổ sung để routing / backend thấy như HTTP/1.1
+        if !req.headers().contains_key(header::HOST) {
+            if let Some(host) = req.uri().authority().and_then(|a| HeaderValue::from_str(a.as_str()).ok()) {
+                req.headers_mut().insert(header::HOST, host);
+            }
+        }
+        if let (None, Some(canonicalization)) = (rejected, &canonicalization) {
+            if normalize::canonicalize(&mut req, canonicalization, secure).is_err() {
+                debug!("Từ chối request từ {}: không chuẩn hoá được URL {}", remote_addr, req.uri());
+                rejected = Some(StatusCode::BAD_REQUEST);
+            }
+        }
+        let in_flight = tracked.start();
+        let pending = match negotiated {
+            Negotiated::H2c if rejected.is_none() => h2c::take(&mut req),
+            _ => None,
+        };
+        let response = match (rejected, pending) {
+            (Some(status), _) => Err(status),
+            (None, Some(pending)) => {
+                *accepted.lock().unwrap() = Some(pending);
+                Ok(None)
+            }
+            (None, None) => Ok(Some(service.call(req))),
+        };
+        async move {
+            let response: Response = match response {
+                Ok(Some(response)) => response.await?,
+                Ok(None) => h2c::switching_protocols(),
+                Err(status) => (status, status.canonical_reason().unwrap_or_default()).into_response(),
+            };
+            Ok::<_, Infallible>(response.map(|body| {
+                Body::new(body.map_frame(move |frame| {
+                    let _ = &in_flight;
+                    frame
+                }))
+            }))
+        }
+    });
+
+    let connections = &settings.connections;
+    let http2 = &settings.http2;
+    let header_timeout = Duration::from_secs(settings.slow.header_read_timeout_secs);
+    let idle_timeout = (connections.keep_alive_timeout_secs > 0).then(|| Duration::from_secs(connections.keep_alive_timeout_secs));
+    let max_requests = (connections.max_requests_per_connection > 0).then_some(connections.max_requests_per_connection);
+    let limits = &settings.request_limits;
+    // Bộ đệm đọc của hyper đủ cho dòng request + header trong giới hạn: request vượt xa hơn bị hyper từ chối
+    // (431) ngay khi đọc, không phải cấp phát thêm; giới hạn chính xác được kiểm tra trong exceeds_limits
+    let max_buf_size = (limits.max_header_bytes + limits.max_uri_length + 1024).max(8192);
+    // HTTP/2 tính mỗi header thêm 32 byte (RFC 7541 mục 4.1), kể cả các pseudo-header
+    let max_header_list_size = (limits.max_header_bytes + limits.max_uri_length + 32 * (limits.max_headers + 4)) as u32;
+    let result = match negotiated {
+        // http1_only() của auto builder không có tác dụng khi cần upgrade (WebSocket): dùng thẳng HTTP/1 builder
+        Negotiated::Http1 => {
+            let mut builder = http1::Builder::new();
+            // Slowloris: client gửi header nhỏ giọt quá header_timeout thì đóng kết nối
+            builder
+                .timer(TokioTimer::new())
+                .header_read_timeout(header_timeout)
+                .keep_alive(connections.keep_alive)
+                .max_headers(limits.max_headers)
+                .max_buf_size(max_buf_size);
+            let conn = builder.serve_connection(io, service).with_upgrades();
+            drive(conn, |conn| conn.graceful_shutdown(), &usage, idle_timeout, max_requests).await.map_err(Into::into)
+        }
+        Negotiated::Http2 | Negotiated::H2c => {
+            let mut builder = Builder::new(TokioExecutor::new());
+            builder
+                .http1()
+                .timer(TokioTimer::new())
+                .header_read_timeout(header_timeout)
+                .keep_alive(connections.keep_alive)
+                .max_headers(limits.max_headers)
+                .max_buf_size(max_buf_size);
+            builder
+                .http2()
+                .timer(TokioTimer::new())
+                .max_header_list_size(max_header_list_size)
+                .max_concurrent_streams(http2.max_concurrent_streams)
+                .initial_stream_window_size(http2.initial_stream_window_kb * 1024)
+                .initial_connection_window_size(http2.initial_connection_window_kb * 1024)
+                .keep_alive_interval((http2.keep_alive_interval_secs > 0).then(|| Duration::from_secs(http2.keep_alive_interval_secs)))
+                .keep_alive_timeout(Duration::from_secs(http2.keep_alive_timeout_secs));
+            let conn = builder.serve_connection_with_upgrades(io, service);
+            drive(conn, |conn| conn.graceful_shutdown(), &usage, idle_timeout, max_requests).await
+        }
+    };
+    if let Err(e) = result {
+        debug!("Kết nối từ {} kết thúc với lỗi: {}", remote_addr, e);
+    }
+    let accepted = upgrade.lock().unwrap().take();
+    accepted
+}
+
+// URI quá dài -> 414, quá nhiều header hoặc tổng kích thước header quá lớn -> 431
+fn exceeds_limits<B>(req: &Request<B>, limits: &RequestLimitsConfig) -> Option<StatusCode> {
+    let uri_length = req.uri().path_and_query().map_or(0, |p| p.as_str().len());
+    if uri_length > limits.max_uri_length {
+        return Some(StatusCode::URI_TOO_LONG);
+    }
+    let headers = req.headers();
+    let header_bytes: usize = headers.iter().map(|(name, value)| name.as_str().len() + value.len()).sum();
+    if headers.len() > limits.max_headers || header_bytes > limits.max_header_bytes {
+        return Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+    }
+    None
+}
+
+// Chạy kết nối tới khi xong; rảnh quá lâu / đủ số request thì đóng nhẹ nhàng:
+// HTTP/1.1 gửi xong response đang dở (kèm "Connection: close") rồi đóng, HTTP/2 gửi GOAWAY
+async fn drive<C, E>(
+    conn: C,
+    graceful_shutdown: impl FnOnce(Pin<&mut C>),
+    usage: &Usage,
+    idle_timeout: Option<Duration>,
+    max_requests: Option<u64>,
+) -> Result<(), E>
+where
+    C: Future<Output = Result<(), E>>,
+{
+    tokio::pin!(conn);
+    tokio::select! {
+        result = conn.as_mut() => result,
+        _ = usage.exhausted(idle_timeout, max_requests) => {
+            graceful_shutdown(conn.as_mut());
+            conn.await
+        }
+    }
+}
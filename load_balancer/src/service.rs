@@ -0,0 +1,136 @@
+// Chạy load balancer dưới dạng Windows service (SCM).
+// Đăng ký: `load_balancer --service install`, gỡ: `load_balancer --service uninstall`.
+use std::{ffi::OsString, time::Duration};
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+
+pub const SERVICE_NAME: &str = "RustLoadBalancer";
+const SERVICE_DISPLAY_NAME: &str = "Rust Load Balancer";
+
+define_windows_service!(ffi_service_main, service_main);
+
+// Gọi từ main khi có `--service`: trao quyền điều khiển cho SCM (block tới khi service dừng)
+pub fn run() -> windows_service::Result<()> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+}
+
+pub fn install() -> windows_service::Result<()> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+
+    let executable_path = std::env::current_exe().map_err(windows_service::Error::Winapi)?;
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(SERVICE_DISPLAY_NAME),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path,
+        launch_arguments: vec![OsString::from("--service")],
+        dependencies: vec![],
+        account_name: None, // LocalSystem
+        account_password: None,
+    };
+
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)?;
+    service.set_description("HTTP load balancer viết bằng Rust/Axum")?;
+    Ok(())
+}
+
+pub fn uninstall() -> windows_service::Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(SERVICE_NAME, ServiceAccess::DELETE)?;
+    service.delete()
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        tracing::error!("❌ Windows service lỗi: {}", e);
+    }
+}
+
+fn run_service() -> windows_service::Result<()> {
+    // SCM khởi động service với thư mục làm việc là System32,
+    // chuyển về thư mục chứa file exe để đọc được servers.json
+    if let Some(dir) = std::env::current_exe().ok().and_then(|p| p.parent().map(|d| d.to_path_buf())) {
+        let _ = std::env::set_current_dir(dir);
+    }
+
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let mut stop_tx = Some(stop_tx);
+
+    let status_handle = service_control_handler::register(SERVICE_NAME, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            if let Some(tx) = stop_tx.take() {
+                let _ = tx.send(());
+            }
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+
+    // Đăng ký với SCM trước rồi mới đọc config: lỗi config được báo thành Stopped kèm mã lỗi riêng,
+    // thay vì SCM chờ tới timeout (lỗi 1053) và mất nguyên nhân
+    let config = match crate::config::load(std::path::Path::new("config.toml")) {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("❌ {}", e);
+            return set_stopped(&status_handle, ServiceExitCode::ServiceSpecific(EXIT_CONFIG));
+        }
+    };
+
+    set_status(&status_handle, ServiceState::Running, ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN)?;
+    tracing::info!("🚀 Windows service đã khởi động");
+
+    let runtime = crate::build_runtime(&config.runtime).map_err(windows_service::Error::Winapi)?;
+    runtime.block_on(crate::run(config, crate::cli::Console::Off, async {
+        let _ = stop_rx.await;
+    }));
+
+    tracing::info!("🛑 Windows service đang dừng");
+    set_status(&status_handle, ServiceState::StopPending, ServiceControlAccept::empty())?;
+    runtime.shutdown_timeout(Duration::from_secs(5));
+    set_status(&status_handle, ServiceState::Stopped, ServiceControlAccept::empty())
+}
+
+// Mã lỗi riêng của service (ServiceSpecific), hiện trong "sc query" và Event Log
+const EXIT_CONFIG: u32 = 1;
+
+fn set_stopped(handle: &ServiceStatusHandle, exit_code: ServiceExitCode) -> windows_service::Result<()> {
+    handle.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: ServiceState::Stopped,
+        controls_accepted: ServiceControlAccept::empty(),
+        exit_code,
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    })
+}
+
+fn set_status(
+    handle: &ServiceStatusHandle,
+    state: ServiceState,
+    controls_accepted: ServiceControlAccept,
+) -> windows_service::Result<()> {
+    handle.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::from_secs(10),
+        process_id: None,
+    })
+}
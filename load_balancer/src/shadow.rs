@@ -0,0 +1,206 @@
+// Shadow traffic: sao chép một phần request của route tới service khác (vd. bản search mới)
+// để so sánh / thử tải mà không ảnh hưởng client. Response của bản sao bị bỏ qua.
+//
+// Body được "tee" qua channel có giới hạn: nếu service bản sao đọc chậm thì bản sao bị huỷ giữa chừng
+// (service bản sao thấy kết nối bị ngắt, không nhận body cụt), request thật không bao giờ phải chờ.
+use crate::{config::ShadowConfig, hop_by_hop};
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, Method},
+};
+use futures::stream::StreamExt;
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+pub const SHADOW_HEADER: &str = "x-lb-shadow";
+
+// Số chunk body tối đa chờ gửi cho bản sao
+const BODY_BUFFER_CHUNKS: usize = 16;
+
+#[derive(Default)]
+struct Counters {
+    sent: AtomicU64,
+    failed: AtomicU64,
+    // Bỏ bản sao vì service bản sao đọc body quá chậm
+    dropped: AtomicU64,
+}
+
+struct Rule {
+    config: ShadowConfig,
+    counters: Counters,
+}
+
+#[derive(Serialize)]
+pub struct RuleStats {
+    pub path_prefix: String,
+    pub target: String,
+    pub sent: u64,
+    pub failed: u64,
+    pub dropped: u64,
+}
+
+pub struct Mirror {
+    rules: Vec<Arc<Rule>>,
+    client: reqwest::Client,
+}
+
+impl Mirror {
+    pub fn new(configs: &[ShadowConfig]) -> Self {
+        Self {
+            rules: configs
+                .iter()
+                .map(|c| Arc::new(Rule { config: c.clone(), counters: Counters::default() }))
+                .collect(),
+            client: reqwest::Client::builder()
+                .danger_accept_invalid_certs(true)
+                .build()
+                .unwrap(),
+        }
+    }
+
+    // Nếu request được chọn để sao chép: gửi bản sao ở task riêng và trả về body đã được tee,
+    // ngược lại trả về body nguyên vẹn
+    pub fn mirror(&self, method: &Method, path_and_query: &str, headers: &HeaderMap, body: Body) -> Body {
+        let path = path_and_query.split('?').next().unwrap_or("");
+        let Some(rule) = self.rules.iter().find(|r| path.starts_with(r.config.path_prefix.as_str())) else {
+            return body;
+        };
+        if rand::random::<f64>() * 100.0 >= rule.config.percent {
+            return body;
+        }
+
+        let url = format!("{}{}", rule.config.target.trim_end_matches('/'), path_and_query);
+        let mut shadow_headers = headers.clone();
+        shadow_headers.remove(header::HOST);
+        hop_by_hop::strip_request(&mut shadow_headers);
+        shadow_headers.insert(SHADOW_HEADER, "1".parse().unwrap());
+
+        let has_body = headers.contains_key(header::TRANSFER_ENCODING)
+            || headers.get(header::CONTENT_LENGTH).is_some_and(|v| v != "0");
+
+        let mut request = self
+            .client
+            .request(method.clone(), &url)
+            .headers(shadow_headers)
+            .timeout(Duration::from_millis(rule.config.timeout_ms));
+
+        let mut tx = None;
+        if has_body {
+            // Thêm một chỗ dành cho lỗi báo ngắt bản sao
+            let (sender, rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(BODY_BUFFER_CHUNKS + 1);
+            request = request.body(reqwest::Body::wrap_stream(tokio_stream::wrappers::ReceiverStream::new(rx)));
+            tx = Some(sender);
+        }
+
+        let task_rule = rule.clone();
+        let task = tokio::spawn(async move {
+            match request.send().await {
+                Ok(res) => {
+                    task_rule.counters.sent.fetch_add(1, Ordering::Relaxed);
+                    debug!("🪞 Shadow {} -> {}", url, res.status());
+                }
+                Err(e) => {
+                    task_rule.counters.failed.fetch_add(1, Ordering::Relaxed);
+                    debug!("🪞 Shadow {} lỗi: {}", url, e);
+                }
+            }
+        });
+
+        let Some(tx) = tx else {
+            return body;
+        };
+        let abort = task.abort_handle();
+        let tx = Mutex::new(Some(tx));
+        let tee_rule = rule.clone();
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            let mut guard = tx.lock().unwrap();
+            if let Some(sender) = guard.as_ref() {
+                let delivered = match &chunk {
+                    Ok(bytes) if sender.capacity() > 1 => sender.try_send(Ok(bytes.clone())).is_ok(),
+                    _ => false,
+                };
+                if !delivered {
+                    // Service bản sao chậm / đã lỗi, hoặc body của client bị ngắt: kết thúc body bản sao bằng lỗi
+                    // (kết nối bị ngắt giữa chừng) và huỷ bản sao, để service bản sao không nhận body cụt như
+                    // request hoàn chỉnh. Request thật vẫn chạy tiếp
+                    let _ = sender.try_send(Err(std::io::Error::other("bản sao bị ngắt")));
+                    abort.abort();
+                    *guard = None;
+                    tee_rule.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            chunk
+        }))
+    }
+
+    pub fn stats(&self) -> Vec<RuleStats> {
+        self.rules
+            .iter()
+            .map(|r| RuleStats {
+                path_prefix: r.config.path_prefix.clone(),
+                target: r.config.target.clone(),
+                sent: r.counters.sent.load(Ordering::Relaxed),
+                failed: r.counters.failed.load(Ordering::Relaxed),
+                dropped: r.counters.dropped.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mirror;
+    use crate::config::ShadowConfig;
+    use axum::{
+        body::{Body, Bytes},
+        http::{header, HeaderMap, Method},
+    };
+    use futures::StreamExt;
+    use std::{convert::Infallible, sync::atomic::Ordering, time::Duration};
+    use tokio::io::AsyncReadExt;
+
+    // Service bản sao không đọc kịp: bản sao bị huỷ giữa chừng, không nhận được chunk kết thúc (0\r\n\r\n)
+    #[tokio::test]
+    async fn slow_shadow_gets_an_aborted_request_not_a_truncated_one() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = format!("http://{}", listener.local_addr().unwrap());
+        let received = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            let mut data = Vec::new();
+            let _ = socket.read_to_end(&mut data).await;
+            data
+        });
+
+        let mirror = Mirror::new(&[ShadowConfig { path_prefix: "/".to_string(), target, percent: 100.0, timeout_ms: 5000 }]);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::TRANSFER_ENCODING, "chunked".parse().unwrap());
+        let chunks = futures::stream::iter((0..200).map(|_| Ok::<_, Infallible>(Bytes::from(vec![b'x'; 64 * 1024]))));
+        let body = mirror.mirror(&Method::POST, "/upload", &headers, Body::from_stream(chunks));
+
+        // Request thật vẫn nhận đủ body. Đọc chậm vài chunk đầu để bản sao kịp kết nối và bắt đầu gửi
+        let mut stream = body.into_data_stream();
+        let mut total = 0;
+        while let Some(chunk) = stream.next().await {
+            total += chunk.unwrap().len();
+            if total <= 4 * 64 * 1024 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }
+        assert_eq!(total, 200 * 64 * 1024);
+
+        let data = tokio::time::timeout(Duration::from_secs(5), received).await.unwrap().unwrap();
+        assert!(data.starts_with(b"POST /upload"));
+        assert!(!data.ends_with(b"0\r\n\r\n"));
+        assert_eq!(mirror.stats()[0].dropped, 1);
+        assert_eq!(mirror.rules[0].counters.sent.load(Ordering::Relaxed), 0);
+    }
+}
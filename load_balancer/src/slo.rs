@@ -0,0 +1,231 @@
+// SLO theo pool / backend: đếm request tốt / chậm / lỗi theo từng phút, tính mức tuân thủ
+// trên cửa sổ dài (vd. 30 ngày) và burn rate trên cửa sổ ngắn để cảnh báo sớm.
+//
+// burn rate = tỉ lệ request xấu / tỉ lệ xấu cho phép (1 - objective).
+// burn rate 1 nghĩa là error budget vừa đủ dùng hết đúng lúc hết cửa sổ.
+use crate::config::SloConfig;
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{info, warn};
+
+// Không cảnh báo khi cửa sổ ngắn có quá ít request (1 request lỗi không nên gây báo động)
+const MIN_BURN_REQUESTS: u64 = 10;
+
+#[derive(Clone, Copy, Default)]
+struct Bucket {
+    minute: u64,
+    total: u64,
+    slow: u64,
+    errors: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    Latency,
+    Errors,
+}
+
+impl Objective {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Objective::Latency => "latency",
+            Objective::Errors => "errors",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectiveReport {
+    pub objective: Objective,
+    pub target: f64,
+    // % request đạt mục tiêu trong cửa sổ dài
+    pub compliance: f64,
+    // Phần error budget còn lại (1 = chưa dùng, âm = đã vượt)
+    pub error_budget_remaining: f64,
+    pub burn_rate: f64,
+    pub alerting: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SloReport {
+    pub name: String,
+    pub pool: String,
+    pub backend: Option<String>,
+    pub requests: u64,
+    pub objectives: Vec<ObjectiveReport>,
+}
+
+struct Slo {
+    config: SloConfig,
+    buckets: Mutex<VecDeque<Bucket>>,
+    // Đang cảnh báo (latency, errors)
+    alerting: Mutex<[bool; 2]>,
+}
+
+pub struct Tracker {
+    slos: Vec<Slo>,
+    client: reqwest::Client,
+}
+
+fn now_minute() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 60
+}
+
+impl Slo {
+    // Cộng dồn các bucket trong `secs` giây gần nhất
+    fn sum(&self, secs: u64, now: u64) -> Bucket {
+        let since = now.saturating_sub(secs.div_ceil(60));
+        let buckets = self.buckets.lock().unwrap();
+        buckets.iter().rev().take_while(|b| b.minute > since).fold(Bucket::default(), |acc, b| Bucket {
+            minute: 0,
+            total: acc.total + b.total,
+            slow: acc.slow + b.slow,
+            errors: acc.errors + b.errors,
+        })
+    }
+
+    fn objectives(&self) -> Vec<(Objective, f64)> {
+        let mut list = Vec::new();
+        if self.config.latency_ms.is_some() {
+            list.push((Objective::Latency, self.config.latency_objective));
+        }
+        list.push((Objective::Errors, self.config.error_objective));
+        list
+    }
+
+    fn report(&self, now: u64) -> SloReport {
+        let window = self.sum(self.config.window_secs, now);
+        let burn = self.sum(self.config.burn_window_secs, now);
+        let alerting = *self.alerting.lock().unwrap();
+
+        let objectives = self
+            .objectives()
+            .into_iter()
+            .map(|(objective, target)| {
+                let bad = |b: &Bucket| match objective {
+                    Objective::Latency => b.slow,
+                    Objective::Errors => b.errors,
+                };
+                let allowed = 1.0 - target / 100.0;
+                let bad_ratio = |b: &Bucket| if b.total == 0 { 0.0 } else { bad(b) as f64 / b.total as f64 };
+                ObjectiveReport {
+                    objective,
+                    target,
+                    compliance: (1.0 - bad_ratio(&window)) * 100.0,
+                    error_budget_remaining: 1.0 - bad_ratio(&window) / allowed,
+                    burn_rate: bad_ratio(&burn) / allowed,
+                    alerting: alerting[objective as usize],
+                }
+            })
+            .collect();
+
+        SloReport {
+            name: self.config.name.clone(),
+            pool: self.config.pool.clone(),
+            backend: self.config.backend.clone(),
+            requests: window.total,
+            objectives,
+        }
+    }
+}
+
+impl Tracker {
+    pub fn new(configs: &[SloConfig]) -> Self {
+        info!("🎯 Theo dõi {} SLO", configs.len());
+        Self {
+            slos: configs
+                .iter()
+                .map(|c| Slo {
+                    config: c.clone(),
+                    buckets: Mutex::new(VecDeque::new()),
+                    alerting: Mutex::new([false; 2]),
+                })
+                .collect(),
+            client: reqwest::Client::builder().timeout(Duration::from_secs(5)).build().unwrap(),
+        }
+    }
+
+    // Ghi nhận một request đã xử lý xong. backend = None khi không có backend nào để gửi tới
+    pub fn record(&self, pool: &str, backend: Option<&str>, status: u16, elapsed: Duration) {
+        let minute = now_minute();
+        for slo in &self.slos {
+            if slo.config.pool != pool || slo.config.backend.as_deref().is_some_and(|b| Some(b) != backend) {
+                continue;
+            }
+            let slow = slo.config.latency_ms.is_some_and(|max| elapsed.as_millis() > max as u128);
+            let mut buckets = slo.buckets.lock().unwrap();
+            if buckets.back().is_none_or(|b| b.minute != minute) {
+                buckets.push_back(Bucket { minute, ..Default::default() });
+                let keep_since = minute.saturating_sub(slo.config.window_secs.div_ceil(60));
+                while buckets.front().is_some_and(|b| b.minute <= keep_since) {
+                    buckets.pop_front();
+                }
+            }
+            let bucket = buckets.back_mut().unwrap();
+            bucket.total += 1;
+            bucket.slow += slow as u64;
+            bucket.errors += (status >= 500) as u64;
+        }
+    }
+
+    pub fn report(&self) -> Vec<SloReport> {
+        let now = now_minute();
+        self.slos.iter().map(|s| s.report(now)).collect()
+    }
+
+    // Kiểm tra burn rate, cảnh báo khi vượt ngưỡng và báo khi đã hồi phục
+    pub async fn evaluate(&self) {
+        let now = now_minute();
+        for slo in &self.slos {
+            let burn_requests = slo.sum(slo.config.burn_window_secs, now).total;
+            let report = slo.report(now);
+            for o in &report.objectives {
+                let firing = burn_requests >= MIN_BURN_REQUESTS && o.burn_rate >= slo.config.burn_rate_threshold;
+                let changed = {
+                    let mut alerting = slo.alerting.lock().unwrap();
+                    let was = std::mem::replace(&mut alerting[o.objective as usize], firing);
+                    was != firing
+                };
+                if !changed {
+                    continue;
+                }
+
+                let kind = o.objective.as_str();
+                if firing {
+                    warn!(
+                        "🔥 SLO {} ({}): burn rate {:.1} >= {} trong {}s, budget còn {:.1}%",
+                        report.name, kind, o.burn_rate, slo.config.burn_rate_threshold,
+                        slo.config.burn_window_secs, o.error_budget_remaining * 100.0
+                    );
+                } else {
+                    info!("✅ SLO {} ({}): burn rate đã về {:.1}", report.name, kind, o.burn_rate);
+                }
+
+                if let Some(url) = &slo.config.webhook {
+                    let payload = serde_json::json!({
+                        "status": if firing { "firing" } else { "resolved" },
+                        "slo": report.name,
+                        "pool": report.pool,
+                        "backend": report.backend,
+                        "objective": o.objective,
+                        "target": o.target,
+                        "burnRate": o.burn_rate,
+                        "threshold": slo.config.burn_rate_threshold,
+                        "compliance": o.compliance,
+                        "errorBudgetRemaining": o.error_budget_remaining,
+                    });
+                    if let Err(e) = self.client.post(url).json(&payload).send().await {
+                        warn!("⚠️ Không gửi được cảnh báo SLO tới {}: {}", url, e);
+                    }
+                }
+            }
+        }
+    }
+}
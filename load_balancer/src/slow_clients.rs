@@ -0,0 +1,231 @@
+// Chống client cố tình gửi/nhận dữ liệu thật chậm để giữ chỗ kết nối (slowloris, slow-read).
+// - Header: timeout của hyper (cấu hình trong server.rs)
+// - Body request: timeout giữa các chunk + tốc độ tối thiểu
+// - Response: ghi bị nghẽn quá lâu (client không đọc) hoặc client nhận quá chậm thì đóng kết nối
+use crate::config::SlowClientsConfig;
+use axum::body::{Body, BodyDataStream, Bytes};
+use futures::stream::Stream;
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::Sleep,
+};
+use tracing::debug;
+
+pub fn guard_body(body: Body, config: &SlowClientsConfig) -> Body {
+    let chunk_timeout = Duration::from_secs(config.body_chunk_timeout_secs);
+    Body::from_stream(GuardedBody {
+        inner: body.into_data_stream(),
+        chunk_timeout,
+        min_rate: config.min_body_rate_bytes_per_sec,
+        grace: Duration::from_secs(config.min_rate_grace_secs),
+        started: Instant::now(),
+        received: 0,
+        polled: false,
+        last_chunk: tokio::time::Instant::now(),
+        deadline: Box::pin(tokio::time::sleep(chunk_timeout)),
+    })
+}
+
+struct GuardedBody {
+    inner: BodyDataStream,
+    chunk_timeout: Duration,
+    min_rate: u64,
+    grace: Duration,
+    started: Instant,
+    received: u64,
+    // Chưa poll lần nào: client gửi "Expect: 100-continue" chỉ bắt đầu gửi body sau khi hyper trả 100 Continue
+    // (ở lần poll đầu, tức là khi backend đã nhận request), nên thời gian chờ backend không tính vào body
+    polled: bool,
+    last_chunk: tokio::time::Instant,
+    deadline: Pin<Box<Sleep>>,
+}
+
+impl Stream for GuardedBody {
+    type Item = Result<Bytes, axum::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if !self.polled {
+            self.polled = true;
+            self.started = Instant::now();
+            self.last_chunk = tokio::time::Instant::now();
+            let next = self.last_chunk + self.chunk_timeout;
+            self.deadline.as_mut().reset(next);
+        }
+        match Pin::new(&mut self.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                self.received += chunk.len() as u64;
+                let elapsed = self.started.elapsed();
+                if self.min_rate > 0 && elapsed > self.grace {
+                    let rate = self.received as f64 / elapsed.as_secs_f64();
+                    if rate < self.min_rate as f64 {
+                        debug!("Body request quá chậm: {:.0} byte/s", rate);
+                        return Poll::Ready(Some(Err(timed_out("body request quá chậm"))));
+                    }
+                }
+                self.last_chunk = tokio::time::Instant::now();
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Pending => {
+                // Deadline chỉ được lùi khi hết hạn mà vẫn có chunk mới, không đặt lại ở mỗi chunk
+                while self.deadline.as_mut().poll(cx).is_ready() {
+                    let next = self.last_chunk + self.chunk_timeout;
+                    if next <= tokio::time::Instant::now() {
+                        return Poll::Ready(Some(Err(timed_out("hết thời gian chờ body request"))));
+                    }
+                    self.deadline.as_mut().reset(next);
+                }
+                Poll::Pending
+            }
+            other => other,
+        }
+    }
+}
+
+fn timed_out(reason: &str) -> axum::Error {
+    axum::Error::new(io::Error::new(io::ErrorKind::TimedOut, reason))
+}
+
+// Bọc kết nối: poll_write bị Pending liên tục quá `timeout`, hoặc tính trên tổng thời gian bị nghẽn
+// client nhận chậm hơn `min_rate` -> lỗi, hyper đóng kết nối
+pub struct StallGuard<S> {
+    inner: S,
+    timeout: Duration,
+    min_rate: u64,
+    grace: Duration,
+    // Tạo một lần cho cả kết nối, chỉ đặt lại khi bắt đầu bị nghẽn (không cấp phát mỗi lần Pending)
+    write_deadline: Pin<Box<Sleep>>,
+    stalled: bool,
+    stall_started: tokio::time::Instant,
+    // Tổng byte đã ghi và tổng thời gian ghi bị nghẽn (các lần trước) của kết nối
+    written: u64,
+    blocked: Duration,
+}
+
+impl<S> StallGuard<S> {
+    pub fn new(inner: S, config: &SlowClientsConfig) -> Self {
+        let timeout = Duration::from_secs(config.write_stall_timeout_secs);
+        Self {
+            inner,
+            timeout,
+            min_rate: config.min_response_rate_bytes_per_sec,
+            grace: Duration::from_secs(config.min_rate_grace_secs),
+            write_deadline: Box::pin(tokio::time::sleep(timeout)),
+            stalled: false,
+            stall_started: tokio::time::Instant::now(),
+            written: 0,
+            blocked: Duration::ZERO,
+        }
+    }
+
+    fn check_stall(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
+        if !self.stalled {
+            self.stalled = true;
+            self.stall_started = tokio::time::Instant::now();
+            let mut deadline = self.stall_started + self.timeout;
+            // Thời điểm tốc độ (byte đã ghi / thời gian bị nghẽn) tụt dưới min_rate nếu vẫn không ghi được gì
+            if self.min_rate > 0 {
+                let budget = self.grace.max(Duration::from_secs_f64(self.written as f64 / self.min_rate as f64));
+                deadline = deadline.min(self.stall_started + budget.saturating_sub(self.blocked));
+            }
+            self.write_deadline.as_mut().reset(deadline);
+        }
+        match self.write_deadline.as_mut().poll(cx) {
+            Poll::Ready(()) if self.stall_started.elapsed() >= self.timeout => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, "client không nhận response"))
+            }
+            Poll::Ready(()) => {
+                debug!("Client nhận response quá chậm: {} byte trong {:?}", self.written, self.blocked + self.stall_started.elapsed());
+                Err(io::Error::new(io::ErrorKind::TimedOut, "client nhận response quá chậm"))
+            }
+            Poll::Pending => Ok(()),
+        }
+    }
+
+    fn guard<T>(&mut self, cx: &mut Context<'_>, result: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        match result {
+            Poll::Pending => match self.check_stall(cx) {
+                Ok(()) => Poll::Pending,
+                Err(e) => Poll::Ready(Err(e)),
+            },
+            ready => {
+                if self.stalled {
+                    self.stalled = false;
+                    self.blocked += self.stall_started.elapsed();
+                }
+                ready
+            }
+        }
+    }
+
+    fn count(&mut self, result: &Poll<io::Result<usize>>) {
+        if let Poll::Ready(Ok(n)) = result {
+            self.written += *n as u64;
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for StallGuard<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for StallGuard<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.count(&result);
+        self.guard(cx, result)
+    }
+
+    // Chuyển tiếp writev: không có thì hyper phải chép header + từng chunk body vào một buffer phẳng
+    fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[io::IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.count(&result);
+        self.guard(cx, result)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let result = Pin::new(&mut self.inner).poll_flush(cx);
+        self.guard(cx, result)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StallGuard;
+    use crate::config::SlowClientsConfig;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Client đọc nhỏ giọt (~320 byte/s) không chạm write_stall_timeout_secs nhưng dưới tốc độ tối thiểu
+    #[tokio::test]
+    async fn trickling_reader_is_cut_by_min_response_rate() {
+        let config = SlowClientsConfig { min_response_rate_bytes_per_sec: 1000, min_rate_grace_secs: 1, ..Default::default() };
+        let (server, mut client) = tokio::io::duplex(64);
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            while client.read(&mut buf).await.is_ok_and(|n| n > 0) {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        });
+        let mut guarded = StallGuard::new(server, &config);
+        let result = tokio::time::timeout(Duration::from_secs(10), guarded.write_all(&[0u8; 64 * 1024])).await;
+        let err = result.expect("phải bị cắt trước write_stall_timeout").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    }
+}
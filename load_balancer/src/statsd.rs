@@ -0,0 +1,103 @@
+// Gửi metrics tới StatsD / DogStatsD qua UDP (cho hệ thống giám sát Datadog / Telegraf).
+// Request nào cũng sinh counter + timing, trạng thái backend gửi dạng gauge theo chu kỳ.
+// Các dòng được gom thành packet ở task riêng; agent không chạy thì metrics bị bỏ, proxy không bị ảnh hưởng.
+use crate::{
+    config::StatsdConfig,
+    metrics::{path_component, BackendSample},
+};
+use std::time::Duration;
+use tokio::{net::UdpSocket, sync::mpsc};
+use tracing::{info, warn};
+
+// Vừa một gói UDP qua mạng có MTU 1500
+const MAX_PACKET: usize = 1432;
+const FLUSH_EVERY: Duration = Duration::from_secs(1);
+// Hàng đợi đầy (agent chậm / mất mạng) thì bỏ metric thay vì giữ bộ nhớ
+const QUEUE: usize = 10_000;
+
+pub struct Sink {
+    config: StatsdConfig,
+    tx: mpsc::Sender<String>,
+}
+
+impl Sink {
+    pub fn new(config: StatsdConfig) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE);
+        tokio::spawn(sender_task(config.address.clone(), rx));
+        info!("📈 Gửi metrics StatsD tới {}", config.address);
+        Self { config, tx }
+    }
+
+    fn emit(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) {
+        let line = if self.config.dogstatsd {
+            let tags: Vec<String> = tags.iter().map(|(k, v)| format!("{}:{}", k, v.replace([',', '|'], "_"))).collect();
+            let tags = if tags.is_empty() { String::new() } else { format!("|#{}", tags.join(",")) };
+            format!("{}.{}:{}|{}{}", self.config.prefix, name, value, kind, tags)
+        } else {
+            let path: String = tags.iter().map(|(_, v)| format!(".{}", path_component(v))).collect();
+            format!("{}.{}{}:{}|{}", self.config.prefix, name, path, value, kind)
+        };
+        let _ = self.tx.try_send(line);
+    }
+
+    // Một request đã xử lý xong
+    pub fn request(&self, pool: &str, backend: Option<&str>, status: u16, elapsed: Duration) {
+        let status_class = format!("{}xx", status / 100);
+        let backend = backend.unwrap_or("none");
+        self.emit("requests", "1", "c", &[("pool", pool), ("backend", backend), ("status", &status_class)]);
+        self.emit("request_time", &elapsed.as_millis().to_string(), "ms", &[("pool", pool), ("backend", backend)]);
+    }
+
+    // Trạng thái backend: up (0/1), request đang xử lý, response time health check
+    pub fn backend_gauges(&self, backend: &BackendSample) {
+        let tags = [("pool", backend.pool.as_str()), ("backend", backend.url.as_str())];
+        self.emit("backend.up", if backend.up { "1" } else { "0" }, "g", &tags);
+        self.emit("backend.active_requests", &backend.active.to_string(), "g", &tags);
+        if let Some(ms) = backend.response_time {
+            self.emit("backend.health_latency_ms", &ms.to_string(), "g", &tags);
+        }
+    }
+
+    pub fn gauge_interval(&self) -> Duration {
+        Duration::from_secs(self.config.gauge_interval_secs.max(1))
+    }
+}
+
+async fn sender_task(address: String, mut rx: mpsc::Receiver<String>) {
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("⚠️ Không mở được UDP socket cho StatsD: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = socket.connect(&address).await {
+        warn!("⚠️ Không phân giải được địa chỉ StatsD {}: {}", address, e);
+        return;
+    }
+
+    let mut packet = String::new();
+    let mut flush = tokio::time::interval(FLUSH_EVERY);
+    loop {
+        tokio::select! {
+            line = rx.recv() => {
+                let Some(line) = line else { break };
+                if !packet.is_empty() && packet.len() + 1 + line.len() > MAX_PACKET {
+                    // Agent không nhận (ICMP unreachable) -> bỏ qua, lần sau thử lại
+                    let _ = socket.send(packet.as_bytes()).await;
+                    packet.clear();
+                }
+                if !packet.is_empty() {
+                    packet.push('\n');
+                }
+                packet.push_str(&line);
+            }
+            _ = flush.tick() => {
+                if !packet.is_empty() {
+                    let _ = socket.send(packet.as_bytes()).await;
+                    packet.clear();
+                }
+            }
+        }
+    }
+}
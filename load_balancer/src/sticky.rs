@@ -0,0 +1,31 @@
+// Lưu / khôi phục sticky map ra file JSON để restart không làm xáo trộn session của client.
+// File có dạng { "<pool>": { "<client>": "<backend url>" } }.
+use std::{collections::HashMap, path::Path};
+
+pub type PoolMaps = HashMap<String, HashMap<String, String>>;
+
+// default_pool: pool nhận dữ liệu khi file còn ở dạng cũ (một map phẳng, trước khi có pool)
+pub fn load(path: &Path, default_pool: &str) -> PoolMaps {
+    let Ok(data) = std::fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+
+    if let Ok(maps) = serde_json::from_str::<PoolMaps>(&data) {
+        return maps;
+    }
+    match serde_json::from_str::<HashMap<String, String>>(&data) {
+        Ok(map) => HashMap::from([(default_pool.to_string(), map)]),
+        Err(e) => {
+            tracing::warn!("⚠️ Không đọc được sticky map từ {}: {}", path.display(), e);
+            HashMap::new()
+        }
+    }
+}
+
+// Ghi ra file tạm rồi rename để không bao giờ để lại file hỏng giữa chừng
+pub fn save(path: &Path, maps: &PoolMaps) -> std::io::Result<()> {
+    let data = serde_json::to_vec(maps)?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)
+}
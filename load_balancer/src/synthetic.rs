@@ -0,0 +1,82 @@
+// Health check nhiều bước (check_type = "steps"): chạy lần lượt các request mô phỏng một giao dịch thật
+// (vd. POST /login rồi GET /api/me), backend chỉ UP khi hoàn thành được cả chuỗi.
+// Cookie từ Set-Cookie được gửi lại ở các bước sau; giá trị capture từ body JSON thay vào {{tên}}
+// trong path, header và body của các bước sau. Header xác thực của backend ("auth") được gửi ở mọi bước,
+// header khai báo trong bước ghi đè lên.
+use crate::pools::ProbeStep;
+use reqwest::{
+    header::{HeaderMap, COOKIE, SET_COOKIE},
+    Client, Method,
+};
+use std::collections::{BTreeMap, HashMap};
+
+fn expand(template: &str, vars: &HashMap<String, String>) -> String {
+    vars.iter()
+        .fold(template.to_string(), |text, (name, value)| text.replace(&format!("{{{{{}}}}}", name), value))
+}
+
+// Err = bước thất bại và lý do (lý do DOWN của backend)
+pub async fn run(client: &Client, base_url: &str, auth: &HeaderMap, steps: &[ProbeStep]) -> Result<(), String> {
+    if steps.is_empty() {
+        return Err("steps: chưa khai báo bước nào".to_string());
+    }
+    let mut cookies: BTreeMap<String, String> = BTreeMap::new();
+    let mut vars: HashMap<String, String> = HashMap::new();
+
+    for (i, step) in steps.iter().enumerate() {
+        let label = format!("bước {} ({} {})", i + 1, step.method, step.path);
+        let method = Method::from_bytes(step.method.to_ascii_uppercase().as_bytes())
+            .map_err(|_| format!("{}: method không hợp lệ", label))?;
+        let path = expand(&step.path, &vars);
+        let url = format!("{}/{}", base_url.trim_end_matches('/'), path.trim_start_matches('/'));
+
+        let mut request = client.request(method, url).headers(auth.clone());
+        for (name, value) in &step.headers {
+            request = request.header(name, expand(value, &vars));
+        }
+        if !cookies.is_empty() {
+            let cookie: Vec<String> = cookies.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+            request = request.header(COOKIE, cookie.join("; "));
+        }
+        if let Some(body) = &step.body {
+            request = request.body(expand(body, &vars));
+        }
+
+        let response = request.send().await.map_err(|e| format!("{}: {}", label, e))?;
+        let status = response.status();
+        for set_cookie in response.headers().get_all(SET_COOKIE) {
+            let pair = set_cookie.to_str().ok().and_then(|c| c.split(';').next()).and_then(|c| c.split_once('='));
+            if let Some((name, value)) = pair {
+                cookies.insert(name.trim().to_string(), value.trim().to_string());
+            }
+        }
+        let expected = match step.expect_status {
+            Some(code) => status.as_u16() == code,
+            None => status.is_success(),
+        };
+        if !expected {
+            return Err(format!("{}: HTTP {}", label, status));
+        }
+        if step.expect_body.is_none() && step.capture.is_empty() {
+            continue;
+        }
+
+        let body = response.text().await.map_err(|e| format!("{}: {}", label, e))?;
+        if let Some(expected) = &step.expect_body {
+            expected.check(&body).map_err(|e| format!("{}: {}", label, e))?;
+        }
+        if !step.capture.is_empty() {
+            let json: serde_json::Value =
+                serde_json::from_str(&body).map_err(|e| format!("{}: body không phải JSON: {}", label, e))?;
+            for (name, pointer) in &step.capture {
+                let value = match json.pointer(pointer) {
+                    Some(serde_json::Value::String(s)) => s.clone(),
+                    Some(value) => value.to_string(),
+                    None => return Err(format!("{}: body không có {}", label, pointer)),
+                };
+                vars.insert(name.clone(), value);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,196 @@
+// Gửi log tới syslog ([syslog] trong config.toml) theo RFC 5424:
+// "<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID - - MSG", qua UDP, TCP (octet counting, RFC 6587) hoặc unix socket.
+// Layer chỉ đưa message vào hàng đợi, một thread riêng lo gửi: syslog chậm / mất kết nối không làm chậm request.
+// Hàng đợi đầy hoặc gửi lỗi thì bỏ message (không ghi log về lỗi này để tránh vòng lặp).
+use crate::config::SyslogConfig;
+use std::{
+    fmt::Write as _,
+    io::Write as _,
+    net::{TcpStream, ToSocketAddrs, UdpSocket},
+    sync::mpsc::{self, Receiver, SyncSender},
+    time::Duration,
+};
+use tracing::{field::Field, Event, Level, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+
+const QUEUE: usize = 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub enum Target {
+    Udp(String),
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl Target {
+    pub fn parse(address: &str) -> Result<Self, String> {
+        let (scheme, rest) = address
+            .split_once("://")
+            .ok_or_else(|| format!("thiếu udp:// / tcp:// / unix:// trong {}", address))?;
+        if rest.is_empty() {
+            return Err(format!("thiếu địa chỉ trong {}", address));
+        }
+        match scheme {
+            "udp" => Ok(Target::Udp(rest.to_string())),
+            "tcp" => Ok(Target::Tcp(rest.to_string())),
+            #[cfg(unix)]
+            "unix" => Ok(Target::Unix(rest.into())),
+            _ => Err(format!("không hỗ trợ {}://", scheme)),
+        }
+    }
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixDatagram),
+}
+
+fn connect(target: &Target) -> std::io::Result<Connection> {
+    match target {
+        Target::Udp(address) => {
+            let addr = address
+                .to_socket_addrs()?
+                .next()
+                .ok_or(std::io::ErrorKind::AddrNotAvailable)?;
+            let bind = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+            let socket = UdpSocket::bind(bind)?;
+            socket.connect(addr)?;
+            Ok(Connection::Udp(socket))
+        }
+        Target::Tcp(address) => {
+            let addr = address
+                .to_socket_addrs()?
+                .next()
+                .ok_or(std::io::ErrorKind::AddrNotAvailable)?;
+            let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+            stream.set_write_timeout(Some(CONNECT_TIMEOUT))?;
+            Ok(Connection::Tcp(stream))
+        }
+        #[cfg(unix)]
+        Target::Unix(path) => {
+            let socket = std::os::unix::net::UnixDatagram::unbound()?;
+            socket.connect(path)?;
+            Ok(Connection::Unix(socket))
+        }
+    }
+}
+
+impl Connection {
+    fn send(&mut self, message: &str) -> std::io::Result<()> {
+        match self {
+            Connection::Udp(socket) => socket.send(message.as_bytes()).map(drop),
+            Connection::Tcp(stream) => write!(stream, "{} {}", message.len(), message),
+            #[cfg(unix)]
+            Connection::Unix(socket) => socket.send(message.as_bytes()).map(drop),
+        }
+    }
+}
+
+// Gửi lần lượt, lỗi thì mở lại kết nối và thử thêm một lần cho message đó
+fn sender(target: Target, queue: Receiver<String>) {
+    let mut connection = None;
+    for message in queue {
+        for _ in 0..2 {
+            if connection.is_none() {
+                connection = connect(&target).ok();
+            }
+            let Some(conn) = connection.as_mut() else {
+                break;
+            };
+            if conn.send(&message).is_ok() {
+                break;
+            }
+            connection = None;
+        }
+    }
+}
+
+// Severity theo RFC 5424 (cũng là PRIORITY của journald)
+pub fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        _ => 7,
+    }
+}
+
+pub struct SyslogLayer {
+    queue: SyncSender<String>,
+    facility: u8,
+    hostname: String,
+    app_name: String,
+    pid: u32,
+}
+
+impl SyslogLayer {
+    pub fn new(config: &SyslogConfig) -> Result<Self, String> {
+        let target = Target::parse(&config.address)?;
+        let (queue, rx) = mpsc::sync_channel(QUEUE);
+        std::thread::Builder::new()
+            .name("syslog".to_string())
+            .spawn(move || sender(target, rx))
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            queue,
+            facility: config.facility as u8,
+            hostname: hostname(),
+            app_name: config.app_name.clone(),
+            pid: std::process::id(),
+        })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SyslogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = format!(
+            "<{}>1 {} {} {} {} - - \u{feff}",
+            self.facility * 8 + severity(event.metadata().level()),
+            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            self.hostname,
+            self.app_name,
+            self.pid
+        );
+        let len = message.len();
+        event.record(&mut MessageVisitor { out: &mut message, len });
+        let _ = self.queue.try_send(message);
+    }
+}
+
+// Nối message + các field còn lại (name=value) vào sau header
+struct MessageVisitor<'a> {
+    out: &'a mut String,
+    len: usize,
+}
+
+impl tracing::field::Visit for MessageVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if self.out.len() > self.len {
+            self.out.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.out, "{:?}", value);
+        } else {
+            let _ = write!(self.out, "{}={:?}", field.name(), value);
+        }
+    }
+}
+
+// HOSTNAME trong header: tên máy (không có dấu cách), "-" nếu không lấy được
+fn hostname() -> String {
+    #[cfg(unix)]
+    let name = {
+        let mut buf = [0u8; 256];
+        let ok = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0;
+        let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        ok.then(|| String::from_utf8_lossy(&buf[..end]).into_owned())
+    };
+    #[cfg(not(unix))]
+    let name = std::env::var("COMPUTERNAME").ok();
+    name.filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_graphic()))
+        .unwrap_or_else(|| "-".to_string())
+}
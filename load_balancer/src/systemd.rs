@@ -0,0 +1,80 @@
+// Tích hợp systemd: sd_notify (Type=notify) và socket activation (LISTEN_FDS).
+// Tự cài đặt giao thức (rất đơn giản) để không phải thêm dependency.
+
+#[cfg(unix)]
+use std::env;
+
+// fd đầu tiên systemd truyền sang luôn là 3 (SD_LISTEN_FDS_START)
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+// Gửi một thông điệp trạng thái tới systemd qua $NOTIFY_SOCKET.
+// Không chạy dưới systemd (không có biến môi trường) thì bỏ qua.
+#[cfg(unix)]
+pub fn notify(state: &str) {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return;
+    };
+
+    // Địa chỉ bắt đầu bằng '@' là abstract socket (Linux)
+    let bytes = path.as_encoded_bytes();
+    let result = if bytes.first() == Some(&b'@') {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(&bytes[1..])
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+        }
+    } else {
+        socket.send_to(state.as_bytes(), &path)
+    };
+
+    if let Err(e) = result {
+        tracing::warn!("⚠️ Không gửi được sd_notify ({}): {}", state, e);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) {}
+
+// Lấy các listener đã được systemd bind sẵn (socket activation, fd 3 .. 3 + LISTEN_FDS), nếu có.
+// Chỉ nhận khi LISTEN_PID trùng với pid hiện tại, đúng như sd_listen_fds().
+#[cfg(unix)]
+pub fn take_listeners() -> Vec<std::net::TcpListener> {
+    use std::os::fd::FromRawFd;
+
+    let pid: Option<u32> = env::var("LISTEN_PID").ok().and_then(|v| v.parse().ok());
+    if pid != Some(std::process::id()) {
+        return Vec::new();
+    }
+    let fds: i32 = env::var("LISTEN_FDS").ok().and_then(|v| v.parse().ok()).unwrap_or(0);
+
+    // Xóa biến môi trường để process con (nếu có) không nhận nhầm fd
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + fds.max(0))
+        .filter_map(|fd| {
+            // SAFETY: systemd đảm bảo các fd từ 3 tới 3 + LISTEN_FDS là socket đang mở và thuộc về process này
+            let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+            listener.set_nonblocking(true).ok()?;
+            Some(listener)
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+pub fn take_listeners() -> Vec<std::net::TcpListener> {
+    Vec::new()
+}
@@ -0,0 +1,287 @@
+// TLS termination (rustls) cho listener, hỗ trợ xác thực client cert (mTLS).
+// Cert/key nằm trong CertStore nên có thể thay khi đang chạy (cert ngắn hạn từ Vault PKI) mà không restart.
+use crate::config::TlsConfig;
+use rustls_pemfile::Item;
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio::sync::{futures::Notified, Notify};
+use tracing::{info, warn};
+use x509_parser::extensions::GeneralName;
+use tokio_rustls::{
+    rustls::{
+        crypto::ring,
+        pki_types::{CertificateDer, PrivateKeyDer},
+        server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+        sign::CertifiedKey,
+        RootCertStore, ServerConfig,
+    },
+    TlsAcceptor,
+};
+
+// Subject của client cert đã xác thực, gắn vào extension của request
+#[derive(Debug, Clone)]
+pub struct ClientCertSubject(pub String);
+
+// Cert đang dùng cho mọi kết nối TLS mới
+#[derive(Debug)]
+pub struct CertStore {
+    key: RwLock<Arc<CertifiedKey>>,
+    // Báo cho task OCSP stapling khi cert bị thay
+    changed: Notify,
+}
+
+impl CertStore {
+    pub fn new(key: CertifiedKey) -> Self {
+        Self {
+            key: RwLock::new(Arc::new(key)),
+            changed: Notify::new(),
+        }
+    }
+
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.key.read().unwrap().clone()
+    }
+
+    // Kết nối đang mở giữ cert cũ, kết nối mới dùng cert này
+    pub fn replace(&self, key: CertifiedKey) {
+        *self.key.write().unwrap() = Arc::new(key);
+        self.changed.notify_waiters();
+    }
+
+    // Gắn OCSP response vào cert đang dùng; false nếu cert đã bị thay trong lúc lấy response
+    pub fn staple(&self, cert: &CertificateDer<'_>, ocsp: Vec<u8>) -> bool {
+        let mut current = self.key.write().unwrap();
+        if current.end_entity_cert().ok() != Some(cert) {
+            return false;
+        }
+        let mut key = CertifiedKey::clone(&current);
+        key.ocsp = Some(ocsp);
+        *current = Arc::new(key);
+        true
+    }
+
+    // Hoàn tất ở lần replace() tiếp theo sau khi gọi
+    pub fn changed(&self) -> Notified<'_> {
+        self.changed.notified()
+    }
+}
+
+// Cert từ cert_file / key_file trong [tls]
+pub fn load_files(config: &TlsConfig) -> Result<CertifiedKey, String> {
+    let (Some(cert_file), Some(key_file)) = (&config.cert_file, &config.key_file) else {
+        return Err("thiếu cert_file / key_file".to_string());
+    };
+    load_pair(cert_file, key_file)
+}
+
+pub fn load_pair(cert_file: &Path, key_file: &Path) -> Result<CertifiedKey, String> {
+    certified_key(load_certs(cert_file)?, load_key(key_file)?)
+}
+
+fn modified(files: [&Path; 2]) -> [Option<SystemTime>; 2] {
+    files.map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+}
+
+// CertStore cho một cặp cert / key, tự nạp lại khi file thay đổi (reload_secs > 0)
+pub fn watched_store(cert_file: &Path, key_file: &Path, reload_secs: u64) -> Result<Arc<CertStore>, String> {
+    let store = Arc::new(CertStore::new(load_pair(cert_file, key_file)?));
+    if reload_secs > 0 {
+        let files = [cert_file.to_path_buf(), key_file.to_path_buf()];
+        tokio::spawn(watch_files(files, Duration::from_secs(reload_secs), store.clone()));
+    }
+    Ok(store)
+}
+
+// Nạp lại cert / key khi file thay đổi. Cặp file lỗi hoặc không khớp (đang ghi dở...) thì giữ cert cũ
+// và thử lại ở lần kiểm tra sau
+async fn watch_files(files: [PathBuf; 2], reload: Duration, store: Arc<CertStore>) {
+    let [cert_file, key_file] = &files;
+    let mut loaded = modified([cert_file, key_file]);
+    let mut interval = tokio::time::interval(reload);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let current = modified([cert_file, key_file]);
+        if current.contains(&None) || current == loaded {
+            continue;
+        }
+        match load_pair(cert_file, key_file) {
+            Ok(key) => {
+                store.replace(key);
+                loaded = current;
+                info!("🔐 Đã nạp lại cert TLS từ {}", cert_file.display());
+            }
+            Err(e) => warn!("⚠️ Không nạp lại được cert TLS {}: {}", cert_file.display(), e),
+        }
+    }
+}
+
+// Chọn cert theo SNI trong handshake: tên trùng khớp trước, rồi tới wildcard "*.example.com" (một nhãn);
+// client không gửi SNI hoặc không khớp tên nào thì dùng cert mặc định ([tls] cert_file / key_file hoặc Vault PKI)
+#[derive(Debug)]
+pub struct SniResolver {
+    default: Arc<CertStore>,
+    certs: Vec<(Vec<String>, Arc<CertStore>)>,
+}
+
+impl SniResolver {
+    pub fn new(config: &TlsConfig, default: Arc<CertStore>) -> Result<Self, String> {
+        let mut certs = Vec::new();
+        for entry in &config.certificates {
+            let store = watched_store(&entry.cert_file, &entry.key_file, config.reload_secs)?;
+            let names = if entry.server_names.is_empty() {
+                dns_names(&store.current())
+            } else {
+                entry.server_names.clone()
+            };
+            if names.is_empty() {
+                return Err(format!("{}: cert không có SAN DNS, cần khai báo server_names", entry.cert_file.display()));
+            }
+            info!("🔐 Cert TLS {} cho {}", entry.cert_file.display(), names.join(", "));
+            certs.push((names.iter().map(|n| n.to_ascii_lowercase()).collect(), store));
+        }
+        Ok(Self { default, certs })
+    }
+
+    // Mọi cert đang phục vụ (cho OCSP stapling)
+    pub fn stores(&self) -> impl Iterator<Item = &Arc<CertStore>> {
+        std::iter::once(&self.default).chain(self.certs.iter().map(|(_, store)| store))
+    }
+
+    fn find(&self, server_name: &str) -> Option<&Arc<CertStore>> {
+        let server_name = server_name.to_ascii_lowercase();
+        let exact = self.certs.iter().find(|(names, _)| names.contains(&server_name));
+        let wildcard = || {
+            let (_, parent) = server_name.split_once('.')?;
+            self.certs
+                .iter()
+                .find(|(names, _)| names.iter().any(|n| n.strip_prefix("*.") == Some(parent)))
+        };
+        exact.or_else(wildcard).map(|(_, store)| store)
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let store = client_hello.server_name().and_then(|name| self.find(name)).unwrap_or(&self.default);
+        Some(store.current())
+    }
+}
+
+// Tên DNS trong SAN của cert đầu chain
+fn dns_names(key: &CertifiedKey) -> Vec<String> {
+    let Some((_, cert)) = key.cert.first().and_then(|c| x509_parser::parse_x509_certificate(c).ok()) else {
+        return Vec::new();
+    };
+    let Ok(Some(san)) = cert.subject_alternative_name() else {
+        return Vec::new();
+    };
+    san.value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::DNSName(dns) => Some(dns.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+// Cert từ PEM (chain, private key), vd. response của Vault PKI
+pub fn parse_pem(cert_pem: &str, key_pem: &str) -> Result<CertifiedKey, String> {
+    let certs: Vec<_> = rustls_pemfile::certs(&mut cert_pem.as_bytes())
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("certificate không hợp lệ: {}", e))?;
+    if certs.is_empty() {
+        return Err("không có certificate nào".to_string());
+    }
+    let key = read_key(&mut key_pem.as_bytes())?.ok_or("không có private key")?;
+    certified_key(certs, key)
+}
+
+fn certified_key(certs: Vec<CertificateDer<'static>>, key: PrivateKeyDer<'static>) -> Result<CertifiedKey, String> {
+    let signing_key = ring::default_provider()
+        .key_provider
+        .load_private_key(key)
+        .map_err(|e| format!("private key không hợp lệ: {}", e))?;
+    let key = CertifiedKey::new(certs, signing_key);
+    key.keys_match().map_err(|e| format!("cert/key không hợp lệ: {}", e))?;
+    Ok(key)
+}
+
+// http2: quảng bá "h2" qua ALPN, client chọn HTTP/2 hoặc HTTP/1.1 trong lúc handshake
+pub fn build_acceptor(config: &TlsConfig, certs: Arc<SniResolver>, http2: bool) -> Result<TlsAcceptor, String> {
+    let provider = Arc::new(ring::default_provider());
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+
+    let builder = match &config.client_ca_file {
+        Some(ca_file) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_file)? {
+                roots.add(cert).map_err(|e| format!("CA không hợp lệ trong {}: {}", ca_file.display(), e))?;
+            }
+
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if config.client_cert_optional {
+                verifier.allow_unauthenticated()
+            } else {
+                verifier
+            };
+            builder.with_client_cert_verifier(verifier.build().map_err(|e| e.to_string())?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder.with_cert_resolver(certs);
+    server_config.alpn_protocols = if http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+// Lấy subject (vd. "CN=client1, O=Acme") từ cert đầu tiên client gửi lên
+pub fn client_cert_subject(certs: Option<&[CertificateDer<'_>]>) -> Option<ClientCertSubject> {
+    let cert = certs?.first()?;
+    let (_, parsed) = x509_parser::parse_x509_certificate(cert.as_ref()).ok()?;
+    Some(ClientCertSubject(parsed.subject().to_string()))
+}
+
+pub fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let file = File::open(path).map_err(|e| format!("không mở được {}: {}", path.display(), e))?;
+    let certs: Vec<_> = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("{} không hợp lệ: {}", path.display(), e))?;
+
+    if certs.is_empty() {
+        return Err(format!("{} không chứa certificate nào", path.display()));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, String> {
+    let file = File::open(path).map_err(|e| format!("không mở được {}: {}", path.display(), e))?;
+    read_key(&mut BufReader::new(file))
+        .map_err(|e| format!("{} không hợp lệ: {}", path.display(), e))?
+        .ok_or_else(|| format!("{} không chứa private key", path.display()))
+}
+
+fn read_key(reader: &mut dyn std::io::BufRead) -> Result<Option<PrivateKeyDer<'static>>, String> {
+    loop {
+        match rustls_pemfile::read_one(reader).map_err(|e| e.to_string())? {
+            Some(Item::Pkcs1Key(key)) => return Ok(Some(key.into())),
+            Some(Item::Pkcs8Key(key)) => return Ok(Some(key.into())),
+            Some(Item::Sec1Key(key)) => return Ok(Some(key.into())),
+            Some(_) => continue,
+            None => return Ok(None),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// Proxy tin cậy (trusted_proxies trong config.toml): khi load balancer đứng sau CDN / reverse proxy,
+// địa chỉ socket là của proxy chứ không phải của client. Nếu peer thuộc dải tin cậy thì IP client
+// lấy từ X-Forwarded-For: đi từ phải sang trái, bỏ qua các hop tin cậy, dừng ở hop đầu tiên không tin cậy
+// (phần bên trái hop đó do client tự khai, không dùng được).
+use axum::http::HeaderMap;
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    // "10.0.0.0/8", "2400:cb00::/32" hoặc một IP đơn lẻ
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix_len) = match s.split_once('/') {
+            Some((ip, len)) => (ip, Some(len)),
+            None => (s, None),
+        };
+        let network: IpAddr = ip.trim().parse().map_err(|_| format!("{}: IP không hợp lệ", s))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len.trim().parse::<u8>().ok().filter(|l| *l <= max).ok_or_else(|| format!("{}: prefix phải trong khoảng 0-{}", s, max))?,
+            None => max,
+        };
+        Ok(Self { network, prefix_len })
+    }
+}
+
+impl Cidr {
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix_len)).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix_len)).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+pub struct TrustedProxies {
+    cidrs: Vec<Cidr>,
+}
+
+impl TrustedProxies {
+    pub fn new(entries: &[String]) -> Result<Self, String> {
+        let cidrs = entries.iter().map(|e| e.parse()).collect::<Result<_, _>>()?;
+        Ok(Self { cidrs })
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.cidrs.iter().any(|c| c.contains(ip))
+    }
+
+    // Địa chỉ client thật; peer không tin cậy hoặc không có X-Forwarded-For thì giữ nguyên địa chỉ socket.
+    // IP lấy từ header không kèm port (port = 0)
+    pub fn client_addr(&self, peer: SocketAddr, headers: &HeaderMap) -> SocketAddr {
+        if !self.is_trusted(peer.ip()) {
+            return peer;
+        }
+        // Nhiều header X-Forwarded-For được nối theo thứ tự xuất hiện
+        let hops: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .collect();
+
+        let mut client = None;
+        for hop in hops.iter().rev() {
+            // Một số proxy ghi kèm port ("1.2.3.4:5678", "[2001:db8::1]:443")
+            let Some(ip) = hop.parse::<IpAddr>().ok().or_else(|| hop.parse::<SocketAddr>().ok().map(|a| a.ip())) else {
+                break;
+            };
+            client = Some(ip.to_canonical());
+            if !self.is_trusted(ip) {
+                break;
+            }
+        }
+        client.map_or(peer, |ip| SocketAddr::new(ip, 0))
+    }
+}
@@ -0,0 +1,472 @@
+// Gọi backend với 3 timeout riêng ([timeouts] trong config.toml, backend có thể ghi đè):
+// - connect: host chết / không route được thì bỏ sớm
+// - response header: backend nhận request nhưng xử lý quá lâu -> 504
+// - body idle: response body im lặng quá lâu giữa hai chunk -> cắt kết nối
+// Body tải chậm nhưng đều đặn không bị giới hạn tổng thời gian.
+//
+// Backend dạng "unix:/var/run/app.sock" được gọi qua Unix domain socket thay vì TCP.
+//
+// Trailer của response (vd. grpc-status) được giữ lại khi đọc body và gửi tiếp cho client sau chunk cuối.
+//
+// Giao thức với backend chọn theo "protocol" trong servers.json: mặc định thương lượng h2 / http/1.1 qua ALPN
+// khi dùng TLS (backend không TLS dùng HTTP/1.1), hoặc cố định http1 / http2 (http2 không TLS = h2c prior knowledge).
+//
+// Cert của backend HTTPS chỉ được xác minh khi backend có "tls" trong servers.json (CA riêng hoặc CA hệ thống),
+// backend không khai báo vẫn chấp nhận mọi cert (self-signed) như trước. Backend quan trọng có thể ghim thêm cert /
+// public key ("pins"), đối chiếu ngay trong TLS handshake (rustls): cert không khớp thì handshake bị huỷ trước khi
+// gửi byte nào của request, request lỗi 502 và health check báo backend DOWN.
+use crate::{config::UpstreamTimeouts, dns};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+use tokio_rustls::rustls::{
+    self,
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
+    crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, StatusCode},
+    BoxError,
+};
+use futures::stream::{Stream, StreamExt};
+use http_body_util::{BodyStream, StreamBody};
+use hyper::body::Frame;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::{Instant, Sleep};
+use tracing::error;
+
+fn limit(ms: u64) -> Option<Duration> {
+    (ms > 0).then(|| Duration::from_millis(ms))
+}
+
+pub enum UpstreamError {
+    Request(reqwest::Error),
+    // Không nhận được response header trong thời gian cho phép
+    HeaderTimeout(Duration),
+}
+
+impl UpstreamError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            UpstreamError::Request(e) if e.is_timeout() => StatusCode::GATEWAY_TIMEOUT,
+            UpstreamError::Request(_) => StatusCode::BAD_GATEWAY,
+            UpstreamError::HeaderTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+}
+
+impl fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpstreamError::Request(e) => write!(f, "{}", describe(e)),
+            UpstreamError::HeaderTimeout(after) => write!(f, "backend không trả response header sau {}ms", after.as_millis()),
+        }
+    }
+}
+
+const UNIX_PREFIX: &str = "unix:";
+
+// Đường dẫn socket nếu backend là "unix:/path/to.sock"
+pub fn unix_socket(backend: &str) -> Option<&str> {
+    backend.strip_prefix(UNIX_PREFIX)
+}
+
+// URL gốc để ghép path khi gọi backend; backend Unix socket dùng host "localhost" (chỉ để điền Host header)
+pub fn http_base(backend: &str) -> &str {
+    if unix_socket(backend).is_some() {
+        "http://localhost"
+    } else {
+        backend
+    }
+}
+
+// Cho client kết nối qua Unix socket nếu backend là "unix:..."
+pub fn with_unix_socket(builder: reqwest::ClientBuilder, backend: &str) -> reqwest::ClientBuilder {
+    match unix_socket(backend) {
+        #[cfg(unix)]
+        Some(path) => builder.unix_socket(path.to_string()),
+        _ => builder,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    // TLS: backend chọn qua ALPN; không TLS: HTTP/1.1
+    #[default]
+    Auto,
+    Http1,
+    // Không TLS thì gửi thẳng HTTP/2 (h2c prior knowledge), vd. backend gRPC
+    Http2,
+}
+
+// "tls" của backend trong servers.json: có thì xác minh cert của backend
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackendTls {
+    // File PEM chứa CA (có thể nhiều cert) ký cert của backend, chỉ tin các CA này; không đặt = CA của hệ thống
+    pub ca_file: Option<String>,
+    // Cert của backend phải khớp ít nhất một pin:
+    //   "sha256/<base64>"    SHA-256 của public key (SPKI), như curl --pinnedpubkey
+    //   "cert-sha256/<hex>"  SHA-256 của cả cert (openssl x509 -noout -fingerprint -sha256, có thể có dấu ":")
+    #[serde(default)]
+    pub pins: Vec<String>,
+}
+
+#[derive(Debug)]
+enum CertPin {
+    Spki(Vec<u8>),
+    Cert(Vec<u8>),
+}
+
+fn parse_pin(pin: &str) -> Result<CertPin, String> {
+    let invalid = || format!("pin không hợp lệ: {} (cần \"sha256/<base64>\" hoặc \"cert-sha256/<hex>\")", pin);
+    if let Some(b64) = pin.strip_prefix("sha256/") {
+        let hash = STANDARD.decode(b64).map_err(|_| invalid())?;
+        return if hash.len() == 32 { Ok(CertPin::Spki(hash)) } else { Err(invalid()) };
+    }
+    let hex: String = pin.strip_prefix("cert-sha256/").ok_or_else(invalid)?.chars().filter(|c| *c != ':').collect();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    (0..64)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()
+        .map(CertPin::Cert)
+        .ok_or_else(invalid)
+}
+
+impl BackendTls {
+    fn certificates(&self) -> Result<Vec<reqwest::Certificate>, String> {
+        let Some(path) = &self.ca_file else {
+            return Ok(Vec::new());
+        };
+        let pem = std::fs::read(path).map_err(|e| format!("không đọc được ca_file {}: {}", path, e))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| format!("ca_file {} không hợp lệ: {}", path, e))?;
+        if certificates.is_empty() {
+            return Err(format!("ca_file {} không có cert nào", path));
+        }
+        Ok(certificates)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for pin in &self.pins {
+            parse_pin(pin)?;
+        }
+        self.certificates().map(|_| ())
+    }
+}
+
+// Lỗi reqwest kèm nguyên nhân gốc (vd. lý do handshake TLS thất bại), Display của reqwest chỉ có URL
+pub fn describe(e: &reqwest::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        let cause_text = cause.to_string();
+        if !message.ends_with(&cause_text) {
+            message = format!("{}: {}", message, cause_text);
+        }
+        source = cause.source();
+    }
+    message
+}
+
+// Đối chiếu pin trong handshake: cert không khớp thì handshake lỗi, request chưa được gửi.
+// Có ca_file thì chain phải hợp lệ với CA đó (và đúng hostname) trước; không có ca_file thì pin là điều kiện duy nhất
+// (giống verify_certificate_spki của Envoy khi không đặt trusted_ca)
+#[derive(Debug)]
+struct PinVerifier {
+    pins: Vec<CertPin>,
+    // Err: ca_file hỏng sau khi nạp servers.json, từ chối mọi cert
+    ca: Option<Result<Arc<WebPkiServerVerifier>, String>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinVerifier {
+    fn new(tls: &BackendTls, provider: Arc<CryptoProvider>) -> Self {
+        let ca = tls.ca_file.as_ref().map(|path| {
+            let mut roots = RootCertStore::empty();
+            for cert in crate::tls::load_certs(std::path::Path::new(path))? {
+                roots.add(cert).map_err(|e| format!("CA không hợp lệ trong {}: {}", path, e))?;
+            }
+            WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .map_err(|e| format!("ca_file {} không hợp lệ: {}", path, e))
+        });
+        if let Some(Err(e)) = &ca {
+            error!("❌ {}", e);
+        }
+        Self { pins: tls.pins.iter().filter_map(|pin| parse_pin(pin).ok()).collect(), ca, provider }
+    }
+}
+
+impl ServerCertVerifier for PinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match &self.ca {
+            Some(Ok(ca)) => {
+                ca.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+            }
+            Some(Err(e)) => return Err(rustls::Error::General(e.clone())),
+            None => {}
+        }
+        let cert_hash = Sha256::digest(end_entity).to_vec();
+        let (_, cert) = x509_parser::parse_x509_certificate(end_entity)
+            .map_err(|e| rustls::Error::General(format!("không đọc được cert của backend: {}", e)))?;
+        let spki_hash = Sha256::digest(cert.tbs_certificate.subject_pki.raw).to_vec();
+        let matched = self.pins.iter().any(|pin| match pin {
+            CertPin::Spki(hash) => *hash == spki_hash,
+            CertPin::Cert(hash) => *hash == cert_hash,
+        });
+        if !matched {
+            return Err(rustls::Error::General(format!(
+                "cert của backend không khớp pin (public key: sha256/{})",
+                STANDARD.encode(spki_hash)
+            )));
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+// Backend có pin: client rustls với PinVerifier thay cho native-tls. ALPN theo "protocol" như reqwest tự đặt
+fn pinned_tls(tls: &BackendTls, protocol: Protocol) -> Result<rustls::ClientConfig, String> {
+    let provider = Arc::new(ring::default_provider());
+    let verifier = Arc::new(PinVerifier::new(tls, provider.clone()));
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+        .with_no_client_auth();
+    config.alpn_protocols = match protocol {
+        Protocol::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        Protocol::Http1 => vec![b"http/1.1".to_vec()],
+        Protocol::Http2 => vec![b"h2".to_vec()],
+    };
+    Ok(config)
+}
+
+// Cách xác minh cert backend cho client gọi backend (proxy và health check)
+pub fn with_tls(builder: reqwest::ClientBuilder, tls: Option<&BackendTls>, protocol: Protocol) -> reqwest::ClientBuilder {
+    let Some(tls) = tls else {
+        // Quan trọng: Tắt verify SSL nếu server đích dùng self-signed hoặc lỗi cert
+        return builder.danger_accept_invalid_certs(true);
+    };
+    if !tls.pins.is_empty() {
+        match pinned_tls(tls, protocol) {
+            Ok(config) => return builder.use_preconfigured_tls(config),
+            // Không dựng được client có pin thì không gọi backend không kiểm tra pin
+            Err(e) => {
+                error!("❌ Không tạo được TLS client có pin: {}", e);
+                return builder.tls_built_in_root_certs(false);
+            }
+        }
+    }
+    if tls.ca_file.is_none() {
+        return builder;
+    }
+    // CA riêng thay cho CA hệ thống; file hỏng sau khi nạp servers.json thì không tin CA nào (request tới backend lỗi)
+    let builder = builder.tls_built_in_root_certs(false);
+    match tls.certificates() {
+        Ok(certificates) => certificates.into_iter().fold(builder, |b, c| b.add_root_certificate(c)),
+        Err(e) => {
+            error!("❌ {}", e);
+            builder
+        }
+    }
+}
+
+// (Unix socket, connect timeout, giao thức, xác minh cert)
+type ClientKey = (Option<String>, u64, Protocol, Option<BackendTls>);
+
+// Client dùng chung theo ClientKey (reqwest chỉ đặt được connect timeout cho cả client)
+pub struct Clients {
+    clients: Mutex<HashMap<ClientKey, reqwest::Client>>,
+    dns: Option<dns::Cache>,
+}
+
+impl Clients {
+    pub fn new(dns: Option<dns::Cache>) -> Self {
+        Self { clients: Mutex::new(HashMap::new()), dns }
+    }
+
+    pub fn get(&self, backend: &str, timeouts: &UpstreamTimeouts, protocol: Protocol, tls: Option<&BackendTls>) -> reqwest::Client {
+        let mut clients = self.clients.lock().unwrap();
+        clients
+            .entry((unix_socket(backend).map(str::to_string), timeouts.connect_ms, protocol, tls.cloned()))
+            .or_insert_with(|| {
+                let builder = with_tls(reqwest::Client::builder(), tls, protocol);
+                let mut builder = with_unix_socket(builder, backend);
+                if let Some(connect) = limit(timeouts.connect_ms) {
+                    builder = builder.connect_timeout(connect);
+                }
+                if let Some(dns) = &self.dns {
+                    builder = builder.dns_resolver(std::sync::Arc::new(dns.clone()));
+                }
+                builder = match protocol {
+                    Protocol::Auto => builder,
+                    Protocol::Http1 => builder.http1_only(),
+                    Protocol::Http2 => builder.http2_prior_knowledge(),
+                };
+                builder.build().unwrap()
+            })
+            .clone()
+    }
+}
+
+// Gửi request, chờ response header tối đa response_header_ms
+pub async fn send(request: reqwest::RequestBuilder, timeouts: &UpstreamTimeouts) -> Result<reqwest::Response, UpstreamError> {
+    match limit(timeouts.response_header_ms) {
+        Some(after) => match tokio::time::timeout(after, request.send()).await {
+            Ok(result) => result.map_err(UpstreamError::Request),
+            Err(_) => Err(UpstreamError::HeaderTimeout(after)),
+        },
+        None => request.send().await.map_err(UpstreamError::Request),
+    }
+}
+
+// Trailer của response backend, có sau khi body được đọc hết
+#[derive(Clone, Default)]
+pub struct Trailers(Arc<Mutex<Option<HeaderMap>>>);
+
+impl Trailers {
+    // Body gửi client: các chunk của `body`, sau cùng là trailer (nếu backend có gửi)
+    pub fn attach<S, E>(self, body: S) -> Body
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        let trailers = futures::stream::once(async move { self.0.lock().unwrap().take() })
+            .filter_map(|trailers| async move { trailers.map(|t| Ok(Frame::trailers(t))) });
+        let frames = body.map(|chunk| chunk.map(Frame::data).map_err(Into::into)).chain(trailers);
+        Body::new(StreamBody::new(frames))
+    }
+}
+
+// Body của response backend dạng stream các chunk, trailer được giữ lại trong Trailers
+pub fn body_stream(res: reqwest::Response) -> (impl Stream<Item = Result<Bytes, BoxError>> + Send, Trailers) {
+    let trailers = Trailers::default();
+    let slot = trailers.clone();
+    let body = axum::http::Response::from(res).into_body();
+    let stream = BodyStream::new(body).filter_map(move |frame| {
+        let chunk = match frame {
+            Ok(frame) => match frame.into_data() {
+                Ok(bytes) => Some(Ok(bytes)),
+                Err(frame) => {
+                    if let Ok(trailers) = frame.into_trailers() {
+                        *slot.0.lock().unwrap() = Some(trailers);
+                    }
+                    None
+                }
+            },
+            Err(e) => Some(Err(e.into())),
+        };
+        futures::future::ready(chunk)
+    });
+    (stream, trailers)
+}
+
+// Response body từ backend, lỗi khi không có chunk mới trong body_idle_ms
+pub fn idle_timeout<S, E>(body: S, timeouts: &UpstreamTimeouts) -> IdleTimeout<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Into<BoxError>,
+{
+    let idle = limit(timeouts.body_idle_ms);
+    IdleTimeout {
+        inner: Box::pin(body),
+        idle,
+        deadline: idle.map(|d| Box::pin(tokio::time::sleep(d))),
+        last_chunk: Instant::now(),
+    }
+}
+
+pub struct IdleTimeout<S> {
+    inner: Pin<Box<S>>,
+    idle: Option<Duration>,
+    // Chỉ đặt lại khi hết hạn (so với last_chunk), không đụng tới timer ở mỗi chunk
+    deadline: Option<Pin<Box<Sleep>>>,
+    last_chunk: Instant,
+}
+
+impl<S, E> Stream for IdleTimeout<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Into<BoxError>,
+{
+    type Item = Result<Bytes, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        match this.inner.as_mut().poll_next(cx) {
+            Poll::Ready(Some(chunk)) => {
+                if this.idle.is_some() {
+                    this.last_chunk = Instant::now();
+                }
+                Poll::Ready(Some(chunk.map_err(Into::into)))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => {
+                let (Some(deadline), Some(idle)) = (this.deadline.as_mut(), this.idle) else {
+                    return Poll::Pending;
+                };
+                while deadline.as_mut().poll(cx).is_ready() {
+                    // Có chunk sau lần đặt deadline trước: lùi deadline theo chunk cuối
+                    let next = this.last_chunk + idle;
+                    if next <= Instant::now() {
+                        this.deadline = None;
+                        this.idle = None;
+                        let error = io::Error::new(io::ErrorKind::TimedOut, "response body từ backend im lặng quá lâu");
+                        return Poll::Ready(Some(Err(error.into())));
+                    }
+                    deadline.as_mut().reset(next);
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+// Lịch sử sẵn sàng của backend: số lần health check UP / DOWN theo từng giờ, lưu ra file
+// để báo cáo SLA ngày / tuần / tháng không bị mất khi restart.
+// File có dạng { "<pool>": { "<backend url>": { "<giờ unix>": [up, down] } } }.
+use crate::pools::Pool;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Giữ lại dữ liệu đủ cho cửa sổ dài nhất (30 ngày) + 1 ngày
+const RETENTION_HOURS: u64 = 31 * 24;
+
+// Cửa sổ báo cáo (giờ): ngày / tuần / tháng
+const WINDOW_HOURS: [u64; 3] = [24, 7 * 24, 30 * 24];
+
+type Samples = BTreeMap<u64, [u64; 2]>;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct History(HashMap<String, HashMap<String, Samples>>);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendReport {
+    pub pool: String,
+    pub url: String,
+    // % health check UP trong 24 giờ / 7 ngày / 30 ngày (None = chưa có dữ liệu)
+    pub daily: Option<f64>,
+    pub weekly: Option<f64>,
+    pub monthly: Option<f64>,
+    // Số lần check DOWN trong 30 ngày
+    pub failed_checks: u64,
+}
+
+fn now_hour() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 3600
+}
+
+impl History {
+    pub fn load(path: &Path) -> Self {
+        let Ok(data) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            tracing::warn!("⚠️ Không đọc được lịch sử uptime từ {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    // Ghi ra file tạm rồi rename như sticky map
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let data = serde_json::to_vec(self)?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, path)
+    }
+
+    pub fn record(&mut self, pool: &str, url: &str, healthy: bool) {
+        let hour = now_hour();
+        let samples = self.0.entry(pool.to_string()).or_default().entry(url.to_string()).or_default();
+        samples.entry(hour).or_default()[if healthy { 0 } else { 1 }] += 1;
+        if samples.first_key_value().is_some_and(|(&h, _)| h + RETENTION_HOURS <= hour) {
+            *samples = samples.split_off(&(hour + 1).saturating_sub(RETENTION_HOURS));
+        }
+    }
+
+    // (up, down) trong `hours` giờ gần nhất
+    fn counts(&self, pool: &str, url: &str, hours: u64) -> [u64; 2] {
+        let since = now_hour().saturating_sub(hours);
+        self.0
+            .get(pool)
+            .and_then(|p| p.get(url))
+            .map(|samples| {
+                samples.range(since + 1..).fold([0, 0], |acc, (_, [up, down])| [acc[0] + up, acc[1] + down])
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn availability(&self, pool: &str, url: &str, hours: u64) -> Option<f64> {
+        let [up, down] = self.counts(pool, url, hours);
+        (up + down > 0).then(|| up as f64 / (up + down) as f64 * 100.0)
+    }
+
+    // Báo cáo cho các backend hiện có trong servers.json
+    pub fn report(&self, pools: &[Pool]) -> Vec<BackendReport> {
+        pools
+            .iter()
+            .flat_map(|p| p.servers.iter())
+            .map(|s| {
+                let [daily, weekly, monthly] = WINDOW_HOURS.map(|hours| self.availability(&s.pool, &s.url, hours));
+                BackendReport {
+                    pool: s.pool.clone(),
+                    url: s.url.clone(),
+                    daily,
+                    weekly,
+                    monthly,
+                    failed_checks: self.counts(&s.pool, &s.url, WINDOW_HOURS[2])[1],
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn render_html(reports: &[BackendReport]) -> String {
+    let cell = |v: Option<f64>| match v {
+        Some(pct) => format!("{:.3} %", pct),
+        None => "-".to_string(),
+    };
+    let rows: String = reports
+        .iter()
+        .map(|r| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                html_escape(&r.pool),
+                html_escape(&r.url),
+                cell(r.daily),
+                cell(r.weekly),
+                cell(r.monthly),
+                r.failed_checks
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="vi">
+  <head>
+    <meta charset="UTF-8" />
+    <title>Uptime Report</title>
+    <style>
+      body {{ font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif; margin: 2em; }}
+      table {{ border-collapse: collapse; width: 100%; }}
+      th, td {{ border: 1px solid #dee2e6; padding: 8px; text-align: left; }}
+      th {{ background-color: #f1f3f5; }}
+    </style>
+  </head>
+  <body>
+    <h1>Uptime Report</h1>
+    <p>Tạo lúc {}</p>
+    <table>
+      <thead>
+        <tr><th>Pool</th><th>URL</th><th>24 giờ</th><th>7 ngày</th><th>30 ngày</th><th>Lần check DOWN (30 ngày)</th></tr>
+      </thead>
+      <tbody>
+{}      </tbody>
+    </table>
+  </body>
+</html>
+"#,
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        rows
+    )
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,113 @@
+// HashiCorp Vault ([vault] trong config.toml):
+// - PKI engine ([vault.pki]): cấp cert cho listener TLS, tự xin cert mới khi đã dùng 2/3 thời hạn
+//   và thay vào CertStore (kết nối mới dùng cert mới, không cần restart)
+// - KV engine: credential gửi lên backend ("auth" trong servers.json) dạng "vault:<path>#<field>",
+//   vd. "vault:secret/data/lb/orders#api_key" (KV v2) hoặc "vault:kv/lb/orders#api_key" (KV v1),
+//   đọc lại mỗi refresh_secs để nhận giá trị đã xoay vòng
+use crate::{
+    config::{VaultConfig, VaultPkiConfig},
+    tls::{self, CertStore},
+};
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+use tracing::{info, warn};
+
+pub const PREFIX: &str = "vault:";
+// Xin cert lỗi thì thử lại sau khoảng này (cert cũ vẫn dùng tới khi hết hạn)
+const RETRY: Duration = Duration::from_secs(30);
+
+pub struct Client {
+    http: reqwest::Client,
+    config: VaultConfig,
+}
+
+impl Client {
+    pub fn new(config: &VaultConfig) -> Self {
+        let http = reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap();
+        Self { http, config: config.clone() }
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        Duration::from_secs(self.config.refresh_secs)
+    }
+
+    async fn call(&self, method: reqwest::Method, path: &str, body: Option<Value>) -> Result<Value, String> {
+        let url = format!("{}/v1/{}", self.config.address.trim_end_matches('/'), path.trim_start_matches('/'));
+        let mut request = self.http.request(method, &url).header("x-vault-token", &self.config.token);
+        if let Some(namespace) = &self.config.namespace {
+            request = request.header("x-vault-namespace", namespace);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.map_err(|e| format!("vault {}: {}", path, e))?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            let errors = body["errors"].as_array().map(|e| e.iter().filter_map(Value::as_str).collect::<Vec<_>>().join("; "));
+            return Err(format!("vault {}: HTTP {} {}", path, status, errors.unwrap_or_default()));
+        }
+        Ok(body)
+    }
+
+    // Giá trị của tham chiếu "vault:<path>#<field>"
+    pub async fn read(&self, reference: &str) -> Result<String, String> {
+        let reference = reference.strip_prefix(PREFIX).unwrap_or(reference);
+        let (path, field) = reference
+            .split_once('#')
+            .ok_or_else(|| format!("{}{}: thiếu #<field>", PREFIX, reference))?;
+        let body = self.call(reqwest::Method::GET, path, None).await?;
+        // KV v2 bọc dữ liệu trong data.data
+        let data = if body["data"]["data"].is_object() { &body["data"]["data"] } else { &body["data"] };
+        match &data[field] {
+            Value::String(s) => Ok(s.clone()),
+            Value::Null => Err(format!("vault {}: không có field {}", path, field)),
+            other => Ok(other.to_string()),
+        }
+    }
+
+    // Xin cert mới từ PKI engine, trả về cert và thời hạn còn lại
+    pub async fn issue(&self, pki: &VaultPkiConfig) -> Result<(tokio_rustls::rustls::sign::CertifiedKey, Duration), String> {
+        let mut request = json!({ "common_name": pki.common_name });
+        if !pki.alt_names.is_empty() {
+            request["alt_names"] = json!(pki.alt_names.join(","));
+        }
+        if let Some(ttl) = &pki.ttl {
+            request["ttl"] = json!(ttl);
+        }
+        let path = format!("{}/issue/{}", pki.mount.trim_matches('/'), pki.role);
+        let body = self.call(reqwest::Method::POST, &path, Some(request)).await?;
+        let data = &body["data"];
+        let certificate = data["certificate"].as_str().ok_or("vault: response thiếu certificate")?;
+        let private_key = data["private_key"].as_str().ok_or("vault: response thiếu private_key")?;
+        // Gửi kèm chain để client xác thực được tới root
+        let mut chain = certificate.to_string();
+        for ca in data["ca_chain"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+            chain.push('\n');
+            chain.push_str(ca);
+        }
+        let key = tls::parse_pem(&chain, private_key)?;
+        let now = chrono::Utc::now().timestamp();
+        let lifetime = data["expiration"].as_i64().map_or(Duration::ZERO, |exp| Duration::from_secs((exp - now).max(0) as u64));
+        Ok((key, lifetime))
+    }
+
+    // Gia hạn cert listener: xin cert mới khi đã dùng 2/3 thời hạn
+    pub async fn renew_certs(self: Arc<Self>, pki: VaultPkiConfig, store: Arc<CertStore>, lifetime: Duration) {
+        let mut wait = lifetime * 2 / 3;
+        loop {
+            tokio::time::sleep(wait.max(RETRY)).await;
+            match self.issue(&pki).await {
+                Ok((key, lifetime)) => {
+                    store.replace(key);
+                    info!("🔐 Đã thay cert TLS từ Vault ({}, hạn {}s)", pki.common_name, lifetime.as_secs());
+                    wait = lifetime * 2 / 3;
+                }
+                Err(e) => {
+                    warn!("⚠️ Không xin được cert mới từ Vault: {}", e);
+                    wait = RETRY;
+                }
+            }
+        }
+    }
+}
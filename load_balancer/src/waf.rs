@@ -0,0 +1,243 @@
+// WAF đơn giản: chặn / ghi log / giới hạn tần suất các request khớp luật trước khi proxy.
+// Path được so khớp sau khi giải mã %xx (cả bản gốc lẫn bản đã chuẩn hoá dot-segment) để không bị lách bằng encode.
+// Điều kiện kích thước body: Content-Length vượt thì chặn ngay, body chunked bị đếm khi stream và cắt (413) khi vượt.
+// Luật có regex trên body chỉ được kiểm tra với route đọc hết body trước khi gửi (body = "buffer").
+use crate::{
+    config::{WafAction, WafConfig},
+    normalize,
+};
+use axum::{
+    http::{header, HeaderMap, HeaderName, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+// Mẫu SQL injection thường gặp trong query string
+const SQLI_PATTERN: &str = r"(?i)(\bunion\b.+\bselect\b|'\s*(or|and)\b|\bor\s+\d+\s*=\s*\d+|;\s*(drop|delete|insert|update|shutdown)\b|--|/\*|\b(sleep|benchmark|pg_sleep)\s*\(|information_schema|xp_cmdshell)";
+
+fn sqli_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(SQLI_PATTERN).unwrap())
+}
+
+struct Rule {
+    name: String,
+    path: Option<Regex>,
+    query: Option<Regex>,
+    header: Option<(HeaderName, Regex)>,
+    sqli: bool,
+    max_body_bytes: Option<u64>,
+    body: Option<Regex>,
+    action: WafAction,
+    rate_limit: u32,
+    rate_window: Duration,
+    hits: AtomicU64,
+}
+
+// Kết quả khớp luật, dùng cho API /load-balancer/api/waf
+#[derive(Serialize)]
+pub struct RuleStats {
+    pub name: String,
+    pub action: WafAction,
+    pub hits: u64,
+}
+
+pub struct Engine {
+    rules: Vec<Rule>,
+    // (luật, IP) -> (bắt đầu cửa sổ, số request trong cửa sổ)
+    windows: Mutex<HashMap<(usize, IpAddr), (Instant, u32)>>,
+}
+
+impl Engine {
+    // Regex đã được kiểm tra trong config::validate
+    pub fn new(config: &WafConfig) -> Self {
+        let rules = config
+            .rules
+            .iter()
+            .map(|r| Rule {
+                name: r.name.clone(),
+                path: r.path.as_deref().map(|p| Regex::new(p).unwrap()),
+                query: r.query.as_deref().map(|p| Regex::new(p).unwrap()),
+                header: r.header.as_deref().zip(r.header_pattern.as_deref()).map(|(name, pattern)| {
+                    (HeaderName::from_bytes(name.as_bytes()).unwrap(), Regex::new(pattern).unwrap())
+                }),
+                sqli: r.sqli,
+                max_body_bytes: r.max_body_bytes,
+                body: r.body.as_deref().map(|p| Regex::new(p).unwrap()),
+                action: r.action,
+                rate_limit: r.rate_limit,
+                rate_window: Duration::from_secs(r.rate_window_secs),
+                hits: AtomicU64::new(0),
+            })
+            .collect();
+        Self { rules, windows: Mutex::new(HashMap::new()) }
+    }
+
+    // None: cho qua. Some(response): chặn (403) hoặc vượt giới hạn (429).
+    // Chỉ xét luật không có điều kiện body
+    pub fn evaluate(&self, ip: IpAddr, uri: &Uri, headers: &HeaderMap) -> Option<Response> {
+        self.run(ip, uri, headers, None)
+    }
+
+    // Sau khi đã đọc hết body: chỉ xét luật có điều kiện body
+    pub fn evaluate_body(&self, ip: IpAddr, uri: &Uri, headers: &HeaderMap, body: &[u8]) -> Option<Response> {
+        self.run(ip, uri, headers, Some(body))
+    }
+
+    // Giới hạn body (byte) theo luật chặn có max_body_bytes khớp request này, áp lên body khi stream
+    pub fn body_limit(&self, uri: &Uri, headers: &HeaderMap) -> Option<u64> {
+        let paths = decode_path(uri.path());
+        let query = uri.query().map(decode_query).unwrap_or_default();
+        self.rules
+            .iter()
+            .filter(|rule| rule.action == WafAction::Block && rule.body.is_none())
+            .filter(|rule| rule.matches_request(&paths, &query, headers))
+            .filter_map(|rule| rule.max_body_bytes)
+            .min()
+    }
+
+    fn run(&self, ip: IpAddr, uri: &Uri, headers: &HeaderMap, body: Option<&[u8]>) -> Option<Response> {
+        let paths = decode_path(uri.path());
+        let query = uri.query().map(decode_query).unwrap_or_default();
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule.body.is_some() != body.is_some() || !rule.matches(&paths, &query, headers, body) {
+                continue;
+            }
+            rule.hits.fetch_add(1, Ordering::Relaxed);
+
+            match rule.action {
+                WafAction::Log => {
+                    warn!("🛡️ WAF [{}] khớp: {} {}", rule.name, ip, uri);
+                }
+                WafAction::Block => {
+                    warn!("🛡️ WAF [{}] chặn: {} {}", rule.name, ip, uri);
+                    if rule.max_body_bytes.is_some() {
+                        return Some((StatusCode::PAYLOAD_TOO_LARGE, "Request body vượt giới hạn của WAF").into_response());
+                    }
+                    return Some((StatusCode::FORBIDDEN, "Request bị chặn bởi WAF").into_response());
+                }
+                WafAction::RateLimit => {
+                    if !self.within_limit(index, rule, ip) {
+                        warn!("🛡️ WAF [{}] giới hạn tần suất: {} {}", rule.name, ip, uri);
+                        let mut resp = (StatusCode::TOO_MANY_REQUESTS, "Quá nhiều request").into_response();
+                        resp.headers_mut().insert(header::RETRY_AFTER, rule.rate_window.as_secs().into());
+                        return Some(resp);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn within_limit(&self, index: usize, rule: &Rule, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        // Dọn cửa sổ đã hết hạn để map không phình mãi
+        if windows.len() > 10_000 {
+            windows.retain(|(i, _), (start, _)| now.duration_since(*start) < self.rules[*i].rate_window);
+        }
+
+        let entry = windows.entry((index, ip)).or_insert((now, 0));
+        if now.duration_since(entry.0) >= rule.rate_window {
+            *entry = (now, 0);
+        }
+        entry.1 += 1;
+        entry.1 <= rule.rate_limit
+    }
+
+    pub fn stats(&self) -> Vec<RuleStats> {
+        self.rules
+            .iter()
+            .map(|r| RuleStats {
+                name: r.name.clone(),
+                action: r.action,
+                hits: r.hits.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+impl Rule {
+    // Điều kiện không phụ thuộc body: path, query, header, sqli
+    fn matches_request(&self, paths: &[String], query: &str, headers: &HeaderMap) -> bool {
+        if self.path.as_ref().is_some_and(|re| !paths.iter().any(|path| re.is_match(path))) {
+            return false;
+        }
+        if self.query.as_ref().is_some_and(|re| !re.is_match(query)) {
+            return false;
+        }
+        if let Some((name, re)) = &self.header {
+            let value = headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or("");
+            if !re.is_match(value) {
+                return false;
+            }
+        }
+        !self.sqli || sqli_regex().is_match(query)
+    }
+
+    fn matches(&self, paths: &[String], query: &str, headers: &HeaderMap, body: Option<&[u8]>) -> bool {
+        if !self.matches_request(paths, query, headers) {
+            return false;
+        }
+        if let Some(max) = self.max_body_bytes {
+            let length = headers
+                .get(header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(0);
+            if length <= max {
+                return false;
+            }
+        }
+        if let (Some(re), Some(body)) = (&self.body, body) {
+            if !re.is_match(&String::from_utf8_lossy(body)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// Path gốc và path đã chuẩn hoá (dot-segment, "//"), cả hai đã giải mã %xx:
+// "/%61dmin" khớp luật "^/admin", "/static/../admin" khớp cả "^/admin" lẫn luật tìm ".."
+fn decode_path(raw: &str) -> Vec<String> {
+    let decoded = percent_decode_str(raw).decode_utf8_lossy().into_owned();
+    let mut paths = vec![decoded];
+    if let Some(canonical) = normalize::canonical_path(raw) {
+        let canonical = percent_decode_str(&canonical).decode_utf8_lossy().into_owned();
+        if canonical != paths[0] {
+            paths.push(canonical);
+        }
+    }
+    paths
+}
+
+// Giải mã %xx và '+' để mẫu không bị lách bằng cách encode
+fn decode_query(query: &str) -> String {
+    percent_decode_str(&query.replace('+', " ")).decode_utf8_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_path;
+
+    #[test]
+    fn path_is_decoded_and_normalized() {
+        assert_eq!(decode_path("/%61dmin"), ["/admin"]);
+        assert_eq!(decode_path("/static/../admin"), ["/static/../admin", "/admin"]);
+        assert_eq!(decode_path("/a//b"), ["/a//b", "/a/b"]);
+    }
+}
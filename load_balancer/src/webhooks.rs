@@ -0,0 +1,111 @@
+// Gọi webhook khi backend chuyển trạng thái UP <-> DOWN.
+// Payload render từ template minijinja để khớp định dạng của PagerDuty, Opsgenie...,
+// vd. {"event_action": "{% if healthy %}resolve{% else %}trigger{% endif %}", "dedup_key": {{ event_id|tojson }}}
+use crate::config::WebhookConfig;
+use minijinja::Environment;
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+#[derive(Clone, Serialize)]
+pub struct HealthEvent {
+    // "up" / "down"
+    pub status: &'static str,
+    pub healthy: bool,
+    pub pool: String,
+    pub backend: String,
+    pub region: String,
+    // Định danh ổn định của backend để hệ thống nhận gộp trigger / resolve
+    pub event_id: String,
+    pub timestamp: String,
+    pub response_time_ms: Option<u128>,
+    // Lý do DOWN (HTTP 503, DNS, timeout...)
+    pub reason: Option<String>,
+}
+
+impl HealthEvent {
+    // reason = None nghĩa là backend UP
+    pub fn new(pool: &str, backend: &str, region: &str, response_time_ms: Option<u128>, reason: Option<&str>) -> Self {
+        let healthy = reason.is_none();
+        Self {
+            status: if healthy { "up" } else { "down" },
+            healthy,
+            pool: pool.to_string(),
+            backend: backend.to_string(),
+            region: region.to_string(),
+            event_id: format!("lb:{}:{}", pool, backend),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            response_time_ms,
+            reason: reason.map(str::to_string),
+        }
+    }
+}
+
+pub struct Notifier {
+    hooks: Vec<WebhookConfig>,
+    // Template của webhook thứ i có tên "webhook-i" (không có thì gửi JSON mặc định)
+    env: Environment<'static>,
+    client: reqwest::Client,
+}
+
+fn template_name(index: usize) -> String {
+    format!("webhook-{}", index)
+}
+
+impl Notifier {
+    pub fn new(hooks: &[WebhookConfig]) -> Result<Self, String> {
+        let mut env = Environment::new();
+        for (i, hook) in hooks.iter().enumerate() {
+            let source = match (&hook.template, &hook.template_file) {
+                (Some(template), _) => template.clone(),
+                (None, Some(path)) => std::fs::read_to_string(path)
+                    .map_err(|e| format!("Không đọc được {}: {}", path.display(), e))?,
+                (None, None) => continue,
+            };
+            env.add_template_owned(template_name(i), source)
+                .map_err(|e| format!("Template webhook {} lỗi: {}", hook.url, e))?;
+        }
+        info!("🔔 {} webhook cho sự kiện health check", hooks.len());
+        Ok(Self {
+            hooks: hooks.to_vec(),
+            env,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    fn payload(&self, index: usize, event: &HealthEvent) -> Result<String, String> {
+        match self.env.get_template(&template_name(index)) {
+            Ok(template) => template.render(event).map_err(|e| e.to_string()),
+            Err(_) => serde_json::to_string(event).map_err(|e| e.to_string()),
+        }
+    }
+
+    // Gửi ở task riêng, không chặn vòng health check
+    pub fn notify(&self, event: &HealthEvent) {
+        for (i, hook) in self.hooks.iter().enumerate() {
+            let body = match self.payload(i, event) {
+                Ok(body) => body,
+                Err(e) => {
+                    warn!("⚠️ Render payload webhook {} lỗi: {}", hook.url, e);
+                    continue;
+                }
+            };
+            let mut request = self
+                .client
+                .post(&hook.url)
+                .timeout(Duration::from_secs(hook.timeout_secs))
+                .header(reqwest::header::CONTENT_TYPE, &hook.content_type)
+                .body(body);
+            for (name, value) in &hook.headers {
+                request = request.header(name, value);
+            }
+            let url = hook.url.clone();
+            tokio::spawn(async move {
+                match request.send().await.and_then(|r| r.error_for_status()) {
+                    Ok(_) => info!("🔔 Đã gửi webhook tới {}", url),
+                    Err(e) => warn!("⚠️ Gửi webhook tới {} lỗi: {}", url, e),
+                }
+            });
+        }
+    }
+}